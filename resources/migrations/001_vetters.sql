@@ -0,0 +1,7 @@
+-- Adds the per-offer and per-account choice of risk vetter
+
+ALTER TABLE offer ADD COLUMN IF NOT EXISTS vetterName VARCHAR NULL;
+
+ALTER TABLE account ADD COLUMN IF NOT EXISTS vetterName VARCHAR NULL;
+
+GRANT UPDATE ON TABLE account TO broker_user;
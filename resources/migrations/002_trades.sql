@@ -0,0 +1,7 @@
+-- Records the quantity of each trade so executions can be kept as trade history
+
+ALTER TABLE trade ADD COLUMN IF NOT EXISTS quantity INT NOT NULL DEFAULT 0;
+
+ALTER TABLE trade ALTER COLUMN quantity DROP DEFAULT;
+
+CREATE INDEX IF NOT EXISTS idx_trade_orderLegId ON trade (orderLegId);
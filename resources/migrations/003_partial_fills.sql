@@ -0,0 +1,13 @@
+-- Adds the PartiallyFilled status and the filled quantity and average fill price of each order
+
+INSERT INTO order_status (orderStatus) VALUES
+    ('PartiallyFilled')
+    ON CONFLICT DO NOTHING;
+
+ALTER TABLE order_state
+    ADD COLUMN IF NOT EXISTS filledQuantity INT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS averageFillPrice REAL NOT NULL DEFAULT 0;
+
+ALTER TABLE order_state_history
+    ADD COLUMN IF NOT EXISTS filledQuantity INT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS averageFillPrice REAL NOT NULL DEFAULT 0;
@@ -0,0 +1,13 @@
+-- Adds the audit trail of discrepancies found when reconciling orders with an exchange
+
+CREATE TABLE IF NOT EXISTS reconciliation_audit (
+    reconciliationAuditId BIGSERIAL PRIMARY KEY,
+    exchangeId INT NOT NULL REFERENCES exchange,
+    orderId BIGINT NOT NULL REFERENCES order_base,
+    createTime BIGINT NOT NULL,
+    discrepancy VARCHAR NOT NULL,
+    description VARCHAR NOT NULL
+);
+
+GRANT SELECT, INSERT ON TABLE reconciliation_audit TO broker_user;
+GRANT SELECT, UPDATE ON ALL SEQUENCES IN SCHEMA public TO broker_user;
@@ -0,0 +1,12 @@
+-- Records the exchange's execution id on each trade so a redelivered execution is applied once
+--
+-- Trades from before this change have no execution id, so they are given one that no exchange
+-- will send, which keeps them unique
+
+ALTER TABLE trade ADD COLUMN IF NOT EXISTS exchangeExecutionId BIGINT NULL;
+
+UPDATE trade SET exchangeExecutionId = -tradeId WHERE exchangeExecutionId IS NULL;
+
+ALTER TABLE trade ALTER COLUMN exchangeExecutionId SET NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS unq_trade_execution ON trade (orderLegId, exchangeExecutionId);
@@ -0,0 +1,41 @@
+-- Adds end of day snapshots of balances, positions and orders for account statements
+
+CREATE TABLE IF NOT EXISTS balance_snapshot (
+    balanceSnapshotId BIGSERIAL PRIMARY KEY,
+    accountId INT NOT NULL REFERENCES account,
+    snapshotDate VARCHAR NOT NULL,
+    cash REAL NOT NULL,
+    createTime BIGINT NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS unq_balance_snapshot ON balance_snapshot (accountId, snapshotDate);
+
+CREATE TABLE IF NOT EXISTS position_snapshot (
+    positionSnapshotId BIGSERIAL PRIMARY KEY,
+    accountId INT NOT NULL REFERENCES account,
+    snapshotDate VARCHAR NOT NULL,
+    instrumentId BIGINT NOT NULL REFERENCES instrument,
+    quantity INT NOT NULL,
+    cost REAL NOT NULL,
+    closedGain REAL NOT NULL,
+    createTime BIGINT NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS unq_position_snapshot ON position_snapshot (accountId, snapshotDate, instrumentId);
+
+CREATE TABLE IF NOT EXISTS order_state_snapshot (
+    orderStateSnapshotId BIGSERIAL PRIMARY KEY,
+    orderId BIGINT NOT NULL REFERENCES order_base,
+    snapshotDate VARCHAR NOT NULL,
+    orderStatus VARCHAR NOT NULL REFERENCES order_status,
+    rejectReason VARCHAR NULL,
+    filledQuantity INT NOT NULL,
+    averageFillPrice REAL NOT NULL,
+    updateTime BIGINT NOT NULL,
+    versionNumber BIGINT NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS unq_order_state_snapshot ON order_state_snapshot (orderId, snapshotDate);
+
+GRANT SELECT, INSERT ON TABLE balance_snapshot, position_snapshot, order_state_snapshot TO broker_user;
+GRANT SELECT, UPDATE ON ALL SEQUENCES IN SCHEMA public TO broker_user;
@@ -0,0 +1,69 @@
+-- Adds the double-entry cash ledger
+
+CREATE TABLE IF NOT EXISTS journal_type (
+    journalType VARCHAR PRIMARY KEY
+);
+
+INSERT INTO journal_type (journalType) VALUES
+    ('InitialFunding'),
+    ('Execution'),
+    ('Deposit'),
+    ('Withdrawal'),
+    ('Fee'),
+    ('Adjustment')
+    ON CONFLICT DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS ledger_account (
+    ledgerAccount VARCHAR PRIMARY KEY
+);
+
+INSERT INTO ledger_account (ledgerAccount) VALUES
+    ('CustomerCash'),
+    ('ExternalFunds'),
+    ('ExchangeSettlement'),
+    ('FeeIncome'),
+    ('Adjustments')
+    ON CONFLICT DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS ledger_journal (
+    journalId BIGSERIAL PRIMARY KEY,
+    journalType VARCHAR NOT NULL REFERENCES journal_type,
+    tradeId BIGINT NULL REFERENCES trade,
+    createTime BIGINT NOT NULL,
+    description VARCHAR NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS ledger_entry (
+    ledgerEntryId BIGSERIAL PRIMARY KEY,
+    journalId BIGINT NOT NULL REFERENCES ledger_journal,
+    accountId INT NOT NULL REFERENCES account,
+    ledgerAccount VARCHAR NOT NULL REFERENCES ledger_account,
+    amount REAL NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_ledger_entry_journalId ON ledger_entry (journalId);
+CREATE INDEX IF NOT EXISTS idx_ledger_entry_accountId ON ledger_entry (accountId, ledgerAccount);
+
+-- Accounts opened before the ledger have cash but no entries. Each gets an opening journal for
+-- its current cash, so the ledger agrees with the balance table from the start
+WITH opening AS (
+    SELECT balance.accountId,
+        balance.cash,
+        nextval(pg_get_serial_sequence('ledger_journal', 'journalid')) AS journalId
+    FROM balance
+    WHERE balance.cash <> 0
+    AND NOT EXISTS (SELECT 1 FROM ledger_entry AS entry
+                    WHERE entry.accountId = balance.accountId AND entry.ledgerAccount = 'CustomerCash')
+), opening_journal AS (
+    INSERT INTO ledger_journal (journalId, journalType, tradeId, createTime, description)
+    SELECT journalId, 'InitialFunding', NULL, (EXTRACT(EPOCH FROM clock_timestamp()) * 1000)::BIGINT, 'Opening balance'
+    FROM opening
+)
+INSERT INTO ledger_entry (journalId, accountId, ledgerAccount, amount)
+SELECT journalId, accountId, 'CustomerCash', cash FROM opening
+UNION ALL
+SELECT journalId, accountId, 'ExternalFunds', -cash FROM opening;
+
+GRANT SELECT ON TABLE journal_type, ledger_account TO broker_user;
+GRANT SELECT, INSERT ON TABLE ledger_journal, ledger_entry TO broker_user;
+GRANT SELECT, UPDATE ON ALL SEQUENCES IN SCHEMA public TO broker_user;
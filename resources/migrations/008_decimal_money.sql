@@ -0,0 +1,46 @@
+-- Converts prices and money amounts from REAL to NUMERIC for databases created
+-- before schema.sql switched to exact decimals, and adds instrument price increments.
+--
+-- A direct REAL to NUMERIC cast keeps only six significant digits, so values go
+-- through DOUBLE PRECISION first and are then rounded to remove binary noise:
+-- prices to six decimal places, cash, cost, gains and ledger amounts to cents.
+
+ALTER TABLE instrument
+    ADD COLUMN IF NOT EXISTS tickSize NUMERIC NOT NULL DEFAULT 0.01,
+    ADD COLUMN IF NOT EXISTS pricePrecision INT NOT NULL DEFAULT 2;
+
+ALTER TABLE order_base
+    ALTER COLUMN price TYPE NUMERIC USING ROUND(price::DOUBLE PRECISION::NUMERIC, 6);
+
+ALTER TABLE order_state
+    ALTER COLUMN averageFillPrice DROP DEFAULT,
+    ALTER COLUMN averageFillPrice TYPE NUMERIC USING ROUND(averageFillPrice::DOUBLE PRECISION::NUMERIC, 6),
+    ALTER COLUMN averageFillPrice SET DEFAULT 0;
+
+ALTER TABLE order_state_history
+    ALTER COLUMN averageFillPrice DROP DEFAULT,
+    ALTER COLUMN averageFillPrice TYPE NUMERIC USING ROUND(averageFillPrice::DOUBLE PRECISION::NUMERIC, 6),
+    ALTER COLUMN averageFillPrice SET DEFAULT 0;
+
+ALTER TABLE order_state_snapshot
+    ALTER COLUMN averageFillPrice TYPE NUMERIC USING ROUND(averageFillPrice::DOUBLE PRECISION::NUMERIC, 6);
+
+ALTER TABLE trade
+    ALTER COLUMN price TYPE NUMERIC USING ROUND(price::DOUBLE PRECISION::NUMERIC, 6);
+
+ALTER TABLE position
+    ALTER COLUMN cost TYPE NUMERIC USING ROUND(cost::DOUBLE PRECISION::NUMERIC, 2),
+    ALTER COLUMN closedGain TYPE NUMERIC USING ROUND(closedGain::DOUBLE PRECISION::NUMERIC, 2);
+
+ALTER TABLE position_snapshot
+    ALTER COLUMN cost TYPE NUMERIC USING ROUND(cost::DOUBLE PRECISION::NUMERIC, 2),
+    ALTER COLUMN closedGain TYPE NUMERIC USING ROUND(closedGain::DOUBLE PRECISION::NUMERIC, 2);
+
+ALTER TABLE balance
+    ALTER COLUMN cash TYPE NUMERIC USING ROUND(cash::DOUBLE PRECISION::NUMERIC, 2);
+
+ALTER TABLE balance_snapshot
+    ALTER COLUMN cash TYPE NUMERIC USING ROUND(cash::DOUBLE PRECISION::NUMERIC, 2);
+
+ALTER TABLE ledger_entry
+    ALTER COLUMN amount TYPE NUMERIC USING ROUND(amount::DOUBLE PRECISION::NUMERIC, 2);
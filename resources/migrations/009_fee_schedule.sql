@@ -0,0 +1,18 @@
+-- Adds fee schedules and records the fee charged on each trade
+
+CREATE TABLE IF NOT EXISTS fee_schedule (
+    feeScheduleId SERIAL PRIMARY KEY,
+    offerId INT NULL REFERENCES offer,
+    accountId INT NULL REFERENCES account,
+    assetClass VARCHAR NULL REFERENCES asset_class,
+    perShare NUMERIC NOT NULL DEFAULT 0,
+    perContract NUMERIC NOT NULL DEFAULT 0,
+    basisPoints NUMERIC NOT NULL DEFAULT 0,
+    minimumTicket NUMERIC NOT NULL DEFAULT 0,
+    CHECK (offerId IS NULL OR accountId IS NULL)
+);
+
+ALTER TABLE trade ADD COLUMN fee NUMERIC NOT NULL DEFAULT 0;
+
+GRANT SELECT, INSERT, DELETE ON TABLE fee_schedule TO broker_user;
+GRANT SELECT, UPDATE ON ALL SEQUENCES IN SCHEMA public TO broker_user;
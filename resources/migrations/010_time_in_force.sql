@@ -0,0 +1,20 @@
+-- Adds time in force to orders and records which exchanges accept one.
+-- Existing orders keep working until canceled.
+
+CREATE TABLE IF NOT EXISTS time_in_force (
+    timeInForce VARCHAR PRIMARY KEY
+);
+
+INSERT INTO time_in_force (timeInForce) VALUES
+    ('Day'),
+    ('Gtc'),
+    ('Gtd'),
+    ('Ioc'),
+    ('Fok') ;
+
+ALTER TABLE order_base ADD COLUMN timeInForce VARCHAR NOT NULL DEFAULT 'Gtc' REFERENCES time_in_force;
+ALTER TABLE order_base ADD COLUMN expireTime BIGINT NULL;
+
+ALTER TABLE exchange ADD COLUMN supportsTimeInForce BOOLEAN NOT NULL DEFAULT FALSE;
+
+GRANT SELECT ON TABLE time_in_force TO broker_user;
@@ -0,0 +1,4 @@
+-- Links the orders on either side of a cancel/replace in their state history
+
+ALTER TABLE order_state_history ADD COLUMN replacesOrderId BIGINT NULL REFERENCES order_base;
+ALTER TABLE order_state_history ADD COLUMN replacedByOrderId BIGINT NULL REFERENCES order_base;
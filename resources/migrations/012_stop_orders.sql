@@ -0,0 +1,20 @@
+-- Adds broker-held stop, stop-limit and trailing-stop orders
+
+CREATE TABLE IF NOT EXISTS order_type (
+    orderType VARCHAR PRIMARY KEY
+);
+
+INSERT INTO order_type (orderType) VALUES
+    ('Limit'),
+    ('Stop'),
+    ('StopLimit'),
+    ('TrailingStop') ;
+
+INSERT INTO order_status (orderStatus) VALUES ('PendingTrigger');
+
+ALTER TABLE order_base ADD COLUMN orderType VARCHAR NOT NULL DEFAULT 'Limit' REFERENCES order_type;
+ALTER TABLE order_base ADD COLUMN stopPrice NUMERIC NULL;
+ALTER TABLE order_base ADD COLUMN trailingOffset NUMERIC NULL;
+
+GRANT SELECT ON TABLE order_type TO broker_user;
+GRANT UPDATE ON TABLE order_base TO broker_user;
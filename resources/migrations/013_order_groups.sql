@@ -0,0 +1,41 @@
+-- Adds bracket and one-cancels-other order groups
+
+INSERT INTO order_status (orderStatus) VALUES ('PendingParent');
+
+CREATE TABLE IF NOT EXISTS order_group_type (
+    groupType VARCHAR PRIMARY KEY
+);
+
+INSERT INTO order_group_type (groupType) VALUES
+    ('Bracket'),
+    ('Oco') ;
+
+CREATE TABLE IF NOT EXISTS order_group_role (
+    groupRole VARCHAR PRIMARY KEY
+);
+
+INSERT INTO order_group_role (groupRole) VALUES
+    ('Entry'),
+    ('TakeProfit'),
+    ('StopLoss'),
+    ('Oco') ;
+
+CREATE TABLE IF NOT EXISTS order_group (
+    orderGroupId BIGSERIAL PRIMARY KEY,
+    accountId INT NOT NULL REFERENCES account,
+    groupType VARCHAR NOT NULL REFERENCES order_group_type,
+    createTime BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS order_group_member (
+    orderGroupId BIGINT NOT NULL REFERENCES order_group,
+    orderId BIGINT NOT NULL REFERENCES order_base,
+    groupRole VARCHAR NOT NULL REFERENCES order_group_role,
+    PRIMARY KEY (orderGroupId, orderId)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS unq_order_group_member_orderId ON order_group_member (orderId);
+
+GRANT SELECT ON TABLE order_group_type, order_group_role TO broker_user;
+GRANT SELECT, INSERT ON TABLE order_group, order_group_member TO broker_user;
+GRANT SELECT, UPDATE ON ALL SEQUENCES IN SCHEMA public TO broker_user;
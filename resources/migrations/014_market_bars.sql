@@ -0,0 +1,28 @@
+-- Adds OHLCV bars aggregated from last trades
+
+CREATE TABLE IF NOT EXISTS bar_interval (
+    barInterval VARCHAR PRIMARY KEY
+);
+
+INSERT INTO bar_interval (barInterval) VALUES
+    ('1s'),
+    ('1m'),
+    ('5m'),
+    ('1h'),
+    ('1d') ;
+
+CREATE TABLE IF NOT EXISTS market_bar (
+    instrumentId BIGINT NOT NULL REFERENCES instrument,
+    barInterval VARCHAR NOT NULL REFERENCES bar_interval,
+    startTime BIGINT NOT NULL,
+    openPrice NUMERIC NOT NULL,
+    highPrice NUMERIC NOT NULL,
+    lowPrice NUMERIC NOT NULL,
+    closePrice NUMERIC NOT NULL,
+    volume BIGINT NOT NULL,
+    tradeCount BIGINT NOT NULL,
+    PRIMARY KEY (instrumentId, barInterval, startTime)
+);
+
+GRANT SELECT ON TABLE bar_interval TO broker_user;
+GRANT SELECT, INSERT, UPDATE ON TABLE market_bar TO broker_user;
@@ -0,0 +1,38 @@
+-- Adds margin calls and their audit trail
+
+CREATE TABLE IF NOT EXISTS margin_call_status (
+    marginCallStatus VARCHAR PRIMARY KEY
+);
+
+INSERT INTO margin_call_status (marginCallStatus) VALUES
+    ('Called'),
+    ('Liquidating'),
+    ('Met') ;
+
+CREATE TABLE IF NOT EXISTS margin_call (
+    marginCallId BIGSERIAL PRIMARY KEY,
+    accountId INT NOT NULL REFERENCES account,
+    marginCallStatus VARCHAR NOT NULL REFERENCES margin_call_status,
+    equity NUMERIC NOT NULL,
+    maintenanceMargin NUMERIC NOT NULL,
+    callTime BIGINT NOT NULL,
+    updateTime BIGINT NOT NULL
+);
+
+-- At most one call per account is open at a time
+CREATE UNIQUE INDEX IF NOT EXISTS unq_margin_call_open_accountId ON margin_call (accountId) WHERE marginCallStatus <> 'Met';
+
+CREATE TABLE IF NOT EXISTS margin_call_audit (
+    marginCallAuditId BIGSERIAL PRIMARY KEY,
+    marginCallId BIGINT NOT NULL REFERENCES margin_call,
+    eventTime BIGINT NOT NULL,
+    marginCallStatus VARCHAR NOT NULL REFERENCES margin_call_status,
+    equity NUMERIC NOT NULL,
+    maintenanceMargin NUMERIC NOT NULL,
+    description VARCHAR NOT NULL
+);
+
+GRANT SELECT ON TABLE margin_call_status TO broker_user;
+GRANT SELECT, INSERT, UPDATE ON TABLE margin_call TO broker_user;
+GRANT SELECT, INSERT ON TABLE margin_call_audit TO broker_user;
+GRANT SELECT, UPDATE ON ALL SEQUENCES IN SCHEMA public TO broker_user;
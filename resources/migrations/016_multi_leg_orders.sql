@@ -0,0 +1,4 @@
+-- Records how many legs each exchange accepts in one order.
+-- Exchanges without a limit take orders of any number of legs, as they always have.
+
+ALTER TABLE exchange ADD COLUMN maxOrderLegs INT NULL CHECK (maxOrderLegs > 0);
@@ -0,0 +1,50 @@
+-- Adds logical instruments listed on several exchanges, and orders routed across those listings
+
+CREATE TABLE IF NOT EXISTS logical_instrument (
+    logicalInstrumentId SERIAL PRIMARY KEY,
+    logicalKey VARCHAR UNIQUE NOT NULL,
+    symbol VARCHAR NOT NULL,
+    description VARCHAR NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS listing (
+    logicalInstrumentId INT NOT NULL REFERENCES logical_instrument,
+    instrumentId BIGINT NOT NULL REFERENCES instrument,
+    preference INT NOT NULL DEFAULT 0,
+    feePerUnit NUMERIC NOT NULL DEFAULT 0,
+    PRIMARY KEY (logicalInstrumentId, instrumentId)
+);
+
+-- An instrument is a listing of at most one logical instrument
+CREATE UNIQUE INDEX IF NOT EXISTS unq_listing_instrumentId ON listing (instrumentId);
+
+CREATE TABLE IF NOT EXISTS routing_strategy (
+    routingStrategy VARCHAR PRIMARY KEY
+);
+
+INSERT INTO routing_strategy (routingStrategy) VALUES
+    ('BestPrice'),
+    ('Preference'),
+    ('Split') ;
+
+CREATE TABLE IF NOT EXISTS routed_order (
+    routedOrderId BIGSERIAL PRIMARY KEY,
+    accountId INT NOT NULL REFERENCES account,
+    logicalInstrumentId INT NOT NULL REFERENCES logical_instrument,
+    routingStrategy VARCHAR NOT NULL REFERENCES routing_strategy,
+    price NUMERIC NOT NULL,
+    quantity INT NOT NULL,
+    createTime BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS routed_order_child (
+    routedOrderId BIGINT NOT NULL REFERENCES routed_order,
+    orderId BIGINT NOT NULL REFERENCES order_base,
+    instrumentId BIGINT NOT NULL REFERENCES instrument,
+    quantity INT NOT NULL,
+    PRIMARY KEY (routedOrderId, orderId)
+);
+
+GRANT SELECT ON TABLE routing_strategy TO broker_user;
+GRANT SELECT, INSERT ON TABLE logical_instrument, listing, routed_order, routed_order_child TO broker_user;
+GRANT SELECT, UPDATE ON ALL SEQUENCES IN SCHEMA public TO broker_user;
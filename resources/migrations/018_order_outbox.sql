@@ -0,0 +1,26 @@
+-- Adds the outbox of Pending orders still to be delivered to their exchanges
+
+CREATE TABLE IF NOT EXISTS order_outbox (
+    orderId BIGINT PRIMARY KEY REFERENCES order_base,
+    price NUMERIC NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    nextAttemptTime BIGINT NOT NULL,
+    lastError VARCHAR,
+    createTime BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_outbox_nextAttemptTime ON order_outbox (nextAttemptTime);
+
+GRANT SELECT, INSERT, UPDATE, DELETE ON TABLE order_outbox TO broker_user;
+
+-- Orders already Pending have no one responsible for delivering them otherwise. They get the
+-- same entry a new order would, leased for two request timeouts (20000 ms) from now
+INSERT INTO order_outbox (orderId, price, attempts, nextAttemptTime, lastError, createTime)
+SELECT base.orderId, base.price, 0,
+    (EXTRACT(EPOCH FROM clock_timestamp()) * 1000)::BIGINT + 20000,
+    NULL,
+    (EXTRACT(EPOCH FROM clock_timestamp()) * 1000)::BIGINT
+FROM order_state AS state
+JOIN order_base AS base ON base.orderId = state.orderId
+WHERE state.orderStatus = 'Pending'
+ON CONFLICT (orderId) DO NOTHING;
@@ -0,0 +1,18 @@
+-- Grants MakeMarkets to the existing accounts that already quote both sides of an instrument,
+-- now that two-sided quoting requires it.
+--
+-- An account counts as a market maker if it has ever had a buy and a sell order on the same
+-- instrument. Each of its relationships that may submit orders is granted the privilege.
+
+INSERT INTO access (relationshipId, privilege)
+SELECT DISTINCT relationship.relationshipId, 'MakeMarkets'
+FROM actor_account_relationship relationship
+JOIN access submit ON submit.relationshipId = relationship.relationshipId AND submit.privilege = 'Submit'
+WHERE relationship.accountId IN (
+    SELECT base.accountId
+    FROM order_base base
+    JOIN order_leg leg ON leg.orderId = base.orderId
+    GROUP BY base.accountId, leg.instrumentId
+    HAVING bool_or(base.quantity * leg.ratio > 0) AND bool_or(base.quantity * leg.ratio < 0)
+)
+ON CONFLICT (relationshipId, privilege) DO NOTHING;
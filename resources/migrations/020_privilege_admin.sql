@@ -0,0 +1,4 @@
+-- Lets admins revoke account privileges, now that they can be granted and revoked after an
+-- account is opened.
+
+GRANT DELETE ON TABLE public.access TO broker_user;
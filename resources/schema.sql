@@ -1,14 +1,40 @@
 
+DROP TABLE IF EXISTS order_outbox;
+DROP TABLE IF EXISTS routed_order_child;
+DROP TABLE IF EXISTS routed_order;
+DROP TABLE IF EXISTS routing_strategy;
+DROP TABLE IF EXISTS listing;
+DROP TABLE IF EXISTS logical_instrument;
+DROP TABLE IF EXISTS margin_call_audit;
+DROP TABLE IF EXISTS margin_call;
+DROP TABLE IF EXISTS margin_call_status;
+DROP TABLE IF EXISTS market_bar;
+DROP TABLE IF EXISTS bar_interval;
+DROP TABLE IF EXISTS fee_schedule;
+DROP TABLE IF EXISTS ledger_entry;
+DROP TABLE IF EXISTS ledger_journal;
+DROP TABLE IF EXISTS ledger_account;
+DROP TABLE IF EXISTS journal_type;
+DROP TABLE IF EXISTS order_state_snapshot;
+DROP TABLE IF EXISTS position_snapshot;
+DROP TABLE IF EXISTS balance_snapshot;
+DROP TABLE IF EXISTS reconciliation_audit;
 DROP TABLE IF EXISTS balance;
 DROP TABLE IF EXISTS position;
 DROP TABLE IF EXISTS trade;
 
+DROP TABLE IF EXISTS order_group_member;
+DROP TABLE IF EXISTS order_group;
+DROP TABLE IF EXISTS order_group_role;
+DROP TABLE IF EXISTS order_group_type;
 DROP TABLE IF EXISTS order_state_history;
 DROP TABLE IF EXISTS order_state;
 DROP TABLE IF EXISTS order_status;
 
 DROP TABLE IF EXISTS order_leg;
 DROP TABLE IF EXISTS order_base;
+DROP TABLE IF EXISTS time_in_force;
+DROP TABLE IF EXISTS order_type;
 DROP TABLE IF EXISTS order_number_generator;
 
 DROP TABLE IF EXISTS admin_role_power;
@@ -42,7 +68,9 @@ CREATE TABLE IF NOT EXISTS exchange (
     url VARCHAR NOT NULL,
     websocketUrl VARCHAR NOT NULL,
     description VARCHAR NOT NULL,
-    apiKey VARCHAR NOT NULL
+    apiKey VARCHAR NOT NULL,
+    supportsTimeInForce BOOLEAN NOT NULL DEFAULT FALSE,
+    maxOrderLegs INT NULL CHECK (maxOrderLegs > 0)
 );
 
 CREATE TABLE IF NOT EXISTS instrument_status (
@@ -79,7 +107,9 @@ CREATE TABLE IF NOT EXISTS instrument (
     symbol VARCHAR NOT NULL,
     assetClass VARCHAR NOT NULL REFERENCES asset_class,
     description VARCHAR NOT NULL,
-    expirationTime BIGINT NOT NULL
+    expirationTime BIGINT NOT NULL,
+    tickSize NUMERIC NOT NULL DEFAULT 0.01,
+    pricePrecision INT NOT NULL DEFAULT 2
 );
 
 CREATE UNIQUE INDEX unq_exchange_instrument ON instrument (exchangeId, exchangeInstrumentId);
@@ -89,7 +119,8 @@ CREATE TABLE IF NOT EXISTS offer (
     offerId SERIAL PRIMARY KEY,
     code VARCHAR UNIQUE NOT NULL,
     description VARCHAR NOT NULL,
-    expirationTime BIGINT NOT NULL
+    expirationTime BIGINT NOT NULL,
+    vetterName VARCHAR NULL
 );
 
 CREATE TABLE IF NOT EXISTS actor (
@@ -115,7 +146,8 @@ CREATE TABLE IF NOT EXISTS account (
     accountId SERIAL PRIMARY KEY,
     accountKey VARCHAR UNIQUE NOT NULL,
     accountNumber VARCHAR UNIQUE NOT NULL,
-    accountName VARCHAR NOT NULL
+    accountName VARCHAR NOT NULL,
+    vetterName VARCHAR NULL
 );
 
 CREATE TABLE IF NOT EXISTS actor_account_relationship (
@@ -183,6 +215,27 @@ CREATE TABLE IF NOT EXISTS order_number_generator (
     lastOrderNumber INT
 );
 
+CREATE TABLE IF NOT EXISTS time_in_force (
+      timeInForce VARCHAR PRIMARY KEY
+);
+
+INSERT INTO time_in_force (timeInForce) VALUES
+      ('Day'),
+      ('Gtc'),
+      ('Gtd'),
+      ('Ioc'),
+      ('Fok') ;
+
+CREATE TABLE IF NOT EXISTS order_type (
+      orderType VARCHAR PRIMARY KEY
+);
+
+INSERT INTO order_type (orderType) VALUES
+      ('Limit'),
+      ('Stop'),
+      ('StopLimit'),
+      ('TrailingStop') ;
+
 CREATE TABLE IF NOT EXISTS order_base (
       orderId BIGSERIAL PRIMARY KEY,
       accountId INT NOT NULL REFERENCES account,
@@ -190,8 +243,13 @@ CREATE TABLE IF NOT EXISTS order_base (
       extOrderId VARCHAR NOT NULL,
       clientOrderId VARCHAR NOT NULL,
       createTime BIGINT NOT NULL,
-      price REAL NOT NULL,
-      quantity INT NOT NULL
+      price NUMERIC NOT NULL,
+      quantity INT NOT NULL,
+      timeInForce VARCHAR NOT NULL DEFAULT 'Gtc' REFERENCES time_in_force,
+      expireTime BIGINT NULL,
+      orderType VARCHAR NOT NULL DEFAULT 'Limit' REFERENCES order_type,
+      stopPrice NUMERIC NULL,
+      trailingOffset NUMERIC NULL
 );
 
 CREATE UNIQUE INDEX unq_accountId_clientOrder ON order_base (accountId, clientOrderId);
@@ -213,15 +271,20 @@ INSERT INTO order_status (orderStatus) VALUES
       ('Rejected'),
       ('Pending'),
       ('Open'),
+      ('PartiallyFilled'),
       ('Filled'),
       ('PendingCancel'),
       ('Canceled'),
-      ('Expired') ;
+      ('Expired'),
+      ('PendingTrigger'),
+      ('PendingParent') ;
 
 CREATE TABLE IF NOT EXISTS order_state (
       orderId BIGINT PRIMARY KEY REFERENCES order_base,
       orderStatus VARCHAR NOT NULL REFERENCES order_status,
       rejectReason VARCHAR NULL,
+      filledQuantity INT NOT NULL DEFAULT 0,
+      averageFillPrice NUMERIC NOT NULL DEFAULT 0,
       updateTime BIGINT NOT NULL,
       versionNumber BIGINT NOT NULL
 );
@@ -231,24 +294,69 @@ CREATE TABLE IF NOT EXISTS order_state_history (
       orderId BIGINT REFERENCES order_base NOT NULL,
       orderStatus VARCHAR NOT NULL REFERENCES order_status,
       rejectReason VARCHAR NULL,
+      filledQuantity INT NOT NULL DEFAULT 0,
+      averageFillPrice NUMERIC NOT NULL DEFAULT 0,
       createTime BIGINT NOT NULL,
-      versionNumber BIGINT NOT NULL
+      versionNumber BIGINT NOT NULL,
+      replacesOrderId BIGINT NULL REFERENCES order_base,
+      replacedByOrderId BIGINT NULL REFERENCES order_base
+);
+
+CREATE TABLE IF NOT EXISTS order_group_type (
+      groupType VARCHAR PRIMARY KEY
+);
+
+INSERT INTO order_group_type (groupType) VALUES
+      ('Bracket'),
+      ('Oco') ;
+
+CREATE TABLE IF NOT EXISTS order_group_role (
+      groupRole VARCHAR PRIMARY KEY
 );
 
+INSERT INTO order_group_role (groupRole) VALUES
+      ('Entry'),
+      ('TakeProfit'),
+      ('StopLoss'),
+      ('Oco') ;
+
+CREATE TABLE IF NOT EXISTS order_group (
+      orderGroupId BIGSERIAL PRIMARY KEY,
+      accountId INT NOT NULL REFERENCES account,
+      groupType VARCHAR NOT NULL REFERENCES order_group_type,
+      createTime BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS order_group_member (
+      orderGroupId BIGINT NOT NULL REFERENCES order_group,
+      orderId BIGINT NOT NULL REFERENCES order_base,
+      groupRole VARCHAR NOT NULL REFERENCES order_group_role,
+      PRIMARY KEY (orderGroupId, orderId)
+);
+
+CREATE UNIQUE INDEX unq_order_group_member_orderId ON order_group_member (orderId);
+
 CREATE TABLE IF NOT EXISTS trade (
       tradeId BIGSERIAL PRIMARY KEY,
       orderLegId BIGINT NOT NULL REFERENCES order_leg,
+      exchangeExecutionId BIGINT NOT NULL,
       createTime BIGINT NOT NULL,
-      price FLOAT NOT NULL
+      price NUMERIC NOT NULL,
+      quantity INT NOT NULL,
+      fee NUMERIC NOT NULL DEFAULT 0
 );
 
+CREATE UNIQUE INDEX unq_trade_execution ON trade (orderLegId, exchangeExecutionId);
+
+CREATE INDEX idx_trade_orderLegId ON trade (orderLegId);
+
 CREATE TABLE IF NOT EXISTS position (
     positionId BIGSERIAL PRIMARY KEY,
     accountId INT NOT NULL REFERENCES account,
     instrumentId BIGINT NOT NULL REFERENCES instrument,
-    cost REAL NOT NULL,
+    cost NUMERIC NOT NULL,
     quantity INT NOT NULL,
-    closedGain REAL NOT NULL,
+    closedGain NUMERIC NOT NULL,
     updateTime BIGINT NOT NULL,
     versionNumber BIGINT NOT NULL
 );
@@ -256,20 +364,235 @@ CREATE TABLE IF NOT EXISTS position (
 CREATE TABLE IF NOT EXISTS balance (
     balanceId SERIAL PRIMARY KEY,
     accountId INT UNIQUE NOT NULL REFERENCES account,
-    cash REAL NOT NULL,
+    cash NUMERIC NOT NULL,
     updateTime BIGINT NOT NULL,
     versionNumber BIGINT NOT NULL
 );
 
-GRANT SELECT ON TABLE privilege, power, admin_role_power, admin_role_membership TO broker_user;
+CREATE TABLE IF NOT EXISTS reconciliation_audit (
+    reconciliationAuditId BIGSERIAL PRIMARY KEY,
+    exchangeId INT NOT NULL REFERENCES exchange,
+    orderId BIGINT NOT NULL REFERENCES order_base,
+    createTime BIGINT NOT NULL,
+    discrepancy VARCHAR NOT NULL,
+    description VARCHAR NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS balance_snapshot (
+    balanceSnapshotId BIGSERIAL PRIMARY KEY,
+    accountId INT NOT NULL REFERENCES account,
+    snapshotDate VARCHAR NOT NULL,
+    cash NUMERIC NOT NULL,
+    createTime BIGINT NOT NULL
+);
+
+CREATE UNIQUE INDEX unq_balance_snapshot ON balance_snapshot (accountId, snapshotDate);
+
+CREATE TABLE IF NOT EXISTS position_snapshot (
+    positionSnapshotId BIGSERIAL PRIMARY KEY,
+    accountId INT NOT NULL REFERENCES account,
+    snapshotDate VARCHAR NOT NULL,
+    instrumentId BIGINT NOT NULL REFERENCES instrument,
+    quantity INT NOT NULL,
+    cost NUMERIC NOT NULL,
+    closedGain NUMERIC NOT NULL,
+    createTime BIGINT NOT NULL
+);
+
+CREATE UNIQUE INDEX unq_position_snapshot ON position_snapshot (accountId, snapshotDate, instrumentId);
+
+CREATE TABLE IF NOT EXISTS order_state_snapshot (
+    orderStateSnapshotId BIGSERIAL PRIMARY KEY,
+    orderId BIGINT NOT NULL REFERENCES order_base,
+    snapshotDate VARCHAR NOT NULL,
+    orderStatus VARCHAR NOT NULL REFERENCES order_status,
+    rejectReason VARCHAR NULL,
+    filledQuantity INT NOT NULL,
+    averageFillPrice NUMERIC NOT NULL,
+    updateTime BIGINT NOT NULL,
+    versionNumber BIGINT NOT NULL
+);
+
+CREATE UNIQUE INDEX unq_order_state_snapshot ON order_state_snapshot (orderId, snapshotDate);
+
+CREATE TABLE IF NOT EXISTS journal_type (
+    journalType VARCHAR PRIMARY KEY
+);
+
+INSERT INTO journal_type (journalType) VALUES
+    ('InitialFunding'),
+    ('Execution'),
+    ('Deposit'),
+    ('Withdrawal'),
+    ('Fee'),
+    ('Adjustment');
+
+CREATE TABLE IF NOT EXISTS ledger_account (
+    ledgerAccount VARCHAR PRIMARY KEY
+);
+
+INSERT INTO ledger_account (ledgerAccount) VALUES
+    ('CustomerCash'),
+    ('ExternalFunds'),
+    ('ExchangeSettlement'),
+    ('FeeIncome'),
+    ('Adjustments');
+
+CREATE TABLE IF NOT EXISTS ledger_journal (
+    journalId BIGSERIAL PRIMARY KEY,
+    journalType VARCHAR NOT NULL REFERENCES journal_type,
+    tradeId BIGINT NULL REFERENCES trade,
+    createTime BIGINT NOT NULL,
+    description VARCHAR NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS ledger_entry (
+    ledgerEntryId BIGSERIAL PRIMARY KEY,
+    journalId BIGINT NOT NULL REFERENCES ledger_journal,
+    accountId INT NOT NULL REFERENCES account,
+    ledgerAccount VARCHAR NOT NULL REFERENCES ledger_account,
+    amount NUMERIC NOT NULL
+);
+
+CREATE INDEX idx_ledger_entry_journalId ON ledger_entry (journalId);
+CREATE INDEX idx_ledger_entry_accountId ON ledger_entry (accountId, ledgerAccount);
+
+CREATE TABLE IF NOT EXISTS fee_schedule (
+    feeScheduleId SERIAL PRIMARY KEY,
+    offerId INT NULL REFERENCES offer,
+    accountId INT NULL REFERENCES account,
+    assetClass VARCHAR NULL REFERENCES asset_class,
+    perShare NUMERIC NOT NULL DEFAULT 0,
+    perContract NUMERIC NOT NULL DEFAULT 0,
+    basisPoints NUMERIC NOT NULL DEFAULT 0,
+    minimumTicket NUMERIC NOT NULL DEFAULT 0,
+    CHECK (offerId IS NULL OR accountId IS NULL)
+);
+
+CREATE TABLE IF NOT EXISTS bar_interval (
+    barInterval VARCHAR PRIMARY KEY
+);
+
+INSERT INTO bar_interval (barInterval) VALUES
+    ('1s'),
+    ('1m'),
+    ('5m'),
+    ('1h'),
+    ('1d') ;
+
+CREATE TABLE IF NOT EXISTS market_bar (
+    instrumentId BIGINT NOT NULL REFERENCES instrument,
+    barInterval VARCHAR NOT NULL REFERENCES bar_interval,
+    startTime BIGINT NOT NULL,
+    openPrice NUMERIC NOT NULL,
+    highPrice NUMERIC NOT NULL,
+    lowPrice NUMERIC NOT NULL,
+    closePrice NUMERIC NOT NULL,
+    volume BIGINT NOT NULL,
+    tradeCount BIGINT NOT NULL,
+    PRIMARY KEY (instrumentId, barInterval, startTime)
+);
+
+CREATE TABLE IF NOT EXISTS margin_call_status (
+    marginCallStatus VARCHAR PRIMARY KEY
+);
+
+INSERT INTO margin_call_status (marginCallStatus) VALUES
+    ('Called'),
+    ('Liquidating'),
+    ('Met') ;
+
+CREATE TABLE IF NOT EXISTS margin_call (
+    marginCallId BIGSERIAL PRIMARY KEY,
+    accountId INT NOT NULL REFERENCES account,
+    marginCallStatus VARCHAR NOT NULL REFERENCES margin_call_status,
+    equity NUMERIC NOT NULL,
+    maintenanceMargin NUMERIC NOT NULL,
+    callTime BIGINT NOT NULL,
+    updateTime BIGINT NOT NULL
+);
+
+CREATE UNIQUE INDEX unq_margin_call_open_accountId ON margin_call (accountId) WHERE marginCallStatus <> 'Met';
+
+CREATE TABLE IF NOT EXISTS margin_call_audit (
+    marginCallAuditId BIGSERIAL PRIMARY KEY,
+    marginCallId BIGINT NOT NULL REFERENCES margin_call,
+    eventTime BIGINT NOT NULL,
+    marginCallStatus VARCHAR NOT NULL REFERENCES margin_call_status,
+    equity NUMERIC NOT NULL,
+    maintenanceMargin NUMERIC NOT NULL,
+    description VARCHAR NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS logical_instrument (
+    logicalInstrumentId SERIAL PRIMARY KEY,
+    logicalKey VARCHAR UNIQUE NOT NULL,
+    symbol VARCHAR NOT NULL,
+    description VARCHAR NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS listing (
+    logicalInstrumentId INT NOT NULL REFERENCES logical_instrument,
+    instrumentId BIGINT NOT NULL REFERENCES instrument,
+    preference INT NOT NULL DEFAULT 0,
+    feePerUnit NUMERIC NOT NULL DEFAULT 0,
+    PRIMARY KEY (logicalInstrumentId, instrumentId)
+);
+
+CREATE UNIQUE INDEX unq_listing_instrumentId ON listing (instrumentId);
+
+CREATE TABLE IF NOT EXISTS routing_strategy (
+    routingStrategy VARCHAR PRIMARY KEY
+);
+
+INSERT INTO routing_strategy (routingStrategy) VALUES
+    ('BestPrice'),
+    ('Preference'),
+    ('Split') ;
+
+CREATE TABLE IF NOT EXISTS routed_order (
+    routedOrderId BIGSERIAL PRIMARY KEY,
+    accountId INT NOT NULL REFERENCES account,
+    logicalInstrumentId INT NOT NULL REFERENCES logical_instrument,
+    routingStrategy VARCHAR NOT NULL REFERENCES routing_strategy,
+    price NUMERIC NOT NULL,
+    quantity INT NOT NULL,
+    createTime BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS routed_order_child (
+    routedOrderId BIGINT NOT NULL REFERENCES routed_order,
+    orderId BIGINT NOT NULL REFERENCES order_base,
+    instrumentId BIGINT NOT NULL REFERENCES instrument,
+    quantity INT NOT NULL,
+    PRIMARY KEY (routedOrderId, orderId)
+);
+
+CREATE TABLE IF NOT EXISTS order_outbox (
+    orderId BIGINT PRIMARY KEY REFERENCES order_base,
+    price NUMERIC NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    nextAttemptTime BIGINT NOT NULL,
+    lastError VARCHAR,
+    createTime BIGINT NOT NULL
+);
+
+CREATE INDEX idx_order_outbox_nextAttemptTime ON order_outbox (nextAttemptTime);
+
+GRANT SELECT ON TABLE privilege, power, admin_role_power, admin_role_membership, journal_type, ledger_account, time_in_force, order_type, order_group_type, order_group_role, bar_interval, margin_call_status, routing_strategy TO broker_user;
 
 GRANT SELECT, INSERT ON TABLE order_base, order_number_generator, order_leg, order_status, order_state,
     order_state_history, trade, position, balance, actor, login_info, offer, account, balance,
-    actor_account_relationship, access, api_key, exchange, instrument
+    actor_account_relationship, access, api_key, exchange, instrument, reconciliation_audit,
+    balance_snapshot, position_snapshot, order_state_snapshot, ledger_journal, ledger_entry, fee_schedule, order_group, order_group_member, market_bar,
+    margin_call, margin_call_audit, logical_instrument, listing, routed_order, routed_order_child, order_outbox
+    TO broker_user;
+
+GRANT UPDATE ON TABLE public.order_state, public.order_base, public.position, public.balance, public.order_number_generator,
+    public.login_info, public.instrument, public.account, public.market_bar, public.margin_call, public.order_outbox
     TO broker_user;
 
-GRANT UPDATE ON TABLE public.order_state, public.position, public.balance, public.order_number_generator,
-    public.login_info, public.instrument
+GRANT DELETE ON TABLE public.fee_schedule, public.order_outbox, public.access
     TO broker_user;
 
 GRANT SELECT, UPDATE ON ALL SEQUENCES IN SCHEMA public TO broker_user;
\ No newline at end of file
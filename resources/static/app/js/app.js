@@ -60,7 +60,9 @@ let ordersTable = new DataTable('#orders_table', {
         {title: 'Side', data: 'side', name: 'side', className: 'dt-right', orderData: [0, 4]},
         {title: 'Quantity', data: 'quantity', name: 'quantity', className: 'dt-right', orderData: [0, 5]},
         {title: 'Price', data: 'price', name: 'price', className: 'dt-right', orderData: [0, 6]},
-        {title: 'Actions', data: 'actions', name: 'actions', className: 'dt-right', orderData: [0, 7]},
+        {title: 'Filled', data: 'filled', name: 'filled', className: 'dt-right', orderData: [0, 7]},
+        {title: 'Avg Price', data: 'average_fill_price', name: 'average_fill_price', className: 'dt-right', orderData: [0, 8]},
+        {title: 'Actions', data: 'actions', name: 'actions', className: 'dt-right', orderData: [0, 9]},
         {title: 'RowId', data: 'row_id', name: 'row_id', className: 'dt-right', visible: false},
     ],
     rowId: 'row_id',
@@ -209,7 +211,8 @@ function orderStateCallback(orderState) {
 
     let actions = "";
     if (orderState.order_status === 'Pending' ||
-        orderState.order_status === 'Open') {
+        orderState.order_status === 'Open' ||
+        orderState.order_status === 'PartiallyFilled') {
         actions += "<button id='" + cancelButtonId + "' className='btn btn-default' type='submit'>Cancel</button>";
     }
     let accountDescription = getAccountSpan(orderState.order.account_key);
@@ -233,19 +236,24 @@ function orderStateCallback(orderState) {
             side: orderSide,
             quantity: Math.abs(orderState.order.quantity),
             price: render(orderState.order.price),
+            filled: Math.abs(orderState.filled_quantity),
+            average_fill_price: render(orderState.average_fill_price),
             actions: actions,
             row_id: id
         }).draw().node();
         orderNode.setAttribute('id', id);
     } else {
         updateCell(ordersTable, id, 'status', orderStatus);
+        updateCell(ordersTable, id, 'filled', Math.abs(orderState.filled_quantity));
+        updateCell(ordersTable, id, 'average_fill_price', render(orderState.average_fill_price));
         updateCell(ordersTable, id, 'actions', actions);
 
         ordersTable.draw()
     }
 
     if (orderState.order_status === 'Pending' ||
-        orderState.order_status === 'Open') {
+        orderState.order_status === 'Open' ||
+        orderState.order_status === 'PartiallyFilled') {
         document.getElementById(cancelButtonId).addEventListener("click", () => {
             openBroker.cancelOrder(orderState.order.account_key, orderState.order.ext_order_id);
         });
@@ -471,6 +479,8 @@ function renderOrderStatus(orderStatus) {
     switch (orderStatus) {
         case "PendingCancel":
             return "Pending Cancel";
+        case "PartiallyFilled":
+            return "Partially Filled";
         default:
             return orderStatus;
     }
@@ -8,8 +8,8 @@ use log::{debug, info};
 use std::collections::HashMap;
 
 const SESSION_ACTOR_KEY: &'static str = "actor";
-const SESSION_ACCOUNT_MAP_KEY: &'static str = "accounts";
-const SESSION_POWERS: &'static str = "powers";
+pub(crate) const SESSION_ACCOUNT_MAP_KEY: &'static str = "accounts";
+pub(crate) const SESSION_POWERS: &'static str = "powers";
 
 #[derive(Clone)]
 pub struct AccessControl {
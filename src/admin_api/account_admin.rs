@@ -0,0 +1,196 @@
+use crate::access_control::AccessControl;
+use crate::dtos::account::{AccountVetter, Privilege};
+use crate::dtos::actor::Power;
+use crate::persistence::dao::Dao;
+use crate::rest_api::base_api::log_dao_error_and_return_500;
+use crate::vetting::vetter_registry::VetterRegistry;
+use actix_session::Session;
+use actix_web::web::{Json, Path, ThinData};
+use actix_web::HttpResponse;
+use log::{error, info};
+use std::str::FromStr;
+
+#[put("/admin/accounts/{account_key}/vetter")]
+pub async fn set_account_vetter(dao: ThinData<Dao>,
+                                access_control: ThinData<AccessControl>,
+                                vetter_registry: ThinData<VetterRegistry>,
+                                session: Session,
+                                path: Path<String>,
+                                account_vetter: Json<AccountVetter>,
+) -> HttpResponse {
+    info!("set_account_vetter called");
+
+    let allowed: bool = match access_control.is_admin_allowed_power(&session, Power::All) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking admin access: {}", error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let account_key = path.into_inner();
+
+    if let Some(vetter_name) = &account_vetter.vetter_name && !vetter_registry.has_vetter(vetter_name.as_str()) {
+        return HttpResponse::PreconditionFailed().json(format!("vetter {} is unknown", vetter_name));
+    }
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.update_vetter_name(&account_key, &account_vetter.vetter_name).await {
+        Ok(_) => {}
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.commit().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    HttpResponse::Ok().finish()
+}
+
+// Privileges are copied into the session at login, so a change reaches an actor already logged in
+// only when they next log in. Owner comes with opening the account and is not granted or revoked here
+#[put("/admin/accounts/{account_key}/actors/{email_address}/privileges/{privilege}")]
+pub async fn grant_privilege(dao: ThinData<Dao>,
+                             access_control: ThinData<AccessControl>,
+                             session: Session,
+                             path: Path<(String, String, String)>,
+) -> HttpResponse {
+    info!("grant_privilege called");
+    update_privilege(&dao, &access_control, &session, path.into_inner(), true).await
+}
+
+#[delete("/admin/accounts/{account_key}/actors/{email_address}/privileges/{privilege}")]
+pub async fn revoke_privilege(dao: ThinData<Dao>,
+                              access_control: ThinData<AccessControl>,
+                              session: Session,
+                              path: Path<(String, String, String)>,
+) -> HttpResponse {
+    info!("revoke_privilege called");
+    update_privilege(&dao, &access_control, &session, path.into_inner(), false).await
+}
+
+async fn update_privilege(dao: &Dao,
+                          access_control: &AccessControl,
+                          session: &Session,
+                          (account_key, email_address, privilege): (String, String, String),
+                          grant: bool) -> HttpResponse {
+    let allowed: bool = match access_control.is_admin_allowed_power(session, Power::All) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking admin access: {}", error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let privilege = match Privilege::from_str(privilege.as_str()) {
+        Ok(Privilege::Owner) => return HttpResponse::BadRequest().json("Owner cannot be granted or revoked"),
+        Ok(privilege) => privilege,
+        Err(()) => return HttpResponse::BadRequest().json(format!("privilege {} is unknown", privilege)),
+    };
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let relationship_id = match txn.get_relationship_id(&account_key, &email_address).await {
+        Ok(Some(relationship_id)) => relationship_id,
+        Ok(None) => return HttpResponse::NotFound().json(format!("{} has no relationship with account {}", email_address, account_key)),
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let update_result = match grant {
+        true => txn.grant_privilege(relationship_id, &privilege).await,
+        false => txn.revoke_privilege(relationship_id, &privilege).await,
+    };
+    match update_result {
+        Ok(_) => {}
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.commit().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    HttpResponse::Ok().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::access_control::{AccessControl, SESSION_POWERS};
+    use crate::admin_api::account_admin::{grant_privilege, revoke_privilege};
+    use crate::dtos::account::Privilege;
+    use crate::dtos::actor::Power;
+    use crate::persistence::account_management::create_test_account;
+    use crate::persistence::dao::{test_dao, Dao};
+    use actix_session::SessionExt;
+    use actix_web::dev::Service;
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::web::ThinData;
+    use actix_web::{test, App};
+
+    async fn call(dao: &Dao,
+                  powers: Vec<Power>,
+                  method: Method,
+                  uri: &str) -> StatusCode {
+        let app = test::init_service(
+            App::new()
+                .app_data(ThinData(dao.clone()))
+                .app_data(ThinData(AccessControl::new()))
+                .wrap_fn(move |req, srv| {
+                    req.get_session().insert(SESSION_POWERS, &powers).unwrap();
+                    srv.call(req)
+                })
+                .service(grant_privilege)
+                .service(revoke_privilege)
+        ).await;
+        test::call_service(&app, test::TestRequest::default().method(method).uri(uri).to_request()).await.status()
+    }
+
+    async fn privileges(dao: &Dao) -> Vec<Privilege> {
+        let mut db_connection = dao.get_connection().await.unwrap();
+        let txn = dao.begin(&mut db_connection).await.unwrap();
+        let actor = txn.get_actor("trader@example.com").await.unwrap().unwrap();
+        let accesses = txn.get_accesses_for_actor(actor.actor_id).await.unwrap();
+        txn.rollback().await.unwrap();
+        accesses.into_iter().map(|access| access.privilege).collect()
+    }
+
+    #[test]
+    async fn test_grant_and_revoke_privilege() {
+        let Some(dao) = test_dao().await else { return };
+        let account_key = create_test_account(&dao, "trader").await;
+        let uri = |privilege: &str| format!("/admin/accounts/{}/actors/trader@example.com/privileges/{}", account_key, privilege);
+
+        assert_eq!(call(&dao, vec![Power::Read], Method::PUT, &uri("MakeMarkets")).await, StatusCode::FORBIDDEN);
+        assert!(!privileges(&dao).await.contains(&Privilege::MakeMarkets));
+
+        assert_eq!(call(&dao, vec![Power::All], Method::PUT, &uri("MakeMarkets")).await, StatusCode::OK);
+        assert_eq!(call(&dao, vec![Power::All], Method::PUT, &uri("Withdraw")).await, StatusCode::OK);
+        // A second grant is a no-op
+        assert_eq!(call(&dao, vec![Power::All], Method::PUT, &uri("Withdraw")).await, StatusCode::OK);
+        let granted = privileges(&dao).await;
+        assert!(granted.contains(&Privilege::MakeMarkets) && granted.contains(&Privilege::Withdraw));
+
+        assert_eq!(call(&dao, vec![Power::All], Method::DELETE, &uri("MakeMarkets")).await, StatusCode::OK);
+        let revoked = privileges(&dao).await;
+        assert!(!revoked.contains(&Privilege::MakeMarkets) && revoked.contains(&Privilege::Withdraw));
+
+        assert_eq!(call(&dao, vec![Power::All], Method::DELETE, &uri("Owner")).await, StatusCode::BAD_REQUEST);
+        assert_eq!(call(&dao, vec![Power::All], Method::PUT, &uri("Everything")).await, StatusCode::BAD_REQUEST);
+        assert_eq!(call(&dao, vec![Power::All], Method::PUT,
+                        &format!("/admin/accounts/{}/actors/nobody@example.com/privileges/Withdraw", account_key)).await,
+                   StatusCode::NOT_FOUND);
+    }
+}
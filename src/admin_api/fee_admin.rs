@@ -0,0 +1,104 @@
+use crate::access_control::AccessControl;
+use crate::constants::APPLICATION_JSON;
+use crate::dtos::actor::Power;
+use crate::dtos::fee::FeeSchedule;
+use crate::money::Money;
+use crate::persistence::dao::Dao;
+use crate::rest_api::base_api::log_dao_error_and_return_500;
+use actix_session::Session;
+use actix_web::web::{Json, ThinData};
+use actix_web::HttpResponse;
+use log::{error, info};
+
+#[post("/admin/fee_schedules")]
+pub async fn save_fee_schedule(dao: ThinData<Dao>,
+                               access_control: ThinData<AccessControl>,
+                               session: Session,
+                               fee_schedule: Json<FeeSchedule>,
+) -> HttpResponse {
+    info!("save_fee_schedule called");
+
+    let allowed: bool = match access_control.is_admin_allowed_power(&session, Power::All) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking admin access: {}", error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    if fee_schedule.offer_code.is_some() && fee_schedule.account_key.is_some() {
+        return HttpResponse::BadRequest().json("A fee schedule may apply to an offer or an account, not both");
+    }
+    if fee_schedule.per_share < Money::ZERO
+        || fee_schedule.per_contract < Money::ZERO
+        || fee_schedule.basis_points < Money::ZERO
+        || fee_schedule.minimum_ticket < Money::ZERO {
+        return HttpResponse::BadRequest().json("Fees must not be negative");
+    }
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let saved_fee_schedule = match txn.save_fee_schedule(fee_schedule.to_entities_fee_schedule()).await {
+        Ok(Some(saved_fee_schedule)) => saved_fee_schedule,
+        Ok(None) => return HttpResponse::NotFound().json("Unknown offer or account"),
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.commit().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(saved_fee_schedule.to_rest_api_fee_schedule())
+}
+
+#[get("/admin/fee_schedules")]
+pub async fn get_fee_schedules(dao: ThinData<Dao>,
+                               access_control: ThinData<AccessControl>,
+                               session: Session,
+) -> HttpResponse {
+    info!("get_fee_schedules called");
+
+    let allowed: bool = match access_control.is_admin_allowed_power(&session, Power::Read) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking admin access: {}", error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let fee_schedules = match txn.get_fee_schedules().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.rollback().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+
+    let rest_api_fee_schedules: Vec<FeeSchedule> = fee_schedules.iter()
+        .map(|fee_schedule| fee_schedule.to_rest_api_fee_schedule())
+        .collect();
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(rest_api_fee_schedules)
+}
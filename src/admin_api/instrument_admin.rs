@@ -1,4 +1,5 @@
 use crate::access_control::AccessControl;
+use crate::constants::APPLICATION_JSON;
 use crate::dtos;
 use crate::dtos::actor::Power;
 use crate::exchange_interface::exchange_client::ExchangeClient;
@@ -91,7 +92,8 @@ pub async fn load_exchange_instruments(dao: ThinData<Dao>,
         Err(dao_error) => return log_dao_error_and_return_500(dao_error),
     };
 
-    let exchange_client = Arc::new(ExchangeClient::new(exchange.url.as_str(), exchange.api_key.as_str()));
+    let exchange_client = Arc::new(ExchangeClient::new(exchange.exchange_id, exchange.url.as_str(), exchange.api_key.as_str(),
+                                                       instrument_manager.get_exchange_health().clone()));
     let instruments = match exchange_client.clone().get_instruments().await {
         Ok(instruments) => instruments,
         Err(instrument_error) => {
@@ -120,3 +122,79 @@ pub async fn load_exchange_instruments(dao: ThinData<Dao>,
     HttpResponse::Ok().finish()
 }
 
+
+// The listings must already be loaded as instruments on their exchanges
+#[post("/admin/logical_instrument")]
+pub async fn create_logical_instrument(dao: ThinData<Dao>,
+                                       instrument_manager: ThinData<InstrumentManager>,
+                                       access_control: ThinData<AccessControl>,
+                                       session: Session,
+                                       logical_instrument: Json<dtos::routing::LogicalInstrument>,
+) -> HttpResponse {
+    info!("create_logical_instrument called");
+
+    let allowed: bool = match access_control.is_admin_allowed_power(&session, Power::All) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking admin access: {}", error.to_string());
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    if logical_instrument.listings.is_empty() {
+        return HttpResponse::BadRequest().json("A logical instrument needs at least one listing");
+    }
+    let db_logical_instrument = match logical_instrument.to_entities_logical_instrument(&instrument_manager) {
+        Ok(x) => x,
+        Err(convert_error) => return HttpResponse::BadRequest().json(convert_error.to_string()),
+    };
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let db_logical_instrument = match txn.save_logical_instrument(db_logical_instrument).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.commit().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match instrument_manager.get_order_router().add(db_logical_instrument) {
+        Ok(_) => {},
+        Err(router_error) => return log_anyhow_error_and_return_500(router_error),
+    };
+    HttpResponse::Ok().finish()
+}
+
+#[get("/admin/exchanges/status")]
+pub async fn get_exchanges_status(instrument_manager: ThinData<InstrumentManager>,
+                                  access_control: ThinData<AccessControl>,
+                                  session: Session,
+) -> HttpResponse {
+    info!("get_exchanges_status called");
+
+    let allowed: bool = match access_control.is_admin_allowed_power(&session, Power::All) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking admin access: {}", error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    match instrument_manager.get_exchange_health().get_all() {
+        Ok(exchange_healths) => HttpResponse::Ok()
+            .content_type(APPLICATION_JSON)
+            .json(exchange_healths),
+        Err(health_error) => log_anyhow_error_and_return_500(health_error),
+    }
+}
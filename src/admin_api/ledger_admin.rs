@@ -0,0 +1,87 @@
+use crate::access_control::AccessControl;
+use crate::constants::APPLICATION_JSON;
+use crate::dtos::actor::Power;
+use crate::dtos::ledger::{AdminJournal, LedgerDiscrepancy};
+use crate::persistence::dao::Dao;
+use crate::rest_api::base_api::log_dao_error_and_return_500;
+use crate::rest_api::cash_api::post_cash_movement;
+use crate::websockets::server::WebSocketServer;
+use actix_session::Session;
+use actix_web::web::{Json, Path, ThinData};
+use actix_web::HttpResponse;
+use log::{error, info};
+
+#[post("/admin/accounts/{account_key}/journal")]
+pub async fn post_admin_journal(dao: ThinData<Dao>,
+                                access_control: ThinData<AccessControl>,
+                                mut web_socket_server: ThinData<WebSocketServer>,
+                                session: Session,
+                                path: Path<String>,
+                                admin_journal: Json<AdminJournal>,
+) -> HttpResponse {
+    info!("post_admin_journal called");
+
+    let allowed: bool = match access_control.is_admin_allowed_power(&session, Power::All) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking admin access: {}", error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let account_key = path.into_inner();
+
+    if admin_journal.amount.is_zero() {
+        return HttpResponse::BadRequest().json("amount must be non-zero");
+    }
+    if admin_journal.description.trim().is_empty() {
+        return HttpResponse::BadRequest().json("description is required");
+    }
+    let admin_journal = admin_journal.into_inner();
+    post_cash_movement(&dao, &mut web_socket_server, &account_key, admin_journal.journal_type, admin_journal.amount, admin_journal.description, true).await
+}
+
+#[get("/admin/ledger/reconciliation")]
+pub async fn get_ledger_reconciliation(dao: ThinData<Dao>,
+                                       access_control: ThinData<AccessControl>,
+                                       session: Session,
+) -> HttpResponse {
+    info!("get_ledger_reconciliation called");
+
+    let allowed: bool = match access_control.is_admin_allowed_power(&session, Power::Read) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking admin access: {}", error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let discrepancies = match txn.get_ledger_discrepancies().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.rollback().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+
+    let rest_api_discrepancies: Vec<LedgerDiscrepancy> = discrepancies.iter()
+        .map(|discrepancy| discrepancy.to_rest_api_ledger_discrepancy())
+        .collect();
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(rest_api_discrepancies)
+}
@@ -1,2 +1,5 @@
 pub(crate) mod offer_admin;
-pub(crate) mod instrument_admin;
\ No newline at end of file
+pub(crate) mod instrument_admin;
+pub(crate) mod account_admin;
+pub(crate) mod ledger_admin;
+pub(crate) mod fee_admin;
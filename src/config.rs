@@ -1,3 +1,4 @@
+use crate::money::Money;
 use confik::Configuration;
 use serde::Deserialize;
 
@@ -10,6 +11,58 @@ pub struct BrokerConfig {
     pub redis_addr: String,
     pub password_key: String,
     pub session_key: String,
+    #[confik(default = "Risk")]
+    pub default_vetter: String,
+    #[confik(default = 1000000)]
+    pub max_gross_exposure: Money,
+    #[confik(default = 500000)]
+    pub max_net_exposure: Money,
+    #[confik(default = 1000000)]
+    pub max_open_order_notional: Money,
+    #[confik(default = 21u8)]
+    pub end_of_day_hour_utc: u8,
+    #[confik(default = 1000u64)]
+    pub expiry_sweep_interval_millis: u64,
+    #[confik(default = 1000u64)]
+    pub mark_push_interval_millis: u64,
+    #[confik(default = 1000u64)]
+    pub bar_save_interval_millis: u64,
+    #[confik(default = Money::new(5, 1))]
+    pub equity_initial_margin_rate: Money,
+    #[confik(default = Money::new(25, 2))]
+    pub equity_long_maintenance_rate: Money,
+    #[confik(default = Money::new(3, 1))]
+    pub equity_short_maintenance_rate: Money,
+    #[confik(default = Money::new(1, 1))]
+    pub future_scan_rate: Money,
+    #[confik(default = Money::new(75, 2))]
+    pub future_spread_credit_rate: Money,
+    #[confik(default = Money::new(11, 1))]
+    pub future_initial_margin_ratio: Money,
+    #[confik(default = Money::from(1))]
+    pub default_margin_rate: Money,
+    #[confik(default = 1000u64)]
+    pub margin_monitor_interval_millis: u64,
+    #[confik(default = false)]
+    pub margin_liquidation_enabled: bool,
+    #[confik(default = 3600000i64)]
+    pub margin_call_grace_millis: i64,
+    #[confik(default = Money::new(5, 2))]
+    pub liquidation_slippage_rate: Money,
+    #[confik(default = 3i32)]
+    pub exchange_failure_threshold: i32,
+    #[confik(default = 30000i64)]
+    pub exchange_circuit_open_millis: i64,
+    #[confik(default = 1000u64)]
+    pub exchange_health_interval_millis: u64,
+    #[confik(default = 1000u64)]
+    pub outbox_interval_millis: u64,
+    #[confik(default = 5000i64)]
+    pub outbox_retry_millis: i64,
+    #[confik(default = 60000i64)]
+    pub outbox_max_retry_millis: i64,
+    #[confik(default = 300000i64)]
+    pub outbox_give_up_millis: i64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -1,3 +1,4 @@
 pub const APPLICATION_JSON: &str = "application/json";
+pub const TEXT_CSV: &str = "text/csv";
 
 pub const ACCOUNT_UPDATE_QUEUE_NAME: &str = "/accounts/{account_key}/updates";
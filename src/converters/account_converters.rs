@@ -1,4 +1,4 @@
-use crate::dtos::account::{Account, Balance, Position};
+use crate::dtos::account::{Account, Balance, Margin, MarginCall, Position};
 use crate::entities;
 use crate::instrument_manager::InstrumentManager;
 use anyhow::Error;
@@ -33,8 +33,25 @@ impl entities::account::Position {
             cost: self.cost,
             closed_gain: self.closed_gain,
             version_number: self.version_number,
+            mark_price: None,
+            market_value: None,
+            unrealized_gain: None,
+            day_gain: None,
         })
     }
+
+    pub fn to_marked_rest_api_position(&self,
+                                       account_key: &str,
+                                       instrument_manager: &InstrumentManager) -> Result<Position, Error> {
+        let mut position = self.to_rest_api_position(account_key, instrument_manager)?;
+        if let Some(valuation) = instrument_manager.get_valuation_service().value(self)? {
+            position.mark_price = Some(valuation.mark_price);
+            position.market_value = Some(valuation.market_value);
+            position.unrealized_gain = Some(valuation.unrealized_gain);
+            position.day_gain = Some(valuation.day_gain);
+        }
+        Ok(position)
+    }
 }
 
 impl entities::account::Balance {
@@ -47,3 +64,31 @@ impl entities::account::Balance {
         }
     }
 }
+
+impl entities::account::MarginSummary {
+    pub fn to_rest_api_margin(&self,
+                              account_key: &str) -> Margin {
+        Margin {
+            account_key: account_key.to_string(),
+            equity: self.equity,
+            initial_margin: self.initial_margin,
+            maintenance_margin: self.maintenance_margin,
+            excess_equity: self.excess_equity,
+            buying_power: self.buying_power,
+        }
+    }
+}
+
+impl entities::account::MarginCall {
+    pub fn to_rest_api_margin_call(&self,
+                                   account_key: &str) -> MarginCall {
+        MarginCall {
+            account_key: account_key.to_string(),
+            status: self.status,
+            equity: self.equity,
+            maintenance_margin: self.maintenance_margin,
+            call_time: self.call_time,
+            update_time: self.update_time,
+        }
+    }
+}
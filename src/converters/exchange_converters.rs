@@ -12,6 +12,8 @@ impl entities::exchange::Instrument {
             exchange_code: exchange.code.clone(),
             description: self.description.clone(),
             expiration_time: self.expiration_time,
+            tick_size: self.tick_size,
+            price_precision: self.price_precision,
         }
     }
 }
@@ -25,6 +27,8 @@ impl dtos::exchange::Exchange {
             websocket_url: self.websocket_url.clone(),
             description: self.description.clone(),
             api_key: self.api_key.clone(),
+            supports_time_in_force: self.supports_time_in_force,
+            max_order_legs: self.max_order_legs,
         }
     }
 }
\ No newline at end of file
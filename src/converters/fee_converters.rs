@@ -0,0 +1,16 @@
+use crate::dtos::fee::FeeSchedule;
+use crate::entities;
+
+impl entities::fee::FeeSchedule {
+    pub fn to_rest_api_fee_schedule(&self) -> FeeSchedule {
+        FeeSchedule {
+            offer_code: self.offer_code.clone(),
+            account_key: self.account_key.clone(),
+            asset_class: self.asset_class.clone(),
+            per_share: self.per_share,
+            per_contract: self.per_contract,
+            basis_points: self.basis_points,
+            minimum_ticket: self.minimum_ticket,
+        }
+    }
+}
@@ -16,6 +16,8 @@ impl exchange_interface::instrument::Instrument {
             asset_class: exchange_asset_class_to_entities_asset_class(&self.asset_class),
             description: self.description.clone(),
             expiration_time: self.expiration_time,
+            tick_size: self.tick_size,
+            price_precision: self.price_precision,
         }
     }
 }
@@ -0,0 +1,34 @@
+use crate::dtos::ledger::{Journal, LedgerDiscrepancy, LedgerEntry};
+use crate::entities;
+
+impl entities::ledger::Journal {
+    pub fn to_rest_api_journal(&self,
+                               account_key: &str,
+                               account_id: i32) -> Journal {
+        Journal {
+            journal_id: self.journal_id,
+            account_key: account_key.to_string(),
+            journal_type: self.journal_type.clone(),
+            create_time: self.create_time,
+            description: self.description.clone(),
+            cash_change: self.cash_change(account_id),
+            entries: self.entries.iter()
+                .filter(|entry| entry.account_id == account_id)
+                .map(|entry| LedgerEntry {
+                    ledger_account: entry.ledger_account.clone(),
+                    amount: entry.amount,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl entities::ledger::LedgerDiscrepancy {
+    pub fn to_rest_api_ledger_discrepancy(&self) -> LedgerDiscrepancy {
+        LedgerDiscrepancy {
+            account_key: self.account_key.clone(),
+            cash: self.cash,
+            ledger_cash: self.ledger_cash,
+        }
+    }
+}
@@ -1,5 +1,5 @@
-use crate::dtos::market_data::{LastTrade, MarketDepth, PriceLevel};
-use crate::exchange_interface;
+use crate::dtos::market_data::{Bar, LastTrade, MarketDepth, PriceLevel};
+use crate::{entities, exchange_interface};
 
 impl exchange_interface::market_data::LastTrade {
     pub fn to_rest_api_last_trade(&self, 
@@ -38,3 +38,20 @@ impl exchange_interface::market_data::PriceLevel {
         }
     }
 }
+
+impl entities::market_data::Bar {
+    pub fn to_rest_api_bar(&self,
+                           instrument_key: String) -> Bar {
+        Bar {
+            instrument_key,
+            interval: self.interval,
+            start_time: self.start_time,
+            open: self.open,
+            high: self.high,
+            low: self.low,
+            close: self.close,
+            volume: self.volume,
+            trade_count: self.trade_count,
+        }
+    }
+}
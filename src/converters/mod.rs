@@ -2,4 +2,7 @@ mod exchange_converters;
 pub(crate) mod order_converters;
 mod account_converters;
 pub(crate) mod market_data_converters;
-pub(crate) mod instrument_converters;
\ No newline at end of file
+pub(crate) mod instrument_converters;
+mod ledger_converters;
+mod fee_converters;
+mod routing_converters;
@@ -1,11 +1,10 @@
-use crate::dtos::order::{Order, OrderLeg, OrderState, OrderStatus};
+use crate::dtos::order::{Order, OrderLeg, OrderState, OrderStatus, TimeInForce, Trade};
 use crate::entities::account::Account;
 use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
 use crate::time::current_time_millis;
 use crate::{entities, exchange_interface};
-use actix_web::web::ThinData;
 use anyhow::Error;
-use uuid::Uuid;
 
 pub fn order_status_to_rest_api_order_status(order_status: exchange_interface::order::OrderStatus)
                                              -> OrderStatus {
@@ -18,6 +17,17 @@ pub fn order_status_to_rest_api_order_status(order_status: exchange_interface::o
     }
 }
 
+pub fn time_in_force_to_exchange_time_in_force(time_in_force: &TimeInForce)
+                                                -> exchange_interface::order::TimeInForce {
+    match time_in_force {
+        TimeInForce::Day => exchange_interface::order::TimeInForce::Day,
+        TimeInForce::Gtc => exchange_interface::order::TimeInForce::Gtc,
+        TimeInForce::Gtd => exchange_interface::order::TimeInForce::Gtd,
+        TimeInForce::Ioc => exchange_interface::order::TimeInForce::Ioc,
+        TimeInForce::Fok => exchange_interface::order::TimeInForce::Fok,
+    }
+}
+
 impl entities::order::OrderLeg {
     pub fn to_rest_api_order_leg(&self, 
                                  instrument_manager: &InstrumentManager) -> Result<OrderLeg, Error> {
@@ -49,6 +59,46 @@ impl entities::order::Order {
             price: self.price,
             quantity: self.quantity,
             legs: order_legs,
+            time_in_force: self.time_in_force.clone(),
+            expire_time: self.expire_time,
+            order_type: self.order_type.clone(),
+            stop_price: self.stop_price,
+            trailing_offset: self.trailing_offset,
+        })
+    }
+}
+
+impl entities::order::Order {
+    // The price is the order's own, except for a released stop order which goes at its trigger price.
+    // Exchanges that cannot take a time in force get none; the expiry sweeper enforces it instead.
+    pub fn to_exchange_order(&self,
+                             instrument_manager: &InstrumentManager,
+                             price: Money) -> Result<exchange_interface::order::Order, Error> {
+        let mut order_legs: Vec<exchange_interface::order::OrderLeg> = Vec::new();
+        let mut supports_time_in_force = true;
+        for leg in self.legs.iter() {
+            let instrument = match instrument_manager.get_instrument(leg.instrument_id)? {
+                Some(instrument) => instrument,
+                None => return Err(anyhow::anyhow!("No instrument for instrument id {}", leg.instrument_id))
+            };
+            supports_time_in_force &= instrument_manager.get_exchange_for_instrument(&instrument)?.supports_time_in_force;
+            order_legs.push(exchange_interface::order::OrderLeg {
+                instrument_id: instrument.exchange_instrument_id,
+                ratio: leg.ratio,
+            });
+        }
+        let (time_in_force, expire_time) = if supports_time_in_force && !order_legs.is_empty() {
+            (Some(time_in_force_to_exchange_time_in_force(&self.time_in_force)), self.expire_time)
+        } else {
+            (None, None)
+        };
+        Ok(exchange_interface::order::Order {
+            client_order_id: self.client_order_id.clone(),
+            price,
+            quantity: self.quantity,
+            legs: order_legs,
+            time_in_force,
+            expire_time,
         })
     }
 }
@@ -60,7 +110,8 @@ impl entities::order::OrderState {
         Ok(OrderState{
             update_time: self.update_time,
             order_status: self.order_status.clone(),
-            filled_quantity: 0,
+            filled_quantity: self.filled_quantity,
+            average_fill_price: self.average_fill_price,
             version_number: self.version_number,
             reject_reason: self.reject_reason.clone(),
             order: self.order.to_rest_api_order(account_key, instrument_manager)?,
@@ -69,36 +120,10 @@ impl entities::order::OrderState {
 }
 
 impl Order {
-    pub fn to_exchange_order(&self, 
-                             instrument_manager: &ThinData<InstrumentManager>) -> Result<exchange_interface::order::Order, Error> {
-        let mut order_legs: Vec<exchange_interface::order::OrderLeg> = Vec::new();
-        for leg in self.legs.iter() {
-            let instrument_result = instrument_manager.get_instrument_by_key(leg.instrument_key.as_str());
-            let instrument_option = instrument_result?;
-            let exchange_instrument = match instrument_option {
-                Some(instrument) => instrument,
-                None => return Err(anyhow::anyhow!("No instrument with key: {}", leg.instrument_key))
-            };
-
-            order_legs.push(exchange_interface::order::OrderLeg {
-                instrument_id: exchange_instrument.exchange_instrument_id,
-                ratio: leg.ratio,
-            });
-        };
-
-        let order_exchange = exchange_interface::order::Order {
-            client_order_id: Uuid::new_v4().simple().to_string(),
-            price: self.price,
-            quantity: self.quantity,
-            legs: order_legs,
-        };
-        Ok(order_exchange)
-    }
-
     pub fn to_entities_order(&self, 
                              account: &Account, 
                              client_order_id: String, 
-                             instrument_manager: &ThinData<InstrumentManager>) -> Result<entities::order::Order, Error> {
+                             instrument_manager: &InstrumentManager) -> Result<entities::order::Order, Error> {
         let mut order_legs: Vec<entities::order::OrderLeg> = Vec::new();
 
         for leg in self.legs.iter() {
@@ -129,7 +154,33 @@ impl Order {
             price: self.price,
             quantity: self.quantity,
             legs: order_legs,
+            time_in_force: self.time_in_force.clone(),
+            expire_time: self.expire_time,
+            order_type: self.order_type.clone(),
+            stop_price: self.stop_price,
+            trailing_offset: self.trailing_offset,
         };
         Ok(order_entity)
     }
 }
+
+impl entities::order::Trade {
+    pub fn to_rest_api_trade(&self,
+                             instrument_manager: &InstrumentManager) -> Result<Trade, Error> {
+        let instrument_option = instrument_manager.get_instrument(self.order_leg.instrument_id)?;
+        let instrument = match instrument_option {
+            Some(instrument) => instrument,
+            None => return Err(anyhow::anyhow!("No instrument for instrument id {}", self.order_leg.instrument_id))
+        };
+        Ok(Trade {
+            trade_id: self.trade_id,
+            create_time: self.create_time,
+            ext_order_id: self.ext_order_id.clone(),
+            order_number: self.order_number,
+            instrument_key: instrument.instrument_key,
+            price: self.price,
+            quantity: self.quantity,
+            fee: self.fee,
+        })
+    }
+}
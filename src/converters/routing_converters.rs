@@ -0,0 +1,53 @@
+use crate::dtos::routing::{Listing, LogicalInstrument};
+use crate::entities;
+use crate::instrument_manager::InstrumentManager;
+use anyhow::Error;
+
+impl entities::routing::LogicalInstrument {
+    pub fn to_rest_api_logical_instrument(&self,
+                                          instrument_manager: &InstrumentManager) -> Result<LogicalInstrument, Error> {
+        let mut listings = Vec::new();
+        for listing in self.listings.iter() {
+            let instrument = match instrument_manager.get_instrument(listing.instrument_id)? {
+                Some(instrument) => instrument,
+                None => return Err(anyhow::anyhow!("No instrument for instrument id {}", listing.instrument_id))
+            };
+            listings.push(Listing {
+                instrument_key: instrument.instrument_key,
+                preference: listing.preference,
+                fee_per_unit: listing.fee_per_unit,
+            });
+        }
+        Ok(LogicalInstrument {
+            logical_key: self.logical_key.clone(),
+            symbol: self.symbol.clone(),
+            description: self.description.clone(),
+            listings,
+        })
+    }
+}
+
+impl LogicalInstrument {
+    pub fn to_entities_logical_instrument(&self,
+                                          instrument_manager: &InstrumentManager) -> Result<entities::routing::LogicalInstrument, Error> {
+        let mut listings = Vec::new();
+        for listing in self.listings.iter() {
+            let instrument = match instrument_manager.get_instrument_by_key(&listing.instrument_key)? {
+                Some(instrument) => instrument,
+                None => return Err(anyhow::anyhow!("No instrument for key: {}", listing.instrument_key))
+            };
+            listings.push(entities::routing::Listing {
+                instrument_id: instrument.instrument_id,
+                preference: listing.preference,
+                fee_per_unit: listing.fee_per_unit,
+            });
+        }
+        Ok(entities::routing::LogicalInstrument {
+            logical_instrument_id: 0,
+            logical_key: self.logical_key.clone(),
+            symbol: self.symbol.clone(),
+            description: self.description.clone(),
+            listings,
+        })
+    }
+}
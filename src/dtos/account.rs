@@ -1,13 +1,16 @@
+use crate::money::Money;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+use strum_macros::EnumIter;
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, EnumIter)]
 pub enum Privilege {
     Owner,
     Read,
     Submit,
     Cancel,
+    MakeMarkets,
     Withdraw,
 }
 
@@ -25,6 +28,7 @@ impl FromStr for Privilege {
             "Read"  => Ok(Privilege::Read),
             "Submit"  => Ok(Privilege::Submit),
             "Cancel"  => Ok(Privilege::Cancel),
+            "MakeMarkets"  => Ok(Privilege::MakeMarkets),
             "Withdraw"  => Ok(Privilege::Withdraw),
             _  => Err(()),
         }
@@ -45,14 +49,71 @@ pub struct Position {
     pub account_key: String,
     pub instrument_key: String,
     pub quantity: i32,
-    pub cost: f32,
+    pub cost: Money,
     pub version_number: i64,
-    pub closed_gain: f32,
+    pub closed_gain: Money,
+    // Valuation at the latest mark; absent when the instrument has not been marked yet
+    pub mark_price: Option<Money>,
+    pub market_value: Option<Money>,
+    pub unrealized_gain: Option<Money>,
+    pub day_gain: Option<Money>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Balance {
     pub account_key: String,
-    pub cash: f32,
+    pub cash: Money,
     pub version_number: i64,
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Margin {
+    pub account_key: String,
+    pub equity: Money,
+    pub initial_margin: Money,
+    pub maintenance_margin: Money,
+    pub excess_equity: Money,
+    pub buying_power: Money,
+}
+
+// An account is Called when its equity falls below maintenance margin, Liquidating once the
+// grace period runs out with the deficit still there, and Met when equity covers maintenance again
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, EnumIter)]
+pub enum MarginCallStatus {
+    Called,
+    Liquidating,
+    Met,
+}
+
+impl Display for MarginCallStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for MarginCallStatus {
+    type Err = ();
+    fn from_str(input: &str) -> Result<MarginCallStatus, Self::Err> {
+        match input {
+            "Called"  => Ok(MarginCallStatus::Called),
+            "Liquidating"  => Ok(MarginCallStatus::Liquidating),
+            "Met"  => Ok(MarginCallStatus::Met),
+            _  => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MarginCall {
+    pub account_key: String,
+    pub status: MarginCallStatus,
+    pub equity: Money,
+    pub maintenance_margin: Money,
+    pub call_time: i64,
+    pub update_time: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AccountVetter {
+    pub vetter_name: Option<String>,
+}
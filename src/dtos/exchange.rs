@@ -1,3 +1,4 @@
+use crate::money::Money;
 use postgres_types::{FromSql, ToSql};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
@@ -70,7 +71,9 @@ pub struct Instrument {
     pub asset_class: AssetClass,
     pub exchange_code: String,
     pub description: String,
-    pub expiration_time: i64
+    pub expiration_time: i64,
+    pub tick_size: Money,
+    pub price_precision: i32,
 }
 
 #[derive(Clone, Serialize, Deserialize, Default)]
@@ -80,5 +83,34 @@ pub struct Exchange {
     pub websocket_url: String,
     pub description: String,
     pub api_key: String,
+    #[serde(default)]
+    pub supports_time_in_force: bool,
+    // How many legs one order may have; left out, orders may have any number
+    pub max_order_legs: Option<i32>,
+}
+
+// Down means the circuit breaker is open and orders for the exchange are rejected before they
+// are saved. Degraded covers a lost websocket or recent failures short of opening the circuit
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, EnumIter)]
+pub enum ExchangeStatus {
+    Connected,
+    Degraded,
+    Down,
+}
 
+impl Display for ExchangeStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ExchangeHealth {
+    pub exchange_code: String,
+    pub status: ExchangeStatus,
+    pub websocket_connected: bool,
+    pub consecutive_failures: i32,
+    pub last_error: Option<String>,
+    pub circuit_open_until: Option<i64>,
+    pub update_time: i64,
 }
@@ -0,0 +1,37 @@
+use crate::dtos::exchange::AssetClass;
+use crate::entities;
+use crate::money::Money;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    #[serde(default)]
+    pub offer_code: Option<String>,
+    #[serde(default)]
+    pub account_key: Option<String>,
+    #[serde(default)]
+    pub asset_class: Option<AssetClass>,
+    #[serde(default)]
+    pub per_share: Money,
+    #[serde(default)]
+    pub per_contract: Money,
+    #[serde(default)]
+    pub basis_points: Money,
+    #[serde(default)]
+    pub minimum_ticket: Money,
+}
+
+impl FeeSchedule {
+    pub fn to_entities_fee_schedule(&self) -> entities::fee::FeeSchedule {
+        entities::fee::FeeSchedule {
+            fee_schedule_id: 0,
+            offer_code: self.offer_code.clone(),
+            account_key: self.account_key.clone(),
+            asset_class: self.asset_class.clone(),
+            per_share: self.per_share,
+            per_contract: self.per_contract,
+            basis_points: self.basis_points,
+            minimum_ticket: self.minimum_ticket,
+        }
+    }
+}
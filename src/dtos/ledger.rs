@@ -0,0 +1,104 @@
+use crate::money::Money;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use strum_macros::EnumIter;
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, EnumIter)]
+pub enum JournalType {
+    InitialFunding,
+    Execution,
+    Deposit,
+    Withdrawal,
+    Fee,
+    Adjustment,
+}
+
+impl Display for JournalType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for JournalType {
+    type Err = ();
+    fn from_str(input: &str) -> Result<JournalType, Self::Err> {
+        match input {
+            "InitialFunding"  => Ok(JournalType::InitialFunding),
+            "Execution"  => Ok(JournalType::Execution),
+            "Deposit"  => Ok(JournalType::Deposit),
+            "Withdrawal"  => Ok(JournalType::Withdrawal),
+            "Fee"  => Ok(JournalType::Fee),
+            "Adjustment"  => Ok(JournalType::Adjustment),
+            _  => Err(()),
+        }
+    }
+}
+
+// CustomerCash is the account holder's cash; every other ledger account is a
+// broker side contra account
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, EnumIter)]
+pub enum LedgerAccount {
+    CustomerCash,
+    ExternalFunds,
+    ExchangeSettlement,
+    FeeIncome,
+    Adjustments,
+}
+
+impl Display for LedgerAccount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for LedgerAccount {
+    type Err = ();
+    fn from_str(input: &str) -> Result<LedgerAccount, Self::Err> {
+        match input {
+            "CustomerCash"  => Ok(LedgerAccount::CustomerCash),
+            "ExternalFunds"  => Ok(LedgerAccount::ExternalFunds),
+            "ExchangeSettlement"  => Ok(LedgerAccount::ExchangeSettlement),
+            "FeeIncome"  => Ok(LedgerAccount::FeeIncome),
+            "Adjustments"  => Ok(LedgerAccount::Adjustments),
+            _  => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CashMovement {
+    pub amount: Money,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AdminJournal {
+    pub journal_type: JournalType,
+    pub amount: Money,
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LedgerEntry {
+    pub ledger_account: LedgerAccount,
+    pub amount: Money,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Journal {
+    pub journal_id: i64,
+    pub account_key: String,
+    pub journal_type: JournalType,
+    pub create_time: i64,
+    pub description: String,
+    pub cash_change: Money,
+    pub entries: Vec<LedgerEntry>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LedgerDiscrepancy {
+    pub account_key: String,
+    pub cash: Money,
+    pub ledger_cash: Money,
+}
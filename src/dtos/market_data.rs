@@ -1,11 +1,15 @@
+use crate::money::Money;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use strum_macros::EnumIter;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct LastTrade {
     pub version_number: i64,
     pub instrument_key: String,
     pub create_time: i64,
-    pub price: f32,
+    pub price: Money,
     pub quantity: i32,
 }
 
@@ -20,6 +24,78 @@ pub struct MarketDepth {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PriceLevel {
-    pub price: f32,
+    pub price: Money,
     pub quantity: i32,
 }
+
+// Bars start on multiples of their interval since the epoch, so daily bars run midnight to midnight UTC
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, EnumIter)]
+pub enum BarInterval {
+    #[serde(rename = "1s")]
+    OneSecond,
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "1d")]
+    OneDay,
+}
+
+impl BarInterval {
+    pub fn millis(&self) -> i64 {
+        match self {
+            BarInterval::OneSecond => 1000,
+            BarInterval::OneMinute => 60 * 1000,
+            BarInterval::FiveMinutes => 5 * 60 * 1000,
+            BarInterval::OneHour => 60 * 60 * 1000,
+            BarInterval::OneDay => 24 * 60 * 60 * 1000,
+        }
+    }
+
+    pub fn start_of(&self,
+                    time: i64) -> i64 {
+        time - time.rem_euclid(self.millis())
+    }
+}
+
+impl Display for BarInterval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let code = match self {
+            BarInterval::OneSecond => "1s",
+            BarInterval::OneMinute => "1m",
+            BarInterval::FiveMinutes => "5m",
+            BarInterval::OneHour => "1h",
+            BarInterval::OneDay => "1d",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+impl FromStr for BarInterval {
+    type Err = ();
+    fn from_str(input: &str) -> Result<BarInterval, Self::Err> {
+        match input {
+            "1s"  => Ok(BarInterval::OneSecond),
+            "1m"  => Ok(BarInterval::OneMinute),
+            "5m"  => Ok(BarInterval::FiveMinutes),
+            "1h"  => Ok(BarInterval::OneHour),
+            "1d"  => Ok(BarInterval::OneDay),
+            _  => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Bar {
+    pub instrument_key: String,
+    pub interval: BarInterval,
+    pub start_time: i64,
+    pub open: Money,
+    pub high: Money,
+    pub low: Money,
+    pub close: Money,
+    pub volume: i64,
+    pub trade_count: i64,
+}
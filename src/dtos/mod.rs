@@ -3,4 +3,9 @@ pub(crate) mod actor;
 pub(crate) mod exchange;
 pub(crate) mod market_data;
 pub(crate) mod order;
-pub(crate) mod offer;
\ No newline at end of file
+pub(crate) mod offer;
+pub(crate) mod statement;
+pub(crate) mod ledger;
+pub(crate) mod fee;
+pub(crate) mod order_group;
+pub(crate) mod routing;
@@ -6,6 +6,8 @@ pub struct Offer {
     pub code: String,
     pub description: String,
     pub expiration_time: i64,
+    #[serde(default)]
+    pub vetter_name: Option<String>,
 }
 
 impl Offer {
@@ -15,6 +17,7 @@ impl Offer {
             code: self.code.clone(),
             description: self.description.clone(),
             expiration_time: self.expiration_time,
+            vetter_name: self.vetter_name.clone(),
         }
     }
 }
\ No newline at end of file
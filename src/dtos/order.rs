@@ -1,7 +1,7 @@
+use crate::money::Money;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
-use std::time::SystemTime;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use tokio_postgres::types::{FromSql, ToSql};
@@ -11,10 +11,13 @@ pub enum OrderStatus {
     Rejected,
     Pending,
     Open,
+    PartiallyFilled,
     Filled,
     PendingCancel,
     Canceled,
     Expired,
+    PendingTrigger,
+    PendingParent,
 }
 
 impl Display for OrderStatus {
@@ -30,10 +33,13 @@ impl FromStr for OrderStatus {
             "Rejected"  => Ok(OrderStatus::Rejected),
             "Pending"  => Ok(OrderStatus::Pending),
             "Open"  => Ok(OrderStatus::Open),
+            "PartiallyFilled" => Ok(OrderStatus::PartiallyFilled),
             "Filled" => Ok(OrderStatus::Filled),
             "PendingCancel" => Ok(OrderStatus::PendingCancel),
             "Canceled" => Ok(OrderStatus::Canceled),
             "Expired" => Ok(OrderStatus::Expired),
+            "PendingTrigger" => Ok(OrderStatus::PendingTrigger),
+            "PendingParent" => Ok(OrderStatus::PendingParent),
             _  => Err(()),
         }
     }
@@ -44,10 +50,81 @@ pub fn is_order_status_viable(order_status: &OrderStatus) -> bool {
         OrderStatus::Rejected => false,
         OrderStatus::Pending => true,
         OrderStatus::Open => true,
+        OrderStatus::PartiallyFilled => true,
         OrderStatus::Filled => false,
         OrderStatus::PendingCancel => true,
         OrderStatus::Canceled => false,
         OrderStatus::Expired => false,
+        OrderStatus::PendingTrigger => true,
+        OrderStatus::PendingParent => true,
+    }
+}
+
+// Held orders are working but have not been sent to the exchange: stop orders waiting
+// for their trigger and bracket children waiting for their entry to fill
+pub fn is_order_status_held(order_status: &OrderStatus) -> bool {
+    matches!(order_status, OrderStatus::PendingTrigger | OrderStatus::PendingParent)
+}
+
+// Stop orders are held by the broker until the last trade reaches the stop price and are then
+// sent as limit orders: StopLimit at the order price, Stop and TrailingStop at the triggering
+// trade price. A trailing stop's stop price follows the market at trailing_offset behind it.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, EnumIter, Default)]
+pub enum OrderType {
+    #[default]
+    Limit,
+    Stop,
+    StopLimit,
+    TrailingStop,
+}
+
+impl Display for OrderType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for OrderType {
+    type Err = ();
+    fn from_str(input: &str) -> Result<OrderType, Self::Err> {
+        match input {
+            "Limit"  => Ok(OrderType::Limit),
+            "Stop"  => Ok(OrderType::Stop),
+            "StopLimit"  => Ok(OrderType::StopLimit),
+            "TrailingStop"  => Ok(OrderType::TrailingStop),
+            _  => Err(()),
+        }
+    }
+}
+
+// Orders without a time in force stay working until canceled, as they always have
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, EnumIter, Default)]
+pub enum TimeInForce {
+    Day,
+    #[default]
+    Gtc,
+    Gtd,
+    Ioc,
+    Fok,
+}
+
+impl Display for TimeInForce {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for TimeInForce {
+    type Err = ();
+    fn from_str(input: &str) -> Result<TimeInForce, Self::Err> {
+        match input {
+            "Day"  => Ok(TimeInForce::Day),
+            "Gtc"  => Ok(TimeInForce::Gtc),
+            "Gtd"  => Ok(TimeInForce::Gtd),
+            "Ioc"  => Ok(TimeInForce::Ioc),
+            "Fok"  => Ok(TimeInForce::Fok),
+            _  => Err(()),
+        }
     }
 }
 
@@ -57,6 +134,7 @@ pub struct OrderState {
     pub update_time: i64,
     pub order_status: OrderStatus,
     pub filled_quantity: i32,
+    pub average_fill_price: Money,
     pub order: Order,
     pub version_number: i64,
     pub reject_reason: Option<String>
@@ -70,9 +148,19 @@ pub struct Order {
     pub order_number: Option<i32>,
     pub ext_order_id: Option<String>,
     pub account_key: Option<String>,
-    pub price: f32,
+    pub price: Money,
     pub quantity: i32,
     pub legs: Vec<OrderLeg>,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    #[serde(default)]
+    pub expire_time: Option<i64>,
+    #[serde(default)]
+    pub order_type: OrderType,
+    #[serde(default)]
+    pub stop_price: Option<Money>,
+    #[serde(default)]
+    pub trailing_offset: Option<Money>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -85,13 +173,71 @@ pub struct OrderLeg {
 #[derive(Debug, Deserialize, Serialize)]
 #[derive(Clone)]
 pub struct Trade {
-    pub create_time: SystemTime,
-    pub price: f32,
+    pub trade_id: i64,
+    pub create_time: i64,
+    pub ext_order_id: String,
+    pub order_number: i32,
+    pub instrument_key: String,
+    pub price: Money,
     pub quantity: i32,
+    pub fee: Money,
+}
+
+// A page of an account's trades, newest first. When there may be older trades, next_before_trade_id
+// is the before_trade_id that fetches the next page
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TradePage {
+    pub trades: Vec<Trade>,
+    pub next_before_trade_id: Option<i64>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct VettingResult {
     pub pass: bool,
-    pub reject_reason: Option<String>
-}
\ No newline at end of file
+    pub reject_reason: Option<String>,
+    #[serde(default)]
+    pub estimated_commission: Option<Money>,
+}
+
+// The check result of a previewed order, with what it would cost. The net amount is the order
+// price times its quantity: positive for a net debit to the account and negative for a net credit
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OrderPreview {
+    #[serde(flatten)]
+    pub check_result: VettingResult,
+    pub net_amount: Money,
+    pub legs: Vec<LegPreview>,
+}
+
+// A single-leg order is valued at its own price; the legs of a combination at their marks,
+// so their notional is absent until the instrument has been marked
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LegPreview {
+    pub instrument_key: String,
+    pub ratio: i32,
+    pub quantity: i32,
+    pub price: Option<Money>,
+    pub notional: Option<Money>,
+}
+
+// One per order of a batch, in the order submitted. Orders that fail their checks are not saved
+// and have no order state
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BatchOrderResult {
+    pub ext_order_id: String,
+    pub check_result: VettingResult,
+    pub order_state: Option<OrderState>,
+    pub error: Option<String>,
+}
+
+// Answers an order request made over the websocket. request_id is whatever the client sent
+// with its request, so it can match answers to requests
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OrderReceipt {
+    pub request_id: Option<String>,
+    pub ext_order_id: Option<String>,
+    pub accepted: bool,
+    pub order_state: Option<OrderState>,
+    pub check_result: Option<VettingResult>,
+    pub reject_reason: Option<String>,
+}
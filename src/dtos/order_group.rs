@@ -0,0 +1,88 @@
+use crate::dtos::order::{Order, OrderState};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use strum_macros::EnumIter;
+
+// A bracket is an entry order with take-profit and stop-loss children that are only sent
+// once the entry fills. In both kinds of group the first fill on one of the exit orders
+// cancels the others.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, EnumIter)]
+pub enum OrderGroupType {
+    Bracket,
+    Oco,
+}
+
+impl Display for OrderGroupType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for OrderGroupType {
+    type Err = ();
+    fn from_str(input: &str) -> Result<OrderGroupType, Self::Err> {
+        match input {
+            "Bracket"  => Ok(OrderGroupType::Bracket),
+            "Oco"  => Ok(OrderGroupType::Oco),
+            _  => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, EnumIter)]
+pub enum OrderGroupRole {
+    Entry,
+    TakeProfit,
+    StopLoss,
+    Oco,
+}
+
+impl Display for OrderGroupRole {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for OrderGroupRole {
+    type Err = ();
+    fn from_str(input: &str) -> Result<OrderGroupRole, Self::Err> {
+        match input {
+            "Entry"  => Ok(OrderGroupRole::Entry),
+            "TakeProfit"  => Ok(OrderGroupRole::TakeProfit),
+            "StopLoss"  => Ok(OrderGroupRole::StopLoss),
+            "Oco"  => Ok(OrderGroupRole::Oco),
+            _  => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone)]
+pub struct OrderGroup {
+    pub group_type: OrderGroupType,
+    pub members: Vec<OrderGroupMember>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone)]
+pub struct OrderGroupMember {
+    pub role: OrderGroupRole,
+    pub order: Order,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone)]
+pub struct OrderGroupState {
+    pub order_group_id: i64,
+    pub group_type: OrderGroupType,
+    pub create_time: i64,
+    pub members: Vec<OrderGroupMemberState>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone)]
+pub struct OrderGroupMemberState {
+    pub role: OrderGroupRole,
+    pub order_state: OrderState,
+}
@@ -0,0 +1,78 @@
+use crate::dtos::order::{BatchOrderResult, TimeInForce};
+use crate::money::Money;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use strum_macros::EnumIter;
+
+// BestPrice sends the whole order to the venue showing the best price after its fee, Preference
+// to the most preferred venue, and Split sweeps the displayed depth of every venue in price order
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, EnumIter, Default)]
+pub enum RoutingStrategy {
+    #[default]
+    BestPrice,
+    Preference,
+    Split,
+}
+
+impl Display for RoutingStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for RoutingStrategy {
+    type Err = ();
+    fn from_str(input: &str) -> Result<RoutingStrategy, Self::Err> {
+        match input {
+            "BestPrice"  => Ok(RoutingStrategy::BestPrice),
+            "Preference"  => Ok(RoutingStrategy::Preference),
+            "Split"  => Ok(RoutingStrategy::Split),
+            _  => Err(()),
+        }
+    }
+}
+
+// One symbol traded on several exchanges, each listing being an ordinary instrument
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LogicalInstrument {
+    pub logical_key: String,
+    pub symbol: String,
+    pub description: String,
+    pub listings: Vec<Listing>,
+}
+
+// Lower preferences are preferred. The fee is what the venue charges per unit traded
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Listing {
+    pub instrument_key: String,
+    #[serde(default)]
+    pub preference: i32,
+    #[serde(default)]
+    pub fee_per_unit: Money,
+}
+
+// A single-instrument limit order on a logical instrument; quantity is negative to sell
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RoutedOrderRequest {
+    pub logical_key: String,
+    pub price: Money,
+    pub quantity: i32,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    #[serde(default)]
+    pub expire_time: Option<i64>,
+    #[serde(default)]
+    pub strategy: RoutingStrategy,
+}
+
+// One result per child order, as for a batch
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RoutedOrder {
+    pub routed_order_id: i64,
+    pub logical_key: String,
+    pub strategy: RoutingStrategy,
+    pub price: Money,
+    pub quantity: i32,
+    pub children: Vec<BatchOrderResult>,
+}
@@ -0,0 +1,14 @@
+use crate::money::Money;
+use crate::dtos::account::Position;
+use crate::dtos::order::OrderState;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Statement {
+    pub account_key: String,
+    pub statement_date: String,
+    pub cash: Money,
+    pub closed_gain: Money,
+    pub positions: Vec<Position>,
+    pub open_orders: Vec<OrderState>,
+}
@@ -1,4 +1,6 @@
+use crate::money::Money;
 use crate::dtos::account::Privilege;
+pub(crate) use crate::dtos::account::MarginCallStatus;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone)]
@@ -7,8 +9,8 @@ pub struct Position {
     pub account_id: i32,
     pub instrument_id: i64,
     pub quantity: i32,
-    pub cost: f32,
-    pub closed_gain: f32,
+    pub cost: Money,
+    pub closed_gain: Money,
     pub update_time: i64,
     pub version_number: i64,
 }
@@ -25,11 +27,33 @@ pub struct Account {
 pub struct Balance {
     pub balance_id: i32,
     pub account_id: i32,
-    pub cash: f32,
+    pub cash: Money,
     pub update_time: i64,
     pub version_number: i64,
 }
 
+// Computed from the balance, marked positions and working orders; never stored
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarginSummary {
+    pub equity: Money,
+    pub initial_margin: Money,
+    pub maintenance_margin: Money,
+    pub excess_equity: Money,
+    pub buying_power: Money,
+}
+
+// Equity and maintenance margin as of the last time the call was recorded
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarginCall {
+    pub margin_call_id: i64,
+    pub account_id: i32,
+    pub status: MarginCallStatus,
+    pub equity: Money,
+    pub maintenance_margin: Money,
+    pub call_time: i64,
+    pub update_time: i64,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Access {
     pub actor_id: i32,
@@ -1,4 +1,5 @@
 use crate::dtos::exchange::{AssetClass, InstrumentStatus};
+use crate::money::Money;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone)]
@@ -11,7 +12,9 @@ pub struct Instrument {
     pub symbol: String,
     pub asset_class: AssetClass,
     pub description: String,
-    pub expiration_time: i64
+    pub expiration_time: i64,
+    pub tick_size: Money,
+    pub price_precision: i32,
 }
 
 
@@ -23,4 +26,7 @@ pub struct Exchange {
     pub websocket_url: String,
     pub description: String,
     pub api_key: String,
+    pub supports_time_in_force: bool,
+    // None when the exchange takes orders of any number of legs
+    pub max_order_legs: Option<i32>,
 }
@@ -0,0 +1,89 @@
+use crate::dtos::exchange::AssetClass;
+use crate::money::Money;
+
+const FEE_DECIMAL_PLACES: u32 = 2;
+const BASIS_POINTS_PER_UNIT: i32 = 10000;
+
+// A schedule applies to an account, to every account opened under an offer, or
+// to everyone when neither is set; asset_class narrows it to one asset class
+#[derive(Clone)]
+pub struct FeeSchedule {
+    pub fee_schedule_id: i32,
+    pub offer_code: Option<String>,
+    pub account_key: Option<String>,
+    pub asset_class: Option<AssetClass>,
+    pub per_share: Money,
+    pub per_contract: Money,
+    pub basis_points: Money,
+    pub minimum_ticket: Money,
+}
+
+impl FeeSchedule {
+    pub fn commission(&self,
+                      asset_class: &AssetClass,
+                      price: Money,
+                      quantity: i32) -> Money {
+        let per_unit = if is_contract(asset_class) { self.per_contract } else { self.per_share };
+        let notional = (price * quantity).abs();
+        (per_unit * quantity.abs() + notional * self.basis_points / BASIS_POINTS_PER_UNIT).round_dp(FEE_DECIMAL_PLACES)
+    }
+
+    // Tops the fee up so that everything charged on the order reaches the minimum ticket
+    pub fn fee_for_fill(&self,
+                        asset_class: &AssetClass,
+                        price: Money,
+                        quantity: i32,
+                        already_charged: Money) -> Money {
+        let commission = self.commission(asset_class, price, quantity);
+        let shortfall = self.minimum_ticket - already_charged - commission;
+        if shortfall > Money::ZERO {
+            commission + shortfall
+        } else {
+            commission
+        }
+    }
+}
+
+fn is_contract(asset_class: &AssetClass) -> bool {
+    matches!(asset_class, AssetClass::Option | AssetClass::Future | AssetClass::Forward | AssetClass::Swap)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dtos::exchange::AssetClass;
+    use crate::entities::fee::FeeSchedule;
+    use crate::money::Money;
+
+    fn fee_schedule() -> FeeSchedule {
+        FeeSchedule {
+            fee_schedule_id: 0,
+            offer_code: None,
+            account_key: None,
+            asset_class: None,
+            per_share: Money::new(5, 3),
+            per_contract: Money::new(65, 2),
+            basis_points: Money::from(2),
+            minimum_ticket: Money::from(1),
+        }
+    }
+
+    #[test]
+    async fn test_per_share_and_basis_points() {
+        // 300 * 0.005 + 300 * 20.00 * 0.0002
+        assert_eq!(fee_schedule().commission(&AssetClass::Equity, Money::from(20), -300), Money::new(270, 2));
+    }
+
+    #[test]
+    async fn test_per_contract() {
+        // 4 * 0.65 + 4 * 3.10 * 0.0002, rounded to cents
+        assert_eq!(fee_schedule().commission(&AssetClass::Option, Money::new(310, 2), 4), Money::new(260, 2));
+    }
+
+    #[test]
+    async fn test_minimum_ticket() {
+        let fee_schedule = fee_schedule();
+        assert_eq!(fee_schedule.fee_for_fill(&AssetClass::Equity, Money::from(10), 10, Money::ZERO), Money::from(1));
+        assert_eq!(fee_schedule.fee_for_fill(&AssetClass::Equity, Money::from(10), 10, Money::new(90, 2)), Money::new(10, 2));
+        assert_eq!(fee_schedule.fee_for_fill(&AssetClass::Equity, Money::from(10), 10, Money::from(1)), Money::new(7, 2));
+    }
+}
@@ -0,0 +1,96 @@
+use crate::money::Money;
+use crate::dtos::ledger::{JournalType, LedgerAccount};
+
+#[derive(Clone)]
+pub struct LedgerEntry {
+    pub ledger_entry_id: i64,
+    pub account_id: i32,
+    pub ledger_account: LedgerAccount,
+    pub amount: Money,
+}
+
+#[derive(Clone)]
+pub struct Journal {
+    pub journal_id: i64,
+    pub journal_type: JournalType,
+    pub trade_id: Option<i64>,
+    pub create_time: i64,
+    pub description: String,
+    pub entries: Vec<LedgerEntry>,
+}
+
+#[derive(Clone)]
+pub struct LedgerDiscrepancy {
+    pub account_key: String,
+    pub cash: Money,
+    pub ledger_cash: Money,
+}
+
+impl Journal {
+    // A positive amount credits the customer's cash, a negative amount debits it;
+    // the contra account always takes the opposite side
+    pub fn cash_movement(journal_type: JournalType,
+                         account_id: i32,
+                         amount: Money,
+                         contra_account: LedgerAccount,
+                         description: String,
+                         create_time: i64) -> Journal {
+        Journal {
+            journal_id: 0,
+            journal_type,
+            trade_id: None,
+            create_time,
+            description,
+            entries: vec![
+                LedgerEntry {
+                    ledger_entry_id: 0,
+                    account_id,
+                    ledger_account: LedgerAccount::CustomerCash,
+                    amount,
+                },
+                LedgerEntry {
+                    ledger_entry_id: 0,
+                    account_id,
+                    ledger_account: contra_account,
+                    amount: -amount,
+                },
+            ],
+        }
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.entries.len() >= 2 && self.entries.iter().map(|entry| entry.amount).sum::<Money>().is_zero()
+    }
+
+    pub fn cash_change(&self,
+                       account_id: i32) -> Money {
+        self.entries.iter()
+            .filter(|entry| entry.account_id == account_id && entry.ledger_account == LedgerAccount::CustomerCash)
+            .map(|entry| entry.amount)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dtos::ledger::{JournalType, LedgerAccount};
+    use crate::entities::ledger::Journal;
+    use crate::money::Money;
+
+    #[test]
+    async fn test_cash_movement_is_balanced() {
+        let journal = Journal::cash_movement(JournalType::Withdrawal, 7, Money::from(-250), LedgerAccount::ExternalFunds, "withdrawal".to_string(), 0);
+        assert!(journal.is_balanced());
+        assert_eq!(journal.cash_change(7), Money::from(-250));
+        assert_eq!(journal.cash_change(8), Money::ZERO);
+    }
+
+    #[test]
+    async fn test_unbalanced_journal() {
+        let mut journal = Journal::cash_movement(JournalType::Deposit, 7, Money::from(100), LedgerAccount::ExternalFunds, "deposit".to_string(), 0);
+        journal.entries[1].amount = Money::from(-90);
+        assert!(!journal.is_balanced());
+        journal.entries.truncate(1);
+        assert!(!journal.is_balanced());
+    }
+}
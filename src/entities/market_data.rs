@@ -0,0 +1,49 @@
+pub(crate) use crate::dtos::market_data::BarInterval;
+use crate::money::Money;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bar {
+    pub instrument_id: i64,
+    pub interval: BarInterval,
+    pub start_time: i64,
+    pub open: Money,
+    pub high: Money,
+    pub low: Money,
+    pub close: Money,
+    pub volume: i64,
+    pub trade_count: i64,
+}
+
+impl Bar {
+    pub fn new(instrument_id: i64,
+               interval: BarInterval,
+               trade_time: i64,
+               price: Money,
+               quantity: i32) -> Bar {
+        Bar {
+            instrument_id,
+            interval,
+            start_time: interval.start_of(trade_time),
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: quantity.unsigned_abs() as i64,
+            trade_count: 1,
+        }
+    }
+
+    pub fn add_trade(&mut self,
+                     price: Money,
+                     quantity: i32) {
+        if price > self.high {
+            self.high = price;
+        }
+        if price < self.low {
+            self.low = price;
+        }
+        self.close = price;
+        self.volume += quantity.unsigned_abs() as i64;
+        self.trade_count += 1;
+    }
+}
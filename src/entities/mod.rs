@@ -2,4 +2,10 @@ pub mod order;
 pub mod actor;
 pub mod account;
 pub mod offer;
-pub mod exchange;
\ No newline at end of file
+pub mod exchange;
+pub mod reconciliation;
+pub mod ledger;
+pub mod fee;
+pub mod order_group;
+pub mod market_data;
+pub mod routing;pub mod outbox;
@@ -6,4 +6,5 @@ pub struct Offer {
     pub code: String,
     pub description: String,
     pub expiration_time: i64,
+    pub vetter_name: Option<String>,
 }
@@ -1,4 +1,8 @@
-pub(crate) use crate::dtos::order::OrderStatus;
+pub(crate) use crate::dtos::order::{is_order_status_held, OrderStatus, OrderType, TimeInForce};
+use crate::money::Money;
+
+// Averages can repeat indefinitely, so they are kept to a fixed number of places
+const AVERAGE_FILL_PRICE_DECIMAL_PLACES: u32 = 8;
 
 #[derive(Clone)]
 pub struct OrderState {
@@ -6,13 +10,79 @@ pub struct OrderState {
     pub update_time: i64,
     pub order_status: OrderStatus,
     pub version_number: i64,
-    pub reject_reason: Option<String>
+    pub reject_reason: Option<String>,
+    pub filled_quantity: i32,
+    pub average_fill_price: Money,
 }
 
 impl OrderState {
     pub fn get_order_mut(&mut self) -> &mut Order {
         &mut self.order
     }
+
+    // Recomputes the fill from all of the order's trades. An order unit is filled once every leg
+    // has traded its ratio of it, so the filled quantity follows the least filled leg; a leg's
+    // quantity short of a whole unit is carried until later executions complete it. The average
+    // is the net price of an order unit, each leg's average price times its ratio. Returns the
+    // leg quantities carried over, by order leg id
+    pub fn apply_trades(&mut self,
+                        trades: &[Trade]) -> Vec<(i64, i32)> {
+        let mut filled_quantity: Option<i32> = None;
+        let mut average_fill_price = Money::ZERO;
+        let mut carried = Vec::new();
+        for leg in self.order.legs.iter().filter(|leg| leg.ratio != 0) {
+            let leg_trades: Vec<&Trade> = trades.iter().filter(|trade| trade.order_leg.order_leg_id == leg.order_leg_id).collect();
+            let leg_quantity: i32 = leg_trades.iter().map(|trade| trade.quantity).sum();
+            let leg_units = leg_quantity / leg.ratio;
+            if leg_quantity % leg.ratio != 0 {
+                carried.push((leg.order_leg_id, leg_quantity % leg.ratio));
+            }
+            if filled_quantity.is_none_or(|filled_quantity| leg_units.abs() < filled_quantity.abs()) {
+                filled_quantity = Some(leg_units);
+            }
+            if leg_quantity != 0 {
+                let leg_cost: Money = leg_trades.iter().map(|trade| trade.price * trade.quantity).sum();
+                average_fill_price += leg_cost / leg_quantity * leg.ratio;
+            }
+        }
+        self.filled_quantity = filled_quantity.unwrap_or(0);
+        self.average_fill_price = match self.filled_quantity {
+            0 => Money::ZERO,
+            _ => average_fill_price.round_dp(AVERAGE_FILL_PRICE_DECIMAL_PLACES),
+        };
+        self.update_fill_status();
+        carried
+    }
+
+    // Only the status follows the exchange's remaining quantity. filled_quantity and the average
+    // price come solely from the executions applied, which may arrive before or after this update
+    pub fn apply_remaining_quantity(&mut self,
+                                    remaining_quantity: i32) {
+        let exchange_filled_quantity = self.exchange_filled_quantity(remaining_quantity);
+        self.update_status_for_filled(exchange_filled_quantity.abs().max(self.filled_quantity.abs()));
+    }
+
+    pub fn exchange_filled_quantity(&self,
+                                    remaining_quantity: i32) -> i32 {
+        (self.order.quantity.abs() - remaining_quantity.abs()) * self.order.quantity.signum()
+    }
+
+    fn update_fill_status(&mut self) {
+        self.update_status_for_filled(self.filled_quantity.abs());
+    }
+
+    fn update_status_for_filled(&mut self,
+                                filled_quantity: i32) {
+        match self.order_status {
+            OrderStatus::Pending | OrderStatus::Open | OrderStatus::PartiallyFilled => {},
+            _ => return
+        }
+        if filled_quantity >= self.order.quantity.abs() {
+            self.order_status = OrderStatus::Filled;
+        } else if filled_quantity != 0 {
+            self.order_status = OrderStatus::PartiallyFilled;
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -23,9 +93,14 @@ pub struct Order {
     pub ext_order_id: String,
     pub client_order_id: String,
     pub create_time: i64,
-    pub price: f32,
+    pub price: Money,
     pub quantity: i32,
     pub legs: Vec<OrderLeg>,
+    pub time_in_force: TimeInForce,
+    pub expire_time: Option<i64>,
+    pub order_type: OrderType,
+    pub stop_price: Option<Money>,
+    pub trailing_offset: Option<Money>,
 }
 
 impl Order {
@@ -46,7 +121,155 @@ pub struct OrderLeg {
 pub struct Trade {
     pub trade_id: i64,
     pub create_time: i64,
+    pub ext_order_id: String,
+    pub order_number: i32,
     pub order_leg: OrderLeg,
-    pub price: f32,
+    pub exchange_execution_id: i64,
+    pub price: Money,
     pub quantity: i32,
+    pub fee: Money,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::order::{Order, OrderLeg, OrderState, OrderStatus, OrderType, TimeInForce, Trade};
+    use crate::money::Money;
+
+    fn order_state(quantity: i32) -> OrderState {
+        OrderState {
+            order: Order {
+                order_id: 0,
+                account_id: 0,
+                order_number: 1,
+                ext_order_id: "ext".to_string(),
+                client_order_id: "client".to_string(),
+                create_time: 0,
+                price: Money::from(10),
+                quantity,
+                legs: vec![OrderLeg {
+                    order_leg_id: 0,
+                    instrument_id: 0,
+                    ratio: 1,
+                }],
+                time_in_force: TimeInForce::Gtc,
+                expire_time: None,
+                order_type: OrderType::Limit,
+                stop_price: None,
+                trailing_offset: None,
+            },
+            update_time: 0,
+            order_status: OrderStatus::Open,
+            version_number: 0,
+            reject_reason: None,
+            filled_quantity: 0,
+            average_fill_price: Money::ZERO,
+        }
+    }
+
+    fn trade(order_leg_id: i64,
+             ratio: i32,
+             price: Money,
+             quantity: i32) -> Trade {
+        Trade {
+            trade_id: 0,
+            create_time: 0,
+            ext_order_id: "ext".to_string(),
+            order_number: 1,
+            order_leg: OrderLeg {
+                order_leg_id,
+                instrument_id: order_leg_id,
+                ratio,
+            },
+            exchange_execution_id: 0,
+            price,
+            quantity,
+            fee: Money::ZERO,
+        }
+    }
+
+    #[test]
+    async fn test_partial_fill() {
+        let mut order_state = order_state(10);
+        order_state.apply_trades(&[trade(0, 1, Money::from(10), 4)]);
+        assert_eq!(order_state.filled_quantity, 4);
+        assert_eq!(order_state.average_fill_price, Money::from(10));
+        assert_eq!(order_state.order_status, OrderStatus::PartiallyFilled);
+    }
+
+    #[test]
+    async fn test_complete_fill_averages_price() {
+        let mut order_state = order_state(-10);
+        order_state.apply_trades(&[trade(0, 1, Money::from(10), -4), trade(0, 1, Money::from(15), -6)]);
+        assert_eq!(order_state.filled_quantity, -10);
+        assert_eq!(order_state.average_fill_price, Money::from(13));
+        assert_eq!(order_state.order_status, OrderStatus::Filled);
+    }
+
+    #[test]
+    async fn test_fill_keeps_pending_cancel() {
+        let mut order_state = order_state(10);
+        order_state.order_status = OrderStatus::PendingCancel;
+        order_state.apply_trades(&[trade(0, 1, Money::from(10), 4)]);
+        assert_eq!(order_state.filled_quantity, 4);
+        assert_eq!(order_state.order_status, OrderStatus::PendingCancel);
+    }
+
+    #[test]
+    async fn test_remaining_quantity_from_exchange() {
+        let mut order_state = order_state(-10);
+        order_state.apply_remaining_quantity(7);
+        assert_eq!(order_state.filled_quantity, 0);
+        assert_eq!(order_state.order_status, OrderStatus::PartiallyFilled);
+        assert_eq!(order_state.exchange_filled_quantity(7), -3);
+    }
+
+    #[test]
+    async fn test_state_message_before_execution_counts_fill_once() {
+        let mut order_state = order_state(6);
+        order_state.apply_remaining_quantity(3);
+        order_state.apply_trades(&[trade(0, 1, Money::from(10), 3)]);
+        assert_eq!(order_state.filled_quantity, 3);
+        assert_eq!(order_state.average_fill_price, Money::from(10));
+        assert_eq!(order_state.order_status, OrderStatus::PartiallyFilled);
+        order_state.apply_remaining_quantity(0);
+        assert_eq!(order_state.order_status, OrderStatus::Filled);
+        order_state.apply_trades(&[trade(0, 1, Money::from(10), 3), trade(0, 1, Money::from(12), 3)]);
+        assert_eq!(order_state.filled_quantity, 6);
+        assert_eq!(order_state.average_fill_price, Money::from(11));
+    }
+
+    // Buys 4 units of a spread, each one long the first leg and short two of the second
+    #[test]
+    async fn test_multi_leg_partial_fill() {
+        let mut order_state = order_state(4);
+        order_state.order.legs.push(OrderLeg {
+            order_leg_id: 1,
+            instrument_id: 1,
+            ratio: -2,
+        });
+        let mut trades = vec![trade(0, 1, Money::from(10), 3)];
+        // Only the first leg has traded, so no unit of the spread is complete
+        assert_eq!(order_state.apply_trades(&trades), vec![]);
+        assert_eq!(order_state.filled_quantity, 0);
+        assert_eq!(order_state.average_fill_price, Money::ZERO);
+        assert_eq!(order_state.order_status, OrderStatus::Open);
+
+        // Half a unit of the second leg is carried
+        trades.push(trade(1, -2, Money::from(4), -5));
+        assert_eq!(order_state.apply_trades(&trades), vec![(1, -1)]);
+        assert_eq!(order_state.filled_quantity, 2);
+        assert_eq!(order_state.average_fill_price, Money::from(2));
+        assert_eq!(order_state.order_status, OrderStatus::PartiallyFilled);
+
+        trades.push(trade(1, -2, Money::from(5), -3));
+        assert_eq!(order_state.apply_trades(&trades), vec![]);
+        assert_eq!(order_state.filled_quantity, 3);
+        assert_eq!(order_state.average_fill_price, Money::new(125, 2));
+
+        trades.push(trade(0, 1, Money::from(11), 1));
+        order_state.apply_trades(&trades);
+        assert_eq!(order_state.filled_quantity, 4);
+        assert_eq!(order_state.average_fill_price, Money::new(15, 1));
+        assert_eq!(order_state.order_status, OrderStatus::Filled);
+    }
 }
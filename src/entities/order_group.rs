@@ -0,0 +1,88 @@
+pub(crate) use crate::dtos::order_group::{OrderGroupRole, OrderGroupType};
+
+#[derive(Clone, Debug)]
+pub struct OrderGroup {
+    pub order_group_id: i64,
+    pub account_id: i32,
+    pub group_type: OrderGroupType,
+    pub create_time: i64,
+    pub members: Vec<OrderGroupMember>,
+}
+
+#[derive(Clone, Debug)]
+pub struct OrderGroupMember {
+    pub order_id: i64,
+    pub client_order_id: String,
+    pub role: OrderGroupRole,
+}
+
+impl OrderGroup {
+    pub fn role_of(&self,
+                   order_id: i64) -> Option<&OrderGroupRole> {
+        self.members.iter()
+            .find(|member| member.order_id == order_id)
+            .map(|member| &member.role)
+    }
+
+    // Bracket children wait on the entry
+    pub fn children(&self) -> Vec<&OrderGroupMember> {
+        self.members.iter()
+            .filter(|member| matches!(member.role, OrderGroupRole::TakeProfit | OrderGroupRole::StopLoss))
+            .collect()
+    }
+
+    // The exit orders that give way once the given one starts to fill
+    pub fn siblings(&self,
+                    order_id: i64) -> Vec<&OrderGroupMember> {
+        match self.role_of(order_id) {
+            None | Some(OrderGroupRole::Entry) => vec![],
+            Some(_) => self.members.iter()
+                .filter(|member| member.order_id != order_id && member.role != OrderGroupRole::Entry)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::order_group::{OrderGroup, OrderGroupMember, OrderGroupRole, OrderGroupType};
+
+    fn member(order_id: i64, role: OrderGroupRole) -> OrderGroupMember {
+        OrderGroupMember {
+            order_id,
+            client_order_id: format!("client{}", order_id),
+            role,
+        }
+    }
+
+    #[test]
+    async fn test_bracket_children_and_siblings() {
+        let bracket = OrderGroup {
+            order_group_id: 1,
+            account_id: 1,
+            group_type: OrderGroupType::Bracket,
+            create_time: 0,
+            members: vec![member(10, OrderGroupRole::Entry), member(11, OrderGroupRole::TakeProfit), member(12, OrderGroupRole::StopLoss)],
+        };
+        let children: Vec<i64> = bracket.children().iter().map(|member| member.order_id).collect();
+        assert_eq!(children, vec![11, 12]);
+        assert!(bracket.siblings(10).is_empty());
+        let siblings: Vec<i64> = bracket.siblings(12).iter().map(|member| member.order_id).collect();
+        assert_eq!(siblings, vec![11]);
+        assert!(bracket.siblings(99).is_empty());
+    }
+
+    #[test]
+    async fn test_oco_siblings() {
+        let oco = OrderGroup {
+            order_group_id: 2,
+            account_id: 1,
+            group_type: OrderGroupType::Oco,
+            create_time: 0,
+            members: vec![member(20, OrderGroupRole::Oco), member(21, OrderGroupRole::Oco)],
+        };
+        assert!(oco.children().is_empty());
+        let siblings: Vec<i64> = oco.siblings(20).iter().map(|member| member.order_id).collect();
+        assert_eq!(siblings, vec![21]);
+    }
+}
@@ -0,0 +1,12 @@
+use crate::money::Money;
+
+// A Pending order still to be delivered to its exchange, at the price it is to be sent at
+#[derive(Clone, Debug)]
+pub struct OutboxEntry {
+    pub order_id: i64,
+    pub price: Money,
+    pub attempts: i32,
+    pub next_attempt_time: i64,
+    pub last_error: Option<String>,
+    pub create_time: i64,
+}
@@ -0,0 +1,9 @@
+#[derive(Clone)]
+pub struct ReconciliationAudit {
+    pub reconciliation_audit_id: i64,
+    pub exchange_id: i32,
+    pub order_id: i64,
+    pub create_time: i64,
+    pub discrepancy: String,
+    pub description: String,
+}
@@ -0,0 +1,38 @@
+use crate::money::Money;
+pub(crate) use crate::dtos::routing::RoutingStrategy;
+
+#[derive(Clone, Debug)]
+pub struct LogicalInstrument {
+    pub logical_instrument_id: i32,
+    pub logical_key: String,
+    pub symbol: String,
+    pub description: String,
+    pub listings: Vec<Listing>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Listing {
+    pub instrument_id: i64,
+    pub preference: i32,
+    pub fee_per_unit: Money,
+}
+
+#[derive(Clone, Debug)]
+pub struct RoutedOrder {
+    pub routed_order_id: i64,
+    pub account_id: i32,
+    pub logical_instrument_id: i32,
+    pub strategy: RoutingStrategy,
+    pub price: Money,
+    pub quantity: i32,
+    pub create_time: i64,
+    pub children: Vec<RoutedChild>,
+}
+
+// A child order sent to one venue
+#[derive(Clone, Debug)]
+pub struct RoutedChild {
+    pub order_id: i64,
+    pub instrument_id: i64,
+    pub quantity: i32,
+}
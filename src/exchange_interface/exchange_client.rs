@@ -1,16 +1,23 @@
 use crate::config::BrokerConfig;
 use crate::exchange_interface::exchange_error::ExchangeError;
+use crate::exchange_interface::exchange_health::ExchangeHealthTracker;
 use crate::exchange_interface::instrument::Instruments;
-use crate::exchange_interface::order::{Order, OrderState, OrderStates, SubmitOrders};
+use crate::exchange_interface::order::{Executions, Order, OrderState, OrderStates, SubmitOrders};
 use log::debug;
 use reqwest::cookie::Jar;
-use reqwest::{Client, Response};
+use reqwest::{Client, Response, StatusCode};
 use std::sync::Arc;
+use std::time::Duration;
 use url::Url;
 
+// A request still unanswered after this long is treated as a failure whose outcome is unknown
+pub const REQUEST_TIMEOUT_MILLIS: u64 = 10000;
+
 pub struct ExchangeClient {
     client: Client,
     exchange_url: String,
+    exchange_id: i32,
+    exchange_health: ExchangeHealthTracker,
 }
 
 pub(crate) fn get_customer_key_cookie(customer_key: &str) -> String {
@@ -18,8 +25,10 @@ pub(crate) fn get_customer_key_cookie(customer_key: &str) -> String {
 }
 
 impl ExchangeClient {
-    pub fn new(exchange_url: &str, 
-               broker_key: &str) -> Self {
+    pub fn new(exchange_id: i32,
+               exchange_url: &str,
+               broker_key: &str,
+               exchange_health: ExchangeHealthTracker) -> Self {
         let jar = Arc::new(Jar::default());
         let url = match exchange_url.parse::<Url>() {
             Ok(url_string) => url_string,
@@ -29,7 +38,10 @@ impl ExchangeClient {
         };
         jar.add_cookie_str(&get_customer_key_cookie(broker_key), &url);
 
-        let client = match Client::builder().cookie_provider(Arc::clone(&jar)).build() {
+        let client = match Client::builder()
+            .cookie_provider(Arc::clone(&jar))
+            .timeout(Duration::from_millis(REQUEST_TIMEOUT_MILLIS))
+            .build() {
             Ok(client) => client,
             Err(reqwest_error) => {
                 panic!("ExchangeClient::new Client::builder().build error {}", reqwest_error);
@@ -39,6 +51,8 @@ impl ExchangeClient {
         ExchangeClient {
             client,
             exchange_url: exchange_url.to_string(),
+            exchange_id,
+            exchange_health,
         }
     }
 
@@ -72,13 +86,7 @@ impl ExchangeClient {
         let send = self.client.get(instruments_url).send();
 
         debug!("About to send for instruments");
-        let response = match send.await {
-            Ok(response) => {
-                response
-            },
-            Err(parse_error) =>
-                return Err(ExchangeError::Failure { description: "send".to_string(), cause: parse_error.to_string() }),
-        };
+        let response = self.send(send).await?;
         debug!("Got instruments");
 
         match response.json::<Instruments>().await {
@@ -96,7 +104,25 @@ impl ExchangeClient {
         };
         let send = self.client.post(url).json(&orders).send();
 
-        Self::execute(send).await
+        self.execute(send).await
+    }
+
+    // The exchange answers with one order state per submitted order
+    pub async fn submit_orders(&self,
+                               orders: Vec<Order>) -> Result<Vec<OrderState>, ExchangeError> {
+        let order_count = orders.len();
+        let orders = SubmitOrders { orders };
+        let url = match self.get_url("orders") {
+            Ok(url) => url,
+            Err(get_url_error) => return Err(ExchangeError::Failure { description: "submit_orders get_url".to_string(), cause: get_url_error.to_string() })
+        };
+        let send = self.client.post(url).json(&orders).send();
+
+        let order_states = self.execute_all(send).await?;
+        if order_states.len() != order_count {
+            return Err(ExchangeError::Failure { description: "Incorrect number of order states returned".to_string(), cause: format!("{} instead of {}", order_states.len(), order_count) })
+        }
+        Ok(order_states)
     }
 
 
@@ -108,27 +134,94 @@ impl ExchangeClient {
         };
         let send = self.client.delete(url).send();
 
-        Self::execute(send).await
+        self.execute(send).await
     }
 
-    async fn execute(send: impl Future<Output=Result<Response, reqwest::Error>>) -> Result<OrderState, ExchangeError> {
-        let response = match send.await {
-            Ok(response) => response,
-            Err(send_error) => return Err(ExchangeError::Failure { description: "send await".to_string(), cause: send_error.to_string() })
+    pub async fn get_order(&self,
+                           client_order_id: &String) -> Result<OrderState, ExchangeError> {
+        let url = match self.get_url_with_id("orders", client_order_id) {
+            Ok(url) => url,
+            Err(get_url_error) => return Err(ExchangeError::Failure { description: "get_order get_url_with_id".to_string(), cause: get_url_error.to_string() })
         };
+        let send = self.client.get(url).send();
 
-        let order_states = match response.json::<OrderStates>().await {
-            Ok(order_states) => order_states,
-            Err(send_error) => return Err(ExchangeError::Failure { description: "json".to_string(), cause: send_error.to_string() })
+        self.execute(send).await
+    }
+
+    // None when the exchange has never seen the order, so that it is safe to send it
+    pub async fn find_order(&self,
+                            client_order_id: &String) -> Result<Option<OrderState>, ExchangeError> {
+        let url = match self.get_url_with_id("orders", client_order_id) {
+            Ok(url) => url,
+            Err(get_url_error) => return Err(ExchangeError::Failure { description: "find_order get_url_with_id".to_string(), cause: get_url_error.to_string() })
         };
+        let response = self.send(self.client.get(url).send()).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        match response.json::<OrderStates>().await {
+            Ok(order_states) => Ok(order_states.order_states.into_iter().next()),
+            Err(json_error) => Err(ExchangeError::Failure { description: "find_order json".to_string(), cause: json_error.to_string() }),
+        }
+    }
 
-        if order_states.order_states.len() != 1 {
-            return Err(ExchangeError::Failure { description: "Incorrect number of order states returned".to_string(), cause: format!("{} instead of 1", order_states.order_states.len()) })
+    pub async fn get_executions(&self,
+                                client_order_id: &String) -> Result<Executions, ExchangeError> {
+        let url = match self.get_url_with_id("executions", client_order_id) {
+            Ok(url) => url,
+            Err(get_url_error) => return Err(ExchangeError::Failure { description: "get_executions get_url_with_id".to_string(), cause: get_url_error.to_string() })
+        };
+        let response = self.send(self.client.get(url).send()).await?;
+
+        match response.json::<Executions>().await {
+            Ok(executions) => Ok(executions),
+            Err(json_error) => Err(ExchangeError::Failure { description: "get_executions json".to_string(), cause: json_error.to_string() }),
         }
+    }
 
-        match order_states.order_states.first() {
+    async fn execute(&self,
+                     send: impl Future<Output=Result<Response, reqwest::Error>>) -> Result<OrderState, ExchangeError> {
+        let order_states = self.execute_all(send).await?;
+
+        if order_states.len() != 1 {
+            return Err(ExchangeError::Failure { description: "Incorrect number of order states returned".to_string(), cause: format!("{} instead of 1", order_states.len()) })
+        }
+
+        match order_states.first() {
             Some(order_state) => Ok(order_state.clone()),
             None => Err(ExchangeError::Failure { description: "first".to_string(), cause: "No order_state available".to_string() })
         }
     }
+
+    async fn execute_all(&self,
+                         send: impl Future<Output=Result<Response, reqwest::Error>>) -> Result<Vec<OrderState>, ExchangeError> {
+        let response = self.send(send).await?;
+
+        match response.json::<OrderStates>().await {
+            Ok(order_states) => Ok(order_states.order_states),
+            Err(send_error) => Err(ExchangeError::Failure { description: "json".to_string(), cause: send_error.to_string() })
+        }
+    }
+
+    // Only an exchange that cannot be reached, or that fails with a server error, counts against
+    // its health; an answer it gives, even a rejection, shows it is up. While the circuit is open,
+    // or half open with its probe still out, nothing is sent
+    async fn send(&self,
+                  send: impl Future<Output=Result<Response, reqwest::Error>>) -> Result<Response, ExchangeError> {
+        match self.exchange_health.start_request(self.exchange_id) {
+            Ok(true) => {},
+            Ok(false) => return Err(ExchangeError::Failure { description: "circuit open".to_string(), cause: format!("exchange {} is unavailable", self.exchange_id) }),
+            Err(health_error) => return Err(ExchangeError::Failure { description: "exchange health".to_string(), cause: health_error.to_string() }),
+        };
+        let exchange_error = match send.await {
+            Ok(response) if !response.status().is_server_error() => {
+                self.exchange_health.record_success(self.exchange_id);
+                return Ok(response);
+            },
+            Ok(response) => ExchangeError::Failure { description: "server error".to_string(), cause: response.status().to_string() },
+            Err(send_error) => ExchangeError::Failure { description: "send await".to_string(), cause: send_error.to_string() },
+        };
+        self.exchange_health.record_failure(self.exchange_id, exchange_error.to_string());
+        Err(exchange_error)
+    }
 }
@@ -0,0 +1,344 @@
+use crate::config::BrokerConfig;
+use crate::dtos::exchange::{ExchangeHealth, ExchangeStatus};
+use crate::entities::exchange::Exchange;
+use crate::time::current_time_millis;
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CircuitSettings {
+    // Consecutive failures, over REST or the websocket, that open the circuit
+    pub failure_threshold: i32,
+    pub open_millis: i64,
+}
+
+impl CircuitSettings {
+    pub fn from_config(config: &BrokerConfig) -> Self {
+        CircuitSettings {
+            failure_threshold: config.exchange_failure_threshold,
+            open_millis: config.exchange_circuit_open_millis,
+        }
+    }
+}
+
+impl Default for CircuitSettings {
+    fn default() -> Self {
+        CircuitSettings {
+            failure_threshold: 3,
+            open_millis: 30000,
+        }
+    }
+}
+
+// Connection state of every exchange. Once the circuit has been open for its time, one request
+// at a time is let through as a probe: success closes the circuit and failure opens it again
+#[derive(Clone)]
+pub struct ExchangeHealthTracker {
+    web_socket_server: WebSocketServer,
+    settings: CircuitSettings,
+    connections: Arc<RwLock<HashMap<i32, Connection>>>,
+}
+
+#[derive(Clone, Debug)]
+struct Connection {
+    exchange_code: String,
+    websocket_connected: bool,
+    consecutive_failures: i32,
+    last_error: Option<String>,
+    open_until: Option<i64>,
+    // When the request probing a half-open circuit was let through
+    probe_time: Option<i64>,
+    published: (ExchangeStatus, bool),
+    update_time: i64,
+}
+
+impl Connection {
+    fn new(exchange_code: String,
+           now: i64) -> Self {
+        Connection {
+            exchange_code,
+            websocket_connected: false,
+            consecutive_failures: 0,
+            last_error: None,
+            open_until: None,
+            probe_time: None,
+            published: (ExchangeStatus::Degraded, false),
+            update_time: now,
+        }
+    }
+
+    fn status(&self,
+              now: i64) -> ExchangeStatus {
+        if self.open_until.is_some_and(|open_until| now < open_until) {
+            ExchangeStatus::Down
+        } else if !self.websocket_connected || self.consecutive_failures > 0 {
+            ExchangeStatus::Degraded
+        } else {
+            ExchangeStatus::Connected
+        }
+    }
+
+    fn is_half_open(&self,
+                    now: i64) -> bool {
+        self.open_until.is_some_and(|open_until| now >= open_until)
+    }
+
+    // A probe that never reports back is given up on after the open time, so one lost request
+    // cannot keep the circuit from closing
+    fn is_probe_in_flight(&self,
+                          settings: &CircuitSettings,
+                          now: i64) -> bool {
+        self.probe_time.is_some_and(|probe_time| now - probe_time < settings.open_millis)
+    }
+
+    fn is_available(&self,
+                    settings: &CircuitSettings,
+                    now: i64) -> bool {
+        match self.status(now) {
+            ExchangeStatus::Down => false,
+            _ => !(self.is_half_open(now) && self.is_probe_in_flight(settings, now)),
+        }
+    }
+
+    fn start_request(&mut self,
+                     settings: &CircuitSettings,
+                     now: i64) -> bool {
+        if !self.is_available(settings, now) {
+            return false;
+        }
+        if self.is_half_open(now) {
+            self.probe_time = Some(now);
+        }
+        true
+    }
+
+    fn on_success(&mut self,
+                  now: i64) {
+        self.consecutive_failures = 0;
+        self.open_until = None;
+        self.probe_time = None;
+        self.update_time = now;
+    }
+
+    fn on_failure(&mut self,
+                  settings: &CircuitSettings,
+                  description: String,
+                  now: i64) {
+        self.consecutive_failures += 1;
+        self.last_error = Some(description);
+        self.probe_time = None;
+        if self.consecutive_failures >= settings.failure_threshold {
+            self.open_until = Some(now + settings.open_millis);
+        }
+        self.update_time = now;
+    }
+
+    fn to_rest_api_exchange_health(&self,
+                                   now: i64) -> ExchangeHealth {
+        ExchangeHealth {
+            exchange_code: self.exchange_code.clone(),
+            status: self.status(now),
+            websocket_connected: self.websocket_connected,
+            consecutive_failures: self.consecutive_failures,
+            last_error: self.last_error.clone(),
+            circuit_open_until: self.open_until.filter(|open_until| now < *open_until),
+            update_time: self.update_time,
+        }
+    }
+}
+
+impl ExchangeHealthTracker {
+    pub fn new(web_socket_server: WebSocketServer,
+               settings: CircuitSettings) -> Self {
+        ExchangeHealthTracker {
+            web_socket_server,
+            settings,
+            connections: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn register(&self,
+                    exchange: &Exchange) {
+        self.update(exchange.exchange_id, |connection, _| {
+            connection.exchange_code = exchange.code.clone();
+        });
+    }
+
+    pub fn record_success(&self,
+                          exchange_id: i32) {
+        self.update(exchange_id, |connection, now| connection.on_success(now));
+    }
+
+    pub fn record_failure(&self,
+                          exchange_id: i32,
+                          description: String) {
+        let settings = self.settings;
+        self.update(exchange_id, |connection, now| connection.on_failure(&settings, description, now));
+    }
+
+    pub fn websocket_connected(&self,
+                               exchange_id: i32) {
+        self.update(exchange_id, |connection, now| {
+            connection.websocket_connected = true;
+            connection.on_success(now);
+        });
+    }
+
+    pub fn websocket_disconnected(&self,
+                                  exchange_id: i32) {
+        let settings = self.settings;
+        self.update(exchange_id, |connection, now| {
+            connection.websocket_connected = false;
+            connection.on_failure(&settings, "websocket disconnected".to_string(), now);
+        });
+    }
+
+    // Orders may go to an exchange unless its circuit is open, or half open with its probe still out
+    pub fn is_available(&self,
+                        exchange_id: i32) -> Result<bool, Error> {
+        let readable_connections = match self.connections.read() {
+            Ok(readable_connections) => readable_connections,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to exchange connections: {}", readable_error)),
+        };
+        Ok(readable_connections.get(&exchange_id)
+            .is_none_or(|connection| connection.is_available(&self.settings, current_time_millis())))
+    }
+
+    // Called before each request to the exchange. When the circuit is half open, the request
+    // that gets true is the probe, and the rest get false until it succeeds or fails
+    pub fn start_request(&self,
+                         exchange_id: i32) -> Result<bool, Error> {
+        let mut writable_connections = match self.connections.write() {
+            Ok(writable_connections) => writable_connections,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to exchange connections: {}", writable_error)),
+        };
+        Ok(match writable_connections.get_mut(&exchange_id) {
+            Some(connection) => connection.start_request(&self.settings, current_time_millis()),
+            None => true,
+        })
+    }
+
+    // An open circuit turns half open with time rather than on any event, so is checked for here
+    pub fn publish_changes(&self) -> Result<(), Error> {
+        let exchange_ids: Vec<i32> = match self.connections.read() {
+            Ok(readable_connections) => readable_connections.keys().copied().collect(),
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to exchange connections: {}", readable_error)),
+        };
+        for exchange_id in exchange_ids {
+            self.update(exchange_id, |_, _| {});
+        }
+        Ok(())
+    }
+
+    pub fn get_all(&self) -> Result<Vec<ExchangeHealth>, Error> {
+        let readable_connections = match self.connections.read() {
+            Ok(readable_connections) => readable_connections,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to exchange connections: {}", readable_error)),
+        };
+        let now = current_time_millis();
+        let mut exchange_healths: Vec<ExchangeHealth> = readable_connections.values()
+            .map(|connection| connection.to_rest_api_exchange_health(now))
+            .collect();
+        exchange_healths.sort_by(|a, b| a.exchange_code.cmp(&b.exchange_code));
+        Ok(exchange_healths)
+    }
+
+    // Publishes the exchange's health whenever its status or websocket state differs from what
+    // was last published
+    fn update(&self,
+              exchange_id: i32,
+              change: impl FnOnce(&mut Connection, i64)) {
+        let now = current_time_millis();
+        let changed = {
+            let mut writable_connections = match self.connections.write() {
+                Ok(writable_connections) => writable_connections,
+                Err(writable_error) => {
+                    error!("Unable to get write access to exchange connections: {}", writable_error);
+                    return;
+                }
+            };
+            let connection = writable_connections.entry(exchange_id)
+                .or_insert_with(|| Connection::new(exchange_id.to_string(), now));
+            change(connection, now);
+            let current = (connection.status(now), connection.websocket_connected);
+            let changed = connection.published != current;
+            connection.published = current;
+            changed.then(|| connection.to_rest_api_exchange_health(now))
+        };
+        if let Some(exchange_health) = changed {
+            match exchange_health.status {
+                ExchangeStatus::Down => warn!("Exchange {} is down: {:?}", exchange_health.exchange_code, exchange_health.last_error),
+                _ => info!("Exchange {} is {}", exchange_health.exchange_code, exchange_health.status),
+            };
+            let destination = format!("/exchanges/{}/status", exchange_health.exchange_code);
+            self.web_socket_server.clone().send_retained_message(destination, &exchange_health);
+        }
+    }
+}
+
+pub fn start_health_publisher(exchange_health: ExchangeHealthTracker,
+                              interval_millis: u64) {
+    tokio::spawn(health_publisher_loop(exchange_health, interval_millis));
+}
+
+async fn health_publisher_loop(exchange_health: ExchangeHealthTracker,
+                               interval_millis: u64) {
+    loop {
+        tokio::time::sleep(Duration::from_millis(interval_millis)).await;
+        match exchange_health.publish_changes() {
+            Ok(_) => {},
+            Err(err) => error!("Unable to publish exchange health: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dtos::exchange::ExchangeStatus;
+    use crate::exchange_interface::exchange_health::{CircuitSettings, Connection};
+
+    #[test]
+    async fn test_circuit_opens_and_closes() {
+        let settings = CircuitSettings { failure_threshold: 2, open_millis: 1000 };
+        let mut connection = Connection::new("EX".to_string(), 0);
+        assert_eq!(connection.status(0), ExchangeStatus::Degraded);
+        connection.websocket_connected = true;
+        assert_eq!(connection.status(0), ExchangeStatus::Connected);
+
+        connection.on_failure(&settings, "send".to_string(), 10);
+        assert_eq!(connection.status(10), ExchangeStatus::Degraded);
+        connection.on_failure(&settings, "send".to_string(), 20);
+        assert_eq!(connection.status(20), ExchangeStatus::Down);
+        assert_eq!(connection.status(1019), ExchangeStatus::Down);
+
+        // After the open time a failed probe opens the circuit again, and a good one closes it
+        assert_eq!(connection.status(1020), ExchangeStatus::Degraded);
+        connection.on_failure(&settings, "send".to_string(), 1030);
+        assert_eq!(connection.status(1030), ExchangeStatus::Down);
+        connection.on_success(2100);
+        assert_eq!(connection.status(2100), ExchangeStatus::Connected);
+        assert_eq!(connection.consecutive_failures, 0);
+    }
+
+    #[test]
+    async fn test_half_open_circuit_lets_one_probe_through() {
+        let settings = CircuitSettings { failure_threshold: 1, open_millis: 1000 };
+        let mut connection = Connection::new("EX".to_string(), 0);
+        connection.on_failure(&settings, "send".to_string(), 0);
+        assert!(!connection.start_request(&settings, 500));
+
+        assert!(connection.start_request(&settings, 1000));
+        assert!(!connection.is_available(&settings, 1001));
+        assert!(!connection.start_request(&settings, 1001));
+
+        // A probe that never reports back stops blocking after the open time
+        assert!(connection.start_request(&settings, 2000));
+        connection.on_success(2010);
+        assert!(connection.start_request(&settings, 2011));
+        assert!(connection.start_request(&settings, 2012));
+    }
+}
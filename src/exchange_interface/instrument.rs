@@ -1,3 +1,4 @@
+use crate::money::Money;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -34,6 +35,19 @@ pub struct Instrument {
     pub description: String,
     #[serde(rename = "expirationTime")]
     pub expiration_time: i64,
+    #[serde(rename = "tickSize", default = "default_tick_size")]
+    pub tick_size: Money,
+    #[serde(rename = "pricePrecision", default = "default_price_precision")]
+    pub price_precision: i32,
+}
+
+// Exchanges that do not publish price increments trade in cents
+fn default_tick_size() -> Money {
+    Money::new(1, 2)
+}
+
+fn default_price_precision() -> i32 {
+    2
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -1,3 +1,4 @@
+use crate::money::Money;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -10,7 +11,7 @@ pub struct LastTrade {
     pub instrument_id: i64,
     #[serde(rename = "createTime")]
     pub create_time: i64,
-    pub price: f32,
+    pub price: Money,
     pub quantity: i32,
 }
 
@@ -30,6 +31,6 @@ pub struct MarketDepth {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PriceLevel {
-    pub price: f32,
+    pub price: Money,
     pub quantity: i32,
 }
\ No newline at end of file
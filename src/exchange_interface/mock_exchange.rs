@@ -0,0 +1,127 @@
+use crate::dtos::exchange::{AssetClass, InstrumentStatus};
+use crate::entities::exchange::{Exchange, Instrument};
+use crate::exchange_interface::order::{Executions, OrderState, OrderStates, OrderStatus, SubmitOrders};
+use crate::money::Money;
+use crate::persistence::dao::Dao;
+use crate::time::current_time_millis;
+use actix_web::web::{Data, Json, Path};
+use actix_web::{App, HttpResponse, HttpServer};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub const MOCK_INSTRUMENT_KEY: &str = "mock_instrument";
+pub const MOCK_OTHER_INSTRUMENT_KEY: &str = "mock_other_instrument";
+
+// An exchange for tests that rests every order it is sent and cancels whatever is still open.
+// It never trades and reports no executions
+pub struct MockExchange {
+    pub url: String,
+}
+
+type Book = Data<Mutex<HashMap<String, OrderState>>>;
+
+impl MockExchange {
+    pub fn start() -> MockExchange {
+        let book: Book = Data::new(Mutex::new(HashMap::new()));
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(book.clone())
+                .service(submit_orders)
+                .service(cancel_order)
+                .service(get_order)
+                .service(get_executions)
+        })
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        MockExchange { url }
+    }
+
+    // Saves the exchange with two active equity instruments on it, whose exchange instrument ids
+    // are 1 and 2. Its websocket address goes nowhere, so an InstrumentManager loading it needs a
+    // failure threshold that will not trip
+    pub async fn save(&self,
+                      dao: &Dao) -> Exchange {
+        let mut db_connection = dao.get_connection().await.unwrap();
+        let txn = dao.begin(&mut db_connection).await.unwrap();
+        let mut exchange = Exchange {
+            exchange_id: 0,
+            code: "MOCK".to_string(),
+            url: self.url.clone(),
+            websocket_url: "ws://127.0.0.1:1/ws".to_string(),
+            description: "Mock exchange".to_string(),
+            api_key: "mock".to_string(),
+            supports_time_in_force: true,
+            max_order_legs: None,
+        };
+        txn.save_exchange(&mut exchange).await.unwrap();
+        for (exchange_instrument_id, instrument_key) in [(1, MOCK_INSTRUMENT_KEY), (2, MOCK_OTHER_INSTRUMENT_KEY)] {
+            let mut instrument = Instrument {
+                instrument_id: 0,
+                instrument_key: instrument_key.to_string(),
+                exchange_id: exchange.exchange_id,
+                exchange_instrument_id,
+                status: InstrumentStatus::Active,
+                symbol: instrument_key.to_uppercase(),
+                asset_class: AssetClass::Equity,
+                description: "Mock instrument".to_string(),
+                expiration_time: current_time_millis() + 365 * 24 * 60 * 60 * 1000,
+                tick_size: Money::new(1, 2),
+                price_precision: 2,
+            };
+            txn.save_instrument(&mut instrument).await.unwrap();
+        }
+        txn.commit().await.unwrap();
+        exchange
+    }
+}
+
+#[post("/orders")]
+async fn submit_orders(book: Book,
+                       submit: Json<SubmitOrders>) -> HttpResponse {
+    let mut book = book.lock().unwrap();
+    let order_states: Vec<OrderState> = submit.into_inner().orders.into_iter().map(|order| {
+        let order_state = OrderState {
+            update_time: current_time_millis(),
+            order_status: OrderStatus::Open,
+            remaining_quantity: order.quantity,
+            order,
+        };
+        book.insert(order_state.order.client_order_id.clone(), order_state.clone());
+        order_state
+    }).collect();
+    HttpResponse::Ok().json(OrderStates { order_states })
+}
+
+#[delete("/orders/{client_order_id}")]
+async fn cancel_order(book: Book,
+                      path: Path<String>) -> HttpResponse {
+    let mut book = book.lock().unwrap();
+    match book.get_mut(&path.into_inner()) {
+        Some(order_state) => {
+            if order_state.order_status == OrderStatus::Open {
+                order_state.order_status = OrderStatus::Canceled;
+                order_state.update_time = current_time_millis();
+            }
+            HttpResponse::Ok().json(OrderStates { order_states: vec![order_state.clone()] })
+        },
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+#[get("/orders/{client_order_id}")]
+async fn get_order(book: Book,
+                   path: Path<String>) -> HttpResponse {
+    let book = book.lock().unwrap();
+    match book.get(&path.into_inner()) {
+        Some(order_state) => HttpResponse::Ok().json(OrderStates { order_states: vec![order_state.clone()] }),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+#[get("/executions/{client_order_id}")]
+async fn get_executions() -> HttpResponse {
+    HttpResponse::Ok().json(Executions { executions: vec![] })
+}
@@ -1,4 +1,7 @@
 pub(crate) mod exchange_client;
+#[cfg(test)]
+pub(crate) mod mock_exchange;
+pub(crate) mod exchange_health;
 pub(crate) mod order;
 pub(crate) mod websocket_client;
 mod exchange_error;
@@ -1,3 +1,4 @@
+use crate::money::Money;
 use serde::{Deserialize, Serialize};
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[derive(Clone)]
@@ -14,6 +15,21 @@ pub enum OrderStatus {
     Rejected,
 }
 
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Clone)]
+pub enum TimeInForce {
+    #[serde(rename = "DAY")]
+    Day,
+    #[serde(rename = "GTC")]
+    Gtc,
+    #[serde(rename = "GTD")]
+    Gtd,
+    #[serde(rename = "IOC")]
+    Ioc,
+    #[serde(rename = "FOK")]
+    Fok,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct OrderStates {
     #[serde(rename = "orderStates")]
@@ -42,9 +58,13 @@ pub struct Order {
     #[serde(rename = "clientOrderId")]
     pub client_order_id: String,
     #[serde(rename = "price")]
-    pub price: f32,
+    pub price: Money,
     pub quantity: i32,
     pub legs: Vec<OrderLeg>,
+    #[serde(rename = "timeInForce", default, skip_serializing_if = "Option::is_none")]
+    pub time_in_force: Option<TimeInForce>,
+    #[serde(rename = "expireTime", default, skip_serializing_if = "Option::is_none")]
+    pub expire_time: Option<i64>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -55,18 +75,25 @@ pub struct OrderLeg {
     pub ratio: i32,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Execution {
+    #[serde(rename = "executionId")]
+    pub execution_id: i64,
     #[serde(rename = "clientOrderId")]
     pub client_order_id: String,
     #[serde(rename = "instrumentId")]
     pub instrument_id: i64,
     #[serde(rename = "createTime")]
     pub create_time: i64,
-    pub price: f32,
+    pub price: Money,
     pub quantity: i32,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Executions {
+    pub executions: Vec<Execution>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ExecutionsTopicWrapper {
     #[serde(rename = "orderState")]
@@ -6,11 +6,21 @@ use crate::persistence::dao::Dao;
 use crate::websockets;
 use crate::websockets::server::WebSocketServer;
 use crate::websockets::stomp::MessageContent;
-use log::{debug, error, info};
+use log::{debug, error};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+// The functions an exchange's websocket messages are handed to
+pub struct ExchangeHandlers {
+    pub execution_handler: fn(mutex: Arc<Mutex<()>>, &Dao, &WebSocketServer, &InstrumentManager, Execution),
+    pub order_state_handler: fn(mutex: Arc<Mutex<()>>, &Dao, &WebSocketServer, &InstrumentManager, OrderState),
+    pub depth_handler: fn(&WebSocketServer, &InstrumentManager, MarketDepth),
+    pub last_trade_handler: fn(&WebSocketServer, &InstrumentManager, LastTrade),
+    pub connected_handler: fn(mutex: Arc<Mutex<()>>, &Dao, &WebSocketServer, &InstrumentManager, i32),
+}
+
 pub struct ExchangeWebsocketClient {
+    pub exchange_id: i32,
     pub websocket_address: String, 
     pub customer_key: String,
     pub dao: Dao,
@@ -20,29 +30,30 @@ pub struct ExchangeWebsocketClient {
     pub order_state_handler: fn(mutex: Arc<Mutex<()>>, &Dao, &WebSocketServer, &InstrumentManager, OrderState),
     pub depth_handler: fn(&WebSocketServer, &InstrumentManager, MarketDepth),
     pub last_trade_handler: fn(&WebSocketServer, &InstrumentManager, LastTrade),
+    pub connected_handler: fn(mutex: Arc<Mutex<()>>, &Dao, &WebSocketServer, &InstrumentManager, i32),
     pub mutex: Arc<Mutex<()>>,
 }
 
 impl ExchangeWebsocketClient {
-    pub fn new(websocket_address: String, 
+    pub fn new(exchange_id: i32,
+               websocket_address: String, 
                customer_key: String,
                dao: Dao,
                web_socket_server: WebSocketServer,
                instrument_manager: InstrumentManager,
-               execution_handler: fn(mutex: Arc<Mutex<()>>, &Dao, &WebSocketServer, &InstrumentManager, Execution),
-               order_state_handler: fn(mutex: Arc<Mutex<()>>, &Dao, &WebSocketServer, &InstrumentManager, OrderState),
-               depth_handler: fn(&WebSocketServer, &InstrumentManager, MarketDepth),
-               last_trade_handler: fn(&WebSocketServer, &InstrumentManager, LastTrade)) -> Self {
+               handlers: ExchangeHandlers) -> Self {
         ExchangeWebsocketClient {
+            exchange_id,
             websocket_address,
             customer_key,
             dao,
             web_socket_server,
             instrument_manager,
-            execution_handler,
-            order_state_handler,
-            depth_handler,
-            last_trade_handler,
+            execution_handler: handlers.execution_handler,
+            order_state_handler: handlers.order_state_handler,
+            depth_handler: handlers.depth_handler,
+            last_trade_handler: handlers.last_trade_handler,
+            connected_handler: handlers.connected_handler,
             mutex: Arc::new(Mutex::new(())),
         }}
 
@@ -52,6 +63,8 @@ impl ExchangeWebsocketClient {
         conn.subscribe("/user/queue/executions", build_executions_receiver(self.mutex.clone(), self.dao.clone(), self.web_socket_server.clone(), self.instrument_manager.clone(), self.execution_handler, self.order_state_handler));
         conn.subscribe("/topics/depth", build_depth_receiver(self.mutex.clone(), self.web_socket_server.clone(), self.instrument_manager.clone(), self.depth_handler));
         conn.subscribe( "/topics/trades", build_last_trade_receiver(self.mutex.clone(), self.web_socket_server.clone(), self.instrument_manager.clone(), self.last_trade_handler));
+        conn.on_connected(build_connected_receiver(self.mutex.clone(), self.dao.clone(), self.web_socket_server.clone(), self.instrument_manager.clone(), self.exchange_id, self.connected_handler));
+        conn.on_disconnected(build_disconnected_receiver(self.instrument_manager.clone(), self.exchange_id));
 
         conn.start();
     }
@@ -66,6 +79,23 @@ fn build_executions_receiver(mutex: Arc<Mutex<()>>,
     Arc::new(move |message| executions_receiver(mutex.clone(), &dao, &web_socket_server, &instrument_manager,  execution_handler, order_state_handler, message))
 }
 
+fn build_connected_receiver(mutex: Arc<Mutex<()>>,
+                            dao: Dao,
+                            web_socket_server: WebSocketServer,
+                            instrument_manager: InstrumentManager,
+                            exchange_id: i32,
+                            connected_handler: fn(mutex: Arc<Mutex<()>>, &Dao, &WebSocketServer, &InstrumentManager, i32)) -> Arc<dyn Fn() + Send + Sync + 'static> {
+    Arc::new(move || {
+        instrument_manager.get_exchange_health().websocket_connected(exchange_id);
+        connected_handler(mutex.clone(), &dao, &web_socket_server, &instrument_manager, exchange_id)
+    })
+}
+
+fn build_disconnected_receiver(instrument_manager: InstrumentManager,
+                               exchange_id: i32) -> Arc<dyn Fn() + Send + Sync + 'static> {
+    Arc::new(move || instrument_manager.get_exchange_health().websocket_disconnected(exchange_id))
+}
+
 fn build_depth_receiver(mutex: Arc<Mutex<()>>, 
                         web_socket_server: WebSocketServer, 
                         instrument_manager: InstrumentManager, 
@@ -104,6 +134,9 @@ fn executions_receiver(mutex: Arc<Mutex<()>>,
     }
     match wrapper.execution {
         None => {}
+        // Every delivery is handed on. An execution is only known to be applied once its trade is
+        // committed, and the unique constraint on the trade's execution id turns a re-delivery into
+        // a no-op, so a delivery whose first attempt failed is still applied
         Some(execution) => {
             execution_handler(mutex, dao, web_socket_server, instrument_manager, execution);
         }
@@ -141,4 +174,4 @@ fn last_trade_receiver(mutex: Arc<Mutex<()>>,
         },
     };
     last_trade_handler(web_socket_server, instrument_manager, last_trade);
-}
\ No newline at end of file
+}
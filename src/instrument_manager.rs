@@ -1,15 +1,24 @@
 use crate::entities::exchange::{Exchange, Instrument};
 use crate::exchange_interface::exchange_client::ExchangeClient;
-use crate::exchange_interface::websocket_client::ExchangeWebsocketClient;
+use crate::exchange_interface::exchange_health::{CircuitSettings, ExchangeHealthTracker};
+use crate::exchange_interface::websocket_client::{ExchangeHandlers, ExchangeWebsocketClient};
+use crate::margin::margin_calls::MarginCalls;
+use crate::market_data::bar_aggregator::BarAggregator;
+use crate::market_data::depth_cache::DepthCache;
 use crate::market_data::receiver::{handle_depth, handle_last_trade};
+use crate::market_data::valuation::ValuationService;
 use crate::persistence::dao::{Dao, DaoTransaction};
+use crate::routing::order_router::OrderRouter;
 use crate::trade_handling::execution_handling::handle_execution;
 use crate::trade_handling::order_state_handling::handle_order_state;
+use crate::trade_handling::reconciliation::handle_connected;
+use crate::trade_handling::trigger_engine::TriggerEngine;
 use crate::websockets::server::WebSocketServer;
 use anyhow::Error;
 use log::info;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use tokio::sync::Mutex;
 
 #[derive(Clone)]
 pub struct InstrumentManager {
@@ -19,6 +28,13 @@ pub struct InstrumentManager {
     instruments_by_key: Arc<RwLock<HashMap<String, Instrument>>>,
     instruments_by_exchange_instrument_id: Arc<RwLock<HashMap<i64, Instrument>>>,
     exchanges_holders_by_id: Arc<RwLock<HashMap<i32, Arc<ExchangeHolder>>>>,
+    trigger_engine: TriggerEngine,
+    bar_aggregator: BarAggregator,
+    valuation_service: ValuationService,
+    margin_calls: MarginCalls,
+    depth_cache: DepthCache,
+    order_router: OrderRouter,
+    exchange_health: ExchangeHealthTracker,
 }
 
 struct ExchangeHolder {
@@ -29,8 +45,16 @@ struct ExchangeHolder {
 
 impl InstrumentManager {
     pub fn new (dao: Dao, 
-                web_socket_server: WebSocketServer) -> Self {
+                web_socket_server: WebSocketServer,
+                circuit_settings: CircuitSettings) -> Self {
         InstrumentManager {
+            trigger_engine: TriggerEngine::new(dao.clone(), web_socket_server.clone()),
+            bar_aggregator: BarAggregator::new(web_socket_server.clone()),
+            valuation_service: ValuationService::new(),
+            margin_calls: MarginCalls::new(),
+            depth_cache: DepthCache::new(),
+            order_router: OrderRouter::new(),
+            exchange_health: ExchangeHealthTracker::new(web_socket_server.clone(), circuit_settings),
             dao,
             web_socket_server,
             instruments: Arc::new(RwLock::new(HashMap::new())),
@@ -49,16 +73,43 @@ impl InstrumentManager {
             Err(dao_error) => panic!("Could not begin: {}", dao_error),
         };
 
-        match self.load_exchanges(&txn).await {
-            Ok(_) => { },
-            Err(err) => panic!("Could not load exchanges: {}", err),
-        };
-
+        // Instruments first, so that the reconciliation run on each exchange connect can resolve them
         match self.load_instruments(&txn).await {
             Ok(x) => x,
             Err(err) => panic!("Could not load instruments: {}", err),
         };
 
+        // Held orders are watching for trades as soon as market data starts arriving
+        match self.trigger_engine.load(&txn).await {
+            Ok(_) => { },
+            Err(err) => panic!("Could not load pending trigger orders: {}", err),
+        };
+
+        match self.bar_aggregator.load(&txn).await {
+            Ok(_) => { },
+            Err(err) => panic!("Could not load bars in progress: {}", err),
+        };
+
+        match self.valuation_service.load(&txn).await {
+            Ok(_) => { },
+            Err(err) => panic!("Could not load prior closes: {}", err),
+        };
+
+        match self.margin_calls.load(&txn).await {
+            Ok(_) => { },
+            Err(err) => panic!("Could not load open margin calls: {}", err),
+        };
+
+        match self.order_router.load(&txn).await {
+            Ok(_) => { },
+            Err(err) => panic!("Could not load logical instruments: {}", err),
+        };
+
+        match self.load_exchanges(&txn).await {
+            Ok(_) => { },
+            Err(err) => panic!("Could not load exchanges: {}", err),
+        };
+
         match txn.rollback().await {
             Ok(_) => Ok(()),
             Err(dao_error) => panic!("Could not rollback: {}", dao_error),
@@ -103,30 +154,94 @@ impl InstrumentManager {
 
     pub async fn setup_exchange(&self, 
                                 exchange: Exchange) -> Result<(), Error> {
-        let exchange_client = ExchangeClient::new(exchange.url.as_str(), exchange.api_key.as_str());
-        let exchange_websocket_client = ExchangeWebsocketClient::new(exchange.websocket_url.clone(),
+        self.exchange_health.register(&exchange);
+        let exchange_client = ExchangeClient::new(exchange.exchange_id, exchange.url.as_str(), exchange.api_key.as_str(),
+                                                  self.exchange_health.clone());
+        let exchange_websocket_client = ExchangeWebsocketClient::new(exchange.exchange_id,
+                                                                     exchange.websocket_url.clone(),
                                                                      exchange.api_key.clone(),
                                                                      self.dao.clone(),
                                                                      self.web_socket_server.clone(),
                                                                      self.clone(),
-                                                                     handle_execution, handle_order_state,
-                                                                     handle_depth, handle_last_trade);
-        exchange_websocket_client.start_exchange_websockets().await;
+                                                                     ExchangeHandlers {
+                                                                         execution_handler: handle_execution,
+                                                                         order_state_handler: handle_order_state,
+                                                                         depth_handler: handle_depth,
+                                                                         last_trade_handler: handle_last_trade,
+                                                                         connected_handler: handle_connected,
+                                                                     });
         let exchange_id = exchange.exchange_id;
+        let exchange_websocket_client = Arc::new(exchange_websocket_client);
         let exchange_holder = ExchangeHolder {
             exchange: Arc::new(exchange),
             exchange_client: Arc::new(exchange_client),
-            exchange_websocket_client: Arc::new(exchange_websocket_client),
-        };
-        let mut writable_exchanges = match self.exchanges_holders_by_id.write() {
-            Ok(writable_exchanges) => writable_exchanges,
-            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to exchanges: {}", writable_error)),
+            exchange_websocket_client: exchange_websocket_client.clone(),
         };
-        writable_exchanges.insert(exchange_id, Arc::from(exchange_holder));
+        {
+            let mut writable_exchanges = match self.exchanges_holders_by_id.write() {
+                Ok(writable_exchanges) => writable_exchanges,
+                Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to exchanges: {}", writable_error)),
+            };
+            writable_exchanges.insert(exchange_id, Arc::from(exchange_holder));
+        }
+        // Started only once the exchange is registered, since every connect triggers a reconciliation against it
+        exchange_websocket_client.start_exchange_websockets().await;
 
         Ok(())
     }
 
+    pub fn get_exchange_client(&self,
+                               exchange_id: i32) -> Result<Arc<ExchangeClient>, Error> {
+        let readable_exchanges = match self.exchanges_holders_by_id.read() {
+            Ok(readable_exchanges) => readable_exchanges,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to exchanges: {}", readable_error)),
+        };
+        match readable_exchanges.get(&exchange_id) {
+            Some(exchange_holder) => Ok(exchange_holder.exchange_client.clone()),
+            None => Err(anyhow::anyhow!("No exchange for exchange id: {}", exchange_id)),
+        }
+    }
+
+    pub fn get_exchange_mutex(&self,
+                              exchange_id: i32) -> Result<Arc<Mutex<()>>, Error> {
+        let readable_exchanges = match self.exchanges_holders_by_id.read() {
+            Ok(readable_exchanges) => readable_exchanges,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to exchanges: {}", readable_error)),
+        };
+        match readable_exchanges.get(&exchange_id) {
+            Some(exchange_holder) => Ok(exchange_holder.exchange_websocket_client.mutex.clone()),
+            None => Err(anyhow::anyhow!("No exchange for exchange id: {}", exchange_id)),
+        }
+    }
+
+    pub fn get_trigger_engine(&self) -> &TriggerEngine {
+        &self.trigger_engine
+    }
+
+    pub fn get_bar_aggregator(&self) -> &BarAggregator {
+        &self.bar_aggregator
+    }
+
+    pub fn get_margin_calls(&self) -> &MarginCalls {
+        &self.margin_calls
+    }
+
+    pub fn get_valuation_service(&self) -> &ValuationService {
+        &self.valuation_service
+    }
+
+    pub fn get_depth_cache(&self) -> &DepthCache {
+        &self.depth_cache
+    }
+
+    pub fn get_order_router(&self) -> &OrderRouter {
+        &self.order_router
+    }
+
+    pub fn get_exchange_health(&self) -> &ExchangeHealthTracker {
+        &self.exchange_health
+    }
+
     pub fn get_exchange_client_for_instrument(&self, 
                                               instrument: &Instrument) -> Result<Arc<ExchangeClient>, Error> {
         let readable_exchanges = match self.exchanges_holders_by_id.read() {
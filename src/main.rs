@@ -11,6 +11,7 @@ use actix_web::cookie::Key;
 use actix_web::{dev::ServiceResponse, http::header, middleware, middleware::{ErrorHandlerResponse, ErrorHandlers}, web::ThinData, App, HttpServer, Result};
 use confik::{Configuration as _, EnvSource};
 use std::io;
+use std::sync::Arc;
 use tokio_postgres::NoTls;
 
 use dotenv::dotenv;
@@ -27,20 +28,35 @@ use crate::access_control::AccessControl;
 use crate::auth::{auth_api, auth_ui, logout};
 use crate::persistence::dao::Dao;
 use crate::rest_api::instrument_api;
+use crate::rest_api::market_data_api;
 use crate::validator::validator::Validator;
+use crate::exchange_interface::exchange_health::CircuitSettings;
+use crate::margin::margin_engine::{MarginEngine, MarginRules};
+use crate::margin::margin_monitor::LiquidationSettings;
+use crate::trade_handling::outbox::OutboxSettings;
+use crate::trade_handling::trading_context::TradingContext;
 use crate::vetting::all_pass_vetter::AllPassVetter;
+use crate::vetting::margin_vetter::MarginVetter;
+use crate::vetting::risk_vetter::{RiskLimits, RiskVetter};
+use crate::vetting::vetter_registry::VetterRegistry;
 use crate::websockets::server::WebSocketServer;
 use crate::websockets::ws_handler;
 use instrument_manager::InstrumentManager;
 use rest_api::account_api;
 use rest_api::balance_position_api;
+use rest_api::cash_api;
 use rest_api::order_api;
+use rest_api::order_group_api;
+use rest_api::routing_api;
+use rest_api::statement_api;
+use rest_api::trade_api;
 
 mod entities;
 mod config;
 mod persistence;
 pub(crate) mod instrument_manager;
 mod time;
+mod money;
 mod access_control;
 mod vetting;
 mod websockets;
@@ -49,6 +65,9 @@ mod market_data;
 mod converters;
 mod dtos;
 mod validator;
+mod statements;
+mod margin;
+mod routing;
 
 fn add_error_header<B>(mut res: ServiceResponse<B>) -> Result<ErrorHandlerResponse<B>> {
     res.response_mut().headers_mut().insert(
@@ -80,7 +99,7 @@ async fn main() -> io::Result<()> {
 
     let dao = Dao::new(pool);
 
-    let mut instrument_manager = InstrumentManager::new(dao.clone(), web_socket_server.clone());
+    let mut instrument_manager = InstrumentManager::new(dao.clone(), web_socket_server.clone(), CircuitSettings::from_config(&config));
     match instrument_manager.initialize().await {
         Ok(_) => { },
         Err(init_error) => panic!("Could not initialize instrument manager: {}", init_error),
@@ -90,10 +109,29 @@ async fn main() -> io::Result<()> {
 
     let access_control = AccessControl::new();
 
-    let vetter = AllPassVetter::new();
+    let mut vetter_registry = VetterRegistry::new(config.default_vetter.as_str());
+    vetter_registry.register(Arc::new(AllPassVetter::new()));
+    vetter_registry.register(Arc::new(RiskVetter::new(instrument_manager.clone(), RiskLimits::from_config(&config))));
+    let margin_engine = MarginEngine::new(instrument_manager.clone(), MarginRules::from_config(&config));
+    vetter_registry.register(Arc::new(MarginVetter::new(margin_engine.clone())));
+    if !vetter_registry.has_vetter(config.default_vetter.as_str()) {
+        panic!("Unknown default vetter: {}", config.default_vetter);
+    }
 
     let validator = Validator::new(instrument_manager.clone());
 
+    statements::end_of_day::start_end_of_day_job(dao.clone(), config.end_of_day_hour_utc);
+    trade_handling::expiry::start_expiry_sweeper(dao.clone(), web_socket_server.clone(), instrument_manager.clone(),
+                                                 config.expiry_sweep_interval_millis, config.end_of_day_hour_utc);
+    market_data::valuation::start_mark_publisher(dao.clone(), web_socket_server.clone(), instrument_manager.clone(),
+                                                 config.mark_push_interval_millis);
+    exchange_interface::exchange_health::start_health_publisher(instrument_manager.get_exchange_health().clone(), config.exchange_health_interval_millis);
+    market_data::bar_aggregator::start_bar_writer(dao.clone(), instrument_manager.clone(), config.bar_save_interval_millis);
+    trade_handling::outbox::start_outbox_worker(dao.clone(), web_socket_server.clone(), instrument_manager.clone(),
+                                                OutboxSettings::from_config(&config), config.outbox_interval_millis);
+    margin::margin_monitor::start_margin_monitor(dao.clone(), web_socket_server.clone(), instrument_manager.clone(), margin_engine.clone(),
+                                                 LiquidationSettings::from_config(&config), config.margin_monitor_interval_millis);
+
     let secret_key = Key::from(config.session_key.as_bytes());
     let redis_store = match RedisSessionStore::new(config.redis_addr)
         .await {
@@ -106,10 +144,12 @@ async fn main() -> io::Result<()> {
             .app_data(ThinData(instrument_manager.clone()))
             .app_data(ThinData(dao.clone()))
             .app_data(ThinData(access_control.clone()))
-            .app_data(ThinData(vetter.clone()))
+            .app_data(ThinData(vetter_registry.clone()))
             .app_data(ThinData(validator.clone()))
+            .app_data(ThinData(margin_engine.clone()))
             .app_data(ThinData(web_socket_server.clone()))
             .app_data(ThinData(oconfig.clone()))
+            .app_data(ThinData(TradingContext::new(dao.clone(), web_socket_server.clone(), instrument_manager.clone())))
             .wrap(middleware::Logger::default())
             .wrap(
                 SessionMiddleware::new(
@@ -120,25 +160,49 @@ async fn main() -> io::Result<()> {
             .wrap(ErrorHandlers::new().default_handler(add_error_header))
             .wrap(
                 Cors::permissive()
-                    .allowed_methods(vec!["GET", "POST", "DELETE", "OPTIONS"])
+                    .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
                     .max_age(3600)
                     )
             .service(order_api::get_order)
             .service(order_api::get_orders)
             .service(order_api::preview_order)
             .service(order_api::submit_order)
+            .service(order_api::submit_orders)
             .service(order_api::cancel_order)
+            .service(order_api::cancel_orders)
+            .service(order_api::replace_order)
+            .service(order_group_api::submit_order_group)
+            .service(routing_api::submit_routed_order)
+            .service(routing_api::get_logical_instruments)
+            .service(trade_api::get_trades)
             .service(balance_position_api::get_positions)
             .service(balance_position_api::get_balance)
+            .service(balance_position_api::get_margin)
+            .service(statement_api::get_statement)
+            .service(cash_api::get_ledger)
+            .service(cash_api::deposit)
+            .service(cash_api::withdraw)
             .service(account_api::get_accounts)
             .service(auth_ui::register_ui)
             .service(auth_ui::login_ui)
             .service(auth_api::login_api)
             .service(logout::logout)
             .service(admin_api::offer_admin::create_offer)
+            .service(admin_api::account_admin::set_account_vetter)
+            .service(admin_api::account_admin::grant_privilege)
+            .service(admin_api::account_admin::revoke_privilege)
+            .service(admin_api::ledger_admin::post_admin_journal)
+            .service(admin_api::ledger_admin::get_ledger_reconciliation)
+            .service(admin_api::fee_admin::save_fee_schedule)
+            .service(admin_api::fee_admin::get_fee_schedules)
             .service(admin_api::instrument_admin::create_exchange)
             .service(admin_api::instrument_admin::load_exchange_instruments)
+            .service(admin_api::instrument_admin::create_logical_instrument)
+            .service(admin_api::instrument_admin::get_exchanges_status)
             .service(instrument_api::get_instruments)
+            .service(market_data_api::get_depth)
+            .service(market_data_api::get_last_trade)
+            .service(market_data_api::get_bars)
             .service(ws_handler::ws_setup)
             .service(fs::Files::new("/app", "./resources/static/app")
                          .index_file("app.html")
@@ -0,0 +1,148 @@
+use crate::dtos;
+use crate::entities::account::{MarginCall, Position};
+use crate::instrument_manager::InstrumentManager;
+use crate::persistence::dao::DaoTransaction;
+use anyhow::Error;
+use log::{error, info};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+// Open margin calls by account key, and the accounts whose margin needs another look because
+// an execution or a mark has changed them since the monitor last ran
+#[derive(Clone)]
+pub struct MarginCalls {
+    calls: Arc<RwLock<HashMap<String, MarginCall>>>,
+    flagged: Arc<RwLock<HashSet<String>>>,
+}
+
+impl MarginCalls {
+    pub fn new() -> Self {
+        MarginCalls {
+            calls: Arc::new(RwLock::new(HashMap::new())),
+            flagged: Arc::new(RwLock::new(HashSet::new())),
+        }
+    }
+
+    pub async fn load(&self,
+                      txn: &DaoTransaction<'_>) -> Result<(), Error> {
+        let open_margin_calls = match txn.get_open_margin_calls().await {
+            Ok(open_margin_calls) => open_margin_calls,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get open margin calls: {}", dao_error)),
+        };
+        let count = open_margin_calls.len();
+        let mut writable_calls = match self.calls.write() {
+            Ok(writable_calls) => writable_calls,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to margin calls: {}", writable_error)),
+        };
+        writable_calls.extend(open_margin_calls);
+        info!("Done loading {} open margin calls", count);
+        Ok(())
+    }
+
+    pub fn flag(&self,
+                account_key: &str) {
+        match self.flagged.write() {
+            Ok(mut writable_flagged) => {
+                writable_flagged.insert(account_key.to_string());
+            },
+            Err(writable_error) => error!("Unable to get write access to flagged accounts: {}", writable_error),
+        };
+    }
+
+    pub fn take_flagged(&self) -> Result<HashSet<String>, Error> {
+        let mut writable_flagged = match self.flagged.write() {
+            Ok(writable_flagged) => writable_flagged,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to flagged accounts: {}", writable_error)),
+        };
+        Ok(std::mem::take(&mut *writable_flagged))
+    }
+
+    pub fn get(&self,
+               account_key: &str) -> Result<Option<MarginCall>, Error> {
+        let readable_calls = match self.calls.read() {
+            Ok(readable_calls) => readable_calls,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to margin calls: {}", readable_error)),
+        };
+        Ok(readable_calls.get(account_key).cloned())
+    }
+
+    pub fn called_accounts(&self) -> Result<Vec<String>, Error> {
+        let readable_calls = match self.calls.read() {
+            Ok(readable_calls) => readable_calls,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to margin calls: {}", readable_error)),
+        };
+        Ok(readable_calls.keys().cloned().collect())
+    }
+
+    // A call that has been met is no longer open
+    pub fn update(&self,
+                  account_key: &str,
+                  margin_call: MarginCall) -> Result<(), Error> {
+        let mut writable_calls = match self.calls.write() {
+            Ok(writable_calls) => writable_calls,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to margin calls: {}", writable_error)),
+        };
+        if margin_call.status == dtos::account::MarginCallStatus::Met {
+            writable_calls.remove(account_key);
+        } else {
+            writable_calls.insert(account_key.to_string(), margin_call);
+        }
+        Ok(())
+    }
+}
+
+// While an account is in a margin call it may only trade towards flat: every leg has to sell
+// down a long or buy in a short without going past zero
+pub fn reduces_risk(instrument_manager: &InstrumentManager,
+                    rest_api_order: &dtos::order::Order,
+                    open_positions: &HashMap<i64, Position>) -> Result<bool, Error> {
+    let mut legs = Vec::new();
+    for leg in rest_api_order.legs.iter() {
+        let instrument = match instrument_manager.get_instrument_by_key(leg.instrument_key.as_str())? {
+            Some(instrument) => instrument,
+            None => return Err(anyhow::anyhow!("Unable to find instrument for key {}", leg.instrument_key))
+        };
+        legs.push((instrument.instrument_id, rest_api_order.quantity * leg.ratio));
+    }
+    Ok(legs_reduce_positions(&legs, open_positions))
+}
+
+fn legs_reduce_positions(legs: &[(i64, i32)],
+                         open_positions: &HashMap<i64, Position>) -> bool {
+    legs.iter().all(|(instrument_id, quantity)| {
+        let position_quantity = open_positions.get(instrument_id).map(|position| position.quantity).unwrap_or(0);
+        position_quantity.signum() == -quantity.signum() && quantity.abs() <= position_quantity.abs()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::account::Position;
+    use crate::margin::margin_calls::legs_reduce_positions;
+    use crate::money::Money;
+    use std::collections::HashMap;
+
+    fn positions() -> HashMap<i64, Position> {
+        [(1, 100), (2, -50)].into_iter().map(|(instrument_id, quantity)| (instrument_id, Position {
+            position_id: instrument_id,
+            account_id: 1,
+            instrument_id,
+            quantity,
+            cost: Money::ZERO,
+            closed_gain: Money::ZERO,
+            update_time: 0,
+            version_number: 0,
+        })).collect()
+    }
+
+    #[test]
+    async fn test_legs_reduce_positions() {
+        assert!(legs_reduce_positions(&[(1, -100)], &positions()));
+        assert!(legs_reduce_positions(&[(1, -10), (2, 10)], &positions()));
+        // Adding to a position, reversing one, or opening a new one all add risk
+        assert!(!legs_reduce_positions(&[(1, 10)], &positions()));
+        assert!(!legs_reduce_positions(&[(2, 60)], &positions()));
+        assert!(!legs_reduce_positions(&[(3, -1)], &positions()));
+        assert!(!legs_reduce_positions(&[(1, -10), (3, 1)], &positions()));
+    }
+}
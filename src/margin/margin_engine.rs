@@ -0,0 +1,270 @@
+use crate::config::BrokerConfig;
+use crate::dtos;
+use crate::dtos::exchange::AssetClass;
+use crate::dtos::order::is_order_status_viable;
+use crate::entities::account::{Balance, MarginSummary, Position};
+use crate::entities::order::OrderState;
+use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
+use crate::persistence::dao::DaoTransaction;
+use anyhow::Error;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct MarginRules {
+    pub equity_initial_rate: Money,
+    pub equity_long_maintenance_rate: Money,
+    pub equity_short_maintenance_rate: Money,
+    pub future_scan_rate: Money,
+    pub future_spread_credit_rate: Money,
+    pub future_initial_ratio: Money,
+    pub default_rate: Money,
+}
+
+impl MarginRules {
+    pub fn from_config(config: &BrokerConfig) -> MarginRules {
+        MarginRules {
+            equity_initial_rate: config.equity_initial_margin_rate,
+            equity_long_maintenance_rate: config.equity_long_maintenance_rate,
+            equity_short_maintenance_rate: config.equity_short_maintenance_rate,
+            future_scan_rate: config.future_scan_rate,
+            future_spread_credit_rate: config.future_spread_credit_rate,
+            future_initial_ratio: config.future_initial_margin_ratio,
+            default_rate: config.default_margin_rate,
+        }
+    }
+}
+
+// An order leg not yet saved, as instrument id, signed quantity and price
+pub type OrderExposure = (i64, i32, Money);
+
+// What one instrument contributes to margin
+#[derive(Clone, Debug)]
+struct Exposure {
+    asset_class: AssetClass,
+    symbol: String,
+    quantity: i32,
+    price: Money,
+}
+
+// Equity is cash plus positions at their marks. Equities are margined Reg-T style on market
+// value; futures are charged a scan rate per symbol, where the smaller of the long and short
+// sides gets a spread credit; everything else is charged the default rate.
+#[derive(Clone)]
+pub struct MarginEngine {
+    instrument_manager: InstrumentManager,
+    rules: MarginRules,
+}
+
+impl MarginEngine {
+    pub fn new(instrument_manager: InstrumentManager,
+               rules: MarginRules) -> MarginEngine {
+        MarginEngine {
+            instrument_manager,
+            rules,
+        }
+    }
+
+    pub async fn compute_for_account(&self,
+                                     txn: &DaoTransaction<'_>,
+                                     account_key: &String) -> Result<MarginSummary, Error> {
+        let viable_orders: HashMap<String, OrderState> = match txn.get_orders(account_key).await {
+            Ok(orders) => orders.into_iter().filter(|(_, order_state)| is_order_status_viable(&order_state.order_status)).collect(),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get orders: {}", dao_error)),
+        };
+        let positions: HashMap<i64, Position> = match txn.get_positions(account_key).await {
+            Ok(positions) => positions.into_iter().filter(|(_, position)| position.quantity != 0).collect(),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get positions: {}", dao_error)),
+        };
+        let balance = match txn.get_balance(account_key).await {
+            Ok(balance) => balance,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get balance: {}", dao_error)),
+        };
+        self.compute(&balance, &positions, &viable_orders, &[])
+    }
+
+    // Working orders, and any new ones, count as if filled in whichever direction needs more margin
+    pub fn compute(&self,
+                   balance: &Balance,
+                   positions: &HashMap<i64, Position>,
+                   viable_orders: &HashMap<String, OrderState>,
+                   new_orders: &[OrderExposure]) -> Result<MarginSummary, Error> {
+        // Working buys, sells and a price by instrument
+        let mut working: HashMap<i64, (i32, i32, Money)> = HashMap::new();
+        let order_legs = viable_orders.values()
+            .flat_map(|order_state| order_state.order.legs.iter().map(move |leg| {
+                (leg.instrument_id, (order_state.order.quantity - order_state.filled_quantity) * leg.ratio, order_state.order.price)
+            }))
+            .chain(new_orders.iter().cloned());
+        for (instrument_id, quantity, price) in order_legs {
+            let (buys, sells, working_price) = working.entry(instrument_id).or_insert((0, 0, price));
+            if quantity > 0 {
+                *buys += quantity;
+            } else {
+                *sells += quantity;
+            }
+            *working_price = price;
+        }
+
+        let mut equity = balance.cash;
+        let mut exposures = Vec::new();
+        let mut instrument_ids: Vec<i64> = positions.keys().chain(working.keys()).cloned().collect();
+        instrument_ids.sort();
+        instrument_ids.dedup();
+        for instrument_id in instrument_ids {
+            let instrument = match self.instrument_manager.get_instrument(instrument_id)? {
+                Some(instrument) => instrument,
+                None => return Err(anyhow::anyhow!("No instrument for instrument id {}", instrument_id)),
+            };
+            let position = positions.get(&instrument_id);
+            let position_quantity = position.map(|position| position.quantity).unwrap_or(0);
+            let (buys, sells, working_price) = working.get(&instrument_id).cloned().unwrap_or((0, 0, Money::ZERO));
+            let price = match position {
+                Some(position) => match self.instrument_manager.get_valuation_service().value(position)? {
+                    Some(valuation) => valuation.mark_price,
+                    None => position.cost / position.quantity,
+                },
+                None => working_price,
+            };
+            equity += price * position_quantity;
+
+            let with_buys = position_quantity + buys;
+            let with_sells = position_quantity + sells;
+            exposures.push(Exposure {
+                asset_class: instrument.asset_class,
+                symbol: instrument.symbol,
+                quantity: if with_buys.abs() >= with_sells.abs() { with_buys } else { with_sells },
+                price,
+            });
+        }
+        Ok(calculate(&self.rules, equity, &exposures))
+    }
+
+    pub fn resolve_order(&self,
+                         rest_api_order: &dtos::order::Order) -> Result<Vec<OrderExposure>, Error> {
+        let mut legs = Vec::new();
+        for leg in rest_api_order.legs.iter() {
+            let instrument = match self.instrument_manager.get_instrument_by_key(leg.instrument_key.as_str())? {
+                Some(instrument) => instrument,
+                None => return Err(anyhow::anyhow!("Unable to find instrument for key {}", leg.instrument_key))
+            };
+            legs.push((instrument.instrument_id, rest_api_order.quantity * leg.ratio, rest_api_order.price));
+        }
+        Ok(legs)
+    }
+}
+
+fn calculate(rules: &MarginRules,
+             equity: Money,
+             exposures: &[Exposure]) -> MarginSummary {
+    let mut initial_margin = Money::ZERO;
+    let mut maintenance_margin = Money::ZERO;
+    // Long and short scan risk by futures symbol
+    let mut future_risk: HashMap<&str, (Money, Money)> = HashMap::new();
+    for exposure in exposures.iter() {
+        let value = (exposure.price * exposure.quantity).abs();
+        match exposure.asset_class {
+            AssetClass::Equity => {
+                initial_margin += value * rules.equity_initial_rate;
+                maintenance_margin += value * if exposure.quantity > 0 { rules.equity_long_maintenance_rate } else { rules.equity_short_maintenance_rate };
+            },
+            AssetClass::Future => {
+                let (long_risk, short_risk) = future_risk.entry(exposure.symbol.as_str()).or_insert((Money::ZERO, Money::ZERO));
+                if exposure.quantity > 0 {
+                    *long_risk += value * rules.future_scan_rate;
+                } else {
+                    *short_risk += value * rules.future_scan_rate;
+                }
+            },
+            _ => {
+                initial_margin += value * rules.default_rate;
+                maintenance_margin += value * rules.default_rate;
+            },
+        }
+    }
+    for (long_risk, short_risk) in future_risk.values() {
+        let scan_risk = (*long_risk).max(*short_risk) + (*long_risk).min(*short_risk) * (Money::from(1) - rules.future_spread_credit_rate);
+        maintenance_margin += scan_risk;
+        initial_margin += scan_risk * rules.future_initial_ratio;
+    }
+
+    let excess_equity = equity - initial_margin;
+    let buying_power = if excess_equity > Money::ZERO {
+        excess_equity.checked_div(rules.equity_initial_rate).unwrap_or(excess_equity)
+    } else {
+        Money::ZERO
+    };
+    MarginSummary {
+        equity,
+        initial_margin,
+        maintenance_margin,
+        excess_equity,
+        buying_power,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dtos::exchange::AssetClass;
+    use crate::margin::margin_engine::{calculate, Exposure, MarginRules};
+    use crate::money::Money;
+
+    fn rules() -> MarginRules {
+        MarginRules {
+            equity_initial_rate: Money::new(5, 1),
+            equity_long_maintenance_rate: Money::new(25, 2),
+            equity_short_maintenance_rate: Money::new(3, 1),
+            future_scan_rate: Money::new(1, 1),
+            future_spread_credit_rate: Money::new(75, 2),
+            future_initial_ratio: Money::new(11, 1),
+            default_rate: Money::from(1),
+        }
+    }
+
+    fn exposure(asset_class: AssetClass, symbol: &str, quantity: i32, price: i32) -> Exposure {
+        Exposure {
+            asset_class,
+            symbol: symbol.to_string(),
+            quantity,
+            price: Money::from(price),
+        }
+    }
+
+    #[test]
+    async fn test_reg_t_equities() {
+        let summary = calculate(&rules(), Money::from(10000), &[
+            exposure(AssetClass::Equity, "ABC", 100, 40),
+            exposure(AssetClass::Equity, "XYZ", -50, 20),
+        ]);
+        assert_eq!(summary.initial_margin, Money::from(2500));
+        assert_eq!(summary.maintenance_margin, Money::from(1300));
+        assert_eq!(summary.excess_equity, Money::from(7500));
+        assert_eq!(summary.buying_power, Money::from(15000));
+    }
+
+    #[test]
+    async fn test_future_spread_credit() {
+        let outright = calculate(&rules(), Money::from(10000), &[
+            exposure(AssetClass::Future, "ES", 2, 5000),
+        ]);
+        assert_eq!(outright.maintenance_margin, Money::from(1000));
+        assert_eq!(outright.initial_margin, Money::from(1100));
+
+        let spread = calculate(&rules(), Money::from(10000), &[
+            exposure(AssetClass::Future, "ES", 2, 5000),
+            exposure(AssetClass::Future, "ES", -2, 5000),
+            exposure(AssetClass::Future, "CL", -1, 100),
+        ]);
+        assert_eq!(spread.maintenance_margin, Money::from(1260));
+    }
+
+    #[test]
+    async fn test_deficit_has_no_buying_power() {
+        let summary = calculate(&rules(), Money::from(100), &[
+            exposure(AssetClass::Bond, "T", 10, 100),
+        ]);
+        assert_eq!(summary.initial_margin, Money::from(1000));
+        assert_eq!(summary.excess_equity, Money::from(-900));
+        assert_eq!(summary.buying_power, Money::ZERO);
+    }
+}
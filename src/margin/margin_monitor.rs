@@ -0,0 +1,445 @@
+use crate::config::BrokerConfig;
+use crate::constants::ACCOUNT_UPDATE_QUEUE_NAME;
+use crate::dtos;
+use crate::dtos::order::is_order_status_viable;
+use crate::entities::account::{Balance, MarginCall, MarginCallStatus, MarginSummary, Position};
+use crate::entities::exchange::Instrument;
+use crate::entities::order::{OrderState, OrderStatus};
+use crate::instrument_manager::InstrumentManager;
+use crate::margin::margin_engine::MarginEngine;
+use crate::money::Money;
+use crate::persistence::dao::Dao;
+use crate::time::current_time_millis;
+use crate::trade_handling::order_actions::{cancel_order_state, send_order_state, submit_pending_order};
+use crate::trade_handling::order_entry::save_new_order;
+use crate::trade_handling::updates::AccountUpdate;
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(Clone, Debug)]
+pub struct LiquidationSettings {
+    pub enabled: bool,
+    pub grace_millis: i64,
+    // How far past the mark a liquidating order is priced, as a fraction of the mark
+    pub slippage_rate: Money,
+}
+
+impl LiquidationSettings {
+    pub fn from_config(config: &BrokerConfig) -> LiquidationSettings {
+        LiquidationSettings {
+            enabled: config.margin_liquidation_enabled,
+            grace_millis: config.margin_call_grace_millis,
+            slippage_rate: config.liquidation_slippage_rate,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum MarginAction {
+    Hold,
+    Call,
+    Meet(MarginCall),
+    Liquidate(MarginCall),
+}
+
+// Maintenance is measured on positions alone, since working orders have not added any risk yet
+fn next_action(margin_call: Option<MarginCall>,
+               summary: &MarginSummary,
+               settings: &LiquidationSettings,
+               now: i64) -> MarginAction {
+    let deficit = summary.equity < summary.maintenance_margin;
+    match margin_call {
+        None if deficit => MarginAction::Call,
+        None => MarginAction::Hold,
+        Some(margin_call) if !deficit => MarginAction::Meet(margin_call),
+        Some(margin_call) if settings.enabled && now - margin_call.call_time >= settings.grace_millis => MarginAction::Liquidate(margin_call),
+        Some(_) => MarginAction::Hold,
+    }
+}
+
+// Accounts are looked at when an execution or a mark has changed them, and every open call
+// is looked at on each pass so that its grace period can run out
+pub fn start_margin_monitor(dao: Dao,
+                            web_socket_server: WebSocketServer,
+                            instrument_manager: InstrumentManager,
+                            margin_engine: MarginEngine,
+                            settings: LiquidationSettings,
+                            interval_millis: u64) {
+    tokio::spawn(margin_monitor_loop(dao, web_socket_server, instrument_manager, margin_engine, settings, interval_millis));
+}
+
+async fn margin_monitor_loop(dao: Dao,
+                             web_socket_server: WebSocketServer,
+                             instrument_manager: InstrumentManager,
+                             margin_engine: MarginEngine,
+                             settings: LiquidationSettings,
+                             interval_millis: u64) {
+    loop {
+        tokio::time::sleep(Duration::from_millis(interval_millis)).await;
+        match check_margin(&dao, &web_socket_server, &instrument_manager, &margin_engine, &settings).await {
+            Ok(_) => {},
+            Err(err) => error!("Unable to check margin: {}", err),
+        }
+    }
+}
+
+async fn check_margin(dao: &Dao,
+                      web_socket_server: &WebSocketServer,
+                      instrument_manager: &InstrumentManager,
+                      margin_engine: &MarginEngine,
+                      settings: &LiquidationSettings) -> Result<(), Error> {
+    let margin_calls = instrument_manager.get_margin_calls();
+    let mut account_keys = margin_calls.take_flagged()?;
+    account_keys.extend(margin_calls.called_accounts()?);
+    for account_key in account_keys {
+        match check_account(dao, web_socket_server, instrument_manager, margin_engine, settings, &account_key).await {
+            Ok(_) => {},
+            Err(err) => error!("Unable to check margin for account {}: {}", account_key, err),
+        }
+    }
+    Ok(())
+}
+
+async fn check_account(dao: &Dao,
+                       web_socket_server: &WebSocketServer,
+                       instrument_manager: &InstrumentManager,
+                       margin_engine: &MarginEngine,
+                       settings: &LiquidationSettings,
+                       account_key: &String) -> Result<(), Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let account = match txn.get_account_by_account_key(account_key).await {
+        Ok(account) => account,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get account: {}", dao_error)),
+    };
+    let viable_orders: Vec<OrderState> = match txn.get_orders(account_key).await {
+        Ok(orders) => orders.into_values().filter(|order_state| is_order_status_viable(&order_state.order_status)).collect(),
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get orders: {}", dao_error)),
+    };
+    let positions: HashMap<i64, Position> = match txn.get_positions(account_key).await {
+        Ok(positions) => positions.into_iter().filter(|(_, position)| position.quantity != 0).collect(),
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get positions: {}", dao_error)),
+    };
+    let balance = match txn.get_balance(account_key).await {
+        Ok(balance) => balance,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get balance: {}", dao_error)),
+    };
+    match txn.rollback().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+    };
+
+    let summary = margin_engine.compute(&balance, &positions, &HashMap::new(), &[])?;
+    let margin_call = instrument_manager.get_margin_calls().get(account_key)?;
+    let now = current_time_millis();
+    match next_action(margin_call, &summary, settings, now) {
+        MarginAction::Hold => Ok(()),
+        MarginAction::Call => {
+            let margin_call = MarginCall {
+                margin_call_id: 0,
+                account_id: account.account_id,
+                status: MarginCallStatus::Called,
+                equity: summary.equity,
+                maintenance_margin: summary.maintenance_margin,
+                call_time: now,
+                update_time: now,
+            };
+            let description = format!("Equity of {:.2} is below maintenance margin of {:.2}", summary.equity, summary.maintenance_margin);
+            record_margin_call(dao, web_socket_server, instrument_manager, account_key, margin_call, &description).await?;
+            Ok(())
+        },
+        MarginAction::Meet(mut margin_call) => {
+            margin_call.status = MarginCallStatus::Met;
+            margin_call.equity = summary.equity;
+            margin_call.maintenance_margin = summary.maintenance_margin;
+            margin_call.update_time = now;
+            let description = format!("Equity of {:.2} covers maintenance margin of {:.2}", summary.equity, summary.maintenance_margin);
+            record_margin_call(dao, web_socket_server, instrument_manager, account_key, margin_call, &description).await?;
+            Ok(())
+        },
+        MarginAction::Liquidate(mut margin_call) => {
+            if margin_call.status == MarginCallStatus::Called {
+                margin_call.status = MarginCallStatus::Liquidating;
+                margin_call.equity = summary.equity;
+                margin_call.maintenance_margin = summary.maintenance_margin;
+                margin_call.update_time = now;
+                let description = format!("Grace period ended with equity of {:.2} below maintenance margin of {:.2}", summary.equity, summary.maintenance_margin);
+                margin_call = record_margin_call(dao, web_socket_server, instrument_manager, account_key, margin_call, &description).await?;
+            }
+            if !viable_orders.is_empty() {
+                return cancel_for_liquidation(dao, web_socket_server, instrument_manager, account_key, margin_call, viable_orders).await;
+            }
+            match size_liquidation(instrument_manager, margin_engine, settings, account_key, &balance, &positions)? {
+                Some(liquidation) => liquidate(dao, web_socket_server, instrument_manager, account_key, margin_call, liquidation).await,
+                None => Ok(()),
+            }
+        },
+    }
+}
+
+// Working orders are canceled first, then the largest position is reduced. Nothing more is
+// sent while any order is still working, so each pass waits on the results of the last one
+async fn cancel_for_liquidation(dao: &Dao,
+                                web_socket_server: &WebSocketServer,
+                                instrument_manager: &InstrumentManager,
+                                account_key: &String,
+                                mut margin_call: MarginCall,
+                                viable_orders: Vec<OrderState>) -> Result<(), Error> {
+    for order_state in viable_orders.into_iter().filter(|order_state| order_state.order_status != OrderStatus::PendingCancel) {
+        let ext_order_id = order_state.order.ext_order_id.clone();
+        match cancel_order_state(dao, &mut web_socket_server.clone(), instrument_manager, account_key, order_state).await {
+            Ok(_) => {},
+            Err(err) => {
+                error!("Unable to cancel order {} for liquidation: {}", ext_order_id, err);
+                continue;
+            }
+        };
+        margin_call.update_time = current_time_millis();
+        margin_call = record_margin_call(dao, web_socket_server, instrument_manager, account_key, margin_call,
+                                         &format!("Canceled order {}", ext_order_id)).await?;
+    }
+    Ok(())
+}
+
+// The order that closes part or all of a position
+struct Liquidation {
+    instrument: Instrument,
+    price: Money,
+    quantity: i32,
+}
+
+// Only as much of the largest position is closed as brings equity back up to maintenance margin
+// once the order's slippage is paid. When no part of it is enough, all of it is closed
+fn size_liquidation(instrument_manager: &InstrumentManager,
+                    margin_engine: &MarginEngine,
+                    settings: &LiquidationSettings,
+                    account_key: &String,
+                    balance: &Balance,
+                    positions: &HashMap<i64, Position>) -> Result<Option<Liquidation>, Error> {
+    let mut largest: Option<(Money, &Position, Money)> = None;
+    for position in positions.values() {
+        let mark_price = match instrument_manager.get_valuation_service().value(position)? {
+            Some(valuation) => valuation.mark_price,
+            None => {
+                warn!("Not liquidating unmarked instrument {} for account {}", position.instrument_id, account_key);
+                continue;
+            }
+        };
+        let market_value = (mark_price * position.quantity).abs();
+        if largest.as_ref().is_none_or(|(largest_value, _, _)| market_value > *largest_value) {
+            largest = Some((market_value, position, mark_price));
+        }
+    }
+    let (_, position, mark_price) = match largest {
+        Some(largest) => largest,
+        None => return Ok(None),
+    };
+
+    let instrument = match instrument_manager.get_instrument(position.instrument_id)? {
+        Some(instrument) => instrument,
+        None => return Err(anyhow::anyhow!("No instrument with id: {}", position.instrument_id)),
+    };
+    let selling = position.quantity > 0;
+    let slippage = mark_price * settings.slippage_rate;
+    let price = if selling { mark_price - slippage } else { mark_price + slippage };
+    let price = price.round_to_increment(instrument.tick_size, !selling);
+
+    let meets_maintenance = |closing_quantity: i32| -> Result<bool, Error> {
+        let order_quantity = -position.quantity.signum() * closing_quantity;
+        let mut balance = balance.clone();
+        balance.cash -= price * order_quantity;
+        let mut positions = positions.clone();
+        if closing_quantity == position.quantity.abs() {
+            positions.remove(&position.instrument_id);
+        } else if let Some(remaining) = positions.get_mut(&position.instrument_id) {
+            remaining.cost = remaining.cost * (remaining.quantity + order_quantity) / remaining.quantity;
+            remaining.quantity += order_quantity;
+        }
+        let summary = margin_engine.compute(&balance, &positions, &HashMap::new(), &[])?;
+        Ok(summary.equity >= summary.maintenance_margin)
+    };
+    let closing_quantity = smallest_sufficient(position.quantity.abs(), meets_maintenance)?;
+    Ok(Some(Liquidation {
+        instrument,
+        price,
+        quantity: -position.quantity.signum() * closing_quantity,
+    }))
+}
+
+// The least of 1 to max that is sufficient, or max when none is. Closing more of a position
+// never leaves less margin to spare, so a binary search finds it
+fn smallest_sufficient(max: i32,
+                       is_sufficient: impl Fn(i32) -> Result<bool, Error>) -> Result<i32, Error> {
+    if !is_sufficient(max)? {
+        return Ok(max);
+    }
+    let (mut low, mut high) = (1, max);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if is_sufficient(middle)? {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    Ok(low)
+}
+
+async fn liquidate(dao: &Dao,
+                   web_socket_server: &WebSocketServer,
+                   instrument_manager: &InstrumentManager,
+                   account_key: &String,
+                   mut margin_call: MarginCall,
+                   liquidation: Liquidation) -> Result<(), Error> {
+    let Liquidation { instrument, price, quantity } = liquidation;
+    let selling = quantity < 0;
+    let rest_api_order = dtos::order::Order {
+        create_time: current_time_millis(),
+        order_number: None,
+        ext_order_id: Some(Uuid::new_v4().simple().to_string()),
+        account_key: Some(account_key.clone()),
+        price,
+        quantity: quantity.abs(),
+        legs: vec![dtos::order::OrderLeg {
+            instrument_key: instrument.instrument_key.clone(),
+            ratio: quantity.signum(),
+        }],
+        time_in_force: dtos::order::TimeInForce::Ioc,
+        expire_time: None,
+        order_type: dtos::order::OrderType::Limit,
+        stop_price: None,
+        trailing_offset: None,
+    };
+    let order_state = save_new_order(dao, instrument_manager, account_key, &rest_api_order, &instrument).await?;
+    let mut web_socket_server = web_socket_server.clone();
+    match send_order_state(&mut web_socket_server, instrument_manager, account_key, &order_state) {
+        Ok(_) => {},
+        Err(send_error) => warn!("Unable to send order state: {}", send_error),
+    };
+    let description = format!("Sent order {} to {} {} {} at {}", order_state.order.ext_order_id,
+                              if selling { "sell" } else { "buy" }, rest_api_order.quantity, instrument.symbol, rest_api_order.price);
+    if order_state.order_status == OrderStatus::Pending {
+        let price = order_state.order.price;
+        submit_pending_order(dao, &mut web_socket_server, instrument_manager, account_key, order_state, price).await?;
+    }
+    margin_call.update_time = current_time_millis();
+    record_margin_call(dao, &web_socket_server, instrument_manager, account_key, margin_call, &description).await?;
+    Ok(())
+}
+
+// Saves the call and what happened to it, then pushes it to the account
+async fn record_margin_call(dao: &Dao,
+                            web_socket_server: &WebSocketServer,
+                            instrument_manager: &InstrumentManager,
+                            account_key: &String,
+                            margin_call: MarginCall,
+                            description: &str) -> Result<MarginCall, Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let margin_call = if margin_call.margin_call_id == 0 {
+        match txn.save_margin_call(margin_call).await {
+            Ok(margin_call) => margin_call,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not save margin call: {}", dao_error)),
+        }
+    } else {
+        match txn.update_margin_call(&margin_call).await {
+            Ok(_) => margin_call,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not update margin call: {}", dao_error)),
+        }
+    };
+    match txn.save_margin_call_audit(&margin_call, description).await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not save margin call audit: {}", dao_error)),
+    };
+    match txn.commit().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+    };
+    info!("Margin call {} for account {} is {}: {}", margin_call.margin_call_id, account_key, margin_call.status, description);
+    instrument_manager.get_margin_calls().update(account_key, margin_call.clone())?;
+
+    let account_update = AccountUpdate {
+        position: None,
+        balance: None,
+        trade: None,
+        order_state: None,
+        margin_call: Some(margin_call.to_rest_api_margin_call(account_key)),
+    };
+    web_socket_server.clone().send_account_message(account_key, ACCOUNT_UPDATE_QUEUE_NAME, &account_update);
+    Ok(margin_call)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::account::{MarginCall, MarginCallStatus, MarginSummary};
+    use crate::margin::margin_monitor::{next_action, smallest_sufficient, LiquidationSettings, MarginAction};
+    use crate::money::Money;
+
+    fn summary(equity: i32, maintenance_margin: i32) -> MarginSummary {
+        MarginSummary {
+            equity: Money::from(equity),
+            initial_margin: Money::from(maintenance_margin * 2),
+            maintenance_margin: Money::from(maintenance_margin),
+            excess_equity: Money::from(equity - maintenance_margin * 2),
+            buying_power: Money::ZERO,
+        }
+    }
+
+    fn margin_call(call_time: i64) -> MarginCall {
+        MarginCall {
+            margin_call_id: 1,
+            account_id: 1,
+            status: MarginCallStatus::Called,
+            equity: Money::from(100),
+            maintenance_margin: Money::from(200),
+            call_time,
+            update_time: call_time,
+        }
+    }
+
+    fn settings(enabled: bool) -> LiquidationSettings {
+        LiquidationSettings {
+            enabled,
+            grace_millis: 1000,
+            slippage_rate: Money::new(5, 2),
+        }
+    }
+
+    #[test]
+    async fn test_call_and_meet() {
+        assert_eq!(next_action(None, &summary(1000, 500), &settings(true), 0), MarginAction::Hold);
+        assert_eq!(next_action(None, &summary(400, 500), &settings(true), 0), MarginAction::Call);
+        assert_eq!(next_action(Some(margin_call(0)), &summary(500, 500), &settings(true), 10), MarginAction::Meet(margin_call(0)));
+    }
+
+    #[test]
+    async fn test_liquidate_after_grace_period() {
+        assert_eq!(next_action(Some(margin_call(0)), &summary(400, 500), &settings(true), 999), MarginAction::Hold);
+        assert_eq!(next_action(Some(margin_call(0)), &summary(400, 500), &settings(true), 1000), MarginAction::Liquidate(margin_call(0)));
+        assert_eq!(next_action(Some(margin_call(0)), &summary(400, 500), &settings(false), 5000), MarginAction::Hold);
+    }
+
+    #[test]
+    async fn test_smallest_sufficient_closing_quantity() {
+        assert_eq!(smallest_sufficient(100, |quantity| Ok(quantity >= 37)).unwrap(), 37);
+        assert_eq!(smallest_sufficient(100, |_| Ok(true)).unwrap(), 1);
+        // When closing everything is still not enough, everything is closed
+        assert_eq!(smallest_sufficient(100, |_| Ok(false)).unwrap(), 100);
+    }
+}
@@ -0,0 +1,3 @@
+pub(crate) mod margin_engine;
+pub(crate) mod margin_calls;
+pub(crate) mod margin_monitor;
@@ -0,0 +1,254 @@
+use crate::entities::exchange::Instrument;
+use crate::entities::market_data::{Bar, BarInterval};
+use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
+use crate::persistence::dao::{Dao, DaoTransaction};
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use log::{debug, error, info};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use strum::IntoEnumIterator;
+
+// Bars by instrument id, interval and start time
+type UnsavedBars = HashMap<(i64, BarInterval, i64), Bar>;
+
+// Builds OHLCV bars for every interval from the last trades. Each update is published on
+// /markets/{instrument_key}/bars/{interval} straight away. Saving is left to a single writer,
+// which only ever sees the latest state of each bar, so saves cannot land out of order.
+#[derive(Clone)]
+pub struct BarAggregator {
+    web_socket_server: WebSocketServer,
+    // The bar in progress by instrument id and interval
+    current_bars: Arc<RwLock<HashMap<(i64, BarInterval), Bar>>>,
+    // The latest state of every bar updated since the last save
+    unsaved_bars: Arc<RwLock<UnsavedBars>>,
+}
+
+impl BarAggregator {
+    pub fn new(web_socket_server: WebSocketServer) -> Self {
+        BarAggregator {
+            web_socket_server,
+            current_bars: Arc::new(RwLock::new(HashMap::new())),
+            unsaved_bars: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    // Picks up the bars that were in progress at shutdown, so trades in the same period extend them
+    pub async fn load(&self,
+                      txn: &DaoTransaction<'_>) -> Result<(), Error> {
+        let bars = match txn.get_latest_bars().await {
+            Ok(bars) => bars,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get latest bars: {}", dao_error)),
+        };
+        let count = bars.len();
+        let mut writable_bars = match self.current_bars.write() {
+            Ok(writable_bars) => writable_bars,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to bars: {}", writable_error)),
+        };
+        for bar in bars {
+            writable_bars.insert((bar.instrument_id, bar.interval), bar);
+        }
+        info!("Done loading {} bars in progress", count);
+        Ok(())
+    }
+
+    pub fn on_last_trade(&self,
+                         instrument: &Instrument,
+                         trade_time: i64,
+                         price: Money,
+                         quantity: i32) {
+        let updated_bars = match self.current_bars.write() {
+            Ok(mut writable_bars) => add_trade(&mut writable_bars, instrument.instrument_id, trade_time, price, quantity),
+            Err(writable_error) => {
+                error!("Unable to get write access to bars: {}", writable_error);
+                return;
+            }
+        };
+        for bar in updated_bars.iter() {
+            let destination = format!("/markets/{}/bars/{}", instrument.instrument_key, bar.interval);
+            self.web_socket_server.clone().send_retained_message(destination, &bar.to_rest_api_bar(instrument.instrument_key.clone()));
+        }
+        match self.unsaved_bars.write() {
+            Ok(mut writable_unsaved_bars) => mark_unsaved(&mut writable_unsaved_bars, updated_bars),
+            Err(writable_error) => error!("Unable to get write access to unsaved bars: {}", writable_error),
+        };
+    }
+
+    fn take_unsaved(&self) -> Result<Vec<Bar>, Error> {
+        let mut writable_unsaved_bars = match self.unsaved_bars.write() {
+            Ok(writable_unsaved_bars) => writable_unsaved_bars,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to unsaved bars: {}", writable_error)),
+        };
+        Ok(std::mem::take(&mut *writable_unsaved_bars).into_values().collect())
+    }
+
+    // Bars whose save failed go back for the next one, unless a later trade has already replaced them
+    fn restore_unsaved(&self,
+                       bars: Vec<Bar>) {
+        match self.unsaved_bars.write() {
+            Ok(mut writable_unsaved_bars) => mark_unsaved(&mut writable_unsaved_bars, bars),
+            Err(writable_error) => error!("Unable to get write access to unsaved bars: {}", writable_error),
+        };
+    }
+}
+
+// Keeps whichever state of each bar has seen the most trades
+fn mark_unsaved(unsaved_bars: &mut UnsavedBars,
+                bars: Vec<Bar>) {
+    for bar in bars {
+        match unsaved_bars.get(&(bar.instrument_id, bar.interval, bar.start_time)) {
+            Some(unsaved_bar) if unsaved_bar.trade_count >= bar.trade_count => {},
+            _ => {
+                unsaved_bars.insert((bar.instrument_id, bar.interval, bar.start_time), bar);
+            },
+        }
+    }
+}
+
+// Returns the bars the trade went into. A trade older than the bar in progress is dropped.
+fn add_trade(current_bars: &mut HashMap<(i64, BarInterval), Bar>,
+             instrument_id: i64,
+             trade_time: i64,
+             price: Money,
+             quantity: i32) -> Vec<Bar> {
+    let mut updated_bars = Vec::new();
+    for interval in BarInterval::iter() {
+        let start_time = interval.start_of(trade_time);
+        match current_bars.get_mut(&(instrument_id, interval)) {
+            Some(bar) if bar.start_time == start_time => {
+                bar.add_trade(price, quantity);
+                updated_bars.push(bar.clone());
+            },
+            Some(bar) if bar.start_time > start_time => {
+                debug!("Dropping late trade at {} for {} bar starting {}", trade_time, interval, bar.start_time);
+            },
+            _ => {
+                let bar = Bar::new(instrument_id, interval, trade_time, price, quantity);
+                current_bars.insert((instrument_id, interval), bar.clone());
+                updated_bars.push(bar);
+            },
+        }
+    }
+    updated_bars
+}
+
+pub fn start_bar_writer(dao: Dao,
+                        instrument_manager: InstrumentManager,
+                        interval_millis: u64) {
+    tokio::spawn(bar_writer_loop(dao, instrument_manager, interval_millis));
+}
+
+async fn bar_writer_loop(dao: Dao,
+                         instrument_manager: InstrumentManager,
+                         interval_millis: u64) {
+    loop {
+        tokio::time::sleep(Duration::from_millis(interval_millis)).await;
+        let bar_aggregator = instrument_manager.get_bar_aggregator();
+        let bars = match bar_aggregator.take_unsaved() {
+            Ok(bars) => bars,
+            Err(err) => {
+                error!("Unable to take unsaved bars: {}", err);
+                continue;
+            }
+        };
+        if bars.is_empty() {
+            continue;
+        }
+        match save_bars(&dao, &bars).await {
+            Ok(_) => {},
+            Err(err) => {
+                error!("Unable to save bars: {}", err);
+                bar_aggregator.restore_unsaved(bars);
+            }
+        }
+    }
+}
+
+async fn save_bars(dao: &Dao,
+                   bars: &[Bar]) -> Result<(), Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    for bar in bars.iter() {
+        match txn.save_bar(bar).await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not save {} bar for instrument {}: {}", bar.interval, bar.instrument_id, dao_error)),
+        };
+    }
+    match txn.commit().await {
+        Ok(_) => Ok(()),
+        Err(dao_error) => Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::market_data::BarInterval;
+    use crate::market_data::bar_aggregator::{add_trade, mark_unsaved};
+    use crate::money::Money;
+    use std::collections::HashMap;
+
+    #[test]
+    async fn test_trades_build_bars() {
+        let mut current_bars = HashMap::new();
+        let first_time = 90_061_500;
+        assert_eq!(add_trade(&mut current_bars, 1, first_time, Money::from(10), 5).len(), 5);
+        add_trade(&mut current_bars, 1, first_time + 200, Money::from(12), -3);
+        add_trade(&mut current_bars, 1, first_time + 400, Money::from(9), 2);
+
+        let second = &current_bars[&(1, BarInterval::OneSecond)];
+        assert_eq!(second.start_time, 90_061_000);
+        assert_eq!((second.open, second.high, second.low, second.close), (Money::from(10), Money::from(12), Money::from(9), Money::from(9)));
+        assert_eq!(second.volume, 10);
+        assert_eq!(second.trade_count, 3);
+
+        let day = &current_bars[&(1, BarInterval::OneDay)];
+        assert_eq!(day.start_time, 86_400_000);
+        assert_eq!(day.trade_count, 3);
+
+        // A new second starts a new one-second bar but extends the minute
+        add_trade(&mut current_bars, 1, first_time + 1000, Money::from(11), 1);
+        assert_eq!(current_bars[&(1, BarInterval::OneSecond)].trade_count, 1);
+        assert_eq!(current_bars[&(1, BarInterval::OneMinute)].trade_count, 4);
+
+        // A trade from the previous second only reaches the longer bars
+        let updated = add_trade(&mut current_bars, 1, first_time, Money::from(20), 1);
+        assert_eq!(updated.len(), 4);
+        assert_eq!(current_bars[&(1, BarInterval::OneSecond)].high, Money::from(11));
+        assert_eq!(current_bars[&(1, BarInterval::OneMinute)].high, Money::from(20));
+    }
+
+    #[test]
+    async fn test_unsaved_bars_keep_latest_state() {
+        let mut current_bars = HashMap::new();
+        let mut unsaved_bars = HashMap::new();
+        let first_time = 90_061_500;
+        let first = add_trade(&mut current_bars, 1, first_time, Money::from(10), 5);
+        let second = add_trade(&mut current_bars, 1, first_time + 200, Money::from(12), -3);
+        mark_unsaved(&mut unsaved_bars, second);
+        // A failed save of an older state does not overwrite the newer one
+        mark_unsaved(&mut unsaved_bars, first);
+        assert_eq!(unsaved_bars[&(1, BarInterval::OneSecond, 90_061_000)].trade_count, 2);
+
+        // The finished one-second bar is kept alongside the one that replaced it
+        mark_unsaved(&mut unsaved_bars, add_trade(&mut current_bars, 1, first_time + 1000, Money::from(11), 1));
+        assert_eq!(unsaved_bars[&(1, BarInterval::OneSecond, 90_061_000)].close, Money::from(12));
+        assert_eq!(unsaved_bars[&(1, BarInterval::OneSecond, 90_062_000)].trade_count, 1);
+        assert_eq!(unsaved_bars[&(1, BarInterval::OneMinute, 90_060_000)].trade_count, 3);
+    }
+
+    #[test]
+    async fn test_bar_interval_codes() {
+        assert_eq!(BarInterval::FiveMinutes.to_string(), "5m");
+        assert_eq!("1h".parse::<BarInterval>(), Ok(BarInterval::OneHour));
+        assert!("2m".parse::<BarInterval>().is_err());
+        assert_eq!(BarInterval::OneMinute.start_of(-1), -60_000);
+    }
+}
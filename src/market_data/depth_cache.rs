@@ -0,0 +1,66 @@
+use crate::exchange_interface::market_data::{MarketDepth, PriceLevel};
+use crate::money::Money;
+use anyhow::Error;
+use log::error;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+// The latest displayed book of every instrument, for routing orders between venues
+#[derive(Clone)]
+pub struct DepthCache {
+    books: Arc<RwLock<HashMap<i64, Book>>>,
+}
+
+// Price levels as price and quantity, best first on each side
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Book {
+    pub bids: Vec<(Money, i32)>,
+    pub asks: Vec<(Money, i32)>,
+}
+
+impl DepthCache {
+    pub fn new() -> Self {
+        DepthCache {
+            books: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn on_depth(&self,
+                    instrument_id: i64,
+                    depth: &MarketDepth) {
+        let book = Book {
+            bids: sorted_levels(&depth.buys, true),
+            asks: sorted_levels(&depth.sells, false),
+        };
+        match self.books.write() {
+            Ok(mut writable_books) => {
+                writable_books.insert(instrument_id, book);
+            },
+            Err(writable_error) => error!("Unable to get write access to depth books: {}", writable_error),
+        };
+    }
+
+    pub fn get(&self,
+               instrument_id: i64) -> Result<Option<Book>, Error> {
+        let readable_books = match self.books.read() {
+            Ok(readable_books) => readable_books,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to depth books: {}", readable_error)),
+        };
+        Ok(readable_books.get(&instrument_id).cloned())
+    }
+}
+
+fn sorted_levels(price_levels: &[PriceLevel],
+                 descending: bool) -> Vec<(Money, i32)> {
+    let mut levels: Vec<(Money, i32)> = price_levels.iter()
+        .filter(|level| level.quantity > 0)
+        .map(|level| (level.price, level.quantity))
+        .collect();
+    if descending {
+        levels.sort_by_key(|level| Reverse(level.0));
+    } else {
+        levels.sort_by_key(|level| level.0);
+    }
+    levels
+}
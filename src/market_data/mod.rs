@@ -1 +1,4 @@
-pub(crate) mod receiver;
\ No newline at end of file
+pub(crate) mod receiver;
+pub(crate) mod bar_aggregator;
+pub(crate) mod valuation;
+pub(crate) mod depth_cache;
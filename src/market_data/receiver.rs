@@ -8,6 +8,16 @@ pub fn handle_depth(web_socket_server: &WebSocketServer,
                     instrument_manager: &InstrumentManager, 
                     depth: MarketDepth) {
     debug!("Depth: {:?}", depth);
+    match instrument_manager.get_instrument_by_exchange_instrument_id(depth.instrument_id) {
+        Ok(Some(instrument)) => {
+            let best_bid = depth.buys.iter().map(|level| level.price).max();
+            let best_ask = depth.sells.iter().map(|level| level.price).min();
+            instrument_manager.get_valuation_service().on_depth(instrument.instrument_id, best_bid, best_ask);
+            instrument_manager.get_depth_cache().on_depth(instrument.instrument_id, &depth);
+        },
+        Ok(None) => {},
+        Err(err) => warn!("Error finding instrument for marks: {:?}", err),
+    }
     let (destination, instrument_key) = match compute_destination(instrument_manager, "depth", depth.instrument_id)
     {
         Ok(destination) => destination,
@@ -23,6 +33,15 @@ pub fn handle_last_trade(web_socket_server: &WebSocketServer,
                          instrument_manager: &InstrumentManager, 
                          last_trade: LastTrade) {
     debug!("Last Trade: {:?}", last_trade);
+    match instrument_manager.get_instrument_by_exchange_instrument_id(last_trade.instrument_id) {
+        Ok(Some(instrument)) => {
+            instrument_manager.get_trigger_engine().on_last_trade(instrument_manager, instrument.instrument_id, last_trade.price);
+            instrument_manager.get_bar_aggregator().on_last_trade(&instrument, last_trade.create_time, last_trade.price, last_trade.quantity);
+            instrument_manager.get_valuation_service().on_last_trade(instrument.instrument_id, last_trade.create_time, last_trade.price);
+        },
+        Ok(None) => {},
+        Err(err) => warn!("Error finding instrument for triggers, bars and marks: {:?}", err),
+    }
     let (destination, instrument_key) = match compute_destination(instrument_manager, "last_trade", last_trade.instrument_id)
     {
         Ok(destination) => destination,
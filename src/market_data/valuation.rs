@@ -0,0 +1,393 @@
+use crate::constants::ACCOUNT_UPDATE_QUEUE_NAME;
+use crate::entities::account::Position;
+use crate::entities::market_data::BarInterval;
+use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
+use crate::persistence::dao::{Dao, DaoTransaction};
+use crate::time::current_time_millis;
+use crate::trade_handling::updates::AccountUpdate;
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use log::{error, info};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+// Marks every instrument to its latest last trade, or to the depth mid when the book is
+// two-sided and was updated more recently. Day gain is measured from the prior day's close,
+// except for what was bought or sold today, which is measured from its fill price.
+#[derive(Clone)]
+pub struct ValuationService {
+    marks: Arc<RwLock<HashMap<i64, InstrumentMark>>>,
+    // Instruments whose mark has moved since positions were last pushed
+    moved: Arc<RwLock<HashSet<i64>>>,
+    // Keyed by account id and instrument id
+    day_trades: Arc<RwLock<HashMap<(i32, i64), DayTrades>>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct InstrumentMark {
+    mark_price: Option<Money>,
+    last_trade_price: Option<Money>,
+    // Start of the UTC day the last trade fell in
+    day_start: i64,
+    prior_close: Option<Money>,
+}
+
+// Net quantity traded in the UTC day starting at day_start, and what it cost
+#[derive(Clone, Debug, Default, PartialEq)]
+struct DayTrades {
+    day_start: i64,
+    quantity: i32,
+    cost: Money,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Valuation {
+    pub mark_price: Money,
+    pub market_value: Money,
+    pub unrealized_gain: Money,
+    pub day_gain: Money,
+}
+
+impl ValuationService {
+    pub fn new() -> Self {
+        ValuationService {
+            marks: Arc::new(RwLock::new(HashMap::new())),
+            moved: Arc::new(RwLock::new(HashSet::new())),
+            day_trades: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    // Prior closes come from the daily bars and today's trades from the trade table, so day
+    // gain survives a restart
+    pub async fn load(&self,
+                      txn: &DaoTransaction<'_>) -> Result<(), Error> {
+        let day_start = BarInterval::OneDay.start_of(current_time_millis());
+        let prior_closes = match txn.get_prior_closes(day_start).await {
+            Ok(prior_closes) => prior_closes,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get prior closes: {}", dao_error)),
+        };
+        let trade_totals = match txn.get_trade_totals_since(day_start).await {
+            Ok(trade_totals) => trade_totals,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get trade totals: {}", dao_error)),
+        };
+        match self.day_trades.write() {
+            Ok(mut writable_day_trades) => {
+                for (key, (quantity, cost)) in trade_totals {
+                    writable_day_trades.insert(key, DayTrades { day_start, quantity, cost });
+                }
+            },
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to day trades: {}", writable_error)),
+        };
+        let count = prior_closes.len();
+        let mut writable_marks = match self.marks.write() {
+            Ok(writable_marks) => writable_marks,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to marks: {}", writable_error)),
+        };
+        for (instrument_id, prior_close) in prior_closes {
+            writable_marks.insert(instrument_id, InstrumentMark {
+                day_start,
+                prior_close: Some(prior_close),
+                ..Default::default()
+            });
+        }
+        info!("Done loading {} prior closes", count);
+        Ok(())
+    }
+
+    pub fn on_last_trade(&self,
+                         instrument_id: i64,
+                         trade_time: i64,
+                         price: Money) {
+        self.update_mark(instrument_id, |mark| mark.on_last_trade(trade_time, price));
+    }
+
+    pub fn on_depth(&self,
+                    instrument_id: i64,
+                    best_bid: Option<Money>,
+                    best_ask: Option<Money>) {
+        if let (Some(best_bid), Some(best_ask)) = (best_bid, best_ask) {
+            self.update_mark(instrument_id, |mark| mark.mark_price = Some((best_bid + best_ask) / 2));
+        }
+    }
+
+    pub fn on_trade(&self,
+                    account_id: i32,
+                    instrument_id: i64,
+                    trade_time: i64,
+                    price: Money,
+                    quantity: i32) {
+        match self.day_trades.write() {
+            Ok(mut writable_day_trades) => writable_day_trades.entry((account_id, instrument_id))
+                .or_default()
+                .on_trade(trade_time, price, quantity),
+            Err(writable_error) => error!("Unable to get write access to day trades: {}", writable_error),
+        };
+    }
+
+    fn update_mark(&self,
+                   instrument_id: i64,
+                   update: impl FnOnce(&mut InstrumentMark)) {
+        let moved = match self.marks.write() {
+            Ok(mut writable_marks) => {
+                let mark = writable_marks.entry(instrument_id).or_default();
+                let previous_mark_price = mark.mark_price;
+                update(mark);
+                mark.mark_price != previous_mark_price
+            },
+            Err(writable_error) => {
+                error!("Unable to get write access to marks: {}", writable_error);
+                return;
+            }
+        };
+        if !moved {
+            return;
+        }
+        match self.moved.write() {
+            Ok(mut writable_moved) => {
+                writable_moved.insert(instrument_id);
+            },
+            Err(writable_error) => error!("Unable to get write access to moved marks: {}", writable_error),
+        };
+    }
+
+    pub fn value(&self,
+                 position: &Position) -> Result<Option<Valuation>, Error> {
+        let readable_marks = match self.marks.read() {
+            Ok(readable_marks) => readable_marks,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to marks: {}", readable_error)),
+        };
+        let readable_day_trades = match self.day_trades.read() {
+            Ok(readable_day_trades) => readable_day_trades,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to day trades: {}", readable_error)),
+        };
+        let day_trades = readable_day_trades.get(&(position.account_id, position.instrument_id));
+        Ok(readable_marks.get(&position.instrument_id).and_then(|mark| mark.value(position, day_trades)))
+    }
+
+    pub fn mark_price(&self,
+                      instrument_id: i64) -> Result<Option<Money>, Error> {
+        let readable_marks = match self.marks.read() {
+            Ok(readable_marks) => readable_marks,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to marks: {}", readable_error)),
+        };
+        Ok(readable_marks.get(&instrument_id).and_then(|mark| mark.mark_price))
+    }
+
+    fn take_moved(&self) -> Result<HashSet<i64>, Error> {
+        let mut writable_moved = match self.moved.write() {
+            Ok(writable_moved) => writable_moved,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to moved marks: {}", writable_error)),
+        };
+        Ok(std::mem::take(&mut *writable_moved))
+    }
+}
+
+impl InstrumentMark {
+    fn on_last_trade(&mut self,
+                     trade_time: i64,
+                     price: Money) {
+        let day_start = BarInterval::OneDay.start_of(trade_time);
+        if day_start > self.day_start {
+            // The first trade of a new day; the last one seen closed the day before
+            if self.last_trade_price.is_some() {
+                self.prior_close = self.last_trade_price;
+            }
+            self.day_start = day_start;
+        }
+        self.last_trade_price = Some(price);
+        self.mark_price = Some(price);
+    }
+
+    // The quantity held at the start of the day gains from the prior close, and what was traded
+    // since from its fill price, so a position opened today shows its gain since it was opened.
+    // Without a prior close day gain is the unrealized gain
+    fn value(&self,
+             position: &Position,
+             day_trades: Option<&DayTrades>) -> Option<Valuation> {
+        let mark_price = self.mark_price?;
+        let market_value = mark_price * position.quantity;
+        let unrealized_gain = market_value - position.cost;
+        let (traded_quantity, traded_cost) = match day_trades {
+            // Trades from a day whose close has become the prior close are already in its basis
+            Some(day_trades) if day_trades.day_start >= self.day_start => (day_trades.quantity, day_trades.cost),
+            _ => (0, Money::ZERO),
+        };
+        let day_gain = match self.prior_close {
+            Some(prior_close) => (mark_price - prior_close) * (position.quantity - traded_quantity)
+                + mark_price * traded_quantity - traded_cost,
+            None => unrealized_gain,
+        };
+        Some(Valuation {
+            mark_price,
+            market_value,
+            unrealized_gain,
+            day_gain,
+        })
+    }
+}
+
+impl DayTrades {
+    fn on_trade(&mut self,
+                trade_time: i64,
+                price: Money,
+                quantity: i32) {
+        let day_start = BarInterval::OneDay.start_of(trade_time);
+        if day_start < self.day_start {
+            return;
+        }
+        if day_start > self.day_start {
+            *self = DayTrades { day_start, ..Default::default() };
+        }
+        self.quantity += quantity;
+        self.cost += price * quantity;
+    }
+}
+
+// Positions are pushed at most once per interval per instrument, however often its mark moves
+pub fn start_mark_publisher(dao: Dao,
+                            web_socket_server: WebSocketServer,
+                            instrument_manager: InstrumentManager,
+                            interval_millis: u64) {
+    tokio::spawn(mark_publisher_loop(dao, web_socket_server, instrument_manager, interval_millis));
+}
+
+async fn mark_publisher_loop(dao: Dao,
+                             web_socket_server: WebSocketServer,
+                             instrument_manager: InstrumentManager,
+                             interval_millis: u64) {
+    loop {
+        tokio::time::sleep(Duration::from_millis(interval_millis)).await;
+        match publish_moved_positions(&dao, &web_socket_server, &instrument_manager).await {
+            Ok(_) => {},
+            Err(err) => error!("Unable to publish marked positions: {}", err),
+        }
+    }
+}
+
+async fn publish_moved_positions(dao: &Dao,
+                                 web_socket_server: &WebSocketServer,
+                                 instrument_manager: &InstrumentManager) -> Result<(), Error> {
+    let moved = instrument_manager.get_valuation_service().take_moved()?;
+    if moved.is_empty() {
+        return Ok(());
+    }
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    for instrument_id in moved {
+        let positions = match txn.get_open_positions_for_instrument(instrument_id).await {
+            Ok(positions) => positions,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get positions for instrument {}: {}", instrument_id, dao_error)),
+        };
+        for (account_key, position) in positions {
+            instrument_manager.get_margin_calls().flag(&account_key);
+            let rest_api_position = position.to_marked_rest_api_position(&account_key, instrument_manager)?;
+            let account_update = AccountUpdate {
+                position: Some(rest_api_position),
+                balance: None,
+                trade: None,
+                order_state: None,
+                margin_call: None,
+            };
+            web_socket_server.clone().send_account_message(&account_key, ACCOUNT_UPDATE_QUEUE_NAME, &account_update);
+        }
+    }
+    match txn.rollback().await {
+        Ok(_) => Ok(()),
+        Err(dao_error) => Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::account::Position;
+    use crate::market_data::valuation::{DayTrades, InstrumentMark, ValuationService};
+    use crate::money::Money;
+
+    const DAY: i64 = 24 * 60 * 60 * 1000;
+
+    fn position(quantity: i32, cost: Money) -> Position {
+        Position {
+            position_id: 0,
+            account_id: 0,
+            instrument_id: 1,
+            quantity,
+            cost,
+            closed_gain: Money::ZERO,
+            update_time: 0,
+            version_number: 0,
+        }
+    }
+
+    #[test]
+    async fn test_value_long_and_short() {
+        let mut mark = InstrumentMark::default();
+        assert_eq!(mark.value(&position(10, Money::from(100)), None), None);
+
+        mark.on_last_trade(DAY + 10, Money::from(12));
+        let long = mark.value(&position(10, Money::from(100)), None).unwrap();
+        assert_eq!(long.market_value, Money::from(120));
+        assert_eq!(long.unrealized_gain, Money::from(20));
+        assert_eq!(long.day_gain, Money::from(20));
+
+        let short = mark.value(&position(-10, Money::from(-100)), None).unwrap();
+        assert_eq!(short.market_value, Money::from(-120));
+        assert_eq!(short.unrealized_gain, Money::from(-20));
+    }
+
+    #[test]
+    async fn test_day_gain_from_prior_close() {
+        let mut mark = InstrumentMark::default();
+        mark.on_last_trade(DAY + 10, Money::from(11));
+        mark.on_last_trade(DAY + 20, Money::from(12));
+        mark.on_last_trade(2 * DAY + 10, Money::from(15));
+        assert_eq!(mark.prior_close, Some(Money::from(12)));
+
+        let valuation = mark.value(&position(10, Money::from(100)), None).unwrap();
+        assert_eq!(valuation.unrealized_gain, Money::from(50));
+        assert_eq!(valuation.day_gain, Money::from(30));
+    }
+
+    #[test]
+    async fn test_day_gain_from_fill_price_for_todays_trades() {
+        let mut mark = InstrumentMark::default();
+        mark.on_last_trade(DAY + 10, Money::from(12));
+        mark.on_last_trade(2 * DAY + 10, Money::from(15));
+
+        // Yesterday's trades are part of the position the prior close applies to
+        let mut day_trades = DayTrades::default();
+        day_trades.on_trade(DAY + 20, Money::from(12), 10);
+        day_trades.on_trade(2 * DAY + 20, Money::from(14), 5);
+        day_trades.on_trade(2 * DAY + 30, Money::from(16), -3);
+        assert_eq!(day_trades.quantity, 2);
+        day_trades.on_trade(DAY + 30, Money::from(11), 100);
+        assert_eq!(day_trades.quantity, 2);
+
+        // 10 held from yesterday gain 3 each, 5 bought at 14 gain 1 each and 3 sold at 16 gained 1 each
+        let valuation = mark.value(&position(12, Money::from(162)), Some(&day_trades)).unwrap();
+        assert_eq!(valuation.day_gain, Money::from(38));
+    }
+
+    #[test]
+    async fn test_depth_mid_marks_and_flags_moves() {
+        let valuation_service = ValuationService::new();
+        valuation_service.on_depth(1, Some(Money::from(10)), None);
+        assert!(valuation_service.take_moved().unwrap().is_empty());
+
+        valuation_service.on_depth(1, Some(Money::from(10)), Some(Money::from(11)));
+        valuation_service.on_last_trade(2, DAY, Money::from(5));
+        assert_eq!(valuation_service.take_moved().unwrap().len(), 2);
+        assert_eq!(valuation_service.value(&position(2, Money::from(20))).unwrap().unwrap().mark_price, Money::new(105, 1));
+
+        // An unchanged mark is not pushed again
+        valuation_service.on_depth(1, Some(Money::from(10)), Some(Money::from(11)));
+        assert!(valuation_service.take_moved().unwrap().is_empty());
+    }
+}
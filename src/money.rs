@@ -0,0 +1,208 @@
+use postgres_types::{FromSql, ToSql};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+// Exact decimal amount for prices, costs and cash. Values are held in NUMERIC
+// columns and travel in JSON as plain numbers written and read digit for digit,
+// never through a float, so that 10.1 stays exactly 10.1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, ToSql, FromSql)]
+#[postgres(transparent)]
+pub struct Money(Decimal);
+
+impl Money {
+    pub const ZERO: Money = Money(Decimal::ZERO);
+
+    // num scaled down by scale decimal places, so Money::new(1005, 2) is 10.05
+    pub fn new(num: i64,
+               scale: u32) -> Money {
+        Money(Decimal::new(num, scale))
+    }
+
+    pub fn abs(&self) -> Money {
+        Money(self.0.abs())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn round_dp(&self,
+                    decimal_places: u32) -> Money {
+        Money(self.0.round_dp_with_strategy(decimal_places, RoundingStrategy::MidpointAwayFromZero))
+    }
+
+    // Number of significant decimal places, ignoring trailing zeros
+    pub fn decimal_places(&self) -> u32 {
+        self.0.normalize().scale()
+    }
+
+    pub fn is_multiple_of(&self,
+                          increment: Money) -> bool {
+        !increment.is_zero() && (self.0 % increment.0).is_zero()
+    }
+
+    // The closest multiple of increment at or above the value when rounding up, at or below
+    // it otherwise. A zero increment leaves the value as it is
+    pub fn round_to_increment(&self,
+                              increment: Money,
+                              round_up: bool) -> Money {
+        if increment.is_zero() {
+            return *self;
+        }
+        let multiples = self.0 / increment.0;
+        let multiples = if round_up { multiples.ceil() } else { multiples.floor() };
+        Money(multiples * increment.0)
+    }
+
+    // None when dividing by zero
+    pub fn checked_div(&self,
+                       divisor: Money) -> Option<Money> {
+        self.0.checked_div(divisor.0).map(Money)
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0.normalize(), f)
+    }
+}
+
+impl FromStr for Money {
+    type Err = rust_decimal::Error;
+    fn from_str(input: &str) -> Result<Money, Self::Err> {
+        Ok(Money(Decimal::from_str(input)?))
+    }
+}
+
+impl From<i32> for Money {
+    fn from(value: i32) -> Money {
+        Money(Decimal::from(value))
+    }
+}
+
+impl From<i64> for Money {
+    fn from(value: i64) -> Money {
+        Money(Decimal::from(value))
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        rust_decimal::serde::arbitrary_precision::serialize(&self.0.normalize(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        Ok(Money(rust_decimal::serde::arbitrary_precision::deserialize(deserializer)?))
+    }
+}
+
+impl confik::Configuration for Money {
+    type Builder = Option<Self>;
+}
+
+impl Add for Money {
+    type Output = Money;
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Money) {
+        self.0 -= other.0;
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Mul<i32> for Money {
+    type Output = Money;
+    fn mul(self, quantity: i32) -> Money {
+        Money(self.0 * Decimal::from(quantity))
+    }
+}
+
+impl Mul for Money {
+    type Output = Money;
+    fn mul(self, other: Money) -> Money {
+        Money(self.0 * other.0)
+    }
+}
+
+impl Div<i32> for Money {
+    type Output = Money;
+    fn div(self, quantity: i32) -> Money {
+        Money(self.0 / Decimal::from(quantity))
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item=Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, |total, value| total + value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::money::Money;
+    use std::str::FromStr;
+
+    #[test]
+    async fn test_exact_arithmetic() {
+        let price = Money::from_str("0.1").unwrap();
+        let total: Money = (0..10).map(|_| price).sum();
+        assert_eq!(total, Money::from(1));
+        assert_eq!(Money::new(33, 1) * 3, Money::new(99, 1));
+        assert_eq!(Money::from(100) / 8, Money::new(125, 1));
+    }
+
+    #[test]
+    async fn test_json_round_trip() {
+        let money: Money = serde_json::from_str("10.1").unwrap();
+        assert_eq!(money, Money::new(101, 1));
+        assert_eq!(serde_json::to_string(&money).unwrap(), "10.1");
+        let money: Money = serde_json::from_str("\"0.30\"").unwrap();
+        assert_eq!(money, Money::new(3, 1));
+        // More digits than a float can hold survive the round trip
+        let money: Money = serde_json::from_str("12345678901234.56789").unwrap();
+        assert_eq!(money, Money::new(1234567890123456789, 5));
+        assert_eq!(serde_json::to_string(&money).unwrap(), "12345678901234.56789");
+    }
+
+    #[test]
+    async fn test_tick_size() {
+        let tick_size = Money::new(5, 2);
+        assert!(Money::new(1015, 2).is_multiple_of(tick_size));
+        assert!(!Money::new(1012, 2).is_multiple_of(tick_size));
+        assert!(!Money::new(1012, 2).is_multiple_of(Money::ZERO));
+        assert_eq!(Money::new(10100, 3).decimal_places(), 1);
+        assert_eq!(Money::new(1015, 3).round_dp(2), Money::new(102, 2));
+        assert_eq!(Money::new(1012, 2).round_to_increment(tick_size, true), Money::new(1015, 2));
+        assert_eq!(Money::new(1012, 2).round_to_increment(tick_size, false), Money::new(1010, 2));
+        assert_eq!(Money::new(1015, 2).round_to_increment(tick_size, true), Money::new(1015, 2));
+    }
+}
@@ -28,6 +28,44 @@ impl<'b> DaoTransaction<'b> {
         Ok(accesses)
     }
 
+    // The relationship between the actor and the account that privileges are granted on, if they have one
+    pub async fn get_relationship_id(&self,
+                                     account_key: &str,
+                                     email_address: &str) -> Result<Option<i32>, DaoError> {
+        let rows = match self.transaction.query(RELATIONSHIP_QUERY,
+                                                &[&account_key, &email_address]).await {
+            Ok(rows) => rows,
+            Err(db_error) => { return Err(gen_dao_error("get_relationship_id", db_error)); }
+        };
+        Ok(rows.first().map(|row| row.get("relationshipId")))
+    }
+
+    // Granting a privilege the relationship already has changes nothing
+    pub async fn grant_privilege(&self,
+                                 relationship_id: i32,
+                                 privilege: &Privilege) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            "INSERT INTO access (relationshipId, privilege) VALUES ($1, $2) \
+            ON CONFLICT (relationshipId, privilege) DO NOTHING",
+            &[&relationship_id, &privilege.to_string()]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("grant_privilege", db_error)),
+        }
+    }
+
+    pub async fn revoke_privilege(&self,
+                                  relationship_id: i32,
+                                  privilege: &Privilege) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            "DELETE FROM access WHERE relationshipId = $1 AND privilege = $2",
+            &[&relationship_id, &privilege.to_string()]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("revoke_privilege", db_error)),
+        }
+    }
+
     fn convert_row_to_access(&self, 
                              row: &Row) -> Result<Access, DaoError> {
         let row_privilege = row.get("privilege");
@@ -57,3 +95,11 @@ JOIN actor_account_relationship relation on relation.actorId = actor.actorId \
 JOIN account on account.accountId = relation.accountId \
 JOIN access on access.relationshipId = relation.relationshipId \
 ";
+
+const RELATIONSHIP_QUERY: &str = "\
+SELECT relation.relationshipId \
+FROM actor_account_relationship relation \
+JOIN actor on actor.actorId = relation.actorId \
+JOIN account on account.accountId = relation.accountId \
+WHERE account.accountKey = $1 AND actor.emailAddress = $2 \
+";
@@ -47,6 +47,52 @@ impl<'b> DaoTransaction<'b> {
 
         Ok(accounts_map)
     }
+
+    pub async fn get_all_accounts(&self) -> Result<Vec<Account>, DaoError> {
+        let rows = match self.transaction.query(ACCOUNT_QUERY, &[]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_all_accounts", db_error)); }
+        };
+        Ok(rows.iter().map(convert_row_to_account).collect())
+    }
+
+    pub async fn get_vetter_name(&self,
+                                 account_key: &String) -> Result<Option<String>, DaoError> {
+        let rows = match self.transaction.query(VETTER_NAME_QUERY,
+                                                &[&account_key]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_vetter_name", db_error)); }
+        };
+        for row in rows {
+            let account_vetter_name: Option<String> = row.get("accountVetterName");
+            if account_vetter_name.is_some() {
+                return Ok(account_vetter_name);
+            }
+            let offer_vetter_name: Option<String> = row.get("offerVetterName");
+            if offer_vetter_name.is_some() {
+                return Ok(offer_vetter_name);
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn update_vetter_name(&self,
+                                    account_key: &String,
+                                    vetter_name: &Option<String>) -> Result<(), DaoError> {
+        let rows_updated = match self.transaction.execute(
+            "UPDATE account SET vetterName = $1 WHERE accountKey = $2",
+            &[&vetter_name,
+                &account_key,
+            ]
+        ).await {
+            Ok(rows) => rows,
+            Err(db_error) => { return Err(gen_dao_error("update_vetter_name", db_error)); }
+        };
+        if rows_updated != 1 {
+            return Err(DaoError::ExecuteFailed { description: format!("update_vetter_name updated {} rows, not 1", rows_updated) });
+        }
+        Ok(())
+    }
 }
 
 fn convert_row_to_account(row: &Row) -> Account {
@@ -62,3 +108,14 @@ const ACCOUNT_QUERY: &str = "\
 SELECT accountId, accountKey, accountNumber, accountName \
 FROM account \
 ";
+
+// The account's own vetter takes precedence over the one attached to the offer its owner signed up with
+const VETTER_NAME_QUERY: &str = "\
+SELECT account.vetterName AS accountVetterName, offer.vetterName AS offerVetterName \
+FROM account \
+LEFT JOIN actor_account_relationship relation ON relation.accountId = account.accountId \
+AND EXISTS (SELECT 1 FROM access WHERE access.relationshipId = relation.relationshipId AND access.privilege = 'Owner') \
+LEFT JOIN actor ON actor.actorId = relation.actorId \
+LEFT JOIN offer ON offer.offerId = actor.offerId \
+WHERE account.accountKey = $1 \
+";
@@ -1,4 +1,7 @@
+use crate::dtos::ledger::{JournalType, LedgerAccount};
 use crate::entities::actor::Actor;
+use crate::entities::ledger::Journal;
+use crate::money::Money;
 use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
 use crate::time::current_time_millis;
 use log::debug;
@@ -6,6 +9,9 @@ use rand::Rng;
 use uuid::Uuid;
 // 0.8.5
 
+const INITIAL_CASH: i32 = 100000;
+
+
 impl<'b> DaoTransaction<'b> {
     pub async fn create_account_for_actor(&self, 
                                           actor: &Actor) -> Result<(), DaoError> {
@@ -45,6 +51,8 @@ impl<'b> DaoTransaction<'b> {
             Err(db_error) => { return Err(gen_dao_error("create_account_for_actor account", db_error)); }
         };
         let account_id: i32 = row.get("accountId");
+        let create_time = current_time_millis();
+        let initial_cash = Money::from(INITIAL_CASH);
 
         match self.transaction.execute(
             "INSERT INTO balance \
@@ -52,14 +60,20 @@ impl<'b> DaoTransaction<'b> {
             VALUES ($1, $2, $3, $4) \
             ",
             &[&account_id,
-                &100000f32,
-                &current_time_millis(),
+                &initial_cash,
+                &create_time,
                 &0i64
             ]
         ).await {
             Ok(x) => x,
             Err(db_error) => { return Err(gen_dao_error("create_account_for_actor balance", db_error)); }
         };
+        self.save_journal(Journal::cash_movement(JournalType::InitialFunding,
+                                                 account_id,
+                                                 initial_cash,
+                                                 LedgerAccount::ExternalFunds,
+                                                 "Initial funding".to_string(),
+                                                 create_time)).await?;
 
         let row = match self.transaction.query_one(
             "INSERT INTO actor_account_relationship \
@@ -107,4 +121,17 @@ impl<'b> DaoTransaction<'b> {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+// An actor with a newly created account, for tests on a test_dao. Returns the account key
+#[cfg(test)]
+pub(crate) async fn create_test_account(dao: &crate::persistence::dao::Dao,
+                                        actor_name: &str) -> String {
+    let mut db_connection = dao.get_connection().await.unwrap();
+    let txn = dao.begin(&mut db_connection).await.unwrap();
+    let actor = txn.save_actor(&format!("{}@example.com", actor_name), actor_name, "none", "hash").await.unwrap();
+    txn.create_account_for_actor(&actor).await.unwrap();
+    let accesses = txn.get_accesses_for_actor(actor.actor_id).await.unwrap();
+    let account = txn.get_account(accesses[0].account_id).await.unwrap().unwrap();
+    txn.commit().await.unwrap();
+    account.account_key
+}
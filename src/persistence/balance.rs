@@ -29,9 +29,23 @@ impl<'b> DaoTransaction<'b> {
 
     pub async fn get_balance(&self, 
                              account_key: &String) -> Result<Balance, DaoError> {
+        self.query_balance(account_key, "").await
+    }
+
+    // For transactions that go on to post to the balance. Executions and cash movements on the
+    // same account then queue up behind each other instead of failing the version check
+    pub async fn get_balance_for_update(&self,
+                                        account_key: &String) -> Result<Balance, DaoError> {
+        self.query_balance(account_key, " FOR UPDATE OF balance").await
+    }
+
+    async fn query_balance(&self,
+                           account_key: &String,
+                           locking_clause: &str) -> Result<Balance, DaoError> {
         let mut query_string: String = "".to_owned();
         query_string.push_str(BALANCE_QUERY);
         query_string.push_str(" WHERE account.accountKey = $1");
+        query_string.push_str(locking_clause);
         let res = match self.transaction.query(&query_string,
                                                &[&account_key]).await {
             Ok(x) => x,
@@ -49,7 +63,7 @@ impl<'b> DaoTransaction<'b> {
 }
 
 
-fn convert_row_to_balance(row: &Row) -> Balance {
+pub(super) fn convert_row_to_balance(row: &Row) -> Balance {
     Balance {
         balance_id: row.get("balanceId"),
         account_id: row.get("accountId"),
@@ -62,6 +62,48 @@ pub struct Dao {
     pool: Pool,
 }
 
+// A Dao whose pool points at a closed port, for tests that must not reach a database
+#[cfg(test)]
+pub(crate) fn unreachable_dao() -> Dao {
+    let mut pg = deadpool_postgres::Config::new();
+    pg.host = Some("127.0.0.1".to_string());
+    pg.port = Some(1);
+    pg.dbname = Some("none".to_string());
+    Dao::new(pg.create_pool(None, tokio_postgres::NoTls).unwrap())
+}
+
+// A Dao on a new database loaded from schema.sql, for tests that need a real one. The server is
+// named by OPENBROKER_TEST_DATABASE as a connection string such as "host=/tmp user=postgres";
+// when it is not set there is no Dao and the test skips itself
+#[cfg(test)]
+pub(crate) async fn test_dao() -> Option<Dao> {
+    use std::str::FromStr;
+
+    let connection_string = match std::env::var("OPENBROKER_TEST_DATABASE") {
+        Ok(connection_string) => connection_string,
+        Err(_) => {
+            log::warn!("OPENBROKER_TEST_DATABASE is not set, skipping");
+            return None;
+        }
+    };
+    let mut pg_config = tokio_postgres::Config::from_str(&connection_string).unwrap();
+    let (client, connection) = pg_config.connect(tokio_postgres::NoTls).await.unwrap();
+    tokio::spawn(connection);
+    // Tests running at the same time may race to create the role, which is fine as long as one does
+    let _ = client.batch_execute("DO $$ BEGIN \
+        IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = 'broker_user') THEN CREATE ROLE broker_user; END IF; \
+        END $$").await;
+    let database_name = format!("openbroker_test_{}", uuid::Uuid::new_v4().simple());
+    client.batch_execute(&format!("CREATE DATABASE {}", database_name)).await.unwrap();
+
+    pg_config.dbname(&database_name);
+    let manager = deadpool_postgres::Manager::new(pg_config, tokio_postgres::NoTls);
+    let dao = Dao::new(Pool::builder(manager).max_size(8).build().unwrap());
+    let db_connection = dao.get_connection().await.unwrap();
+    db_connection.batch_execute(include_str!("../../resources/schema.sql")).await.unwrap();
+    Some(dao)
+}
+
 pub struct DaoTransaction<'a> {
     pub transaction: Transaction<'a>
 }
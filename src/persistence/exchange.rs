@@ -11,14 +11,16 @@ impl<'b> DaoTransaction<'b> {
                                exchange: &mut Exchange) -> Result<(), DaoError> {
         let row = match self.transaction.query_one(
             "INSERT INTO exchange \
-            (code, url, websocketUrl, description, apiKey) \
-            VALUES ($1, $2, $3, $4, $5) \
+            (code, url, websocketUrl, description, apiKey, supportsTimeInForce, maxOrderLegs) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7) \
             RETURNING exchangeId",
             &[&exchange.code,
                 &exchange.url,
                 &exchange.websocket_url,
                 &exchange.description,
-                &exchange.api_key
+                &exchange.api_key,
+                &exchange.supports_time_in_force,
+                &exchange.max_order_legs,
             ]
         ).await {
             Ok(x) => x,
@@ -34,7 +36,7 @@ impl<'b> DaoTransaction<'b> {
     pub async fn get_exchange(&self, 
                               exchange_code: &str) -> Result<Exchange, DaoError> {
         let row = match self.transaction.query_one(
-            "SELECT exchangeId, code, url, websocketUrl, description, apiKey FROM exchange \
+            "SELECT exchangeId, code, url, websocketUrl, description, apiKey, supportsTimeInForce, maxOrderLegs FROM exchange \
             WHERE code = $1",
             &[&exchange_code
             ]
@@ -50,6 +52,8 @@ impl<'b> DaoTransaction<'b> {
             websocket_url: row.get("websocketUrl"),
             description: row.get("description"),
             api_key: row.get("apiKey"),
+            supports_time_in_force: row.get("supportsTimeInForce"),
+            max_order_legs: row.get("maxOrderLegs"),
         })
     }
 
@@ -72,15 +76,18 @@ impl<'b> DaoTransaction<'b> {
                                  instrument: &mut Instrument) -> Result<(), DaoError> {
         let row = match self.transaction.query_one(
             "INSERT INTO instrument \
-            (instrumentKey, exchangeId, exchangeInstrumentId, status, symbol, assetClass, description, expirationTime) \
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+            (instrumentKey, exchangeId, exchangeInstrumentId, status, symbol, assetClass, description, expirationTime, \
+             tickSize, pricePrecision) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
             ON CONFLICT (exchangeId, exchangeInstrumentId)
             DO UPDATE \
             SET status = $4,\
              symbol = $5,\
              assetClass = $6,\
              description = $7,\
-             expirationTime = $8 \
+             expirationTime = $8,\
+             tickSize = $9,\
+             pricePrecision = $10 \
             RETURNING instrumentId",
             &[&instrument.instrument_key,
                 &instrument.exchange_id,
@@ -89,7 +96,9 @@ impl<'b> DaoTransaction<'b> {
                 &instrument.symbol,
                 &instrument.asset_class.to_string(),
                 &instrument.description,
-                &instrument.expiration_time
+                &instrument.expiration_time,
+                &instrument.tick_size,
+                &instrument.price_precision,
             ]
         ).await {
             Ok(x) => x,
@@ -135,6 +144,8 @@ fn convert_row_to_exchange(row: &Row) -> Exchange {
         websocket_url: row.get("websocketUrl"),
         description: row.get("description"),
         api_key: row.get("apiKey"),
+        supports_time_in_force: row.get("supportsTimeInForce"),
+        max_order_legs: row.get("maxOrderLegs"),
     }
 }
 
@@ -174,15 +185,17 @@ fn convert_row_to_instrument(row: &Row) -> Result<Instrument, DaoError> {
         asset_class,
         description: row.get("description"),
         expiration_time: row.get("expirationTime"),
+        tick_size: row.get("tickSize"),
+        price_precision: row.get("pricePrecision"),
     })
 }
 
 const EXCHANGE_QUERY: &str = "SELECT exchangeId, code, url, \
-websocketUrl, description, apiKey \
+websocketUrl, description, apiKey, supportsTimeInForce, maxOrderLegs \
 FROM exchange \
 ";
 
 const INSTRUMENT_QUERY: &str = "SELECT instrumentId, instrumentKey, exchangeId, exchangeInstrumentId, \
-status, symbol, assetClass, description, expirationTime \
+status, symbol, assetClass, description, expirationTime, tickSize, pricePrecision \
 FROM instrument \
 ";
@@ -0,0 +1,132 @@
+use crate::dtos::exchange::AssetClass;
+use crate::entities::fee::FeeSchedule;
+use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
+use std::str::FromStr;
+use tokio_postgres::Row;
+
+impl<'b> DaoTransaction<'b> {
+    // Replaces any schedule with the same offer, account and asset class.
+    // Returns None when the offer or account does not exist
+    pub async fn save_fee_schedule(&self,
+                                   mut fee_schedule: FeeSchedule) -> Result<Option<FeeSchedule>, DaoError> {
+        let asset_class = fee_schedule.asset_class.as_ref().map(|asset_class| asset_class.to_string());
+        match self.transaction.execute(FEE_SCHEDULE_DELETE_STATEMENT,
+                                       &[&fee_schedule.offer_code,
+                                           &fee_schedule.account_key,
+                                           &asset_class,
+                                       ]).await {
+            Ok(_) => {},
+            Err(db_error) => { return Err(gen_dao_error("save_fee_schedule delete", db_error)); }
+        };
+        let row = match self.transaction.query_one(
+            FEE_SCHEDULE_SAVE_STATEMENT,
+            &[&fee_schedule.offer_code,
+                &fee_schedule.account_key,
+                &asset_class,
+                &fee_schedule.per_share,
+                &fee_schedule.per_contract,
+                &fee_schedule.basis_points,
+                &fee_schedule.minimum_ticket,
+            ]
+        ).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("save_fee_schedule", db_error)); }
+        };
+        let offer_id: Option<i32> = row.get("offerId");
+        let account_id: Option<i32> = row.get("accountId");
+        if offer_id.is_none() != fee_schedule.offer_code.is_none()
+            || account_id.is_none() != fee_schedule.account_key.is_none() {
+            return Ok(None);
+        }
+        fee_schedule.fee_schedule_id = row.get("feeScheduleId");
+        Ok(Some(fee_schedule))
+    }
+
+    pub async fn get_fee_schedules(&self) -> Result<Vec<FeeSchedule>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(FEE_SCHEDULE_QUERY);
+        query_string.push_str(" ORDER BY fee.feeScheduleId");
+        let rows = match self.transaction.query(&query_string, &[]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_fee_schedules", db_error)); }
+        };
+        rows.iter().map(convert_row_to_fee_schedule).collect()
+    }
+
+    // The most specific schedule wins: the account's own, then its owner's offer,
+    // then the default, preferring one for the asset class at each level
+    pub async fn get_fee_schedule_for_account(&self,
+                                              account_id: i32,
+                                              asset_class: &AssetClass) -> Result<Option<FeeSchedule>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(FEE_SCHEDULE_QUERY);
+        query_string.push_str(FEE_SCHEDULE_FOR_ACCOUNT_CONDITION);
+        let row_option = match self.transaction.query_opt(&query_string,
+                                                          &[&account_id,
+                                                              &asset_class.to_string(),
+                                                          ]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_fee_schedule_for_account", db_error)); }
+        };
+        match row_option {
+            Some(row) => Ok(Some(convert_row_to_fee_schedule(&row)?)),
+            None => Ok(None)
+        }
+    }
+}
+
+fn convert_row_to_fee_schedule(row: &Row) -> Result<FeeSchedule, DaoError> {
+    let row_asset_class: Option<&str> = row.get("assetClass");
+    let asset_class = match row_asset_class {
+        Some(row_asset_class) => match AssetClass::from_str(row_asset_class) {
+            Ok(asset_class) => Some(asset_class),
+            Err(()) => return Err(DaoError::ConversionFailed { description: format!("Could not parse asset class {}", row_asset_class) })
+        },
+        None => None
+    };
+    Ok(FeeSchedule {
+        fee_schedule_id: row.get("feeScheduleId"),
+        offer_code: row.get("offerCode"),
+        account_key: row.get("accountKey"),
+        asset_class,
+        per_share: row.get("perShare"),
+        per_contract: row.get("perContract"),
+        basis_points: row.get("basisPoints"),
+        minimum_ticket: row.get("minimumTicket"),
+    })
+}
+
+const FEE_SCHEDULE_DELETE_STATEMENT: &str = "
+DELETE FROM fee_schedule \
+WHERE offerId IS NOT DISTINCT FROM (SELECT offerId FROM offer WHERE code = $1) \
+AND accountId IS NOT DISTINCT FROM (SELECT accountId FROM account WHERE accountKey = $2) \
+AND assetClass IS NOT DISTINCT FROM $3
+";
+
+const FEE_SCHEDULE_SAVE_STATEMENT: &str = "
+INSERT INTO fee_schedule \
+(offerId, accountId, assetClass, perShare, perContract, basisPoints, minimumTicket) \
+VALUES \
+((SELECT offerId FROM offer WHERE code = $1), (SELECT accountId FROM account WHERE accountKey = $2), $3, $4, $5, $6, $7) \
+RETURNING feeScheduleId, offerId, accountId
+";
+
+const FEE_SCHEDULE_QUERY: &str = "
+SELECT fee.feeScheduleId, offer.code AS offerCode, account.accountKey, fee.assetClass, \
+fee.perShare, fee.perContract, fee.basisPoints, fee.minimumTicket \
+FROM fee_schedule AS fee \
+LEFT JOIN offer ON offer.offerId = fee.offerId \
+LEFT JOIN account ON account.accountId = fee.accountId \
+";
+
+const FEE_SCHEDULE_FOR_ACCOUNT_CONDITION: &str = "
+WHERE (fee.accountId = $1 \
+    OR fee.offerId IN (SELECT actor.offerId FROM actor_account_relationship relation \
+        JOIN access ON access.relationshipId = relation.relationshipId AND access.privilege = 'Owner' \
+        JOIN actor ON actor.actorId = relation.actorId \
+        WHERE relation.accountId = $1) \
+    OR (fee.accountId IS NULL AND fee.offerId IS NULL)) \
+AND (fee.assetClass = $2 OR fee.assetClass IS NULL) \
+ORDER BY (fee.accountId IS NOT NULL) DESC, (fee.offerId IS NOT NULL) DESC, (fee.assetClass IS NOT NULL) DESC \
+LIMIT 1
+";
@@ -0,0 +1,176 @@
+use crate::dtos::ledger::{JournalType, LedgerAccount};
+use crate::entities::account::Balance;
+use crate::entities::ledger::{Journal, LedgerDiscrepancy, LedgerEntry};
+use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
+use std::str::FromStr;
+use tokio_postgres::Row;
+
+impl<'b> DaoTransaction<'b> {
+    pub async fn save_journal(&self,
+                              mut journal: Journal) -> Result<Journal, DaoError> {
+        if !journal.is_balanced() {
+            return Err(DaoError::ExecuteFailed { description: format!("Journal {} does not balance", journal.description) });
+        }
+        let row = match self.transaction.query_one(
+            JOURNAL_SAVE_STATEMENT,
+            &[&journal.journal_type.to_string(),
+                &journal.trade_id,
+                &journal.create_time,
+                &journal.description,
+            ]
+        ).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("save_journal", db_error)); }
+        };
+        journal.journal_id = row.get("journalId");
+
+        for entry in journal.entries.iter_mut() {
+            let row = match self.transaction.query_one(
+                LEDGER_ENTRY_SAVE_STATEMENT,
+                &[&journal.journal_id,
+                    &entry.account_id,
+                    &entry.ledger_account.to_string(),
+                    &entry.amount,
+                ]
+            ).await {
+                Ok(x) => x,
+                Err(db_error) => { return Err(gen_dao_error("save_journal entry", db_error)); }
+            };
+            entry.ledger_entry_id = row.get("ledgerEntryId");
+        }
+        Ok(journal)
+    }
+
+    // Records the journal and moves the balance by the customer cash it posts,
+    // so that the balance never changes without a matching ledger entry
+    pub async fn post_journal(&self,
+                              balance: &mut Balance,
+                              journal: Journal) -> Result<Journal, DaoError> {
+        let journal = self.save_journal(journal).await?;
+        balance.cash += journal.cash_change(balance.account_id);
+        balance.update_time = journal.create_time;
+        self.update_balance(balance).await?;
+        Ok(journal)
+    }
+
+    pub async fn get_journals(&self,
+                              account_key: &String,
+                              start_time: i64,
+                              end_time: i64) -> Result<Vec<Journal>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(JOURNAL_QUERY);
+        query_string.push_str("WHERE account.accountKey = $1 ");
+        query_string.push_str(" AND journal.createTime >= $2 AND journal.createTime < $3 ");
+        query_string.push_str(" ORDER BY journal.createTime DESC, journal.journalId DESC, entry.ledgerEntryId ");
+        let rows = match self.transaction.query(&query_string,
+                                                &[&account_key,
+                                                    &start_time,
+                                                    &end_time,
+                                                ]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_journals", db_error)); }
+        };
+        convert_rows_to_journals(&rows)
+    }
+
+    // Accounts whose balance does not match the sum of their customer cash entries
+    pub async fn get_ledger_discrepancies(&self) -> Result<Vec<LedgerDiscrepancy>, DaoError> {
+        let rows = match self.transaction.query(LEDGER_DISCREPANCY_QUERY, &[]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_ledger_discrepancies", db_error)); }
+        };
+        Ok(rows.iter().map(|row| LedgerDiscrepancy {
+            account_key: row.get("accountKey"),
+            cash: row.get("cash"),
+            ledger_cash: row.get("ledgerCash"),
+        }).collect())
+    }
+}
+
+fn convert_rows_to_journals(rows: &[Row]) -> Result<Vec<Journal>, DaoError> {
+    let mut journals: Vec<Journal> = Vec::new();
+    for row in rows {
+        let journal_id: i64 = row.get("journalId");
+        let entry = convert_row_to_ledger_entry(row)?;
+        match journals.last_mut() {
+            Some(journal) if journal.journal_id == journal_id => journal.entries.push(entry),
+            _ => {
+                let mut journal = convert_row_to_journal(row)?;
+                journal.entries.push(entry);
+                journals.push(journal);
+            }
+        }
+    }
+    Ok(journals)
+}
+
+fn convert_row_to_journal(row: &Row) -> Result<Journal, DaoError> {
+    let row_journal_type = row.get("journalType");
+    let journal_type = match JournalType::from_str(row_journal_type) {
+        Ok(journal_type) => journal_type,
+        Err(()) => {
+            return Err(DaoError::ConversionFailed {
+                description: format!("Unknown journal type {}", row_journal_type)
+            })
+        }
+    };
+    Ok(Journal {
+        journal_id: row.get("journalId"),
+        journal_type,
+        trade_id: row.get("tradeId"),
+        create_time: row.get("createTime"),
+        description: row.get("description"),
+        entries: vec![],
+    })
+}
+
+fn convert_row_to_ledger_entry(row: &Row) -> Result<LedgerEntry, DaoError> {
+    let row_ledger_account = row.get("ledgerAccount");
+    let ledger_account = match LedgerAccount::from_str(row_ledger_account) {
+        Ok(ledger_account) => ledger_account,
+        Err(()) => {
+            return Err(DaoError::ConversionFailed {
+                description: format!("Unknown ledger account {}", row_ledger_account)
+            })
+        }
+    };
+    Ok(LedgerEntry {
+        ledger_entry_id: row.get("ledgerEntryId"),
+        account_id: row.get("accountId"),
+        ledger_account,
+        amount: row.get("amount"),
+    })
+}
+
+const JOURNAL_SAVE_STATEMENT: &str = "
+INSERT INTO ledger_journal \
+(journalType, tradeId, createTime, description) \
+VALUES \
+($1, $2, $3, $4) \
+RETURNING journalId
+";
+
+const LEDGER_ENTRY_SAVE_STATEMENT: &str = "
+INSERT INTO ledger_entry \
+(journalId, accountId, ledgerAccount, amount) \
+VALUES \
+($1, $2, $3, $4) \
+RETURNING ledgerEntryId
+";
+
+const JOURNAL_QUERY: &str = "
+SELECT journal.journalId, journal.journalType, journal.tradeId, journal.createTime, journal.description, \
+entry.ledgerEntryId, entry.accountId, entry.ledgerAccount, entry.amount \
+FROM ledger_journal AS journal \
+JOIN ledger_entry AS entry ON entry.journalId = journal.journalId \
+JOIN account ON account.accountId = entry.accountId \
+";
+
+const LEDGER_DISCREPANCY_QUERY: &str = "
+SELECT account.accountKey, balance.cash, COALESCE(SUM(entry.amount), 0) AS ledgerCash \
+FROM balance \
+JOIN account ON account.accountId = balance.accountId \
+LEFT JOIN ledger_entry AS entry ON entry.accountId = balance.accountId AND entry.ledgerAccount = 'CustomerCash' \
+GROUP BY account.accountKey, balance.cash \
+HAVING balance.cash <> COALESCE(SUM(entry.amount), 0) \
+";
@@ -0,0 +1,120 @@
+use crate::entities::account::{MarginCall, MarginCallStatus};
+use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
+use std::str::FromStr;
+use tokio_postgres::Row;
+
+impl<'b> DaoTransaction<'b> {
+    pub async fn save_margin_call(&self,
+                                  mut margin_call: MarginCall) -> Result<MarginCall, DaoError> {
+        let row = match self.transaction.query_one(
+            MARGIN_CALL_SAVE_STATEMENT,
+            &[&margin_call.account_id,
+                &margin_call.status.to_string(),
+                &margin_call.equity,
+                &margin_call.maintenance_margin,
+                &margin_call.call_time,
+                &margin_call.update_time,
+            ]
+        ).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("save_margin_call", db_error)); }
+        };
+        margin_call.margin_call_id = row.get("marginCallId");
+        Ok(margin_call)
+    }
+
+    pub async fn update_margin_call(&self,
+                                    margin_call: &MarginCall) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            MARGIN_CALL_UPDATE_STATEMENT,
+            &[&margin_call.status.to_string(),
+                &margin_call.equity,
+                &margin_call.maintenance_margin,
+                &margin_call.update_time,
+                &margin_call.margin_call_id,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("update_margin_call", db_error)),
+        }
+    }
+
+    // Records the call as it stands, with what happened to it
+    pub async fn save_margin_call_audit(&self,
+                                        margin_call: &MarginCall,
+                                        description: &str) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            MARGIN_CALL_AUDIT_SAVE_STATEMENT,
+            &[&margin_call.margin_call_id,
+                &margin_call.update_time,
+                &margin_call.status.to_string(),
+                &margin_call.equity,
+                &margin_call.maintenance_margin,
+                &description,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("save_margin_call_audit", db_error)),
+        }
+    }
+
+    // Calls that have not been met, with their account keys
+    pub async fn get_open_margin_calls(&self) -> Result<Vec<(String, MarginCall)>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(MARGIN_CALL_QUERY);
+        query_string.push_str("WHERE margin_call.marginCallStatus <> $1 ");
+        let rows = match self.transaction.query(&query_string, &[&MarginCallStatus::Met.to_string()]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_open_margin_calls", db_error)); }
+        };
+        let mut margin_calls = Vec::new();
+        for row in rows.iter() {
+            margin_calls.push((row.get("accountKey"), convert_row_to_margin_call(row)?));
+        }
+        Ok(margin_calls)
+    }
+}
+
+fn convert_row_to_margin_call(row: &Row) -> Result<MarginCall, DaoError> {
+    let row_status: &str = row.get("marginCallStatus");
+    let status = match MarginCallStatus::from_str(row_status) {
+        Ok(status) => status,
+        Err(()) => return Err(DaoError::ConversionFailed { description: format!("Could not parse margin call status {}", row_status) })
+    };
+    Ok(MarginCall {
+        margin_call_id: row.get("marginCallId"),
+        account_id: row.get("accountId"),
+        status,
+        equity: row.get("equity"),
+        maintenance_margin: row.get("maintenanceMargin"),
+        call_time: row.get("callTime"),
+        update_time: row.get("updateTime"),
+    })
+}
+
+const MARGIN_CALL_SAVE_STATEMENT: &str = "
+INSERT INTO margin_call \
+(accountId, marginCallStatus, equity, maintenanceMargin, callTime, updateTime) \
+VALUES \
+($1, $2, $3, $4, $5, $6) \
+RETURNING marginCallId
+";
+
+const MARGIN_CALL_UPDATE_STATEMENT: &str = "
+UPDATE margin_call \
+SET marginCallStatus = $1, equity = $2, maintenanceMargin = $3, updateTime = $4 \
+WHERE marginCallId = $5
+";
+
+const MARGIN_CALL_AUDIT_SAVE_STATEMENT: &str = "
+INSERT INTO margin_call_audit \
+(marginCallId, eventTime, marginCallStatus, equity, maintenanceMargin, description) \
+VALUES \
+($1, $2, $3, $4, $5, $6)
+";
+
+const MARGIN_CALL_QUERY: &str = "
+SELECT marginCallId, margin_call.accountId, account.accountKey, marginCallStatus, equity, maintenanceMargin, callTime, updateTime \
+FROM margin_call \
+JOIN account ON account.accountId = margin_call.accountId \
+";
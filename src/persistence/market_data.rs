@@ -0,0 +1,130 @@
+use crate::entities::market_data::{Bar, BarInterval};
+use crate::money::Money;
+use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
+use std::collections::HashMap;
+use std::str::FromStr;
+use tokio_postgres::Row;
+
+impl<'b> DaoTransaction<'b> {
+    // Saves run out of order, so an update only lands if it has seen more trades than the stored bar
+    pub async fn save_bar(&self,
+                          bar: &Bar) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            BAR_SAVE_STATEMENT,
+            &[&bar.instrument_id,
+                &bar.interval.to_string(),
+                &bar.start_time,
+                &bar.open,
+                &bar.high,
+                &bar.low,
+                &bar.close,
+                &bar.volume,
+                &bar.trade_count,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("save_bar", db_error)),
+        }
+    }
+
+    // The latest bars in the range, oldest first
+    pub async fn get_bars(&self,
+                          instrument_id: i64,
+                          interval: BarInterval,
+                          start_time: i64,
+                          end_time: i64,
+                          limit: i64) -> Result<Vec<Bar>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(BAR_QUERY);
+        query_string.push_str("WHERE instrumentId = $1 AND barInterval = $2 AND startTime >= $3 AND startTime < $4 ");
+        query_string.push_str("ORDER BY startTime DESC LIMIT $5 ");
+        let rows = match self.transaction.query(&query_string,
+                                                &[&instrument_id,
+                                                    &interval.to_string(),
+                                                    &start_time,
+                                                    &end_time,
+                                                    &limit]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_bars", db_error)); }
+        };
+        let mut bars = Vec::new();
+        for row in rows.iter().rev() {
+            bars.push(convert_row_to_bar(row)?);
+        }
+        Ok(bars)
+    }
+
+    // The bar in progress for each instrument and interval, as of the last trade seen
+    pub async fn get_latest_bars(&self) -> Result<Vec<Bar>, DaoError> {
+        let rows = match self.transaction.query(LATEST_BAR_QUERY, &[]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_latest_bars", db_error)); }
+        };
+        let mut bars = Vec::new();
+        for row in rows.iter() {
+            bars.push(convert_row_to_bar(row)?);
+        }
+        Ok(bars)
+    }
+
+    // The close of the last daily bar that started before the given time, by instrument id
+    pub async fn get_prior_closes(&self,
+                                  before_time: i64) -> Result<HashMap<i64, Money>, DaoError> {
+        let rows = match self.transaction.query(PRIOR_CLOSE_QUERY, &[&BarInterval::OneDay.to_string(), &before_time]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_prior_closes", db_error)); }
+        };
+        Ok(rows.iter()
+            .map(|row| (row.get("instrumentId"), row.get("closePrice")))
+            .collect())
+    }
+}
+
+fn convert_row_to_bar(row: &Row) -> Result<Bar, DaoError> {
+    let row_interval: &str = row.get("barInterval");
+    let interval = match BarInterval::from_str(row_interval) {
+        Ok(interval) => interval,
+        Err(()) => return Err(DaoError::ConversionFailed { description: format!("Could not parse bar interval {}", row_interval) })
+    };
+    Ok(Bar {
+        instrument_id: row.get("instrumentId"),
+        interval,
+        start_time: row.get("startTime"),
+        open: row.get("openPrice"),
+        high: row.get("highPrice"),
+        low: row.get("lowPrice"),
+        close: row.get("closePrice"),
+        volume: row.get("volume"),
+        trade_count: row.get("tradeCount"),
+    })
+}
+
+const BAR_SAVE_STATEMENT: &str = "
+INSERT INTO market_bar \
+(instrumentId, barInterval, startTime, openPrice, highPrice, lowPrice, closePrice, volume, tradeCount) \
+VALUES \
+($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+ON CONFLICT (instrumentId, barInterval, startTime) DO UPDATE \
+SET openPrice = EXCLUDED.openPrice, highPrice = EXCLUDED.highPrice, lowPrice = EXCLUDED.lowPrice, \
+closePrice = EXCLUDED.closePrice, volume = EXCLUDED.volume, tradeCount = EXCLUDED.tradeCount \
+WHERE market_bar.tradeCount < EXCLUDED.tradeCount
+";
+
+const BAR_QUERY: &str = "
+SELECT instrumentId, barInterval, startTime, openPrice, highPrice, lowPrice, closePrice, volume, tradeCount \
+FROM market_bar \
+";
+
+const LATEST_BAR_QUERY: &str = "
+SELECT DISTINCT ON (instrumentId, barInterval) \
+instrumentId, barInterval, startTime, openPrice, highPrice, lowPrice, closePrice, volume, tradeCount \
+FROM market_bar \
+ORDER BY instrumentId, barInterval, startTime DESC
+";
+
+const PRIOR_CLOSE_QUERY: &str = "
+SELECT DISTINCT ON (instrumentId) instrumentId, closePrice \
+FROM market_bar \
+WHERE barInterval = $1 AND startTime < $2 \
+ORDER BY instrumentId, startTime DESC
+";
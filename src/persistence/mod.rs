@@ -7,5 +7,15 @@ mod balance;
 mod position;
 mod exchange;
 mod offer;
+mod trade;
+mod reconciliation;
+mod snapshot;
+mod ledger;
+mod fee;
+mod order_group;
+mod market_data;
+mod margin_call;
+mod routing;
+mod outbox;
 pub mod admin;
-pub mod account_management;
\ No newline at end of file
+pub mod account_management;
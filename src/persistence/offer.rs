@@ -23,12 +23,13 @@ impl<'b> DaoTransaction<'b> {
                             mut offer: Offer) -> Result<(), DaoError> {
         let row = match self.transaction.query_one(
             "INSERT INTO offer \
-            (code, description, expirationTime) \
-            VALUES ($1, $2, $3) \
+            (code, description, expirationTime, vetterName) \
+            VALUES ($1, $2, $3, $4) \
             RETURNING offerId",
             &[&offer.code,
                 &offer.description,
                 &offer.expiration_time,
+                &offer.vetter_name,
             ]
         ).await {
             Ok(x) => x,
@@ -0,0 +1,119 @@
+use crate::entities::order_group::{OrderGroup, OrderGroupMember, OrderGroupRole, OrderGroupType};
+use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
+use std::str::FromStr;
+use tokio_postgres::Row;
+
+impl<'b> DaoTransaction<'b> {
+    // The member orders must already be saved
+    pub async fn save_order_group(&self,
+                                  mut order_group: OrderGroup) -> Result<OrderGroup, DaoError> {
+        let row = match self.transaction.query_one(
+            ORDER_GROUP_SAVE_STATEMENT,
+            &[&order_group.account_id,
+                &order_group.group_type.to_string(),
+                &order_group.create_time,
+            ]
+        ).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("save_order_group", db_error)); }
+        };
+        order_group.order_group_id = row.get("orderGroupId");
+        for member in order_group.members.iter() {
+            match self.transaction.execute(
+                ORDER_GROUP_MEMBER_SAVE_STATEMENT,
+                &[&order_group.order_group_id,
+                    &member.order_id,
+                    &member.role.to_string(),
+                ]
+            ).await {
+                Ok(_) => {},
+                Err(db_error) => { return Err(gen_dao_error("save_order_group member", db_error)); }
+            };
+        }
+        Ok(order_group)
+    }
+
+    // A replacement takes the place of the order it replaces; the old member stays on record
+    pub async fn save_order_group_member(&self,
+                                         order_group_id: i64,
+                                         member: &OrderGroupMember) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            ORDER_GROUP_MEMBER_SAVE_STATEMENT,
+            &[&order_group_id,
+                &member.order_id,
+                &member.role.to_string(),
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("save_order_group_member", db_error)),
+        }
+    }
+
+    pub async fn get_order_group_for_order(&self,
+                                           order_id: i64) -> Result<Option<OrderGroup>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(ORDER_GROUP_QUERY);
+        query_string.push_str("WHERE grp.orderGroupId = (SELECT orderGroupId FROM order_group_member WHERE orderId = $1) ");
+        query_string.push_str(" ORDER BY member.orderId ");
+        let rows = match self.transaction.query(&query_string, &[&order_id]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_order_group_for_order", db_error)); }
+        };
+        let first_row = match rows.first() {
+            Some(first_row) => first_row,
+            None => return Ok(None),
+        };
+        let row_group_type: &str = first_row.get("groupType");
+        let group_type = match OrderGroupType::from_str(row_group_type) {
+            Ok(group_type) => group_type,
+            Err(()) => return Err(DaoError::ConversionFailed { description: format!("Could not parse order group type {}", row_group_type) })
+        };
+        let mut members = Vec::new();
+        for row in rows.iter() {
+            members.push(convert_row_to_order_group_member(row)?);
+        }
+        Ok(Some(OrderGroup {
+            order_group_id: first_row.get("orderGroupId"),
+            account_id: first_row.get("accountId"),
+            group_type,
+            create_time: first_row.get("createTime"),
+            members,
+        }))
+    }
+}
+
+fn convert_row_to_order_group_member(row: &Row) -> Result<OrderGroupMember, DaoError> {
+    let row_role: &str = row.get("groupRole");
+    let role = match OrderGroupRole::from_str(row_role) {
+        Ok(role) => role,
+        Err(()) => return Err(DaoError::ConversionFailed { description: format!("Could not parse order group role {}", row_role) })
+    };
+    Ok(OrderGroupMember {
+        order_id: row.get("orderId"),
+        client_order_id: row.get("clientOrderId"),
+        role,
+    })
+}
+
+const ORDER_GROUP_SAVE_STATEMENT: &str = "
+INSERT INTO order_group \
+(accountId, groupType, createTime) \
+VALUES \
+($1, $2, $3) \
+RETURNING orderGroupId
+";
+
+const ORDER_GROUP_MEMBER_SAVE_STATEMENT: &str = "
+INSERT INTO order_group_member \
+(orderGroupId, orderId, groupRole) \
+VALUES \
+($1, $2, $3)
+";
+
+const ORDER_GROUP_QUERY: &str = "
+SELECT grp.orderGroupId, grp.accountId, grp.groupType, grp.createTime, \
+member.orderId, member.groupRole, base.clientOrderId \
+FROM order_group AS grp \
+JOIN order_group_member AS member ON member.orderGroupId = grp.orderGroupId \
+JOIN order_base AS base ON base.orderId = member.orderId \
+";
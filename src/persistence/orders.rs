@@ -1,5 +1,6 @@
-use crate::dtos::order::{is_order_status_viable, OrderStatus};
+use crate::dtos::order::{is_order_status_viable, OrderStatus, OrderType, TimeInForce};
 use crate::entities::order::{Order, OrderLeg, OrderState};
+use crate::money::Money;
 use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
 use crate::time::current_time_millis;
 use log::info;
@@ -10,7 +11,14 @@ use tokio_postgres::Row;
 
 impl<'b> DaoTransaction<'b> {
     pub async fn save_order(&self,
-                            mut order_state: OrderState) -> Result<OrderState, DaoError> {
+                            order_state: OrderState) -> Result<OrderState, DaoError> {
+        self.save_replacement_order(order_state, None).await
+    }
+
+    // replaces_order_id links the new order's first history entry to the order it replaces
+    pub async fn save_replacement_order(&self,
+                                        mut order_state: OrderState,
+                                        replaces_order_id: Option<i64>) -> Result<OrderState, DaoError> {
 
         let order_number_row = match self.transaction.query_one(
             "INSERT INTO order_number_generator \
@@ -32,8 +40,9 @@ impl<'b> DaoTransaction<'b> {
 
         let row = match self.transaction.query_one(
             "INSERT INTO order_base \
-            (accountId, extOrderId, orderNumber, clientOrderId, createTime, price, quantity) \
-            VALUES ($1, $2, $3, $4, $5, $6, $7) \
+            (accountId, extOrderId, orderNumber, clientOrderId, createTime, price, quantity, timeInForce, expireTime, \
+            orderType, stopPrice, trailingOffset) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) \
             RETURNING orderId",
             &[&order_state.order.account_id,
                      &order_state.order.ext_order_id,
@@ -42,6 +51,11 @@ impl<'b> DaoTransaction<'b> {
                      &order_state.order.create_time,
                      &order_state.order.price,
                      &order_state.order.quantity,
+                     &order_state.order.time_in_force.to_string(),
+                     &order_state.order.expire_time,
+                     &order_state.order.order_type.to_string(),
+                     &order_state.order.stop_price,
+                     &order_state.order.trailing_offset,
             ]
         ).await {
             Ok(x) => x,
@@ -68,13 +82,15 @@ impl<'b> DaoTransaction<'b> {
 
         let order_state_row_count = match self.transaction.execute(
             "INSERT INTO order_state \
-                (orderId, orderStatus, updateTime, versionNumber, rejectReason) \
-                VALUES ($1, $2, $3, $4, $5)",
+                (orderId, orderStatus, updateTime, versionNumber, rejectReason, filledQuantity, averageFillPrice) \
+                VALUES ($1, $2, $3, $4, $5, $6, $7)",
             &[&order_state.order.order_id,
                      &order_state.order_status.to_string(),
                      &order_state.update_time,
                      &order_state.version_number,
-                     &order_state.reject_reason
+                     &order_state.reject_reason,
+                     &order_state.filled_quantity,
+                     &order_state.average_fill_price,
             ]
         ).await {
             Ok(row_count) => row_count,
@@ -83,7 +99,7 @@ impl<'b> DaoTransaction<'b> {
         if order_state_row_count != 1 {
             return Err(DaoError::ExecuteFailed { description: format!("save_order order_state insert returned {} rows, not 1", order_state_row_count) });
         }
-        let order_state_history_row_count = match self.insert_order_state_history(&order_state).await {
+        let order_state_history_row_count = match self.insert_order_state_history(&order_state, replaces_order_id, None).await {
             Ok(row_count) => row_count,
             Err(db_error) => { return Err(db_error) }
         };
@@ -95,18 +111,28 @@ impl<'b> DaoTransaction<'b> {
 
     pub async fn update_order(&self,
                               order_state: &mut OrderState) -> Result<(), DaoError> {
+        self.update_replaced_order(order_state, None).await
+    }
+
+    // replaced_by_order_id links the history entry to the order that replaced this one
+    pub async fn update_replaced_order(&self,
+                                       order_state: &mut OrderState,
+                                       replaced_by_order_id: Option<i64>) -> Result<(), DaoError> {
         let next_version_number = order_state.version_number + 1;
         if (order_state.order_status != OrderStatus::Rejected) {
             order_state.reject_reason = None;
         }
         let rows_updated = match self.transaction.execute(
             "UPDATE order_state \
-                set orderStatus = $1, updateTime = $2, versionNumber = $3, rejectReason = $4 \
-                WHERE orderId = $5 and versionNumber = $6",
+                set orderStatus = $1, updateTime = $2, versionNumber = $3, rejectReason = $4, \
+                filledQuantity = $5, averageFillPrice = $6 \
+                WHERE orderId = $7 and versionNumber = $8",
             &[&order_state.order_status.to_string(),
                      &order_state.update_time,
                      &next_version_number,
                      &order_state.reject_reason,
+                     &order_state.filled_quantity,
+                     &order_state.average_fill_price,
                      &order_state.order.order_id,
                      &order_state.version_number,
             ]
@@ -119,7 +145,7 @@ impl<'b> DaoTransaction<'b> {
             return Err(DaoError::OptimisticLockingFailed{ description: "update order 0 rows modified".to_string() });
         }
         order_state.version_number = next_version_number;
-        let order_state_history_row_count = match self.insert_order_state_history(&order_state).await {
+        let order_state_history_row_count = match self.insert_order_state_history(order_state, None, replaced_by_order_id).await {
             Ok(order_state_history_row_count) => order_state_history_row_count,
             Err(db_error) => return Err(db_error)
         };
@@ -131,16 +157,23 @@ impl<'b> DaoTransaction<'b> {
     }
 
     async fn insert_order_state_history(&self,
-                                        order_state: &OrderState) -> Result<u64, DaoError> {
+                                        order_state: &OrderState,
+                                        replaces_order_id: Option<i64>,
+                                        replaced_by_order_id: Option<i64>) -> Result<u64, DaoError> {
         match self.transaction.execute(
             "INSERT INTO order_state_history \
-                (orderId, orderStatus, createTime, versionNumber, rejectReason) \
-                VALUES ($1, $2, $3, $4, $5)",
+                (orderId, orderStatus, createTime, versionNumber, rejectReason, filledQuantity, averageFillPrice, \
+                replacesOrderId, replacedByOrderId) \
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
             &[&order_state.order.order_id,
                      &order_state.order_status.to_string(),
                      &order_state.update_time,
                      &order_state.version_number,
-                     &order_state.reject_reason
+                     &order_state.reject_reason,
+                     &order_state.filled_quantity,
+                     &order_state.average_fill_price,
+                     &replaces_order_id,
+                     &replaced_by_order_id,
             ]
         ).await {
             Ok(row_count) => Ok(row_count),
@@ -169,7 +202,7 @@ impl<'b> DaoTransaction<'b> {
         query_string.push_str(" AND (state.updateTime > $2 AND state.orderStatus = ANY ($3)) ");
         query_string.push_str(" ORDER BY base.orderNumber DESC ");
         query_string.push_str(" LIMIT 100");
-        query_string.push_str(" ) UNION ( ");
+        query_string.push_str(" ) AS recent_orders UNION ( ");
         query_string.push_str(ORDER_QUERY);
         query_string.push_str("WHERE account.accountKey = $1 ");
         query_string.push_str(" AND state.orderStatus = ANY ($4) ");
@@ -196,6 +229,81 @@ impl<'b> DaoTransaction<'b> {
         }
     }
 
+    // Orders held by the broker until their stop price trades, across all accounts
+    pub async fn get_pending_trigger_orders(&self) -> Result<Vec<OrderState>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(ORDER_QUERY);
+        query_string.push_str("WHERE state.orderStatus = $1 ");
+        query_string.push_str(" ORDER BY base.orderId, leg.orderLegId ");
+        let res = match self.transaction.query(&query_string,
+                                               &[&OrderStatus::PendingTrigger.to_string()]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_pending_trigger_orders", db_error)); }
+        };
+        let mut order_states: Vec<OrderState> = Vec::new();
+        for row in res {
+            let order_id: i64 = row.get("orderId");
+            match order_states.last_mut() {
+                Some(order_state) if order_state.order.order_id == order_id => add_leg_to_order_state(order_state, &row),
+                _ => {
+                    let mut order_state = convert_row_to_order_state(&row)?;
+                    add_leg_to_order_state(&mut order_state, &row);
+                    order_states.push(order_state);
+                }
+            }
+        }
+        Ok(order_states)
+    }
+
+    // A trailing stop's stop price moves with the market, and only ever tightens: up for a sell,
+    // down for a buy. Saves can land out of order, and a stale one leaves the row alone
+    pub async fn update_stop_price(&self,
+                                   order_id: i64,
+                                   stop_price: Money) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            "UPDATE order_base SET stopPrice = $1 WHERE orderId = $2 \
+            AND (stopPrice IS NULL OR (quantity < 0 AND stopPrice < $1) OR (quantity > 0 AND stopPrice > $1))",
+            &[&stop_price,
+                &order_id,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("update_stop_price", db_error)),
+        }
+    }
+
+    // A bracket child shrinks to what its entry actually filled
+    pub async fn update_order_quantity(&self,
+                                       order_id: i64,
+                                       quantity: i32) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            "UPDATE order_base SET quantity = $1 WHERE orderId = $2",
+            &[&quantity,
+                &order_id,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("update_order_quantity", db_error)),
+        }
+    }
+
+    pub async fn get_viable_orders(&self) -> Result<HashMap<String, OrderState>, DaoError> {
+        let viable_statuses: Vec<String> = OrderStatus::iter()
+            .filter(is_order_status_viable)
+            .map(|order_status| order_status.to_string())
+            .collect();
+
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(ORDER_QUERY);
+        query_string.push_str("WHERE state.orderStatus = ANY ($1) ");
+        let res = match self.transaction.query(&query_string,
+                                               &[&viable_statuses]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_viable_orders", db_error)); }
+        };
+        convert_rows_to_order_states(res)
+    }
+
     pub async fn get_order_by_ext_order_id(&self,
                                            account_key: &String,
                                            ext_order_id: &String) -> Result<Option<OrderState>, DaoError> {
@@ -208,18 +316,17 @@ impl<'b> DaoTransaction<'b> {
             Ok(x) => x,
            Err(db_error) => { return Err(gen_dao_error("get_order", db_error)); }
        };
-        if res.len() > 1 {
-            if res.len() > 1 {
-                return Err(DaoError::QueryFailed {
-                    description: format!("get_order_by_ext_order_id got {} rows, expected 1", res.len()),
-                });
-            }
-        }
+        // One row per leg
         let order_state_map_result = convert_rows_to_order_states(res);
         let order_state_map = match order_state_map_result {
             Ok(order_state_map) => order_state_map,
             Err(db_error) => return Err(db_error)
         };
+        if order_state_map.len() > 1 {
+            return Err(DaoError::QueryFailed {
+                description: format!("get_order_by_ext_order_id got {} orders, expected 1", order_state_map.len()),
+            });
+        }
 
         let order_state = match order_state_map.get(ext_order_id) {
             None => {None}
@@ -238,18 +345,17 @@ impl<'b> DaoTransaction<'b> {
             Ok(x) => x,
             Err(db_error) => { return Err(gen_dao_error("get_order_by_client_order_id", db_error)); }
         };
-        if res.len() > 1 {
-            if res.len() > 1 {
-                return Err(DaoError::QueryFailed {
-                    description: format!("get_order_by_client_order_id got {} rows, expected 1", res.len()),
-                });
-            }
-        }
+        // One row per leg
         let order_state_map_result = convert_rows_to_order_states(res);
         let order_state_map = match order_state_map_result {
             Ok(order_state_map) => order_state_map,
             Err(db_error) => return Err(db_error)
         };
+        if order_state_map.len() > 1 {
+            return Err(DaoError::QueryFailed {
+                description: format!("get_order_by_client_order_id got {} orders, expected 1", order_state_map.len()),
+            });
+        }
         let order_state = order_state_map.values().next();
         let order_state = match order_state {
             None => {None}
@@ -257,9 +363,23 @@ impl<'b> DaoTransaction<'b> {
         };
         Ok(order_state)
     }
+
+    pub(crate) async fn get_order_by_order_id(&self,
+                                              order_id: i64) -> Result<Option<OrderState>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(ORDER_QUERY);
+        query_string.push_str(" WHERE base.orderId = $1");
+        let res = match self.transaction.query(&query_string,
+                                               &[&order_id]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_order_by_order_id", db_error)); }
+        };
+        let order_state_map = convert_rows_to_order_states(res)?;
+        Ok(order_state_map.into_values().next())
+    }
 }
 
-fn convert_rows_to_order_states(res: Vec<Row>) -> Result<HashMap<String, OrderState>, DaoError> {
+pub(super) fn convert_rows_to_order_states(res: Vec<Row>) -> Result<HashMap<String, OrderState>, DaoError> {
     let mut order_states = HashMap::new();
     for row in res {
         let ext_order_id: String = row.get("extOrderId");
@@ -302,6 +422,24 @@ fn convert_row_to_order_state(row: &Row) -> Result<OrderState, DaoError> {
             })
         }
     };
+    let row_time_in_force = row.get("timeInForce");
+    let time_in_force = match TimeInForce::from_str(row_time_in_force) {
+        Ok(time_in_force) => time_in_force,
+        Err(()) => {
+            return Err(DaoError::ConversionFailed {
+                description: format!("Unknown time in force {}", row_time_in_force)
+            })
+        }
+    };
+    let row_order_type = row.get("orderType");
+    let order_type = match OrderType::from_str(row_order_type) {
+        Ok(order_type) => order_type,
+        Err(()) => {
+            return Err(DaoError::ConversionFailed {
+                description: format!("Unknown order type {}", row_order_type)
+            })
+        }
+    };
     Ok(OrderState {
         order: Order {
             order_id: row.get("orderId"),
@@ -313,17 +451,26 @@ fn convert_row_to_order_state(row: &Row) -> Result<OrderState, DaoError> {
             price: row.get("price"),
             quantity: row.get("quantity"),
             legs: vec![],
+            time_in_force,
+            expire_time: row.get("expireTime"),
+            order_type,
+            stop_price: row.get("stopPrice"),
+            trailing_offset: row.get("trailingOffset"),
         },
         update_time: row.get("updateTime"),
         order_status,
         reject_reason: row.get("rejectReason"),
         version_number: row.get("versionNumber"),
+        filled_quantity: row.get("filledQuantity"),
+        average_fill_price: row.get("averageFillPrice"),
     })
 }
 
 const ORDER_QUERY: &str = "SELECT base.orderId, base.accountId, base.orderNumber, \
 base.extOrderId, base.clientOrderId, base.createTime, base.price, base.quantity, \
+base.timeInForce, base.expireTime, base.orderType, base.stopPrice, base.trailingOffset, \
 state.orderStatus, state.updateTime, state.versionNumber, state.rejectReason, \
+state.filledQuantity, state.averageFillPrice, \
 leg.orderLegId, leg.instrumentId, leg.ratio \
 FROM order_base AS base \
 JOIN order_state AS state ON state.orderId = base.orderId \
@@ -0,0 +1,103 @@
+use crate::entities::outbox::OutboxEntry;
+use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
+use tokio_postgres::Row;
+
+impl<'b> DaoTransaction<'b> {
+    pub async fn save_outbox_entry(&self,
+                                   outbox_entry: &OutboxEntry) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            OUTBOX_SAVE_STATEMENT,
+            &[&outbox_entry.order_id,
+                &outbox_entry.price,
+                &outbox_entry.attempts,
+                &outbox_entry.next_attempt_time,
+                &outbox_entry.last_error,
+                &outbox_entry.create_time,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("save_outbox_entry", db_error)),
+        }
+    }
+
+    pub async fn update_outbox_entry(&self,
+                                     outbox_entry: &OutboxEntry) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            OUTBOX_UPDATE_STATEMENT,
+            &[&outbox_entry.attempts,
+                &outbox_entry.next_attempt_time,
+                &outbox_entry.last_error,
+                &outbox_entry.order_id,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("update_outbox_entry", db_error)),
+        }
+    }
+
+    pub async fn delete_outbox_entry(&self,
+                                     order_id: i64) -> Result<(), DaoError> {
+        match self.transaction.execute(OUTBOX_DELETE_STATEMENT, &[&order_id]).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("delete_outbox_entry", db_error)),
+        }
+    }
+
+    // Locked, so that only one delivery of an entry is under way at a time
+    pub async fn get_outbox_entry_for_update(&self,
+                                             order_id: i64) -> Result<Option<OutboxEntry>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(OUTBOX_QUERY);
+        query_string.push_str("WHERE orderId = $1 FOR UPDATE");
+        match self.transaction.query_opt(&query_string, &[&order_id]).await {
+            Ok(row) => Ok(row.as_ref().map(convert_row_to_outbox_entry)),
+            Err(db_error) => Err(gen_dao_error("get_outbox_entry_for_update", db_error)),
+        }
+    }
+
+    pub async fn get_due_outbox_entries(&self,
+                                        now: i64) -> Result<Vec<OutboxEntry>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(OUTBOX_QUERY);
+        query_string.push_str("WHERE nextAttemptTime <= $1 ORDER BY nextAttemptTime");
+        let rows = match self.transaction.query(&query_string, &[&now]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_due_outbox_entries", db_error)); }
+        };
+        Ok(rows.iter().map(convert_row_to_outbox_entry).collect())
+    }
+}
+
+fn convert_row_to_outbox_entry(row: &Row) -> OutboxEntry {
+    OutboxEntry {
+        order_id: row.get("orderId"),
+        price: row.get("price"),
+        attempts: row.get("attempts"),
+        next_attempt_time: row.get("nextAttemptTime"),
+        last_error: row.get("lastError"),
+        create_time: row.get("createTime"),
+    }
+}
+
+const OUTBOX_SAVE_STATEMENT: &str = "
+INSERT INTO order_outbox \
+(orderId, price, attempts, nextAttemptTime, lastError, createTime) \
+VALUES \
+($1, $2, $3, $4, $5, $6)
+";
+
+const OUTBOX_UPDATE_STATEMENT: &str = "
+UPDATE order_outbox \
+SET attempts = $1, nextAttemptTime = $2, lastError = $3 \
+WHERE orderId = $4
+";
+
+const OUTBOX_DELETE_STATEMENT: &str = "
+DELETE FROM order_outbox \
+WHERE orderId = $1
+";
+
+const OUTBOX_QUERY: &str = "
+SELECT orderId, price, attempts, nextAttemptTime, lastError, createTime \
+FROM order_outbox \
+";
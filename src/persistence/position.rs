@@ -43,6 +43,25 @@ impl<'b> DaoTransaction<'b> {
         Ok(position)
     }
 
+    // Non-flat positions in one instrument across all accounts, with their account keys
+    pub async fn get_open_positions_for_instrument(&self,
+                                                   instrument_id: i64) -> Result<Vec<(String, Position)>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(POSITION_WITH_ACCOUNT_KEY_QUERY);
+        query_string.push_str("WHERE position.instrumentId = $1 AND position.quantity <> 0");
+        let res = match self.transaction.query(&query_string,
+                                               &[&instrument_id]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_open_positions_for_instrument", db_error)); }
+        };
+        Ok(res.into_iter()
+            .map(|row| {
+                let account_key: String = row.get("accountKey");
+                (account_key, convert_row_to_position(row))
+            })
+            .collect())
+    }
+
     pub async fn update_position(&self, 
                                  position: &mut Position) -> Result<(), DaoError> {
         let next_version_number = position.version_number + 1;
@@ -91,7 +110,7 @@ impl<'b> DaoTransaction<'b> {
     }
 }
 
-fn convert_rows_to_positions(rows: Vec<Row>) -> HashMap<i64, Position> {
+pub(super) fn convert_rows_to_positions(rows: Vec<Row>) -> HashMap<i64, Position> {
     let mut positions = HashMap::new();
     for row in rows {
         let position = convert_row_to_position(row);
@@ -127,6 +146,11 @@ SELECT positionId, position.accountId, instrumentId, cost, quantity, closedGain,
 JOIN account on account.accountId = position.accountId \
 ";
 
+const POSITION_WITH_ACCOUNT_KEY_QUERY: &str = "
+SELECT positionId, position.accountId, account.accountKey, instrumentId, cost, quantity, closedGain, updateTime, versionNumber FROM position \
+JOIN account on account.accountId = position.accountId \
+";
+
 const POSITION_UPDATE_STATEMENT: &str = "
 UPDATE position
 SET cost = $1, quantity = $2, closedGain = $3, updateTime = $4, versionNumber = $5 \
@@ -0,0 +1,30 @@
+use crate::entities::reconciliation::ReconciliationAudit;
+use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
+
+impl<'b> DaoTransaction<'b> {
+    pub async fn save_reconciliation_audit(&self,
+                                           mut reconciliation_audit: ReconciliationAudit) -> Result<ReconciliationAudit, DaoError> {
+        let row = match self.transaction.query_one(
+            RECONCILIATION_AUDIT_SAVE_STATEMENT,
+            &[&reconciliation_audit.exchange_id,
+                &reconciliation_audit.order_id,
+                &reconciliation_audit.create_time,
+                &reconciliation_audit.discrepancy,
+                &reconciliation_audit.description,
+            ]
+        ).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("save_reconciliation_audit", db_error)); }
+        };
+        reconciliation_audit.reconciliation_audit_id = row.get("reconciliationAuditId");
+        Ok(reconciliation_audit)
+    }
+}
+
+const RECONCILIATION_AUDIT_SAVE_STATEMENT: &str = "
+INSERT INTO reconciliation_audit \
+(exchangeId, orderId, createTime, discrepancy, description) \
+VALUES \
+($1, $2, $3, $4, $5) \
+RETURNING reconciliationAuditId
+";
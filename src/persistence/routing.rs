@@ -0,0 +1,145 @@
+use crate::entities::routing::{Listing, LogicalInstrument, RoutedChild, RoutedOrder};
+use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
+use std::collections::HashMap;
+use tokio_postgres::Row;
+
+impl<'b> DaoTransaction<'b> {
+    pub async fn save_logical_instrument(&self,
+                                         mut logical_instrument: LogicalInstrument) -> Result<LogicalInstrument, DaoError> {
+        let row = match self.transaction.query_one(
+            LOGICAL_INSTRUMENT_SAVE_STATEMENT,
+            &[&logical_instrument.logical_key,
+                &logical_instrument.symbol,
+                &logical_instrument.description,
+            ]
+        ).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("save_logical_instrument", db_error)); }
+        };
+        logical_instrument.logical_instrument_id = row.get("logicalInstrumentId");
+        for listing in logical_instrument.listings.iter() {
+            match self.transaction.execute(
+                LISTING_SAVE_STATEMENT,
+                &[&logical_instrument.logical_instrument_id,
+                    &listing.instrument_id,
+                    &listing.preference,
+                    &listing.fee_per_unit,
+                ]
+            ).await {
+                Ok(_) => {},
+                Err(db_error) => { return Err(gen_dao_error("save_logical_instrument listing", db_error)); }
+            };
+        }
+        Ok(logical_instrument)
+    }
+
+    pub async fn get_logical_instruments(&self) -> Result<Vec<LogicalInstrument>, DaoError> {
+        let rows = match self.transaction.query(LOGICAL_INSTRUMENT_QUERY, &[]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_logical_instruments", db_error)); }
+        };
+        let mut logical_instruments: HashMap<i32, LogicalInstrument> = HashMap::new();
+        for row in rows.iter() {
+            let logical_instrument_id: i32 = row.get("logicalInstrumentId");
+            logical_instruments.entry(logical_instrument_id)
+                .or_insert_with(|| convert_row_to_logical_instrument(row))
+                .listings.push(convert_row_to_listing(row));
+        }
+        Ok(logical_instruments.into_values().collect())
+    }
+
+    // Any child orders must already be saved. Routing saves the parent first, with no children,
+    // and links each child as it is saved
+    pub async fn save_routed_order(&self,
+                                   mut routed_order: RoutedOrder) -> Result<RoutedOrder, DaoError> {
+        let row = match self.transaction.query_one(
+            ROUTED_ORDER_SAVE_STATEMENT,
+            &[&routed_order.account_id,
+                &routed_order.logical_instrument_id,
+                &routed_order.strategy.to_string(),
+                &routed_order.price,
+                &routed_order.quantity,
+                &routed_order.create_time,
+            ]
+        ).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("save_routed_order", db_error)); }
+        };
+        routed_order.routed_order_id = row.get("routedOrderId");
+        for child in routed_order.children.iter() {
+            self.save_routed_order_child(routed_order.routed_order_id, child).await?;
+        }
+        Ok(routed_order)
+    }
+
+    pub async fn save_routed_order_child(&self,
+                                         routed_order_id: i64,
+                                         child: &RoutedChild) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            ROUTED_ORDER_CHILD_SAVE_STATEMENT,
+            &[&routed_order_id,
+                &child.order_id,
+                &child.instrument_id,
+                &child.quantity,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("save_routed_order_child", db_error)),
+        }
+    }
+}
+
+fn convert_row_to_logical_instrument(row: &Row) -> LogicalInstrument {
+    LogicalInstrument {
+        logical_instrument_id: row.get("logicalInstrumentId"),
+        logical_key: row.get("logicalKey"),
+        symbol: row.get("symbol"),
+        description: row.get("description"),
+        listings: Vec::new(),
+    }
+}
+
+fn convert_row_to_listing(row: &Row) -> Listing {
+    Listing {
+        instrument_id: row.get("instrumentId"),
+        preference: row.get("preference"),
+        fee_per_unit: row.get("feePerUnit"),
+    }
+}
+
+const LOGICAL_INSTRUMENT_SAVE_STATEMENT: &str = "
+INSERT INTO logical_instrument \
+(logicalKey, symbol, description) \
+VALUES \
+($1, $2, $3) \
+RETURNING logicalInstrumentId
+";
+
+const LISTING_SAVE_STATEMENT: &str = "
+INSERT INTO listing \
+(logicalInstrumentId, instrumentId, preference, feePerUnit) \
+VALUES \
+($1, $2, $3, $4)
+";
+
+const LOGICAL_INSTRUMENT_QUERY: &str = "
+SELECT logical.logicalInstrumentId, logicalKey, symbol, description, instrumentId, preference, feePerUnit \
+FROM logical_instrument logical \
+JOIN listing ON listing.logicalInstrumentId = logical.logicalInstrumentId \
+ORDER BY logical.logicalInstrumentId, preference, instrumentId
+";
+
+const ROUTED_ORDER_SAVE_STATEMENT: &str = "
+INSERT INTO routed_order \
+(accountId, logicalInstrumentId, routingStrategy, price, quantity, createTime) \
+VALUES \
+($1, $2, $3, $4, $5, $6) \
+RETURNING routedOrderId
+";
+
+const ROUTED_ORDER_CHILD_SAVE_STATEMENT: &str = "
+INSERT INTO routed_order_child \
+(routedOrderId, orderId, instrumentId, quantity) \
+VALUES \
+($1, $2, $3, $4)
+";
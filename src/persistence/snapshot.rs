@@ -0,0 +1,156 @@
+use crate::entities::account::{Balance, Position};
+use crate::entities::order::OrderState;
+use crate::persistence::balance::convert_row_to_balance;
+use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
+use crate::persistence::orders::convert_rows_to_order_states;
+use crate::persistence::position::convert_rows_to_positions;
+use std::collections::HashMap;
+
+// Snapshots are keyed by date and saving is a no-op for a date that already has one,
+// so the end of day job can safely be re-run.
+impl<'b> DaoTransaction<'b> {
+    pub async fn save_balance_snapshot(&self,
+                                       snapshot_date: &String,
+                                       balance: &Balance,
+                                       create_time: i64) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            BALANCE_SNAPSHOT_SAVE_STATEMENT,
+            &[&balance.account_id,
+                &snapshot_date,
+                &balance.cash,
+                &create_time,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("save_balance_snapshot", db_error)),
+        }
+    }
+
+    pub async fn save_position_snapshot(&self,
+                                        snapshot_date: &String,
+                                        position: &Position,
+                                        create_time: i64) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            POSITION_SNAPSHOT_SAVE_STATEMENT,
+            &[&position.account_id,
+                &snapshot_date,
+                &position.instrument_id,
+                &position.quantity,
+                &position.cost,
+                &position.closed_gain,
+                &create_time,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("save_position_snapshot", db_error)),
+        }
+    }
+
+    pub async fn save_order_state_snapshot(&self,
+                                           snapshot_date: &String,
+                                           order_state: &OrderState) -> Result<(), DaoError> {
+        match self.transaction.execute(
+            ORDER_STATE_SNAPSHOT_SAVE_STATEMENT,
+            &[&order_state.order.order_id,
+                &snapshot_date,
+                &order_state.order_status.to_string(),
+                &order_state.reject_reason,
+                &order_state.filled_quantity,
+                &order_state.average_fill_price,
+                &order_state.update_time,
+                &order_state.version_number,
+            ]
+        ).await {
+            Ok(_) => Ok(()),
+            Err(db_error) => Err(gen_dao_error("save_order_state_snapshot", db_error)),
+        }
+    }
+
+    pub async fn get_balance_snapshot(&self,
+                                      account_key: &String,
+                                      snapshot_date: &String) -> Result<Option<Balance>, DaoError> {
+        let rows = match self.transaction.query(BALANCE_SNAPSHOT_QUERY,
+                                                &[&account_key,
+                                                    &snapshot_date]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_balance_snapshot", db_error)); }
+        };
+        Ok(rows.first().map(convert_row_to_balance))
+    }
+
+    pub async fn get_position_snapshots(&self,
+                                        account_key: &String,
+                                        snapshot_date: &String) -> Result<HashMap<i64, Position>, DaoError> {
+        let rows = match self.transaction.query(POSITION_SNAPSHOT_QUERY,
+                                                &[&account_key,
+                                                    &snapshot_date]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_position_snapshots", db_error)); }
+        };
+        Ok(convert_rows_to_positions(rows))
+    }
+
+    pub async fn get_order_state_snapshots(&self,
+                                           account_key: &String,
+                                           snapshot_date: &String) -> Result<HashMap<String, OrderState>, DaoError> {
+        let rows = match self.transaction.query(ORDER_STATE_SNAPSHOT_QUERY,
+                                                &[&account_key,
+                                                    &snapshot_date]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_order_state_snapshots", db_error)); }
+        };
+        convert_rows_to_order_states(rows)
+    }
+}
+
+const BALANCE_SNAPSHOT_SAVE_STATEMENT: &str = "
+INSERT INTO balance_snapshot \
+(accountId, snapshotDate, cash, createTime) \
+VALUES \
+($1, $2, $3, $4) \
+ON CONFLICT (accountId, snapshotDate) DO NOTHING
+";
+
+const POSITION_SNAPSHOT_SAVE_STATEMENT: &str = "
+INSERT INTO position_snapshot \
+(accountId, snapshotDate, instrumentId, quantity, cost, closedGain, createTime) \
+VALUES \
+($1, $2, $3, $4, $5, $6, $7) \
+ON CONFLICT (accountId, snapshotDate, instrumentId) DO NOTHING
+";
+
+const ORDER_STATE_SNAPSHOT_SAVE_STATEMENT: &str = "
+INSERT INTO order_state_snapshot \
+(orderId, snapshotDate, orderStatus, rejectReason, filledQuantity, averageFillPrice, updateTime, versionNumber) \
+VALUES \
+($1, $2, $3, $4, $5, $6, $7, $8) \
+ON CONFLICT (orderId, snapshotDate) DO NOTHING
+";
+
+// Columns are aliased to match the live tables so the existing row converters apply
+const BALANCE_SNAPSHOT_QUERY: &str = "
+SELECT 0 AS balanceId, snap.accountId, snap.cash, snap.createTime AS updateTime, 0::BIGINT AS versionNumber \
+FROM balance_snapshot AS snap \
+JOIN account ON account.accountId = snap.accountId \
+WHERE account.accountKey = $1 AND snap.snapshotDate = $2 \
+";
+
+const POSITION_SNAPSHOT_QUERY: &str = "
+SELECT snap.positionSnapshotId AS positionId, snap.accountId, snap.instrumentId, snap.cost, snap.quantity, \
+snap.closedGain, snap.createTime AS updateTime, 0::BIGINT AS versionNumber \
+FROM position_snapshot AS snap \
+JOIN account ON account.accountId = snap.accountId \
+WHERE account.accountKey = $1 AND snap.snapshotDate = $2 \
+";
+
+const ORDER_STATE_SNAPSHOT_QUERY: &str = "SELECT base.orderId, base.accountId, base.orderNumber, \
+base.extOrderId, base.clientOrderId, base.createTime, base.price, base.quantity, \
+snap.orderStatus, snap.updateTime, snap.versionNumber, snap.rejectReason, \
+snap.filledQuantity, snap.averageFillPrice, \
+leg.orderLegId, leg.instrumentId, leg.ratio \
+FROM order_state_snapshot AS snap \
+JOIN order_base AS base ON base.orderId = snap.orderId \
+JOIN order_leg AS leg ON leg.orderId = base.orderId \
+JOIN account ON account.accountId = base.accountId \
+WHERE account.accountKey = $1 AND snap.snapshotDate = $2 \
+";
@@ -0,0 +1,134 @@
+use crate::entities::order::{OrderLeg, Trade};
+use crate::money::Money;
+use crate::persistence::dao::{gen_dao_error, DaoError, DaoTransaction};
+use std::collections::HashMap;
+use tokio_postgres::Row;
+
+impl<'b> DaoTransaction<'b> {
+    // Returns None when the execution was already recorded
+    pub async fn save_trade(&self,
+                            mut trade: Trade) -> Result<Option<Trade>, DaoError> {
+        let row_option = match self.transaction.query_opt(
+            TRADE_SAVE_STATEMENT,
+            &[&trade.order_leg.order_leg_id,
+                &trade.exchange_execution_id,
+                &trade.create_time,
+                &trade.price,
+                &trade.quantity,
+                &trade.fee,
+            ]
+        ).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("save_trade", db_error)); }
+        };
+        let row = match row_option {
+            Some(row) => row,
+            None => return Ok(None)
+        };
+        trade.trade_id = row.get("tradeId");
+        Ok(Some(trade))
+    }
+
+    // Newest first. With before_trade_id, only the trades that come after that one in this order
+    pub async fn get_trades(&self,
+                            account_key: &String,
+                            start_time: i64,
+                            end_time: i64,
+                            instrument_id: Option<i64>,
+                            before_trade_id: Option<i64>,
+                            limit: i64) -> Result<Vec<Trade>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(TRADE_QUERY);
+        query_string.push_str("WHERE account.accountKey = $1 ");
+        query_string.push_str(" AND trade.createTime >= $2 AND trade.createTime < $3 ");
+        query_string.push_str(" AND ($4::BIGINT IS NULL OR leg.instrumentId = $4) ");
+        query_string.push_str(" AND ($5::BIGINT IS NULL OR (trade.createTime, trade.tradeId) < \
+            (SELECT cursor.createTime, cursor.tradeId FROM trade AS cursor WHERE cursor.tradeId = $5)) ");
+        query_string.push_str(" ORDER BY trade.createTime DESC, trade.tradeId DESC ");
+        query_string.push_str(" LIMIT $6");
+        let rows = match self.transaction.query(&query_string,
+                                                &[&account_key,
+                                                    &start_time,
+                                                    &end_time,
+                                                    &instrument_id,
+                                                    &before_trade_id,
+                                                    &limit,
+                                                ]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_trades", db_error)); }
+        };
+        Ok(rows.iter().map(convert_row_to_trade).collect())
+    }
+
+    // Net quantity and cost of each account's trades in each instrument since start_time
+    pub async fn get_trade_totals_since(&self,
+                                        start_time: i64) -> Result<HashMap<(i32, i64), (i32, Money)>, DaoError> {
+        let rows = match self.transaction.query(TRADE_TOTALS_QUERY, &[&start_time]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_trade_totals_since", db_error)); }
+        };
+        Ok(rows.iter()
+            .map(|row| ((row.get("accountId"), row.get("instrumentId")), (row.get("quantity"), row.get("cost"))))
+            .collect())
+    }
+
+    pub async fn get_trades_for_order(&self,
+                                      order_id: i64) -> Result<Vec<Trade>, DaoError> {
+        let mut query_string: String = "".to_owned();
+        query_string.push_str(TRADE_QUERY);
+        query_string.push_str("WHERE base.orderId = $1 ");
+        query_string.push_str(" ORDER BY trade.createTime, trade.tradeId ");
+        let rows = match self.transaction.query(&query_string,
+                                                &[&order_id]).await {
+            Ok(x) => x,
+            Err(db_error) => { return Err(gen_dao_error("get_trades_for_order", db_error)); }
+        };
+        Ok(rows.iter().map(convert_row_to_trade).collect())
+    }
+}
+
+fn convert_row_to_trade(row: &Row) -> Trade {
+    Trade {
+        trade_id: row.get("tradeId"),
+        create_time: row.get("createTime"),
+        ext_order_id: row.get("extOrderId"),
+        order_number: row.get("orderNumber"),
+        order_leg: OrderLeg {
+            order_leg_id: row.get("orderLegId"),
+            instrument_id: row.get("instrumentId"),
+            ratio: row.get("ratio"),
+        },
+        exchange_execution_id: row.get("exchangeExecutionId"),
+        price: row.get("price"),
+        quantity: row.get("quantity"),
+        fee: row.get("fee"),
+    }
+}
+
+const TRADE_SAVE_STATEMENT: &str = "
+INSERT INTO trade \
+(orderLegId, exchangeExecutionId, createTime, price, quantity, fee) \
+VALUES \
+($1, $2, $3, $4, $5, $6) \
+ON CONFLICT (orderLegId, exchangeExecutionId) DO NOTHING \
+RETURNING tradeId
+";
+
+const TRADE_QUERY: &str = "
+SELECT trade.tradeId, trade.exchangeExecutionId, trade.createTime, trade.price, trade.quantity, trade.fee, \
+leg.orderLegId, leg.instrumentId, leg.ratio, \
+base.extOrderId, base.orderNumber \
+FROM trade \
+JOIN order_leg AS leg ON leg.orderLegId = trade.orderLegId \
+JOIN order_base AS base ON base.orderId = leg.orderId \
+JOIN account ON account.accountId = base.accountId \
+";
+
+const TRADE_TOTALS_QUERY: &str = "
+SELECT base.accountId, leg.instrumentId, SUM(trade.quantity)::INT AS quantity, SUM(trade.price * trade.quantity) AS cost \
+FROM trade \
+JOIN order_leg AS leg ON leg.orderLegId = trade.orderLegId \
+JOIN order_base AS base ON base.orderId = leg.orderId \
+WHERE trade.createTime >= $1 \
+GROUP BY base.accountId, leg.instrumentId
+";
@@ -2,9 +2,10 @@ use crate::access_control::AccessControl;
 use crate::constants::APPLICATION_JSON;
 use crate::dtos::account::{Position, Privilege};
 use crate::instrument_manager::InstrumentManager;
+use crate::margin::margin_engine::MarginEngine;
 use crate::persistence::dao::Dao;
 use crate::rest_api::base_api;
-use crate::rest_api::base_api::log_dao_error_and_return_500;
+use crate::rest_api::base_api::{log_anyhow_error_and_return_500, log_dao_error_and_return_500};
 use actix_session::Session;
 use actix_web::web::{Path, ThinData};
 use actix_web::{HttpRequest, HttpResponse};
@@ -49,7 +50,7 @@ pub async fn get_positions(dao: ThinData<Dao>,
     };
     let mut rest_api_positions = HashMap::new();
     for position in positions.values() {
-        let rest_api_position = match position.to_rest_api_position(account_key, &instrument_manager) {
+        let rest_api_position = match position.to_marked_rest_api_position(account_key, &instrument_manager) {
             Ok(rest_api_position) => rest_api_position,
             Err(y) => {
                 error!("get_positions error: {}", y);
@@ -103,4 +104,38 @@ pub async fn get_balance(dao: ThinData<Dao>,
                 .finish()
         }
     }
-}
\ No newline at end of file
+}
+#[get("/accounts/{account_key}/margin")]
+pub async fn get_margin(dao: ThinData<Dao>,
+                        access_control: ThinData<AccessControl>,
+                        margin_engine: ThinData<MarginEngine>,
+                        session: Session,
+                        path: Path<String>) -> HttpResponse {
+    let account_key = path.into_inner();
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Read) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let margin_summary = match margin_engine.compute_for_account(&txn, &account_key).await {
+        Ok(margin_summary) => margin_summary,
+        Err(margin_error) => return log_anyhow_error_and_return_500(margin_error),
+    };
+    match txn.rollback().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(margin_summary.to_rest_api_margin(&account_key))
+}
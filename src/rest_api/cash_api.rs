@@ -0,0 +1,204 @@
+use crate::access_control::AccessControl;
+use crate::constants::{ACCOUNT_UPDATE_QUEUE_NAME, APPLICATION_JSON};
+use crate::dtos::account::Privilege;
+use crate::dtos::ledger::{CashMovement, Journal, JournalType, LedgerAccount};
+use crate::dtos::order::is_order_status_viable;
+use crate::entities;
+use crate::entities::order::OrderState;
+use crate::money::Money;
+use crate::persistence::dao::Dao;
+use crate::rest_api::base_api::{log_anyhow_error_and_return_500, log_dao_error_and_return_500};
+use crate::time::current_time_millis;
+use crate::trade_handling::updates::AccountUpdate;
+use crate::vetting::risk_vetter::open_buy_notional;
+use crate::websockets::server::WebSocketServer;
+use actix_session::Session;
+use actix_web::web::{Json, Path, Query, ThinData};
+use actix_web::HttpResponse;
+use log::info;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize)]
+pub struct LedgerQuery {
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+}
+
+#[post("/accounts/{account_key}/deposits")]
+pub async fn deposit(dao: ThinData<Dao>,
+                     access_control: ThinData<AccessControl>,
+                     mut web_socket_server: ThinData<WebSocketServer>,
+                     session: Session,
+                     path: Path<String>,
+                     cash_movement: Json<CashMovement>,
+) -> HttpResponse {
+    info!("deposit called");
+    let account_key = path.into_inner();
+
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Withdraw) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    if cash_movement.amount <= Money::ZERO {
+        return HttpResponse::BadRequest().json("amount must be positive");
+    }
+    let description = cash_movement.description.clone().unwrap_or_else(|| "Deposit".to_string());
+    post_cash_movement(&dao, &mut web_socket_server, &account_key, JournalType::Deposit, cash_movement.amount, description, false).await
+}
+
+#[post("/accounts/{account_key}/withdrawals")]
+pub async fn withdraw(dao: ThinData<Dao>,
+                      access_control: ThinData<AccessControl>,
+                      mut web_socket_server: ThinData<WebSocketServer>,
+                      session: Session,
+                      path: Path<String>,
+                      cash_movement: Json<CashMovement>,
+) -> HttpResponse {
+    info!("withdraw called");
+    let account_key = path.into_inner();
+
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Withdraw) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    if cash_movement.amount <= Money::ZERO {
+        return HttpResponse::BadRequest().json("amount must be positive");
+    }
+    let description = cash_movement.description.clone().unwrap_or_else(|| "Withdrawal".to_string());
+    post_cash_movement(&dao, &mut web_socket_server, &account_key, JournalType::Withdrawal, -cash_movement.amount, description, false).await
+}
+
+#[get("/accounts/{account_key}/ledger")]
+pub async fn get_ledger(dao: ThinData<Dao>,
+                        access_control: ThinData<AccessControl>,
+                        session: Session,
+                        path: Path<String>,
+                        ledger_query: Query<LedgerQuery>,
+) -> HttpResponse {
+    info!("get_ledger called");
+    let account_key = path.into_inner();
+
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Read) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let start_time = ledger_query.start_time.unwrap_or(0);
+    let end_time = ledger_query.end_time.unwrap_or_else(|| current_time_millis() + 1);
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let account = match txn.get_account_by_account_key(&account_key).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let journals = match txn.get_journals(&account_key, start_time, end_time).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.rollback().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+
+    let rest_api_journals: Vec<Journal> = journals.iter()
+        .map(|journal| journal.to_rest_api_journal(account_key.as_str(), account.account_id))
+        .collect();
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(rest_api_journals)
+}
+
+pub(crate) fn contra_account_for(journal_type: &JournalType) -> Option<LedgerAccount> {
+    match journal_type {
+        JournalType::Deposit | JournalType::Withdrawal => Some(LedgerAccount::ExternalFunds),
+        JournalType::Fee => Some(LedgerAccount::FeeIncome),
+        JournalType::Adjustment => Some(LedgerAccount::Adjustments),
+        JournalType::InitialFunding | JournalType::Execution => None,
+    }
+}
+
+// Posts a signed movement of customer cash against the contra account for the
+// journal type and publishes the new balance to the account's subscribers
+pub(crate) async fn post_cash_movement(dao: &Dao,
+                                       web_socket_server: &mut ThinData<WebSocketServer>,
+                                       account_key: &String,
+                                       journal_type: JournalType,
+                                       amount: Money,
+                                       description: String,
+                                       allow_negative_cash: bool) -> HttpResponse {
+    let contra_account = match contra_account_for(&journal_type) {
+        Some(contra_account) => contra_account,
+        None => return HttpResponse::BadRequest().json(format!("journal type {} cannot be posted directly", journal_type)),
+    };
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let mut balance = match txn.get_balance_for_update(account_key).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    if !allow_negative_cash && amount < Money::ZERO {
+        // Cash held for working buy orders is not available to withdraw
+        let orders = match txn.get_orders(account_key).await {
+            Ok(x) => x,
+            Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+        };
+        let viable_orders: HashMap<String, OrderState> = orders.into_iter()
+            .filter(|(_, order_state)| is_order_status_viable(&order_state.order_status))
+            .collect();
+        let available_cash = balance.cash - open_buy_notional(&viable_orders);
+        if available_cash + amount < Money::ZERO {
+            return HttpResponse::PreconditionFailed().json(format!("Insufficient cash: {:.2} requested, {:.2} available", -amount, available_cash));
+        }
+    }
+
+    let journal = entities::ledger::Journal::cash_movement(journal_type,
+                                                           balance.account_id,
+                                                           amount,
+                                                           contra_account,
+                                                           description,
+                                                           current_time_millis());
+    let journal = match txn.post_journal(&mut balance, journal).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.commit().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+
+    let account_update = AccountUpdate {
+        balance: Some(balance.to_rest_api_balance(account_key.as_str())),
+        position: None,
+        trade: None,
+        order_state: None,
+        margin_call: None,
+    };
+    web_socket_server.send_account_message(account_key.as_str(), ACCOUNT_UPDATE_QUEUE_NAME, &account_update);
+
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(journal.to_rest_api_journal(account_key.as_str(), balance.account_id))
+}
@@ -0,0 +1,132 @@
+use crate::access_control::AccessControl;
+use crate::constants::APPLICATION_JSON;
+use crate::dtos::market_data::{Bar, BarInterval};
+use crate::instrument_manager::InstrumentManager;
+use crate::persistence::dao::Dao;
+use crate::rest_api::base_api::{log_anyhow_error_and_return_500, log_dao_error_and_return_500};
+use crate::time::current_time_millis;
+use crate::websockets::server::WebSocketServer;
+use actix_session::Session;
+use actix_web::web::{Path, Query, ThinData};
+use actix_web::HttpResponse;
+use log::info;
+use serde::Deserialize;
+use std::str::FromStr;
+
+const DEFAULT_BAR_LIMIT: i64 = 500;
+const MAX_BAR_LIMIT: i64 = 5000;
+
+#[derive(Debug, Deserialize)]
+pub struct BarQuery {
+    pub interval: Option<String>,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[get("/markets/{instrument_key}/depth")]
+pub async fn get_depth(instrument_manager: ThinData<InstrumentManager>,
+                       access_control: ThinData<AccessControl>,
+                       web_socket_server: ThinData<WebSocketServer>,
+                       session: Session,
+                       path: Path<String>) -> HttpResponse {
+    info!("get_depth called");
+    get_snapshot(&instrument_manager, &access_control, &web_socket_server, &session, &path.into_inner(), "depth")
+}
+
+#[get("/markets/{instrument_key}/last_trade")]
+pub async fn get_last_trade(instrument_manager: ThinData<InstrumentManager>,
+                            access_control: ThinData<AccessControl>,
+                            web_socket_server: ThinData<WebSocketServer>,
+                            session: Session,
+                            path: Path<String>) -> HttpResponse {
+    info!("get_last_trade called");
+    get_snapshot(&instrument_manager, &access_control, &web_socket_server, &session, &path.into_inner(), "last_trade")
+}
+
+// Snapshots are the messages retained for the STOMP topic of the same name
+fn get_snapshot(instrument_manager: &InstrumentManager,
+                access_control: &AccessControl,
+                web_socket_server: &WebSocketServer,
+                session: &Session,
+                instrument_key: &String,
+                scope: &str) -> HttpResponse {
+    let allowed = match access_control.is_allowed(session) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    match instrument_manager.get_instrument_by_key(instrument_key) {
+        Ok(Some(_)) => {},
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(instrument_error) => return log_anyhow_error_and_return_500(instrument_error),
+    };
+    let destination = format!("/markets/{}/{}", instrument_key, scope);
+    match web_socket_server.get_retained_message(&destination) {
+        Ok(Some(body)) => HttpResponse::Ok()
+            .content_type(APPLICATION_JSON)
+            .body(body),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(retained_error) => log_anyhow_error_and_return_500(retained_error),
+    }
+}
+
+#[get("/markets/{instrument_key}/bars")]
+pub async fn get_bars(dao: ThinData<Dao>,
+                      instrument_manager: ThinData<InstrumentManager>,
+                      access_control: ThinData<AccessControl>,
+                      session: Session,
+                      path: Path<String>,
+                      bar_query: Query<BarQuery>) -> HttpResponse {
+    info!("get_bars called");
+    let instrument_key = path.into_inner();
+
+    let allowed = match access_control.is_allowed(&session) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let instrument = match instrument_manager.get_instrument_by_key(&instrument_key) {
+        Ok(Some(instrument)) => instrument,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(instrument_error) => return log_anyhow_error_and_return_500(instrument_error),
+    };
+    let interval = match &bar_query.interval {
+        Some(interval) => match BarInterval::from_str(interval) {
+            Ok(interval) => interval,
+            Err(()) => return HttpResponse::BadRequest().json(format!("unknown bar interval {}", interval)),
+        },
+        None => BarInterval::OneMinute,
+    };
+    let start_time = bar_query.start_time.unwrap_or(0);
+    let end_time = bar_query.end_time.unwrap_or_else(|| current_time_millis() + 1);
+    let limit = bar_query.limit.unwrap_or(DEFAULT_BAR_LIMIT).clamp(1, MAX_BAR_LIMIT);
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let bars = match txn.get_bars(instrument.instrument_id, interval, start_time, end_time, limit).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.rollback().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+
+    let rest_api_bars: Vec<Bar> = bars.iter()
+        .map(|bar| bar.to_rest_api_bar(instrument_key.clone()))
+        .collect();
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(rest_api_bars)
+}
@@ -1,6 +1,147 @@
 pub(crate) mod order_api;
+pub(crate) mod order_group_api;
 pub(crate) mod balance_position_api;
 
 pub(crate) mod instrument_api;
+pub(crate) mod market_data_api;
 pub(crate) mod base_api;
 pub(crate) mod account_api;
+pub(crate) mod trade_api;
+pub(crate) mod statement_api;
+pub(crate) mod cash_api;
+pub(crate) mod routing_api;
+
+#[cfg(test)]
+mod privilege_tests {
+    use crate::access_control::{AccessControl, SESSION_ACCOUNT_MAP_KEY};
+    use crate::dtos::account::{Account, Privilege};
+    use crate::exchange_interface::mock_exchange::MOCK_INSTRUMENT_KEY;
+    use crate::rest_api::order_api;
+    use crate::trade_handling::trading_context::{test_trading_context, TradingContext};
+    use crate::validator::validator::Validator;
+    use crate::vetting::all_pass_vetter::{AllPassVetter, ALL_PASS_VETTER};
+    use crate::vetting::vetter_registry::VetterRegistry;
+    use actix_session::SessionExt;
+    use actix_web::dev::Service;
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::web::ThinData;
+    use actix_web::{test, App};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use strum::IntoEnumIterator;
+
+    struct Broker {
+        trading_context: TradingContext,
+        vetter_registry: VetterRegistry,
+        account_key: String,
+    }
+
+    async fn broker() -> Option<Broker> {
+        let (trading_context, account_key) = test_trading_context().await?;
+        let mut vetter_registry = VetterRegistry::new(ALL_PASS_VETTER);
+        vetter_registry.register(Arc::new(AllPassVetter::new()));
+        Some(Broker { trading_context, vetter_registry, account_key })
+    }
+
+    impl Broker {
+        async fn call(&self,
+                      privileges: Vec<Privilege>,
+                      method: Method,
+                      uri: &str,
+                      body: Option<String>) -> StatusCode {
+            let mut accounts = HashMap::new();
+            accounts.insert(self.account_key.clone(), Account {
+                account_key: self.account_key.clone(),
+                account_number: "1".to_string(),
+                account_name: "name".to_string(),
+                nickname: "nickname".to_string(),
+                privileges,
+            });
+            let TradingContext { dao, web_socket_server, instrument_manager } = self.trading_context.clone();
+            let app = test::init_service(
+                App::new()
+                    .app_data(ThinData(self.trading_context.clone()))
+                    .app_data(ThinData(Validator::new(instrument_manager.clone())))
+                    .app_data(ThinData(instrument_manager))
+                    .app_data(ThinData(dao))
+                    .app_data(ThinData(web_socket_server))
+                    .app_data(ThinData(AccessControl::new()))
+                    .app_data(ThinData(self.vetter_registry.clone()))
+                    .wrap_fn(move |req, srv| {
+                        req.get_session().insert(SESSION_ACCOUNT_MAP_KEY, &accounts).unwrap();
+                        srv.call(req)
+                    })
+                    .service(order_api::preview_order)
+                    .service(order_api::submit_order)
+                    .service(order_api::submit_orders)
+                    .service(order_api::cancel_order)
+                    .service(order_api::cancel_orders)
+                    .service(order_api::replace_order)
+            ).await;
+
+            let mut request = test::TestRequest::default()
+                .method(method)
+                .uri(uri);
+            if let Some(body) = body {
+                request = request.insert_header(("content-type", "application/json"))
+                    .set_payload(body);
+            }
+            test::call_service(&app, request.to_request()).await.status()
+        }
+
+        // A working bid for the cancel and replace routes to act on
+        async fn rest_bid(&self,
+                          ext_order_id: &str) {
+            let status = self.call(Privilege::iter().collect(), Method::POST, &format!("/accounts/{}/orders", self.account_key),
+                                   Some(order_body(ext_order_id, 10, 1))).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+    }
+
+    fn order_body(ext_order_id: &str,
+                  price: i32,
+                  quantity: i32) -> String {
+        format!("{{\"ext_order_id\": \"{}\", \"price\": {}, \"quantity\": {}, \"legs\": [{{\"instrument_key\": \"{}\", \"ratio\": 1}}]}}",
+                ext_order_id, price, quantity, MOCK_INSTRUMENT_KEY)
+    }
+
+    // Each route is called once by an actor holding every privilege but the one it needs, and once by
+    // an actor holding only that one. Cancel and replace each get a freshly rested bid to act on
+    #[test]
+    async fn test_order_route_privilege_matrix() {
+        let Some(broker) = broker().await else { return };
+        let account_key = broker.account_key.clone();
+        let routes = vec![
+            ("preview", Method::POST, format!("/accounts/{}/previewOrder", account_key), Some(order_body("preview", 10, 1)), Privilege::Submit),
+            ("submit", Method::POST, format!("/accounts/{}/orders", account_key), Some(order_body("submit", 10, 1)), Privilege::Submit),
+            ("batch", Method::POST, format!("/accounts/{}/orders/batch", account_key), Some(format!("[{}]", order_body("batch", 10, 1))), Privilege::Submit),
+            ("replace", Method::PUT, format!("/accounts/{}/orders/replace", account_key), Some(order_body("replacement", 10, 2)), Privilege::Submit),
+            ("cancel", Method::DELETE, format!("/accounts/{}/orders/cancel", account_key), None, Privilege::Cancel),
+            ("mass-cancel", Method::DELETE, format!("/accounts/{}/orders", account_key), None, Privilege::Cancel),
+        ];
+
+        for (name, method, uri, body, required_privilege) in routes {
+            if name == "replace" || name == "cancel" {
+                broker.rest_bid(name).await;
+            }
+            let without: Vec<Privilege> = Privilege::iter().filter(|privilege| *privilege != required_privilege).collect();
+            assert_eq!(broker.call(without, method.clone(), &uri, body.clone()).await, StatusCode::FORBIDDEN,
+                       "{} without {}", name, required_privilege);
+            assert_eq!(broker.call(vec![required_privilege.clone()], method, &uri, body).await, StatusCode::OK,
+                       "{} with {}", name, required_privilege);
+        }
+    }
+
+    // An offer against the account's own resting bid quotes both sides of the instrument
+    #[test]
+    async fn test_two_sided_quote_requires_make_markets() {
+        let Some(broker) = broker().await else { return };
+        broker.rest_bid("bid").await;
+        let uri = format!("/accounts/{}/orders", broker.account_key);
+
+        assert_eq!(broker.call(vec![Privilege::Submit], Method::POST, &uri, Some(order_body("offer", 11, -1))).await,
+                   StatusCode::PRECONDITION_FAILED);
+        assert_eq!(broker.call(vec![Privilege::Submit, Privilege::MakeMarkets], Method::POST, &uri, Some(order_body("offer", 11, -1))).await,
+                   StatusCode::OK);
+    }
+}
@@ -1,29 +1,38 @@
 use crate::access_control::AccessControl;
-use crate::constants::{ACCOUNT_UPDATE_QUEUE_NAME, APPLICATION_JSON};
+use crate::constants::APPLICATION_JSON;
 use actix_session::Session;
-use actix_web::web::{Json, Path, ThinData};
+use actix_web::web::{Json, Path, Query, ThinData};
 use actix_web::HttpResponse;
 use anyhow::Error;
-use deadpool_postgres::Object;
 use log::{error, info, warn};
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::string::ToString;
 use uuid::Uuid;
 
-use crate::converters::order_converters::order_status_to_rest_api_order_status;
 use crate::dtos::account::Privilege;
-use crate::dtos::exchange::InstrumentStatus;
-use crate::dtos::order::{is_order_status_viable, Order, OrderState, OrderStatus, VettingResult};
-use crate::entities::account::Position;
+use crate::dtos::order::{is_order_status_viable, BatchOrderResult, LegPreview, Order, OrderPreview, OrderState, OrderStatus, VettingResult};
 use crate::instrument_manager::InstrumentManager;
-use crate::persistence::dao::{Dao, DaoError};
+use crate::money::Money;
+use crate::persistence::dao::Dao;
 use crate::rest_api::base_api::{log_anyhow_error_and_return_500, log_dao_error_and_return_500};
-use crate::time::current_time_millis;
-use crate::validator::validator::Validator;
-use crate::vetting::all_pass_vetter::AllPassVetter;
+use crate::trade_handling::order_actions::{cancel_order_state, send_order_state, submit_pending_orders};
+use crate::trade_handling::order_entry;
+use crate::trade_handling::order_entry::{check_order, save_new_order, OrderEntryError};
+use crate::trade_handling::order_groups::handle_group_update;
+use crate::trade_handling::trading_context::TradingContext;
+use crate::validator::validator::{normalize_leg_ratios, Validator};
+use crate::vetting::vetter_registry::VetterRegistry;
 use crate::websockets::server::WebSocketServer;
-use crate::{dtos, entities, exchange_interface};
+use crate::entities;
+
+const MAX_BATCH_ORDERS: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct CancelOrdersQuery {
+    pub instrument_key: Option<String>,
+}
 
 #[get("/accounts/{account_key}/orders")]
 pub async fn get_orders(dao: ThinData<Dao>,
@@ -120,204 +129,270 @@ pub async fn get_order(dao: ThinData<Dao>,
 
 
 #[post("/accounts/{account_key}/previewOrder")]
-pub async fn preview_order(dao: ThinData<Dao>,
+pub async fn preview_order(trading_context: ThinData<TradingContext>,
                            access_control: ThinData<AccessControl>,
                            session: Session,
                            validator: ThinData<Validator>,
-                           vetter: ThinData<AllPassVetter>,
+                           vetter_registry: ThinData<VetterRegistry>,
                            path: Path<(String)>,
-                           mut rest_api_order: Json<Order>) -> HttpResponse {
+                           rest_api_order: Json<Order>) -> HttpResponse {
+    let TradingContext { dao, instrument_manager, .. } = trading_context.0;
     let account_key = path.into_inner();
-    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Read) {
+    let mut rest_api_order = rest_api_order.into_inner();
+    normalize_leg_ratios(&mut rest_api_order, &instrument_manager);
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Submit) {
         Ok(allowed) => allowed,
         Err(error) => return log_anyhow_error_and_return_500(error)
     };
     if !allowed {
         return HttpResponse::Forbidden().finish();
     }
-    let check_result = match check_order(&dao, vetter, validator, &mut rest_api_order, &account_key).await {
+    let may_make_markets: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::MakeMarkets) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    let mut check_result = match check_order(&dao, &vetter_registry, &validator, &rest_api_order, &account_key, may_make_markets, None).await {
         Ok(check_result) => check_result,
         Err(error) => return log_anyhow_error_and_return_500(error)
     };
-    
-    HttpResponse::Ok().json(check_result)
+    let legs = match preview_legs(&instrument_manager, &rest_api_order) {
+        Ok(legs) => legs,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if check_result.pass {
+        check_result.estimated_commission = match estimate_commission(&dao, &instrument_manager, &account_key, &legs).await {
+            Ok(estimated_commission) => estimated_commission,
+            Err(error) => return log_anyhow_error_and_return_500(error)
+        };
+    }
+
+    HttpResponse::Ok().json(OrderPreview {
+        check_result,
+        net_amount: rest_api_order.price * rest_api_order.quantity,
+        legs,
+    })
+}
+
+fn preview_legs(instrument_manager: &InstrumentManager,
+                rest_api_order: &Order) -> Result<Vec<LegPreview>, Error> {
+    let single_leg = rest_api_order.legs.len() == 1;
+    let mut legs = Vec::new();
+    for leg in rest_api_order.legs.iter() {
+        let instrument = match instrument_manager.get_instrument_by_key(leg.instrument_key.as_str())? {
+            Some(instrument) => instrument,
+            None => return Err(anyhow::anyhow!("Unable to find instrument for key {}", leg.instrument_key))
+        };
+        let quantity = rest_api_order.quantity * leg.ratio;
+        let price = if single_leg {
+            Some(rest_api_order.price)
+        } else {
+            instrument_manager.get_valuation_service().mark_price(instrument.instrument_id)?
+        };
+        legs.push(LegPreview {
+            instrument_key: leg.instrument_key.clone(),
+            ratio: leg.ratio,
+            quantity,
+            price,
+            notional: price.map(|price| price * quantity),
+        });
+    }
+    Ok(legs)
 }
 
 #[post("/accounts/{account_key}/orders")]
-pub async fn submit_order(dao: ThinData<Dao>,
-                          instrument_manager: ThinData<InstrumentManager>,
+pub async fn submit_order(mut trading_context: ThinData<TradingContext>,
                           access_control: ThinData<AccessControl>,
                           session: Session,
-                          vetter: ThinData<AllPassVetter>,
+                          vetter_registry: ThinData<VetterRegistry>,
                           validator: ThinData<Validator>,
-                          mut web_socket_server: ThinData<WebSocketServer>,
                           path: Path<(String)>,
-                          mut rest_api_order: Json<Order>) -> HttpResponse {
+                          rest_api_order: Json<Order>) -> HttpResponse {
     info!("submit_order called");
 
     let account_key = path.into_inner();
 
-    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Read) {
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Submit) {
         Ok(allowed) => allowed,
         Err(error) => return log_anyhow_error_and_return_500(error)
     };
     if !allowed {
         return HttpResponse::Forbidden().finish();
     }
-
-    let check_result = match check_order(&dao, vetter, validator, &mut rest_api_order, &account_key).await {
-        Ok(check_result) => check_result,
+    let may_make_markets: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::MakeMarkets) {
+        Ok(allowed) => allowed,
         Err(error) => return log_anyhow_error_and_return_500(error)
     };
-    if !check_result.pass {
-        return HttpResponse::PreconditionFailed().json(check_result)
+
+    match order_entry::submit_order(&mut trading_context, &vetter_registry, &validator,
+                                    &account_key, may_make_markets, rest_api_order.into_inner()).await {
+        Ok(order_state) => order_state_response(&order_state, &account_key, &trading_context.instrument_manager),
+        Err(entry_error) => order_entry_error_response(entry_error, &account_key, &trading_context.instrument_manager),
     }
+}
 
-    let first_leg_instrument_key = match rest_api_order.legs.first() {
-        Some(leg0) => leg0.instrument_key.clone(),
-        None => return HttpResponse::PreconditionFailed().json("no order legs")
-    };
+// Each order is checked and saved in turn, so later orders are vetted against the earlier ones.
+// The saved Pending orders then go to each exchange in a single request. An order that fails
+// only gets an error in its own result; the rest of the batch carries on
+#[post("/accounts/{account_key}/orders/batch")]
+pub async fn submit_orders(trading_context: ThinData<TradingContext>,
+                           access_control: ThinData<AccessControl>,
+                           session: Session,
+                           vetter_registry: ThinData<VetterRegistry>,
+                           validator: ThinData<Validator>,
+                           path: Path<String>,
+                           rest_api_orders: Json<Vec<Order>>) -> HttpResponse {
+    info!("submit_orders called");
+    let TradingContext { dao, mut web_socket_server, instrument_manager } = trading_context.0;
 
-    let instrument_result = instrument_manager.get_instrument_by_key(&first_leg_instrument_key);
-    let instrument_option = match instrument_result {
-        Ok(instrument_option) => instrument_option,
-        Err(instrument_error) => {
-            error!("Could not get instrument: {}", instrument_error);
-            return HttpResponse::PreconditionFailed().finish()
-        }
+    let account_key = path.into_inner();
 
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Submit) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
     };
-    let instrument = match instrument_option {
-        Some(instrument) => instrument,
-        None => {
-            return HttpResponse::PreconditionFailed().json(format!("instrument {} is unknown", first_leg_instrument_key))
-        }
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let may_make_markets: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::MakeMarkets) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
     };
 
-    let ext_order_id = rest_api_order.ext_order_id.clone().unwrap_or_else(|| Uuid::new_v4().simple().to_string());
-    rest_api_order.ext_order_id = Some(ext_order_id);
-    rest_api_order.account_key = Some(account_key.clone());
-
-    let mut db_connection = match dao.get_connection().await {
-        Ok(x) => x,
-        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
-    };
-    let txn = match dao.begin(&mut db_connection).await {
-        Ok(x) => x,
-        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    let rest_api_orders = rest_api_orders.into_inner();
+    if rest_api_orders.len() > MAX_BATCH_ORDERS {
+        return HttpResponse::BadRequest().json(format!("a batch may have at most {} orders", MAX_BATCH_ORDERS));
+    }
 
-    };
-    let account = match txn.get_account_by_account_key(&account_key.clone()).await {
-        Ok(x) => x,
-        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
-    };
-    let exchange_order_result = rest_api_order.to_exchange_order(&instrument_manager);
-    let exchange_order = match exchange_order_result {
-        Ok(exchange_order) => exchange_order,
-        Err(err) => return log_anyhow_error_and_return_500(err)
-    };
-    let entities_order_result = rest_api_order.to_entities_order(&account, exchange_order.client_order_id.clone(), &instrument_manager);
-    let entities_order = match entities_order_result {
-        Ok(entities_order) => entities_order,
-        Err(err) => return log_anyhow_error_and_return_500(err)
-    };
-    let mut order_state = entities::order::OrderState {
-        update_time: current_time_millis(),
-        order_status: OrderStatus::Pending,
-        reject_reason: None,
-        order: entities_order,
-        version_number: 0,
-    };
-
-    match instrument.status {
-        InstrumentStatus::Active => {}
-        InstrumentStatus::Inactive => {
-            order_state.order_status = OrderStatus::Rejected;
-            order_state.reject_reason = Some("Instrument is inactive".to_string());
+    let mut results = Vec::new();
+    let mut pending_indexes = Vec::new();
+    let mut pending_order_states = Vec::new();
+    for mut rest_api_order in rest_api_orders {
+        normalize_leg_ratios(&mut rest_api_order, &instrument_manager);
+        let ext_order_id = rest_api_order.ext_order_id.clone().unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+        rest_api_order.ext_order_id = Some(ext_order_id.clone());
+        rest_api_order.account_key = Some(account_key.clone());
+
+        let check_result = match check_order(&dao, &vetter_registry, &validator, &rest_api_order, &account_key, may_make_markets, None).await {
+            Ok(check_result) => check_result,
+            Err(error) => {
+                error!("Could not check order {}: {}", ext_order_id, error);
+                results.push(BatchOrderResult {
+                    ext_order_id,
+                    check_result: VettingResult {
+                        pass: false,
+                        reject_reason: None,
+                        estimated_commission: None,
+                    },
+                    order_state: None,
+                    error: Some(error.to_string()),
+                });
+                continue;
+            }
+        };
+        let mut result = BatchOrderResult {
+            ext_order_id,
+            check_result: check_result.clone(),
+            order_state: None,
+            error: None,
+        };
+        if !check_result.pass {
+            results.push(result);
+            continue;
         }
-    }
 
-    if instrument.expiration_time < current_time_millis() {
-        order_state.order_status = OrderStatus::Rejected;
-        order_state.reject_reason = Some("Instrument has expired".to_string());
-    }
+        let instrument = match rest_api_order.legs.first() {
+            Some(leg0) => match instrument_manager.get_instrument_by_key(&leg0.instrument_key) {
+                Ok(Some(instrument)) => instrument,
+                Ok(None) => {
+                    result.error = Some(format!("instrument {} is unknown", leg0.instrument_key));
+                    results.push(result);
+                    continue;
+                },
+                Err(instrument_error) => {
+                    error!("Could not get instrument for order {}: {}", result.ext_order_id, instrument_error);
+                    result.error = Some(instrument_error.to_string());
+                    results.push(result);
+                    continue;
+                },
+            },
+            None => {
+                result.error = Some("no order legs".to_string());
+                results.push(result);
+                continue;
+            }
+        };
 
-    let mut db_connection = match dao.get_connection().await {
-        Ok(x) => x,
-        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
-    };
-    let txn = match dao.begin(&mut db_connection).await {
-        Ok(x) => x,
-        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
-    };
-    order_state = match txn.save_order(order_state).await {
-        Ok(x) => x,
-        Err(dao_error) => {
-            return log_dao_error_and_return_500(dao_error);
-        },
-    };
-    match txn.commit().await {
-        Ok(x) => x,
-        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
-    };
-    match send_order_state(&mut web_socket_server, &instrument_manager, &account_key, &order_state) {
-        Ok(_) => {},
-        Err(send_error) => {
-            warn!("Unable to send order state: {}", send_error);
+        let order_state = match save_new_order(&dao, &instrument_manager, &account_key, &rest_api_order, &instrument).await {
+            Ok(order_state) => order_state,
+            Err(save_error) => {
+                error!("Could not save order {}: {}", result.ext_order_id, save_error);
+                result.error = Some(save_error.to_string());
+                results.push(result);
+                continue;
+            }
+        };
+        match send_order_state(&mut web_socket_server, &instrument_manager, &account_key, &order_state) {
+            Ok(_) => {},
+            Err(send_error) => {
+                warn!("Unable to send order state: {}", send_error);
+            }
+        };
+        if order_state.order_status == OrderStatus::PendingTrigger {
+            match instrument_manager.get_trigger_engine().add(order_state.clone()) {
+                Ok(_) => {},
+                Err(error) => {
+                    error!("Could not hold order {} for its trigger: {}", result.ext_order_id, error);
+                    result.error = Some(error.to_string());
+                },
+            };
         }
-    };
-    if order_state.order_status != OrderStatus::Pending {
-        let rest_api_order_state = match order_state.to_rest_api_order_state(account_key.as_str(), &instrument_manager) {
-            Ok(rest_api_order_state) => rest_api_order_state,
-            Err(convert_error) => return log_anyhow_error_and_return_500(convert_error),
+        if order_state.order_status == OrderStatus::Pending {
+            pending_indexes.push(results.len());
+            pending_order_states.push(order_state.clone());
+        }
+        result.order_state = match order_state.to_rest_api_order_state(account_key.as_str(), &instrument_manager) {
+            Ok(rest_api_order_state) => Some(rest_api_order_state),
+            Err(convert_error) => {
+                error!("Could not convert order {}: {}", result.ext_order_id, convert_error);
+                result.error = Some(convert_error.to_string());
+                None
+            },
         };
-        return HttpResponse::Ok()
-            .content_type(APPLICATION_JSON)
-            .json(rest_api_order_state);
+        results.push(result);
     }
 
-    let exchange_client = match instrument_manager.get_exchange_client_for_instrument(&instrument) {
-        Ok(exchange_client) => exchange_client,
-        Err(instrument_error) => {
-            error!("Could not get exchange for instrument {}: {}", instrument.instrument_id, instrument_error);
-            return HttpResponse::PreconditionFailed().finish()
-        }
-    };
-
-    let exchange_order_state = match exchange_client.submit_order(exchange_order).await {
-        Ok(exchange_order_state) => exchange_order_state,
-        Err(submit_order_error) => {
-            error!("submit_order_error: {}", submit_order_error);
-            return HttpResponse::InternalServerError().finish()
-        },
-    };
-
-    // We'll get async notifications for all status updates other than Rejected
-    if exchange_order_state.order_status == exchange_interface::order::OrderStatus::Rejected {
-        order_state.order_status = OrderStatus::Rejected;
-        order_state.reject_reason = Some("Exchange reject".to_string());
-        order_state.update_time = current_time_millis();
-
-        match update_order_state(dao, &mut db_connection, &mut order_state).await {
-            Ok(_) => {}
-            Err(update_error) => {
-                return log_dao_error_and_return_500(update_error);
+    let submit_results = submit_pending_orders(&dao, &mut web_socket_server, &instrument_manager, &account_key, pending_order_states).await;
+    for (index, submit_result) in pending_indexes.into_iter().zip(submit_results) {
+        match submit_result {
+            Ok(order_state) => match order_state.to_rest_api_order_state(account_key.as_str(), &instrument_manager) {
+                Ok(rest_api_order_state) => results[index].order_state = Some(rest_api_order_state),
+                Err(convert_error) => {
+                    error!("Could not convert order {}: {}", results[index].ext_order_id, convert_error);
+                    results[index].error = Some(convert_error.to_string());
+                }
+            },
+            Err(submit_error) => {
+                error!("Could not submit order {}: {}", results[index].ext_order_id, submit_error);
+                results[index].error = Some(submit_error.to_string());
             }
-        };
-        
-        let _ = send_order_state(&mut web_socket_server, &instrument_manager, &account_key, &order_state);
+        }
     }
 
-    let rest_api_order_state = match order_state.to_rest_api_order_state(account_key.as_str(), &instrument_manager) {
-        Ok(rest_api_order_state) => rest_api_order_state,
-        Err(convert_error) => return log_anyhow_error_and_return_500(convert_error),
-    };
     HttpResponse::Ok()
         .content_type(APPLICATION_JSON)
-        .json(rest_api_order_state)
+        .json(results)
 }
 
-async fn check_order<'a>(dao: &ThinData<Dao>, vetter: ThinData<AllPassVetter>, validator: ThinData<Validator>, rest_api_order: &mut Json<Order>, account_key: &String) -> Result<VettingResult, Error> {
+// Estimates the commission as if every leg filled in full at its own previewed price. A combination's
+// order price is net across its legs, so it says nothing about any one leg; without a price for
+// every leg there is no estimate
+async fn estimate_commission(dao: &Dao,
+                             instrument_manager: &InstrumentManager,
+                             account_key: &String,
+                             legs: &[LegPreview]) -> Result<Option<Money>, Error> {
+    if legs.iter().any(|leg| leg.price.is_none()) {
+        return Ok(None);
+    }
     let mut db_connection = match dao.get_connection().await {
         Ok(x) => x,
         Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error))
@@ -326,44 +401,34 @@ async fn check_order<'a>(dao: &ThinData<Dao>, vetter: ThinData<AllPassVetter>, v
         Ok(x) => x,
         Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error))
     };
-
-    let existing_orders = match txn.get_orders(&account_key).await {
-        Ok(existing_orders) => existing_orders,
-        Err(dao_error) => return Err(anyhow::anyhow!("Could not get orders: {}", dao_error))
-    };
-
-    let orders: HashMap<String, entities::order::OrderState> = existing_orders.iter().filter(|(a, b)| {
-        is_order_status_viable(&b.order_status)
-    }).map(|(k, v)| { return (k.clone(), v.clone()) }).collect();
-
-    let existing_positions = match txn.get_positions(&account_key).await {
-        Ok(positions) => positions,
-        Err(dao_error) => return Err(anyhow::anyhow!("Could not get positions: {}", dao_error))
+    let account = match txn.get_account_by_account_key(account_key).await {
+        Ok(account) => account,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get account: {}", dao_error))
     };
 
-    let positions: HashMap<i64, Position> = existing_positions.iter().filter(|(a, b)| {
-        b.quantity != 0
-    }).map(|(k, v)| { return (k.clone(), v.clone()) }).collect();
+    let mut estimated_commission = Money::ZERO;
+    for (leg, price) in legs.iter().filter_map(|leg| leg.price.map(|price| (leg, price))) {
+        let instrument = match instrument_manager.get_instrument_by_key(leg.instrument_key.as_str())? {
+            Some(instrument) => instrument,
+            None => return Err(anyhow::anyhow!("Unable to find instrument for key {}", leg.instrument_key))
+        };
+        let fee_schedule = match txn.get_fee_schedule_for_account(account.account_id, &instrument.asset_class).await {
+            Ok(fee_schedule) => fee_schedule,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get fee schedule: {}", dao_error))
+        };
+        if let Some(fee_schedule) = fee_schedule {
+            estimated_commission += fee_schedule.fee_for_fill(&instrument.asset_class,
+                                                              price,
+                                                              leg.quantity,
+                                                              estimated_commission);
+        }
+    }
 
     match txn.rollback().await {
         Ok(x) => x,
         Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error))
     };
-
-    let validation_result = match validator.validate_order(&rest_api_order, &orders) {
-        Ok(validation_result) => validation_result,
-        Err(validation_error) => return Err(anyhow::anyhow!("validation error: {}", validation_error))
-    };
-    if !validation_result.pass {
-        return Ok(validation_result);
-    }
-
-    let vetting_result = match vetter.vet_order(&rest_api_order, &orders, &positions).await {
-        Ok(x) => x,
-        Err(vetting_error) => return Err(anyhow::anyhow!("vetting error: {}", vetting_error))
-
-    };
-    Ok(vetting_result)
+    Ok(Some(estimated_commission))
 }
 
 #[delete("/accounts/{account_key}/orders/{ext_order_id}")]
@@ -376,13 +441,49 @@ pub async fn cancel_order(dao: ThinData<Dao>,
     let (account_key, ext_order_id) = path.into_inner();
 
     info!("cancel_order called for ext_order_id {ext_order_id}");
-    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Read) {
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Cancel) {
         Ok(allowed) => allowed,
         Err(error) => return log_anyhow_error_and_return_500(error)
     };
     if !allowed {
         return HttpResponse::Forbidden().finish();
     }
+
+    match order_entry::cancel_order(&dao, &mut web_socket_server, &instrument_manager, &account_key, &ext_order_id).await {
+        Ok(order_state) => order_state_response(&order_state, &account_key, &instrument_manager),
+        Err(entry_error) => order_entry_error_response(entry_error, &account_key, &instrument_manager),
+    }
+}
+
+// Cancels every viable order of the account, or only those with a leg in the given instrument
+#[delete("/accounts/{account_key}/orders")]
+pub async fn cancel_orders(dao: ThinData<Dao>,
+                           mut web_socket_server: ThinData<WebSocketServer>,
+                           access_control: ThinData<AccessControl>,
+                           session: Session,
+                           instrument_manager: ThinData<InstrumentManager>,
+                           path: Path<String>,
+                           cancel_query: Query<CancelOrdersQuery>) -> HttpResponse {
+    let account_key = path.into_inner();
+
+    info!("cancel_orders called");
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Cancel) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let instrument_id = match &cancel_query.instrument_key {
+        Some(instrument_key) => match instrument_manager.get_instrument_by_key(instrument_key) {
+            Ok(Some(instrument)) => Some(instrument.instrument_id),
+            Ok(None) => return HttpResponse::PreconditionFailed().json(format!("instrument {} is unknown", instrument_key)),
+            Err(instrument_error) => return log_anyhow_error_and_return_500(instrument_error),
+        },
+        None => None
+    };
+
     let mut db_connection = match dao.get_connection().await {
         Ok(x) => x,
         Err(dao_error) => return log_dao_error_and_return_500(dao_error),
@@ -391,110 +492,98 @@ pub async fn cancel_order(dao: ThinData<Dao>,
         Ok(x) => x,
         Err(dao_error) => return log_dao_error_and_return_500(dao_error),
     };
-
-    let order_state_option = match txn.get_order_by_ext_order_id(&account_key, &ext_order_id).await {
-        Ok(x) => x,
-        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
-    };
-
-    let mut order_state = match order_state_option {
-        Some(x) => x,
-        None => return HttpResponse::NotFound().finish(),
-    };
-
-    if !is_order_status_viable(&order_state.order_status) {
-        return HttpResponse::PreconditionFailed().finish();
-    }
-
-    order_state.order_status = OrderStatus::PendingCancel;
-    match txn.update_order(&mut order_state).await {
+    let order_states = match txn.get_orders(&account_key).await {
         Ok(x) => x,
         Err(dao_error) => return log_dao_error_and_return_500(dao_error),
     };
-    match txn.commit().await {
+    match txn.rollback().await {
         Ok(x) => x,
         Err(dao_error) => return log_dao_error_and_return_500(dao_error),
     };
-    match send_order_state(&mut web_socket_server, &instrument_manager, &account_key, &order_state) {
-        Ok(_) => {},
-        Err(send_error) => {
-            warn!("Unable to send order state: {}", send_error);
-        }
-    };
 
-    let first_leg_instrument_id = match order_state.order.legs.first() {
-        Some(leg0) => leg0.instrument_id,
-        None => return HttpResponse::PreconditionFailed().json("no order legs")
-    };
-
-    let instrument_result = instrument_manager.get_instrument(first_leg_instrument_id);
-    let instrument_option = match instrument_result {
-        Ok(instrument_option) => instrument_option,
-        Err(instrument_error) => {
-            error!("Could not get instrument: {}", instrument_error);
-            return HttpResponse::PreconditionFailed().finish()
+    let mut api_order_states: HashMap<String, OrderState> = HashMap::new();
+    for (ext_order_id, order_state) in order_states {
+        if !is_order_status_viable(&order_state.order_status) || order_state.order_status == OrderStatus::PendingCancel {
+            continue;
         }
-    };
-    let instrument = match instrument_option {
-        Some(instrument) => instrument,
-        None => {
-            return HttpResponse::PreconditionFailed().json("instrument 0 is unknown")
+        if instrument_id.is_some_and(|instrument_id| !order_state.order.legs.iter().any(|leg| leg.instrument_id == instrument_id)) {
+            continue;
         }
-    };
+        // One failed cancel should not stop the rest
+        let order_state = match cancel_order_state(&dao, &mut web_socket_server, &instrument_manager, &account_key, order_state).await {
+            Ok(order_state) => order_state,
+            Err(cancel_error) => {
+                error!("Could not cancel order {}: {}", ext_order_id, cancel_error);
+                continue;
+            }
+        };
+        tokio::spawn(handle_group_update(dao.0.clone(), web_socket_server.0.clone(), instrument_manager.0.clone(), order_state.clone()));
 
-    let exchange_client = match instrument_manager.get_exchange_client_for_instrument(&instrument) {
-        Ok(exchange_client) => exchange_client,
-        Err(instrument_error) => {
-            error!("Could not get exchange: {}", instrument_error);
-            return HttpResponse::PreconditionFailed().finish()
-        }
-    };
+        let rest_api_order_state = match order_state.to_rest_api_order_state(account_key.as_str(), &instrument_manager) {
+            Ok(rest_api_order_state) => rest_api_order_state,
+            Err(convert_error) => return log_anyhow_error_and_return_500(convert_error),
+        };
+        api_order_states.insert(ext_order_id, rest_api_order_state);
+    }
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(api_order_states)
+}
 
-    let exchange_order_state = match exchange_client.cancel_order(order_state.clone().order.client_order_id).await {
-        Ok(exchange_order_state) => exchange_order_state,
-        Err(cancel_order_error) => {
-            error!("cancel_order_error: {}", cancel_order_error);
-            return HttpResponse::InternalServerError().finish()
-        },
+#[put("/accounts/{account_key}/orders/{ext_order_id}")]
+pub async fn replace_order(mut trading_context: ThinData<TradingContext>,
+                           access_control: ThinData<AccessControl>,
+                           session: Session,
+                           vetter_registry: ThinData<VetterRegistry>,
+                           validator: ThinData<Validator>,
+                           path: Path<(String, String)>,
+                           rest_api_order: Json<Order>) -> HttpResponse {
+    let (account_key, ext_order_id) = path.into_inner();
+
+    info!("replace_order called for ext_order_id {ext_order_id}");
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Submit) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let may_make_markets: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::MakeMarkets) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
     };
 
-    order_state.order_status = order_status_to_rest_api_order_status(exchange_order_state.order_status);
-    order_state.update_time = current_time_millis();
+    match order_entry::replace_order(&mut trading_context, &vetter_registry, &validator,
+                                     &account_key, may_make_markets, &ext_order_id, rest_api_order.into_inner()).await {
+        Ok(order_state) => order_state_response(&order_state, &account_key, &trading_context.instrument_manager),
+        Err(entry_error) => order_entry_error_response(entry_error, &account_key, &trading_context.instrument_manager),
+    }
+}
 
-    match update_order_state(dao, &mut db_connection, &mut order_state).await {
-        Ok(_) => {}
-        Err(update_error) => {
-            error!("Could not update order state: {}", update_error);
-        }
-    };
-    
-    let rest_api_order_state = match send_order_state(&mut web_socket_server, &instrument_manager, &account_key, &order_state) {
+fn order_state_response(order_state: &entities::order::OrderState,
+                        account_key: &str,
+                        instrument_manager: &InstrumentManager) -> HttpResponse {
+    let rest_api_order_state = match order_state.to_rest_api_order_state(account_key, instrument_manager) {
         Ok(rest_api_order_state) => rest_api_order_state,
         Err(convert_error) => return log_anyhow_error_and_return_500(convert_error),
     };
-
     HttpResponse::Ok()
         .content_type(APPLICATION_JSON)
         .json(rest_api_order_state)
 }
 
-async fn update_order_state(dao: ThinData<Dao>, mut db_connection: &mut Object, order_state: &mut entities::order::OrderState) -> Result<(), DaoError> {
-    let txn = dao.begin(&mut db_connection).await?;
-    txn.update_order(order_state).await?;
-    txn.commit().await
+pub(crate) fn order_entry_error_response(entry_error: OrderEntryError,
+                                         account_key: &str,
+                                         instrument_manager: &InstrumentManager) -> HttpResponse {
+    match entry_error {
+        OrderEntryError::Rejected(check_result) => HttpResponse::PreconditionFailed().json(check_result),
+        OrderEntryError::PreconditionFailed(reason) => HttpResponse::PreconditionFailed().json(reason),
+        OrderEntryError::BadRequest(reason) => HttpResponse::BadRequest().json(reason),
+        OrderEntryError::NotFound => HttpResponse::NotFound().finish(),
+        OrderEntryError::Conflict(order_state) => match order_state.to_rest_api_order_state(account_key, instrument_manager) {
+            Ok(rest_api_order_state) => HttpResponse::Conflict().json(rest_api_order_state),
+            Err(convert_error) => log_anyhow_error_and_return_500(convert_error),
+        },
+        OrderEntryError::Failed(error) => log_anyhow_error_and_return_500(error),
+    }
 }
-
-pub fn send_order_state(web_socket_server: &mut ThinData<WebSocketServer>,
-                        instrument_manager: &ThinData<InstrumentManager>,
-                        account_key: &String,
-                        order_state: &entities::order::OrderState) -> Result<OrderState, Error> {
-    let rest_api_order_state = order_state.to_rest_api_order_state(account_key.as_str(), instrument_manager)?;
-    let account_update = crate::trade_handling::updates::AccountUpdate {
-        balance: None,
-        position: None,
-        trade: None,
-        order_state: Some(rest_api_order_state.clone()),
-    };
-    web_socket_server.send_account_message(account_key.as_str(), ACCOUNT_UPDATE_QUEUE_NAME, &account_update);
-    Ok(rest_api_order_state)
-}
\ No newline at end of file
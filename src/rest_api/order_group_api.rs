@@ -0,0 +1,200 @@
+use crate::access_control::AccessControl;
+use crate::constants::APPLICATION_JSON;
+use crate::dtos::account::Privilege;
+use crate::dtos::exchange::InstrumentStatus;
+use crate::dtos::order::{OrderStatus, OrderType};
+use crate::dtos::order_group::{OrderGroup, OrderGroupMemberState, OrderGroupRole, OrderGroupState};
+use crate::entities;
+use crate::money::Money;
+use crate::rest_api::base_api::{log_anyhow_error_and_return_500, log_dao_error_and_return_500};
+use crate::time::current_time_millis;
+use crate::trade_handling::order_actions::{send_order_state, submit_pending_order};
+use crate::trade_handling::order_entry::check_order;
+use crate::trade_handling::order_groups::handle_group_update;
+use crate::trade_handling::outbox::new_outbox_entry;
+use crate::trade_handling::trading_context::TradingContext;
+use crate::validator::validator::{check_order_group, normalize_leg_ratios, Validator};
+use crate::vetting::vetter_registry::VetterRegistry;
+use actix_session::Session;
+use actix_web::web::{Json, Path, ThinData};
+use actix_web::{post, HttpResponse};
+use log::{info, warn};
+use uuid::Uuid;
+
+// Every member is checked on its own, then all are saved together with the group. Bracket
+// children are held as PendingParent until their entry fills.
+#[post("/accounts/{account_key}/order_groups")]
+pub async fn submit_order_group(trading_context: ThinData<TradingContext>,
+                                access_control: ThinData<AccessControl>,
+                                session: Session,
+                                vetter_registry: ThinData<VetterRegistry>,
+                                validator: ThinData<Validator>,
+                                path: Path<String>,
+                                mut rest_api_order_group: Json<OrderGroup>) -> HttpResponse {
+    info!("submit_order_group called");
+    let TradingContext { dao, mut web_socket_server, instrument_manager } = trading_context.0;
+
+    let account_key = path.into_inner();
+
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Submit) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let may_make_markets: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::MakeMarkets) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+
+    for member in rest_api_order_group.members.iter_mut() {
+        normalize_leg_ratios(&mut member.order, &instrument_manager);
+    }
+    if let Some(reject_reason) = check_order_group(&rest_api_order_group) {
+        return HttpResponse::BadRequest().json(reject_reason);
+    }
+
+    for member in rest_api_order_group.members.iter_mut() {
+        member.order.ext_order_id = Some(member.order.ext_order_id.clone().unwrap_or_else(|| Uuid::new_v4().simple().to_string()));
+        member.order.account_key = Some(account_key.clone());
+
+        let check_result = match check_order(&dao, &vetter_registry, &validator, &member.order, &account_key, may_make_markets, None).await {
+            Ok(check_result) => check_result,
+            Err(error) => return log_anyhow_error_and_return_500(error)
+        };
+        if !check_result.pass {
+            return HttpResponse::PreconditionFailed().json(check_result)
+        }
+
+        let instrument = match member.order.legs.first() {
+            Some(leg0) => match instrument_manager.get_instrument_by_key(&leg0.instrument_key) {
+                Ok(Some(instrument)) => instrument,
+                Ok(None) => return HttpResponse::PreconditionFailed().json(format!("instrument {} is unknown", leg0.instrument_key)),
+                Err(instrument_error) => return log_anyhow_error_and_return_500(instrument_error),
+            },
+            None => return HttpResponse::PreconditionFailed().json("no order legs")
+        };
+        if instrument.status == InstrumentStatus::Inactive {
+            return HttpResponse::PreconditionFailed().json(format!("instrument {} is inactive", instrument.instrument_key));
+        }
+        if instrument.expiration_time < current_time_millis() {
+            return HttpResponse::PreconditionFailed().json(format!("instrument {} has expired", instrument.instrument_key));
+        }
+    }
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let account = match txn.get_account_by_account_key(&account_key).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+
+    let mut member_states = Vec::new();
+    for member in rest_api_order_group.members.iter() {
+        let entities_order = match member.order.to_entities_order(&account, Uuid::new_v4().simple().to_string(), &instrument_manager) {
+            Ok(entities_order) => entities_order,
+            Err(err) => return log_anyhow_error_and_return_500(err)
+        };
+        let order_status = match (&member.role, &entities_order.order_type) {
+            (OrderGroupRole::TakeProfit | OrderGroupRole::StopLoss, _) => OrderStatus::PendingParent,
+            (_, OrderType::Limit) => OrderStatus::Pending,
+            _ => OrderStatus::PendingTrigger,
+        };
+        let order_state = entities::order::OrderState {
+            update_time: current_time_millis(),
+            order_status,
+            reject_reason: None,
+            order: entities_order,
+            version_number: 0,
+            filled_quantity: 0,
+            average_fill_price: Money::ZERO,
+        };
+        let order_state = match txn.save_order(order_state).await {
+            Ok(x) => x,
+            Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+        };
+        if order_state.order_status == OrderStatus::Pending {
+            match txn.save_outbox_entry(&new_outbox_entry(order_state.order.order_id, order_state.order.price)).await {
+                Ok(_) => {},
+                Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+            };
+        }
+        member_states.push((member.role.clone(), order_state));
+    }
+
+    let order_group = entities::order_group::OrderGroup {
+        order_group_id: 0,
+        account_id: account.account_id,
+        group_type: rest_api_order_group.group_type.clone(),
+        create_time: current_time_millis(),
+        members: member_states.iter().map(|(role, order_state)| entities::order_group::OrderGroupMember {
+            order_id: order_state.order.order_id,
+            client_order_id: order_state.order.client_order_id.clone(),
+            role: role.clone(),
+        }).collect(),
+    };
+    let order_group = match txn.save_order_group(order_group).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.commit().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+
+    let mut members = Vec::new();
+    for (role, order_state) in member_states {
+        match send_order_state(&mut web_socket_server, &instrument_manager, &account_key, &order_state) {
+            Ok(_) => {},
+            Err(send_error) => {
+                warn!("Unable to send order state: {}", send_error);
+            }
+        };
+        let order_state = match order_state.order_status {
+            OrderStatus::PendingTrigger => {
+                match instrument_manager.get_trigger_engine().add(order_state.clone()) {
+                    Ok(_) => {},
+                    Err(error) => return log_anyhow_error_and_return_500(error)
+                };
+                order_state
+            },
+            OrderStatus::Pending => {
+                let price = order_state.order.price;
+                let order_state = match submit_pending_order(&dao, &mut web_socket_server, &instrument_manager, &account_key, order_state, price).await {
+                    Ok(order_state) => order_state,
+                    Err(submit_error) => return log_anyhow_error_and_return_500(submit_error),
+                };
+                // A rejected entry takes its children with it
+                if order_state.order_status == OrderStatus::Rejected {
+                    handle_group_update(dao.clone(), web_socket_server.clone(), instrument_manager.clone(), order_state.clone()).await;
+                }
+                order_state
+            },
+            _ => order_state,
+        };
+        let rest_api_order_state = match order_state.to_rest_api_order_state(account_key.as_str(), &instrument_manager) {
+            Ok(rest_api_order_state) => rest_api_order_state,
+            Err(convert_error) => return log_anyhow_error_and_return_500(convert_error),
+        };
+        members.push(OrderGroupMemberState {
+            role,
+            order_state: rest_api_order_state,
+        });
+    }
+
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(OrderGroupState {
+            order_group_id: order_group.order_group_id,
+            group_type: order_group.group_type,
+            create_time: order_group.create_time,
+            members,
+        })
+}
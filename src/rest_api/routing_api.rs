@@ -0,0 +1,82 @@
+use crate::access_control::AccessControl;
+use crate::constants::APPLICATION_JSON;
+use crate::dtos::account::Privilege;
+use crate::dtos::routing::RoutedOrderRequest;
+use crate::instrument_manager::InstrumentManager;
+use crate::rest_api::base_api::log_anyhow_error_and_return_500;
+use crate::rest_api::order_api::order_entry_error_response;
+use crate::routing::order_router;
+use crate::trade_handling::trading_context::TradingContext;
+use crate::validator::validator::Validator;
+use crate::vetting::vetter_registry::VetterRegistry;
+use actix_session::Session;
+use actix_web::web::{Json, Path, ThinData};
+use actix_web::HttpResponse;
+use log::{error, info};
+
+#[get("/logical_instruments")]
+pub async fn get_logical_instruments(access_control: ThinData<AccessControl>,
+                                     instrument_manager: ThinData<InstrumentManager>,
+                                     session: Session,) -> HttpResponse {
+    info!("get_logical_instruments called");
+    let allowed = match access_control.is_allowed(&session) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking access: {}", error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let logical_instruments = match instrument_manager.get_order_router().get_all() {
+        Ok(x) => x,
+        Err(get_error) => return log_anyhow_error_and_return_500(get_error),
+    };
+    let mut rest_api_logical_instruments = Vec::new();
+    for logical_instrument in logical_instruments.iter() {
+        match logical_instrument.to_rest_api_logical_instrument(&instrument_manager) {
+            Ok(x) => rest_api_logical_instruments.push(x),
+            Err(convert_error) => return log_anyhow_error_and_return_500(convert_error),
+        };
+    }
+
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(rest_api_logical_instruments)
+}
+
+// Routes a limit order on a logical instrument to one or more of its listings. The response
+// has a result per child order; a rejected child does not fail the others
+#[post("/accounts/{account_key}/routed_orders")]
+pub async fn submit_routed_order(mut trading_context: ThinData<TradingContext>,
+                                 access_control: ThinData<AccessControl>,
+                                 session: Session,
+                                 vetter_registry: ThinData<VetterRegistry>,
+                                 validator: ThinData<Validator>,
+                                 path: Path<String>,
+                                 request: Json<RoutedOrderRequest>) -> HttpResponse {
+    info!("submit_routed_order called");
+
+    let account_key = path.into_inner();
+
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Submit) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+    let may_make_markets: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::MakeMarkets) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+
+    match order_router::route_order(&mut trading_context, &vetter_registry, &validator,
+                                    &account_key, may_make_markets, request.into_inner()).await {
+        Ok(routed_order) => HttpResponse::Ok()
+            .content_type(APPLICATION_JSON)
+            .json(routed_order),
+        Err(entry_error) => order_entry_error_response(entry_error, &account_key, &trading_context.instrument_manager),
+    }
+}
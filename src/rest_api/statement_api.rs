@@ -0,0 +1,66 @@
+use crate::access_control::AccessControl;
+use crate::constants::{APPLICATION_JSON, TEXT_CSV};
+use crate::dtos::account::Privilege;
+use crate::instrument_manager::InstrumentManager;
+use crate::persistence::dao::Dao;
+use crate::rest_api::base_api::log_anyhow_error_and_return_500;
+use crate::statements::statement_builder::build_statement;
+use crate::statements::statement_csv::statement_to_csv;
+use crate::time::parse_date;
+use actix_session::Session;
+use actix_web::web::{Path, Query, ThinData};
+use actix_web::HttpResponse;
+use log::info;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct StatementQuery {
+    pub format: Option<String>,
+}
+
+#[get("/accounts/{account_key}/statements/{date}")]
+pub async fn get_statement(dao: ThinData<Dao>,
+                           instrument_manager: ThinData<InstrumentManager>,
+                           access_control: ThinData<AccessControl>,
+                           session: Session,
+                           path: Path<(String, String)>,
+                           statement_query: Query<StatementQuery>,
+) -> HttpResponse {
+    info!("get_statement called");
+    let (account_key, date) = path.into_inner();
+
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Read) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let csv = match statement_query.format.as_deref() {
+        None | Some("json") => false,
+        Some("csv") => true,
+        Some(format) => return HttpResponse::BadRequest().json(format!("format {} is not supported, use json or csv", format)),
+    };
+    let statement_date = match parse_date(date.as_str()) {
+        Some(statement_date) => statement_date,
+        None => return HttpResponse::BadRequest().json(format!("date {} is not in YYYY-MM-DD format", date)),
+    };
+
+    let statement = match build_statement(&dao, &instrument_manager, &account_key, statement_date).await {
+        Ok(Some(statement)) => statement,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(statement_error) => return log_anyhow_error_and_return_500(statement_error),
+    };
+
+    if csv {
+        HttpResponse::Ok()
+            .content_type(TEXT_CSV)
+            .insert_header(("Content-Disposition", format!("attachment; filename=\"statement-{}-{}.csv\"", account_key, statement.statement_date)))
+            .body(statement_to_csv(&statement))
+    } else {
+        HttpResponse::Ok()
+            .content_type(APPLICATION_JSON)
+            .json(statement)
+    }
+}
@@ -0,0 +1,202 @@
+use crate::access_control::AccessControl;
+use crate::constants::APPLICATION_JSON;
+use crate::dtos::account::Privilege;
+use crate::dtos::order::{Trade, TradePage};
+use crate::instrument_manager::InstrumentManager;
+use crate::persistence::dao::Dao;
+use crate::rest_api::base_api::{log_anyhow_error_and_return_500, log_dao_error_and_return_500};
+use crate::time::current_time_millis;
+use actix_session::Session;
+use actix_web::web::{Path, Query, ThinData};
+use actix_web::HttpResponse;
+use log::info;
+use serde::Deserialize;
+
+// The most trades one call returns; a larger limit is cut down to this
+pub const MAX_TRADES_PER_PAGE: i64 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct TradeQuery {
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    pub instrument_key: Option<String>,
+    pub before_trade_id: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+fn page_size(limit: Option<i64>) -> Result<i64, String> {
+    match limit {
+        None => Ok(MAX_TRADES_PER_PAGE),
+        Some(limit) if limit < 1 => Err("limit must be positive".to_string()),
+        Some(limit) => Ok(limit.min(MAX_TRADES_PER_PAGE)),
+    }
+}
+
+#[get("/accounts/{account_key}/trades")]
+pub async fn get_trades(dao: ThinData<Dao>,
+                        instrument_manager: ThinData<InstrumentManager>,
+                        access_control: ThinData<AccessControl>,
+                        session: Session,
+                        path: Path<String>,
+                        trade_query: Query<TradeQuery>,
+) -> HttpResponse {
+    info!("get_trades called");
+    let account_key = path.into_inner();
+
+    let allowed: bool = match access_control.is_allowed_account_privilege(&session, &account_key, Privilege::Read) {
+        Ok(allowed) => allowed,
+        Err(error) => return log_anyhow_error_and_return_500(error)
+    };
+    if !allowed {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let instrument_id = match &trade_query.instrument_key {
+        Some(instrument_key) => match instrument_manager.get_instrument_by_key(instrument_key) {
+            Ok(Some(instrument)) => Some(instrument.instrument_id),
+            Ok(None) => return HttpResponse::PreconditionFailed().json(format!("instrument {} is unknown", instrument_key)),
+            Err(instrument_error) => return log_anyhow_error_and_return_500(instrument_error),
+        },
+        None => None
+    };
+    let page_size = match page_size(trade_query.limit) {
+        Ok(page_size) => page_size,
+        Err(message) => return HttpResponse::BadRequest().json(message),
+    };
+    let start_time = trade_query.start_time.unwrap_or(0);
+    let end_time = trade_query.end_time.unwrap_or_else(|| current_time_millis() + 1);
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    // One more than the page holds tells whether there is a next page
+    let mut trades = match txn.get_trades(&account_key, start_time, end_time, instrument_id,
+                                          trade_query.before_trade_id, page_size + 1).await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    match txn.rollback().await {
+        Ok(x) => x,
+        Err(dao_error) => return log_dao_error_and_return_500(dao_error),
+    };
+    let next_before_trade_id = if trades.len() as i64 > page_size {
+        trades.truncate(page_size as usize);
+        trades.last().map(|trade| trade.trade_id)
+    } else {
+        None
+    };
+
+    let mut rest_api_trades: Vec<Trade> = Vec::new();
+    for trade in trades.iter() {
+        let rest_api_trade = match trade.to_rest_api_trade(&instrument_manager) {
+            Ok(rest_api_trade) => rest_api_trade,
+            Err(convert_error) => return log_anyhow_error_and_return_500(convert_error),
+        };
+        rest_api_trades.push(rest_api_trade);
+    }
+    HttpResponse::Ok()
+        .content_type(APPLICATION_JSON)
+        .json(TradePage {
+            trades: rest_api_trades,
+            next_before_trade_id,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::access_control::{AccessControl, SESSION_ACCOUNT_MAP_KEY};
+    use crate::dtos::account::{Account, Privilege};
+    use crate::dtos::order::TradePage;
+    use crate::exchange_interface::mock_exchange::MOCK_INSTRUMENT_KEY;
+    use crate::exchange_interface::order::Execution;
+    use crate::money::Money;
+    use crate::rest_api::trade_api::{get_trades, page_size, MAX_TRADES_PER_PAGE};
+    use crate::time::current_time_millis;
+    use crate::trade_handling::execution_handling::handle_execution_thread;
+    use crate::trade_handling::trading_context::{test_trading_context, test_working_order};
+    use actix_session::SessionExt;
+    use actix_web::dev::Service;
+    use actix_web::http::StatusCode;
+    use actix_web::web::ThinData;
+    use actix_web::{test, App};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    #[test]
+    async fn test_page_size() {
+        assert_eq!(page_size(None), Ok(MAX_TRADES_PER_PAGE));
+        assert_eq!(page_size(Some(1)), Ok(1));
+        assert_eq!(page_size(Some(MAX_TRADES_PER_PAGE)), Ok(MAX_TRADES_PER_PAGE));
+        assert_eq!(page_size(Some(MAX_TRADES_PER_PAGE + 1)), Ok(MAX_TRADES_PER_PAGE));
+        assert!(page_size(Some(0)).is_err());
+        assert!(page_size(Some(-1)).is_err());
+    }
+
+    // Three fills at the same time are paged two at a time, in trade id order within that time
+    #[test]
+    async fn test_trades_are_paged() {
+        let Some((mut context, account_key)) = test_trading_context().await else { return };
+        let order_state = test_working_order(&mut context, &account_key, serde_json::json!({
+            "price": 10.0, "quantity": 3, "legs": [{"instrument_key": MOCK_INSTRUMENT_KEY, "ratio": 1}]
+        })).await;
+        let create_time = current_time_millis();
+        for execution_id in 1..=3 {
+            handle_execution_thread(Arc::new(Mutex::new(())), context.web_socket_server.clone(), context.dao.clone(),
+                                    context.instrument_manager.clone(), Execution {
+                    execution_id,
+                    client_order_id: order_state.order.client_order_id.clone(),
+                    instrument_id: 1,
+                    create_time,
+                    price: Money::from(10),
+                    quantity: 1,
+                }).await;
+        }
+
+        let mut accounts = HashMap::new();
+        accounts.insert(account_key.clone(), Account {
+            account_key: account_key.clone(),
+            account_number: "1".to_string(),
+            account_name: "name".to_string(),
+            nickname: "nickname".to_string(),
+            privileges: vec![Privilege::Read],
+        });
+        let app = test::init_service(
+            App::new()
+                .app_data(ThinData(context.dao.clone()))
+                .app_data(ThinData(context.instrument_manager.clone()))
+                .app_data(ThinData(AccessControl::new()))
+                .wrap_fn(move |req, srv| {
+                    req.get_session().insert(SESSION_ACCOUNT_MAP_KEY, &accounts).unwrap();
+                    srv.call(req)
+                })
+                .service(get_trades)
+        ).await;
+
+        let request = test::TestRequest::get().uri(&format!("/accounts/{}/trades?limit=2", account_key)).to_request();
+        let first_page: TradePage = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(first_page.trades.len(), 2);
+        assert!(first_page.trades[0].trade_id > first_page.trades[1].trade_id);
+        assert_eq!(first_page.next_before_trade_id, Some(first_page.trades[1].trade_id));
+
+        let request = test::TestRequest::get().uri(&format!("/accounts/{}/trades?limit=2&before_trade_id={}",
+                                                            account_key, first_page.trades[1].trade_id)).to_request();
+        let second_page: TradePage = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(second_page.trades.len(), 1);
+        assert!(second_page.trades[0].trade_id < first_page.trades[1].trade_id);
+        assert_eq!(second_page.next_before_trade_id, None);
+
+        let request = test::TestRequest::get().uri(&format!("/accounts/{}/trades?limit=3", account_key)).to_request();
+        let whole_page: TradePage = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(whole_page.trades.len(), 3);
+        assert_eq!(whole_page.next_before_trade_id, None);
+
+        let request = test::TestRequest::get().uri(&format!("/accounts/{}/trades?limit=0", account_key)).to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::dtos;
+use crate::dtos::order::BatchOrderResult;
+use crate::dtos::routing::RoutedOrderRequest;
+use crate::entities::routing::{LogicalInstrument, RoutedOrder, RoutingStrategy};
+use crate::money::Money;
+use crate::persistence::dao::{Dao, DaoTransaction};
+use crate::time::current_time_millis;
+use crate::trade_handling::order_entry;
+use crate::trade_handling::order_entry::OrderEntryError;
+use crate::trade_handling::trading_context::TradingContext;
+use crate::validator::validator::Validator;
+use crate::vetting::vetter::pass;
+use crate::vetting::vetter_registry::VetterRegistry;
+use anyhow::Error;
+use log::{error, info};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+// Logical instruments by key. Each of their listings is an ordinary instrument on one exchange
+#[derive(Clone)]
+pub struct OrderRouter {
+    logical_instruments: Arc<RwLock<HashMap<String, LogicalInstrument>>>,
+}
+
+impl OrderRouter {
+    pub fn new() -> Self {
+        OrderRouter {
+            logical_instruments: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn load(&self,
+                      txn: &DaoTransaction<'_>) -> Result<(), Error> {
+        let logical_instruments = match txn.get_logical_instruments().await {
+            Ok(logical_instruments) => logical_instruments,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get logical instruments: {}", dao_error)),
+        };
+        let count = logical_instruments.len();
+        for logical_instrument in logical_instruments {
+            self.add(logical_instrument)?;
+        }
+        info!("Done loading {} logical instruments", count);
+        Ok(())
+    }
+
+    pub fn add(&self,
+               logical_instrument: LogicalInstrument) -> Result<(), Error> {
+        let mut writable_logical_instruments = match self.logical_instruments.write() {
+            Ok(writable_logical_instruments) => writable_logical_instruments,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to logical instruments: {}", writable_error)),
+        };
+        writable_logical_instruments.insert(logical_instrument.logical_key.clone(), logical_instrument);
+        Ok(())
+    }
+
+    pub fn get(&self,
+               logical_key: &str) -> Result<Option<LogicalInstrument>, Error> {
+        let readable_logical_instruments = match self.logical_instruments.read() {
+            Ok(readable_logical_instruments) => readable_logical_instruments,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to logical instruments: {}", readable_error)),
+        };
+        Ok(readable_logical_instruments.get(logical_key).cloned())
+    }
+
+    pub fn get_all(&self) -> Result<Vec<LogicalInstrument>, Error> {
+        let readable_logical_instruments = match self.logical_instruments.read() {
+            Ok(readable_logical_instruments) => readable_logical_instruments,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to logical instruments: {}", readable_error)),
+        };
+        Ok(readable_logical_instruments.values().cloned().collect())
+    }
+}
+
+// A listing with its displayed levels on the side an order would trade against, best first
+#[derive(Clone, Debug)]
+struct Venue {
+    instrument_id: i64,
+    preference: i32,
+    fee_per_unit: Money,
+    levels: Vec<(Money, i32)>,
+}
+
+// Splits the order into child quantities by instrument id, signed like the order. Prices are
+// compared after each venue's fee, and only levels within the order's limit are counted
+fn plan_route(strategy: RoutingStrategy,
+              price: Money,
+              quantity: i32,
+              venues: &[Venue]) -> Vec<(i64, i32)> {
+    let buying = quantity > 0;
+    let effective = |venue: &Venue, level_price: Money| if buying { level_price + venue.fee_per_unit } else { level_price - venue.fee_per_unit };
+    let marketable = |level_price: Money| if buying { level_price <= price } else { level_price >= price };
+    let best_first = |a: Money, b: Money| if buying { a.cmp(&b) } else { b.cmp(&a) };
+
+    let preferred = match venues.iter().min_by_key(|venue| (venue.preference, venue.instrument_id)) {
+        Some(preferred) => preferred,
+        None => return vec![],
+    };
+    let best_priced = venues.iter()
+        .filter_map(|venue| venue.levels.first()
+            .filter(|(level_price, _)| marketable(*level_price))
+            .map(|(level_price, _)| (effective(venue, *level_price), venue)))
+        .min_by(|(a, a_venue), (b, b_venue)| best_first(*a, *b)
+            .then(a_venue.preference.cmp(&b_venue.preference)))
+        .map(|(_, venue)| venue)
+        .unwrap_or(preferred);
+
+    match strategy {
+        RoutingStrategy::Preference => vec![(preferred.instrument_id, quantity)],
+        RoutingStrategy::BestPrice => vec![(best_priced.instrument_id, quantity)],
+        RoutingStrategy::Split => {
+            let mut levels: Vec<(Money, &Venue, i32)> = venues.iter()
+                .flat_map(|venue| venue.levels.iter()
+                    .filter(|(level_price, _)| marketable(*level_price))
+                    .map(move |(level_price, level_quantity)| (effective(venue, *level_price), venue, *level_quantity)))
+                .collect();
+            levels.sort_by(|(a, a_venue, _), (b, b_venue, _)| match best_first(*a, *b) {
+                Ordering::Equal => a_venue.preference.cmp(&b_venue.preference),
+                ordering => ordering,
+            });
+
+            let mut children: Vec<(i64, i32)> = Vec::new();
+            let mut remaining = quantity.abs();
+            let displayed = levels.into_iter().map(|(_, venue, level_quantity)| (venue.instrument_id, level_quantity));
+            // Whatever the displayed depth cannot fill rests at the best priced venue
+            let rest = std::iter::once((best_priced.instrument_id, i32::MAX));
+            for (instrument_id, available) in displayed.chain(rest) {
+                if remaining == 0 {
+                    break;
+                }
+                let fill = remaining.min(available);
+                remaining -= fill;
+                match children.iter_mut().find(|(child_instrument_id, _)| *child_instrument_id == instrument_id) {
+                    Some((_, child_quantity)) => *child_quantity += fill,
+                    None => children.push((instrument_id, fill)),
+                }
+            }
+            children.into_iter().map(|(instrument_id, child_quantity)| (instrument_id, child_quantity * quantity.signum())).collect()
+        },
+    }
+}
+
+// Each child goes through order entry like any other order, so is checked and vetted on its own.
+// The parent is saved before any child, and each child is linked to it as it is saved, so no
+// child reaches an exchange unrecorded. Children that fail are reported but do not stop the rest
+pub(crate) async fn route_order(context: &mut TradingContext,
+                                vetter_registry: &VetterRegistry,
+                                validator: &Validator,
+                                account_key: &String,
+                                may_make_markets: bool,
+                                request: RoutedOrderRequest) -> Result<dtos::routing::RoutedOrder, OrderEntryError> {
+    let instrument_manager = context.instrument_manager.clone();
+    if request.quantity == 0 {
+        return Err(OrderEntryError::BadRequest("Order quantity is 0".to_string()));
+    }
+    let logical_instrument = match instrument_manager.get_order_router().get(&request.logical_key) {
+        Ok(Some(logical_instrument)) => logical_instrument,
+        Ok(None) => return Err(OrderEntryError::NotFound),
+        Err(router_error) => return Err(OrderEntryError::Failed(router_error)),
+    };
+    let mut venues = Vec::new();
+    for listing in logical_instrument.listings.iter() {
+        let book = match instrument_manager.get_depth_cache().get(listing.instrument_id) {
+            Ok(book) => book.unwrap_or_default(),
+            Err(depth_error) => return Err(OrderEntryError::Failed(depth_error)),
+        };
+        venues.push(Venue {
+            instrument_id: listing.instrument_id,
+            preference: listing.preference,
+            fee_per_unit: listing.fee_per_unit,
+            levels: if request.quantity > 0 { book.asks } else { book.bids },
+        });
+    }
+    let plan = plan_route(request.strategy, request.price, request.quantity, &venues);
+    info!("Routing {} {} at {} by {}: {:?}", request.quantity, request.logical_key, request.price, request.strategy, plan);
+
+    let routed_order = match save_routed_order(&context.dao, account_key, &logical_instrument, &request).await {
+        Ok(routed_order) => routed_order,
+        Err(save_error) => return Err(OrderEntryError::Failed(save_error)),
+    };
+
+    let mut results = Vec::new();
+    for (instrument_id, child_quantity) in plan {
+        let ext_order_id = Uuid::new_v4().simple().to_string();
+        let instrument = match instrument_manager.get_instrument(instrument_id) {
+            Ok(Some(instrument)) => instrument,
+            Ok(None) => {
+                results.push(failed_child(ext_order_id, format!("No instrument with id: {}", instrument_id)));
+                continue;
+            },
+            Err(instrument_error) => {
+                results.push(failed_child(ext_order_id, instrument_error.to_string()));
+                continue;
+            },
+        };
+        let child_order = dtos::order::Order {
+            create_time: current_time_millis(),
+            order_number: None,
+            ext_order_id: Some(ext_order_id.clone()),
+            account_key: Some(account_key.clone()),
+            price: request.price,
+            quantity: child_quantity,
+            legs: vec![dtos::order::OrderLeg {
+                instrument_key: instrument.instrument_key.clone(),
+                ratio: 1,
+            }],
+            time_in_force: request.time_in_force.clone(),
+            expire_time: request.expire_time,
+            order_type: dtos::order::OrderType::Limit,
+            stop_price: None,
+            trailing_offset: None,
+        };
+        let mut result = BatchOrderResult {
+            ext_order_id,
+            check_result: pass(),
+            order_state: None,
+            error: None,
+        };
+        match order_entry::submit_routed_order(context, vetter_registry, validator,
+                                               account_key, may_make_markets, child_order, Some(routed_order.routed_order_id)).await {
+            Ok(order_state) => match order_state.to_rest_api_order_state(account_key.as_str(), &instrument_manager) {
+                Ok(rest_api_order_state) => result.order_state = Some(rest_api_order_state),
+                Err(convert_error) => result.error = Some(convert_error.to_string()),
+            },
+            Err(OrderEntryError::Rejected(check_result)) => result.check_result = check_result,
+            Err(entry_error) => {
+                error!("Could not submit child order {} on {}: {}", result.ext_order_id, instrument.instrument_key, entry_error);
+                result.error = Some(entry_error.to_string());
+            },
+        };
+        results.push(result);
+    }
+
+    Ok(dtos::routing::RoutedOrder {
+        routed_order_id: routed_order.routed_order_id,
+        logical_key: logical_instrument.logical_key,
+        strategy: request.strategy,
+        price: request.price,
+        quantity: request.quantity,
+        children: results,
+    })
+}
+
+fn failed_child(ext_order_id: String,
+                error: String) -> BatchOrderResult {
+    error!("Could not submit child order {}: {}", ext_order_id, error);
+    BatchOrderResult {
+        ext_order_id,
+        check_result: pass(),
+        order_state: None,
+        error: Some(error),
+    }
+}
+
+async fn save_routed_order(dao: &Dao,
+                           account_key: &String,
+                           logical_instrument: &LogicalInstrument,
+                           request: &RoutedOrderRequest) -> Result<RoutedOrder, Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error))
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error))
+    };
+    let account = match txn.get_account_by_account_key(account_key).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get account: {}", dao_error))
+    };
+    let routed_order = RoutedOrder {
+        routed_order_id: 0,
+        account_id: account.account_id,
+        logical_instrument_id: logical_instrument.logical_instrument_id,
+        strategy: request.strategy,
+        price: request.price,
+        quantity: request.quantity,
+        create_time: current_time_millis(),
+        children: vec![],
+    };
+    let routed_order = match txn.save_routed_order(routed_order).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not save routed order: {}", dao_error))
+    };
+    match txn.commit().await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error))
+    };
+    Ok(routed_order)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::routing::RoutingStrategy;
+    use crate::money::Money;
+    use crate::routing::order_router::{plan_route, Venue};
+
+    fn venue(instrument_id: i64, preference: i32, fee_per_unit: Money, levels: &[(i32, i32)]) -> Venue {
+        Venue {
+            instrument_id,
+            preference,
+            fee_per_unit,
+            levels: levels.iter().map(|(price, quantity)| (Money::from(*price), *quantity)).collect(),
+        }
+    }
+
+    fn venues() -> Vec<Venue> {
+        vec![
+            venue(1, 0, Money::ZERO, &[(101, 10), (102, 50)]),
+            venue(2, 1, Money::ZERO, &[(100, 5), (103, 50)]),
+        ]
+    }
+
+    #[test]
+    async fn test_best_price_and_preference() {
+        assert_eq!(plan_route(RoutingStrategy::BestPrice, Money::from(105), 20, &venues()), vec![(2, 20)]);
+        assert_eq!(plan_route(RoutingStrategy::Preference, Money::from(105), 20, &venues()), vec![(1, 20)]);
+
+        // A fee can outweigh a better displayed price
+        let mut with_fee = venues();
+        with_fee[1].fee_per_unit = Money::new(15, 1);
+        assert_eq!(plan_route(RoutingStrategy::BestPrice, Money::from(105), 20, &with_fee), vec![(1, 20)]);
+
+        // Nothing displayed within the limit falls back to preference
+        assert_eq!(plan_route(RoutingStrategy::BestPrice, Money::from(99), 20, &venues()), vec![(1, 20)]);
+    }
+
+    #[test]
+    async fn test_split_sweeps_displayed_depth() {
+        assert_eq!(plan_route(RoutingStrategy::Split, Money::from(102), 40, &venues()), vec![(2, 5), (1, 35)]);
+        // Beyond the displayed depth within the limit, the rest goes to the best priced venue
+        assert_eq!(plan_route(RoutingStrategy::Split, Money::from(101), 40, &venues()), vec![(2, 30), (1, 10)]);
+    }
+
+    #[test]
+    async fn test_split_sell_against_bids() {
+        let bids = vec![
+            venue(1, 0, Money::ZERO, &[(99, 10)]),
+            venue(2, 1, Money::ZERO, &[(100, 5), (98, 10)]),
+        ];
+        assert_eq!(plan_route(RoutingStrategy::Split, Money::from(99), -12, &bids), vec![(2, -5), (1, -7)]);
+    }
+
+    #[test]
+    async fn test_no_venues() {
+        assert!(plan_route(RoutingStrategy::Split, Money::from(99), 10, &[]).is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::dtos::order::is_order_status_viable;
+use crate::entities::account::Account;
+use crate::persistence::dao::Dao;
+use crate::time::{current_date, current_time_millis, millis_until_next_hour_utc};
+use anyhow::Error;
+use log::{error, info, warn};
+use std::time::Duration;
+
+pub fn start_end_of_day_job(dao: Dao,
+                            end_of_day_hour_utc: u8) {
+    tokio::spawn(end_of_day_loop(dao, end_of_day_hour_utc));
+}
+
+async fn end_of_day_loop(dao: Dao,
+                         end_of_day_hour_utc: u8) {
+    loop {
+        let wait_millis = millis_until_next_hour_utc(current_time_millis(), end_of_day_hour_utc);
+        info!("Next end of day snapshot in {} ms", wait_millis);
+        tokio::time::sleep(Duration::from_millis(wait_millis as u64)).await;
+
+        let snapshot_date = match current_date() {
+            Ok(snapshot_date) => snapshot_date.to_string(),
+            Err(date_error) => {
+                error!("Unable to determine snapshot date: {}", date_error);
+                continue;
+            }
+        };
+        take_snapshots(&dao, &snapshot_date).await;
+    }
+}
+
+pub async fn take_snapshots(dao: &Dao,
+                            snapshot_date: &String) {
+    let start = current_time_millis();
+    let accounts = match get_all_accounts(dao).await {
+        Ok(accounts) => accounts,
+        Err(err) => {
+            error!("Unable to get accounts for end of day snapshot: {}", err);
+            return;
+        }
+    };
+    for account in accounts.iter() {
+        match snapshot_account(dao, account, snapshot_date).await {
+            Ok(_) => {},
+            Err(err) => error!("Unable to snapshot account {}: {}", account.account_key, err),
+        }
+    }
+    check_ledger(dao).await;
+    let end = current_time_millis();
+    info!("End of day snapshot for {} of {} accounts took {} ms", snapshot_date, accounts.len(), end-start);
+}
+
+// Balances are only ever moved through the ledger, so any difference points at a bug or a manual change
+async fn check_ledger(dao: &Dao) {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => {
+            error!("Unable to get connection for ledger check: {}", dao_error);
+            return;
+        }
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => {
+            error!("Unable to begin ledger check: {}", dao_error);
+            return;
+        }
+    };
+    match txn.get_ledger_discrepancies().await {
+        Ok(discrepancies) => {
+            for discrepancy in discrepancies.iter() {
+                warn!("Balance of account {} is {:.2} but its ledger sums to {:.2}",
+                      discrepancy.account_key, discrepancy.cash, discrepancy.ledger_cash);
+            }
+        },
+        Err(dao_error) => error!("Unable to get ledger discrepancies: {}", dao_error),
+    };
+    match txn.rollback().await {
+        Ok(_) => {},
+        Err(dao_error) => error!("Unable to rollback ledger check: {}", dao_error),
+    };
+}
+
+async fn get_all_accounts(dao: &Dao) -> Result<Vec<Account>, Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let accounts = match txn.get_all_accounts().await {
+        Ok(accounts) => accounts,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get accounts: {}", dao_error)),
+    };
+    match txn.rollback().await {
+        Ok(_) => Ok(accounts),
+        Err(dao_error) => Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+    }
+}
+
+async fn snapshot_account(dao: &Dao,
+                          account: &Account,
+                          snapshot_date: &String) -> Result<(), Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let create_time = current_time_millis();
+
+    let balance = match txn.get_balance(&account.account_key).await {
+        Ok(balance) => balance,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get balance: {}", dao_error)),
+    };
+    match txn.save_balance_snapshot(snapshot_date, &balance, create_time).await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not save balance snapshot: {}", dao_error)),
+    };
+
+    let positions = match txn.get_positions(&account.account_key).await {
+        Ok(positions) => positions,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get positions: {}", dao_error)),
+    };
+    for position in positions.values() {
+        match txn.save_position_snapshot(snapshot_date, position, create_time).await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not save position snapshot: {}", dao_error)),
+        };
+    }
+
+    let order_states = match txn.get_orders(&account.account_key).await {
+        Ok(order_states) => order_states,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get orders: {}", dao_error)),
+    };
+    for order_state in order_states.values().filter(|order_state| is_order_status_viable(&order_state.order_status)) {
+        match txn.save_order_state_snapshot(snapshot_date, order_state).await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not save order state snapshot: {}", dao_error)),
+        };
+    }
+
+    match txn.commit().await {
+        Ok(_) => Ok(()),
+        Err(dao_error) => Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+    }
+}
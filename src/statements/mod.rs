@@ -0,0 +1,3 @@
+pub(crate) mod end_of_day;
+pub(crate) mod statement_builder;
+pub(crate) mod statement_csv;
@@ -0,0 +1,98 @@
+use crate::dtos::order::is_order_status_viable;
+use crate::dtos::statement::Statement;
+use crate::entities::account::{Balance, Position};
+use crate::entities::order::OrderState;
+use crate::instrument_manager::InstrumentManager;
+use crate::persistence::dao::{Dao, DaoTransaction};
+use crate::time::current_date;
+use anyhow::Error;
+use std::collections::HashMap;
+
+// Past dates come from the end of day snapshots; today falls back to the live
+// balance, positions and orders until today's snapshot has been taken.
+pub async fn build_statement(dao: &Dao,
+                             instrument_manager: &InstrumentManager,
+                             account_key: &String,
+                             statement_date: ::time::Date) -> Result<Option<Statement>, Error> {
+    let today = current_date()?;
+    if statement_date > today {
+        return Ok(None);
+    }
+    let snapshot_date = statement_date.to_string();
+
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let contents = match txn.get_balance_snapshot(account_key, &snapshot_date).await {
+        Ok(Some(balance)) => Some(get_snapshot_contents(&txn, account_key, &snapshot_date, balance).await?),
+        Ok(None) if statement_date == today => Some(get_live_contents(&txn, account_key).await?),
+        Ok(None) => None,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get balance snapshot: {}", dao_error)),
+    };
+    match txn.rollback().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+    };
+    let (balance, positions, order_states) = match contents {
+        Some(contents) => contents,
+        None => return Ok(None)
+    };
+
+    let mut rest_api_positions = Vec::new();
+    for position in positions.values() {
+        rest_api_positions.push(position.to_rest_api_position(account_key, instrument_manager)?);
+    }
+    rest_api_positions.sort_by(|a, b| a.instrument_key.cmp(&b.instrument_key));
+
+    let mut rest_api_order_states = Vec::new();
+    for order_state in order_states.values().filter(|order_state| is_order_status_viable(&order_state.order_status)) {
+        rest_api_order_states.push(order_state.to_rest_api_order_state(account_key, instrument_manager)?);
+    }
+    rest_api_order_states.sort_by_key(|order_state| order_state.order.order_number);
+
+    Ok(Some(Statement {
+        account_key: account_key.clone(),
+        statement_date: snapshot_date,
+        cash: balance.cash,
+        closed_gain: positions.values().map(|position| position.closed_gain).sum(),
+        positions: rest_api_positions,
+        open_orders: rest_api_order_states,
+    }))
+}
+
+async fn get_snapshot_contents(txn: &DaoTransaction<'_>,
+                               account_key: &String,
+                               snapshot_date: &String,
+                               balance: Balance) -> Result<(Balance, HashMap<i64, Position>, HashMap<String, OrderState>), Error> {
+    let positions = match txn.get_position_snapshots(account_key, snapshot_date).await {
+        Ok(positions) => positions,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get position snapshots: {}", dao_error)),
+    };
+    let order_states = match txn.get_order_state_snapshots(account_key, snapshot_date).await {
+        Ok(order_states) => order_states,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get order state snapshots: {}", dao_error)),
+    };
+    Ok((balance, positions, order_states))
+}
+
+async fn get_live_contents(txn: &DaoTransaction<'_>,
+                           account_key: &String) -> Result<(Balance, HashMap<i64, Position>, HashMap<String, OrderState>), Error> {
+    let balance = match txn.get_balance(account_key).await {
+        Ok(balance) => balance,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get balance: {}", dao_error)),
+    };
+    let positions = match txn.get_positions(account_key).await {
+        Ok(positions) => positions,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get positions: {}", dao_error)),
+    };
+    let order_states = match txn.get_orders(account_key).await {
+        Ok(order_states) => order_states,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get orders: {}", dao_error)),
+    };
+    Ok((balance, positions, order_states))
+}
@@ -0,0 +1,134 @@
+use crate::dtos::statement::Statement;
+
+const CSV_HEADER: &str = "record_type,account_key,statement_date,instrument_key,ext_order_id,order_status,quantity,filled_quantity,price,cost,cash,closed_gain";
+
+// One row per record, with the record type in the first column: a summary row,
+// then a row per position and a row per open order
+pub fn statement_to_csv(statement: &Statement) -> String {
+    let mut csv = String::new();
+    csv.push_str(CSV_HEADER);
+    csv.push('\n');
+
+    push_row(&mut csv, &["summary",
+        &statement.account_key,
+        &statement.statement_date,
+        "", "", "", "", "", "", "",
+        &statement.cash.to_string(),
+        &statement.closed_gain.to_string()]);
+
+    for position in statement.positions.iter() {
+        push_row(&mut csv, &["position",
+            &statement.account_key,
+            &statement.statement_date,
+            &position.instrument_key,
+            "", "",
+            &position.quantity.to_string(),
+            "", "",
+            &position.cost.to_string(),
+            "",
+            &position.closed_gain.to_string()]);
+    }
+
+    for order_state in statement.open_orders.iter() {
+        let instrument_keys: Vec<String> = order_state.order.legs.iter()
+            .map(|leg| format!("{}:{}", leg.instrument_key, leg.ratio))
+            .collect();
+        push_row(&mut csv, &["order",
+            &statement.account_key,
+            &statement.statement_date,
+            &instrument_keys.join(";"),
+            order_state.order.ext_order_id.as_deref().unwrap_or(""),
+            &order_state.order_status.to_string(),
+            &order_state.order.quantity.to_string(),
+            &order_state.filled_quantity.to_string(),
+            &order_state.order.price.to_string(),
+            "", "", ""]);
+    }
+    csv
+}
+
+fn push_row(csv: &mut String,
+            fields: &[&str]) {
+    let escaped: Vec<String> = fields.iter().map(|field| escape_field(field)).collect();
+    csv.push_str(&escaped.join(","));
+    csv.push('\n');
+}
+
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dtos::account::Position;
+    use crate::dtos::order::{Order, OrderLeg, OrderState, OrderStatus, OrderType, TimeInForce};
+    use crate::dtos::statement::Statement;
+    use crate::money::Money;
+    use crate::statements::statement_csv::{escape_field, statement_to_csv};
+
+    #[test]
+    async fn test_escape_field() {
+        assert_eq!(escape_field("plain"), "plain");
+        assert_eq!(escape_field("a,b"), "\"a,b\"");
+        assert_eq!(escape_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    async fn test_statement_to_csv() {
+        let statement = Statement {
+            account_key: "acct".to_string(),
+            statement_date: "2024-03-01".to_string(),
+            cash: Money::from(1000),
+            closed_gain: Money::new(125, 1),
+            positions: vec![Position {
+                account_key: "acct".to_string(),
+                instrument_key: "ABC".to_string(),
+                quantity: 10,
+                cost: Money::new(10025, 2),
+                version_number: 1,
+                closed_gain: Money::new(125, 1),
+                mark_price: None,
+                market_value: None,
+                unrealized_gain: None,
+                day_gain: None,
+            }],
+            open_orders: vec![OrderState {
+                update_time: 0,
+                order_status: OrderStatus::PartiallyFilled,
+                filled_quantity: -2,
+                average_fill_price: Money::from(11),
+                order: Order {
+                    create_time: 0,
+                    order_number: Some(3),
+                    ext_order_id: Some("ext".to_string()),
+                    account_key: Some("acct".to_string()),
+                    price: Money::new(1105, 2),
+                    quantity: -5,
+                    legs: vec![OrderLeg {
+                        instrument_key: "ABC".to_string(),
+                        ratio: 1,
+                    }, OrderLeg {
+                        instrument_key: "XYZ".to_string(),
+                        ratio: -1,
+                    }],
+                    time_in_force: TimeInForce::Gtc,
+                    expire_time: None,
+                    order_type: OrderType::Limit,
+                    stop_price: None,
+                    trailing_offset: None,
+                },
+                version_number: 1,
+                reject_reason: None,
+            }],
+        };
+        let lines: Vec<String> = statement_to_csv(&statement).lines().map(|line| line.to_string()).collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1], "summary,acct,2024-03-01,,,,,,,,1000,12.5");
+        assert_eq!(lines[2], "position,acct,2024-03-01,ABC,,,10,,,100.25,,12.5");
+        assert_eq!(lines[3], "order,acct,2024-03-01,ABC:1;XYZ:-1,ext,PartiallyFilled,-5,-2,11.05,,,");
+    }
+}
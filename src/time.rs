@@ -7,3 +7,64 @@ pub fn current_time_millis() -> i64 {
         Err(time_error) => panic!("Unable to get system time: {}", time_error.to_string()),
     }
 }
+
+const MILLIS_PER_DAY: i64 = 86400 * 1000;
+
+// Dates are UTC calendar dates in YYYY-MM-DD form
+pub fn date_of_millis(millis: i64) -> Result<::time::Date, anyhow::Error> {
+    match ::time::OffsetDateTime::from_unix_timestamp(millis.div_euclid(1000)) {
+        Ok(date_time) => Ok(date_time.date()),
+        Err(range_error) => Err(anyhow::anyhow!("Time {} is out of range: {}", millis, range_error)),
+    }
+}
+
+pub fn current_date() -> Result<::time::Date, anyhow::Error> {
+    date_of_millis(current_time_millis())
+}
+
+pub fn parse_date(input: &str) -> Option<::time::Date> {
+    let mut parts = input.splitn(3, '-');
+    let year: i32 = parts.next()?.parse().ok()?;
+    let month: u8 = parts.next()?.parse().ok()?;
+    let day: u8 = parts.next()?.parse().ok()?;
+    let month = ::time::Month::try_from(month).ok()?;
+    ::time::Date::from_calendar_date(year, month, day).ok()
+}
+
+// Milliseconds until the next occurrence of hour_utc:00, strictly in the future
+pub fn millis_until_next_hour_utc(now_millis: i64,
+                                  hour_utc: u8) -> i64 {
+    let start_of_day = now_millis - now_millis.rem_euclid(MILLIS_PER_DAY);
+    let mut next = start_of_day + hour_utc as i64 * 3600 * 1000;
+    if next <= now_millis {
+        next += MILLIS_PER_DAY;
+    }
+    next - now_millis
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::time::{date_of_millis, millis_until_next_hour_utc, parse_date};
+
+    #[test]
+    async fn test_parse_date() {
+        let date = parse_date("2024-02-29").unwrap();
+        assert_eq!(date.to_string(), "2024-02-29");
+        assert!(parse_date("2023-02-29").is_none());
+        assert!(parse_date("yesterday").is_none());
+    }
+
+    #[test]
+    async fn test_date_of_millis() {
+        assert_eq!(date_of_millis(1_709_251_199_999).unwrap().to_string(), "2024-02-29");
+        assert_eq!(date_of_millis(1_709_251_200_000).unwrap().to_string(), "2024-03-01");
+    }
+
+    #[test]
+    async fn test_millis_until_next_hour() {
+        let midnight = 1_709_251_200_000;
+        assert_eq!(millis_until_next_hour_utc(midnight, 21), 21 * 3600 * 1000);
+        assert_eq!(millis_until_next_hour_utc(midnight + 22 * 3600 * 1000, 21), 23 * 3600 * 1000);
+        assert_eq!(millis_until_next_hour_utc(midnight + 21 * 3600 * 1000, 21), 24 * 3600 * 1000);
+    }
+}
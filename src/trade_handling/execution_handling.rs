@@ -1,14 +1,18 @@
 use crate::constants::ACCOUNT_UPDATE_QUEUE_NAME;
+use crate::dtos::ledger::{JournalType, LedgerAccount};
 use crate::entities::account::Position;
+use crate::entities::ledger::Journal;
+use crate::entities::order::Trade;
 use crate::exchange_interface::order::Execution;
 use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
 use crate::persistence::dao::Dao;
 use crate::time::current_time_millis;
+use crate::trade_handling::order_groups::handle_group_update;
 use crate::trade_handling::updates::AccountUpdate;
 use crate::websockets::server::WebSocketServer;
 use anyhow::Error;
 use log::{error, info};
-use std::ops::Neg;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -21,12 +25,16 @@ pub fn handle_execution(mutex: Arc<Mutex<()>>,
     tokio::spawn(handle_execution_thread(mutex.clone(), web_socket_server.clone(), dao.clone(), instrument_manager.clone(), execution));
 }
 
-async fn handle_execution_thread(mutex: Arc<Mutex<()>>, 
+pub(crate) async fn handle_execution_thread(mutex: Arc<Mutex<()>>, 
                                  mut web_socket_server: WebSocketServer, 
                                  dao: Dao, 
                                  instrument_manager: InstrumentManager, 
                                  execution: Execution) {
     let start = current_time_millis();
+    if execution.quantity == 0 {
+        error!("Rejecting execution {} for order {} with no quantity", execution.execution_id, execution.client_order_id);
+        return;
+    }
     let _lock = mutex.lock().await;
     let mut db_connection = match dao.get_connection().await {
         Ok(db_connection) => db_connection,
@@ -52,7 +60,7 @@ async fn handle_execution_thread(mutex: Arc<Mutex<()>>,
         }
     };
 
-    let db_order_state = match db_order_state_option {
+    let mut db_order_state = match db_order_state_option {
         Some(db_order_state) => {
             db_order_state
         }
@@ -78,42 +86,142 @@ async fn handle_execution_thread(mutex: Arc<Mutex<()>>,
         }
     };
 
-    let execution_cost = (execution.price * execution.quantity as f32);
+    let instrument_result = instrument_manager.get_instrument_by_exchange_instrument_id(execution.instrument_id);
+    let instrument_option = match instrument_result {
+        Ok(instrument_option) => instrument_option,
+        Err(err) => {
+            error!("Unable to get instrument: {}", err);
+            return;
+        }
+    };
+    let instrument = match instrument_option {
+        Some(instrument) => instrument,
+        None => {
+            error!("No instrument with id: {}", execution.instrument_id);
+            return;
+        }
+    };
+
+    let order_leg = match db_order_state.order.legs.iter().find(|leg| leg.instrument_id == instrument.instrument_id) {
+        Some(order_leg) => order_leg.clone(),
+        None => {
+            error!("Order {} has no leg for instrument {}", db_order_state.order.ext_order_id, instrument.instrument_id);
+            return;
+        }
+    };
 
-    let mut balance = match txn.get_balance(&account.account_key).await {
-        Ok(x) => x,
+    let fee_schedule = match txn.get_fee_schedule_for_account(account.account_id, &instrument.asset_class).await {
+        Ok(fee_schedule) => fee_schedule,
         Err(err) => {
-            error!("Unable to get_balance: {}", err);
+            error!("Unable to get_fee_schedule_for_account: {}", err);
+            return;
+        },
+    };
+    let fee = match fee_schedule {
+        Some(fee_schedule) => {
+            let already_charged: Money = match txn.get_trades_for_order(db_order_state.order.order_id).await {
+                Ok(trades) => trades.iter().map(|trade| trade.fee).sum(),
+                Err(err) => {
+                    error!("Unable to get_trades_for_order: {}", err);
+                    return;
+                },
+            };
+            fee_schedule.fee_for_fill(&instrument.asset_class, execution.price, execution.quantity, already_charged)
+        },
+        None => Money::ZERO
+    };
+
+    let trade = Trade {
+        trade_id: 0,
+        create_time: execution.create_time,
+        ext_order_id: db_order_state.order.ext_order_id.clone(),
+        order_number: db_order_state.order.order_number,
+        order_leg: order_leg.clone(),
+        exchange_execution_id: execution.execution_id,
+        price: execution.price,
+        quantity: execution.quantity,
+        fee,
+    };
+    // The unique constraint on the execution id makes re-deliveries a no-op
+    let trade = match txn.save_trade(trade).await {
+        Ok(Some(trade)) => trade,
+        Ok(None) => {
+            info!("Ignoring already recorded execution {} for order {}", execution.execution_id, execution.client_order_id);
+            return;
+        },
+        Err(err) => {
+            error!("Unable to save_trade: {}", err);
             return;
         },
     };
 
-    balance.cash -= execution_cost;
-    balance.update_time = current_time_millis();
+    let execution_cost = execution.price * execution.quantity;
 
-    match txn.update_balance(&mut balance).await {
-        Ok(_) => {},
+    let mut balance = match txn.get_balance_for_update(&account.account_key).await {
+        Ok(x) => x,
         Err(err) => {
-            error!("Unable to update_balance: {}", err);
+            error!("Unable to get_balance: {}", err);
             return;
         },
     };
 
-    let instrument_result = instrument_manager.get_instrument_by_exchange_instrument_id(execution.instrument_id);
-    let instrument_option = match instrument_result {
-        Ok(instrument_option) => instrument_option,
+    let mut journal = Journal::cash_movement(JournalType::Execution,
+                                             account.account_id,
+                                             -execution_cost,
+                                             LedgerAccount::ExchangeSettlement,
+                                             format!("Execution {} of {} at {} for order {}",
+                                                     execution.execution_id, execution.quantity, execution.price, db_order_state.order.ext_order_id),
+                                             current_time_millis());
+    journal.trade_id = Some(trade.trade_id);
+    match txn.post_journal(&mut balance, journal).await {
+        Ok(_) => {},
         Err(err) => {
-            error!("Unable to get instrument: {}", err);
+            error!("Unable to post_journal: {}", err);
             return;
-        }
+        },
     };
-    let instrument = match instrument_option {
-        Some(instrument) => instrument,
-        None => {
-            error!("No instrument with id: {}", execution.instrument_id);
+    if !fee.is_zero() {
+        let mut fee_journal = Journal::cash_movement(JournalType::Fee,
+                                                     account.account_id,
+                                                     -fee,
+                                                     LedgerAccount::FeeIncome,
+                                                     format!("Commission on execution {} for order {}",
+                                                             execution.execution_id, db_order_state.order.ext_order_id),
+                                                     current_time_millis());
+        fee_journal.trade_id = Some(trade.trade_id);
+        match txn.post_journal(&mut balance, fee_journal).await {
+            Ok(_) => {},
+            Err(err) => {
+                error!("Unable to post_journal: {}", err);
+                return;
+            },
+        };
+    }
+
+    let order_trades = match txn.get_trades_for_order(db_order_state.order.order_id).await {
+        Ok(order_trades) => order_trades,
+        Err(err) => {
+            error!("Unable to get_trades_for_order: {}", err);
             return;
-        }
+        },
     };
+    let (filled_quantity, average_fill_price) = (db_order_state.filled_quantity, db_order_state.average_fill_price);
+    for (order_leg_id, carried_quantity) in db_order_state.apply_trades(&order_trades) {
+        info!("Order {} leg {} carries {} short of a whole order unit", db_order_state.order.ext_order_id, order_leg_id, carried_quantity);
+    }
+    let order_state_changed = db_order_state.filled_quantity != filled_quantity || db_order_state.average_fill_price != average_fill_price;
+    if order_state_changed {
+        db_order_state.update_time = current_time_millis();
+    }
+    if order_state_changed {
+        match txn.update_order(&mut db_order_state).await {
+            Ok(_) => {},
+            Err(err) => {
+                error!("Unable to update_order: {}", err);
+                return;
+            },
+        };
+    }
 
     let position_result = txn.get_position(&account.account_key, instrument.instrument_id).await;
 
@@ -132,8 +240,8 @@ async fn handle_execution_thread(mutex: Arc<Mutex<()>>,
                 account_id: account.account_id,
                 instrument_id: instrument.instrument_id,
                 quantity: 0,
-                cost: 0.0,
-                closed_gain: 0.0,
+                cost: Money::ZERO,
+                closed_gain: Money::ZERO,
                 update_time: current_time_millis(),
                 version_number: 0,
             };
@@ -146,7 +254,7 @@ async fn handle_execution_thread(mutex: Arc<Mutex<()>>,
             }
         }
     };
-    apply_execution(&mut position, execution);
+    apply_execution(&mut position, execution, fee);
 
     match txn.update_position(&mut position).await {
         Ok(_) => {},
@@ -163,25 +271,55 @@ async fn handle_execution_thread(mutex: Arc<Mutex<()>>,
             return;
         },
     };
-    let rest_api_position = match position.to_rest_api_position(account.account_key.as_str(), &instrument_manager) {
+    instrument_manager.get_margin_calls().flag(account.account_key.as_str());
+    instrument_manager.get_valuation_service().on_trade(account.account_id, instrument.instrument_id,
+                                                        trade.create_time, trade.price, trade.quantity);
+    let rest_api_position = match position.to_marked_rest_api_position(account.account_key.as_str(), &instrument_manager) {
         Ok(rest_api_position) => rest_api_position,
         Err(err) => {
             error!("Unable to convert position to rest_api_position: {}", err);
             return;
         },
     };
+    let rest_api_trade = match trade.to_rest_api_trade(&instrument_manager) {
+        Ok(rest_api_trade) => rest_api_trade,
+        Err(err) => {
+            error!("Unable to convert trade to rest_api_trade: {}", err);
+            return;
+        },
+    };
+    let rest_api_order_state = if order_state_changed {
+        match db_order_state.to_rest_api_order_state(account.account_key.as_str(), &instrument_manager) {
+            Ok(rest_api_order_state) => Some(rest_api_order_state),
+            Err(err) => {
+                error!("Unable to convert order_state to rest_api_order_state: {}", err);
+                return;
+            },
+        }
+    } else {
+        None
+    };
     let account_update = AccountUpdate {
         balance: Some(balance.to_rest_api_balance(account.account_key.as_str())),
         position: Some(rest_api_position),
-        trade: None,
-        order_state: None,
+        trade: Some(rest_api_trade),
+        order_state: rest_api_order_state,
+        margin_call: None,
     };
     web_socket_server.send_account_message(account.account_key.as_str(), ACCOUNT_UPDATE_QUEUE_NAME, &account_update);
+    if order_state_changed {
+        tokio::spawn(handle_group_update(dao.clone(), web_socket_server.clone(), instrument_manager.clone(), db_order_state));
+    }
     let end = current_time_millis();
     info!("handle_execution_thread took {} ms", end-start);
 }
 
-fn apply_execution(position: &mut Position, execution: Execution) {
+// The fee is split between the closing and opening parts of the execution: the
+// closing share reduces the realized gain and the opening share adds to cost basis
+fn apply_execution(position: &mut Position, execution: Execution, fee: Money) {
+    if execution.quantity == 0 {
+        return;
+    }
     let mut opening_quantity = execution.quantity;
 
     // closing
@@ -195,29 +333,183 @@ fn apply_execution(position: &mut Position, execution: Execution) {
             opening_quantity = 0;
         }
 
-        let cost_basis = position.cost / position.quantity as f32;
-        let closing_cost = closing_quantity as f32 * cost_basis;
+        // Multiplying before dividing keeps the closing cost exact whenever the cost basis is
+        let closing_cost = position.cost * closing_quantity / position.quantity;
 
         position.quantity += closing_quantity;
         position.cost += closing_cost;
-        position.closed_gain += closing_quantity.neg() as f32 * (execution.price - cost_basis);
+        let closing_fee = fee - fee * opening_quantity.abs() / execution.quantity.abs();
+        position.closed_gain += closing_cost - execution.price * closing_quantity - closing_fee;
         position.update_time = current_time_millis();
 
         if position.quantity == 0 {
-            position.cost = 0f32;   // Clear out cumulative rounding errors
+            position.cost = Money::ZERO;   // Clear out any remainder of an inexact cost basis
         }
     }
     position.quantity += opening_quantity;
-    position.cost += opening_quantity as f32 * execution.price;
+    position.cost += execution.price * opening_quantity;
+    if opening_quantity != 0 {
+        position.cost += fee * opening_quantity.abs() / execution.quantity.abs();
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
+    use crate::dtos::ledger::{JournalType, LedgerAccount};
     use crate::entities::account::Position;
+    use crate::entities::ledger::Journal;
+    use crate::entities::order::{OrderState, OrderStatus};
+    use crate::exchange_interface::mock_exchange::{MOCK_INSTRUMENT_KEY, MOCK_OTHER_INSTRUMENT_KEY};
     use crate::exchange_interface::order::Execution;
+    use crate::money::Money;
+    use crate::persistence::dao::unreachable_dao;
+    use crate::rest_api::cash_api::post_cash_movement;
     use crate::time::current_time_millis;
-    use crate::trade_handling::execution_handling::apply_execution;
+    use crate::trade_handling::execution_handling::{apply_execution, handle_execution_thread};
+    use crate::trade_handling::trading_context::{test_trading_context, test_working_order, TradingContext};
+    use actix_web::http::StatusCode;
+    use actix_web::web::ThinData;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::Mutex;
+
+    // Executions are on the mock exchange's first instrument unless given its other one
+    fn execution(execution_id: i64,
+                 order_state: &OrderState,
+                 price: Money,
+                 quantity: i32) -> Execution {
+        Execution {
+            execution_id,
+            client_order_id: order_state.order.client_order_id.clone(),
+            instrument_id: 1,
+            create_time: current_time_millis(),
+            price,
+            quantity,
+        }
+    }
+
+    async fn cash(context: &TradingContext,
+                  account_key: &String) -> Money {
+        let mut db_connection = context.dao.get_connection().await.unwrap();
+        let txn = context.dao.begin(&mut db_connection).await.unwrap();
+        let balance = txn.get_balance(account_key).await.unwrap();
+        txn.rollback().await.unwrap();
+        balance.cash
+    }
+
+    async fn saved_order_state(context: &TradingContext,
+                               account_key: &String,
+                               ext_order_id: &String) -> OrderState {
+        let mut db_connection = context.dao.get_connection().await.unwrap();
+        let txn = context.dao.begin(&mut db_connection).await.unwrap();
+        let order_state = txn.get_order_by_ext_order_id(account_key, ext_order_id).await.unwrap().unwrap();
+        txn.rollback().await.unwrap();
+        order_state
+    }
+
+    // The first delivery is lost to an unavailable database; the exchange delivers it again, and
+    // then once more. The unique execution id on the trade makes the last delivery a no-op
+    #[test]
+    async fn test_redelivery_after_failed_persist_is_applied_once() {
+        let Some((mut context, account_key)) = test_trading_context().await else { return };
+        let order_state = test_working_order(&mut context, &account_key, serde_json::json!({
+            "price": 10.0, "quantity": 5, "legs": [{"instrument_key": MOCK_INSTRUMENT_KEY, "ratio": 1}]
+        })).await;
+        let starting_cash = cash(&context, &account_key).await;
+        let instrument_id = order_state.order.legs[0].instrument_id;
+        let deliver = |dao| handle_execution_thread(Arc::new(Mutex::new(())), context.web_socket_server.clone(), dao,
+                                                    context.instrument_manager.clone(), execution(42, &order_state, Money::from(10), 5));
+
+        deliver(unreachable_dao()).await;
+        assert_eq!(saved_order_state(&context, &account_key, &order_state.order.ext_order_id).await.filled_quantity, 0);
+
+        deliver(context.dao.clone()).await;
+        deliver(context.dao.clone()).await;
+
+        let mut db_connection = context.dao.get_connection().await.unwrap();
+        let txn = context.dao.begin(&mut db_connection).await.unwrap();
+        assert_eq!(txn.get_trades_for_order(order_state.order.order_id).await.unwrap().len(), 1);
+        assert_eq!(txn.get_position(&account_key, instrument_id).await.unwrap().unwrap().quantity, 5);
+        txn.rollback().await.unwrap();
+        assert_eq!(saved_order_state(&context, &account_key, &order_state.order.ext_order_id).await.filled_quantity, 5);
+        assert_eq!(cash(&context, &account_key).await, starting_cash - Money::from(50));
+    }
+
+    // A spread long one of the first instrument and short two of the other, with the exchange
+    // reporting each leg's executions separately
+    #[test]
+    async fn test_multi_leg_partial_fill() {
+        let Some((mut context, account_key)) = test_trading_context().await else { return };
+        let order_state = test_working_order(&mut context, &account_key, serde_json::json!({
+            "price": 2.0, "quantity": 2, "legs": [{"instrument_key": MOCK_INSTRUMENT_KEY, "ratio": 1},
+                                                  {"instrument_key": MOCK_OTHER_INSTRUMENT_KEY, "ratio": -2}]
+        })).await;
+        let ext_order_id = &order_state.order.ext_order_id;
+        let deliver = |execution| handle_execution_thread(Arc::new(Mutex::new(())), context.web_socket_server.clone(), context.dao.clone(),
+                                                          context.instrument_manager.clone(), execution);
+
+        // The mock exchange sends no order state to move the order on from Pending
+        deliver(execution(1, &order_state, Money::from(10), 2)).await;
+        let saved = saved_order_state(&context, &account_key, ext_order_id).await;
+        assert_eq!((saved.filled_quantity, saved.order_status), (0, OrderStatus::Pending));
+
+        deliver(Execution { instrument_id: 2, ..execution(2, &order_state, Money::from(4), -3) }).await;
+        let saved = saved_order_state(&context, &account_key, ext_order_id).await;
+        assert_eq!((saved.filled_quantity, saved.order_status), (1, OrderStatus::PartiallyFilled));
+        assert_eq!(saved.average_fill_price, Money::from(2));
+
+        deliver(Execution { instrument_id: 2, ..execution(3, &order_state, Money::from(5), -1) }).await;
+        let saved = saved_order_state(&context, &account_key, ext_order_id).await;
+        assert_eq!((saved.filled_quantity, saved.order_status), (2, OrderStatus::Filled));
+        assert_eq!(saved.average_fill_price, Money::new(15, 1));
+    }
+
+    // Each side holds its balance update open while the other starts. The one that starts second
+    // waits for the first to commit rather than failing its version check and losing its posting
+    #[test]
+    async fn test_deposit_and_execution_interleave() {
+        let Some((mut context, account_key)) = test_trading_context().await else { return };
+        let order_state = test_working_order(&mut context, &account_key, serde_json::json!({
+            "price": 10.0, "quantity": 2, "legs": [{"instrument_key": MOCK_INSTRUMENT_KEY, "ratio": 1}]
+        })).await;
+        let starting_cash = cash(&context, &account_key).await;
+
+        // A deposit is in flight when the execution arrives
+        let mut db_connection = context.dao.get_connection().await.unwrap();
+        let txn = context.dao.begin(&mut db_connection).await.unwrap();
+        let mut balance = txn.get_balance_for_update(&account_key).await.unwrap();
+        let deposit = Journal::cash_movement(JournalType::Deposit, balance.account_id, Money::from(1000),
+                                             LedgerAccount::ExternalFunds, "Deposit".to_string(), current_time_millis());
+        txn.post_journal(&mut balance, deposit).await.unwrap();
+        let execution_task = actix_web::rt::spawn(handle_execution_thread(Arc::new(Mutex::new(())), context.web_socket_server.clone(),
+                                                                          context.dao.clone(), context.instrument_manager.clone(),
+                                                                          execution(1, &order_state, Money::from(10), 1)));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!execution_task.is_finished());
+        txn.commit().await.unwrap();
+        execution_task.await.unwrap();
+        assert_eq!(cash(&context, &account_key).await, starting_cash + Money::from(1000) - Money::from(10));
+
+        // An execution is in flight when the deposit arrives
+        let txn = context.dao.begin(&mut db_connection).await.unwrap();
+        let mut balance = txn.get_balance_for_update(&account_key).await.unwrap();
+        let fee = Journal::cash_movement(JournalType::Fee, balance.account_id, Money::from(-1),
+                                         LedgerAccount::FeeIncome, "Fee".to_string(), current_time_millis());
+        txn.post_journal(&mut balance, fee).await.unwrap();
+        let dao = context.dao.clone();
+        let mut web_socket_server = ThinData(context.web_socket_server.clone());
+        let deposit_account_key = account_key.clone();
+        let deposit_task = actix_web::rt::spawn(async move {
+            post_cash_movement(&dao, &mut web_socket_server, &deposit_account_key, JournalType::Deposit, Money::from(500),
+                               "Deposit".to_string(), false).await.status()
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!deposit_task.is_finished());
+        txn.commit().await.unwrap();
+        assert_eq!(deposit_task.await.unwrap(), StatusCode::OK);
+        assert_eq!(cash(&context, &account_key).await, starting_cash + Money::from(1000) - Money::from(10) - Money::from(1) + Money::from(500));
+    }
 
     #[test]
     async fn test_flat_position_empty_execution() {
@@ -226,22 +518,49 @@ mod tests {
             account_id: 0,
             instrument_id: 0,
             quantity: 0,
-            cost: 0.0,
-            closed_gain: 0.0,
+            cost: Money::ZERO,
+            closed_gain: Money::ZERO,
             update_time: current_time_millis(),
             version_number: 0,
         };
         let execution = Execution {
+            execution_id: 0,
             client_order_id: "".to_string(),
             instrument_id: 0,
             create_time: 0,
-            price: 0.0,
+            price: Money::ZERO,
             quantity: 0,
         };
-        apply_execution(&mut position, execution);
+        apply_execution(&mut position, execution, Money::ZERO);
         assert_eq!(position.quantity, 0);
-        assert_eq!(round(position.cost, 2), round(0f32, 2));
-        assert_eq!(round(position.closed_gain, 2), round(0.0, 2));
+        assert_eq!(position.cost, Money::ZERO);
+        assert_eq!(position.closed_gain, Money::ZERO);
+    }
+
+    #[test]
+    async fn test_open_position_empty_execution() {
+        let mut position = Position {
+            position_id: 0,
+            account_id: 0,
+            instrument_id: 0,
+            quantity: 4,
+            cost: Money::from(40),
+            closed_gain: Money::ZERO,
+            update_time: current_time_millis(),
+            version_number: 0,
+        };
+        let execution = Execution {
+            execution_id: 0,
+            client_order_id: "".to_string(),
+            instrument_id: 0,
+            create_time: 0,
+            price: Money::from(11),
+            quantity: 0,
+        };
+        apply_execution(&mut position, execution, Money::from(1));
+        assert_eq!(position.quantity, 4);
+        assert_eq!(position.cost, Money::from(40));
+        assert_eq!(position.closed_gain, Money::ZERO);
     }
 
     #[test]
@@ -251,22 +570,23 @@ mod tests {
             account_id: 0,
             instrument_id: 0,
             quantity: 0,
-            cost: 0.0,
-            closed_gain: 0.0,
+            cost: Money::ZERO,
+            closed_gain: Money::ZERO,
             update_time: current_time_millis(),
             version_number: 0,
         };
         let execution = Execution {
+            execution_id: 0,
             client_order_id: "".to_string(),
             instrument_id: 0,
             create_time: 0,
-            price: 3.3,
+            price: Money::new(33, 1),
             quantity: 3,
         };
-        apply_execution(&mut position, execution);
+        apply_execution(&mut position, execution, Money::ZERO);
         assert_eq!(position.quantity, 3);
-        assert_eq!(round(position.cost, 2), round(3.3 * 3f32, 2));
-        assert_eq!(round(position.closed_gain, 2), round(0.0, 2));
+        assert_eq!(position.cost, Money::new(33, 1) * 3);
+        assert_eq!(position.closed_gain, Money::ZERO);
     }
 
     #[test]
@@ -276,22 +596,23 @@ mod tests {
             account_id: 0,
             instrument_id: 0,
             quantity: 0,
-            cost: 0.0,
-            closed_gain: 0.0,
+            cost: Money::ZERO,
+            closed_gain: Money::ZERO,
             update_time: current_time_millis(),
             version_number: 0,
         };
         let execution = Execution {
+            execution_id: 0,
             client_order_id: "".to_string(),
             instrument_id: 0,
             create_time: 0,
-            price: 3.2,
+            price: Money::new(32, 1),
             quantity: -7,
         };
-        apply_execution(&mut position, execution);
+        apply_execution(&mut position, execution, Money::ZERO);
         assert_eq!(position.quantity, -7);
-        assert_eq!(round(position.cost, 2), round(3.2 * -7f32, 2));
-        assert_eq!(round(position.closed_gain, 2), round(0.0, 2));
+        assert_eq!(position.cost, Money::new(32, 1) * -7);
+        assert_eq!(position.closed_gain, Money::ZERO);
     }
 
     #[test]
@@ -301,22 +622,23 @@ mod tests {
             account_id: 0,
             instrument_id: 0,
             quantity: 5,
-            cost: 20f32,
-            closed_gain: 0.0,
+            cost: Money::from(20),
+            closed_gain: Money::ZERO,
             update_time: current_time_millis(),
             version_number: 0,
         };
         let execution = Execution {
+            execution_id: 0,
             client_order_id: "".to_string(),
             instrument_id: 0,
             create_time: 0,
-            price: 30f32,
+            price: Money::from(30),
             quantity: 2,
         };
-        apply_execution(&mut position, execution);
+        apply_execution(&mut position, execution, Money::ZERO);
         assert_eq!(position.quantity, 7);
-        assert_eq!(round(position.cost, 2), round(80f32, 2));
-        assert_eq!(round(position.closed_gain, 2), round(0.0, 2));
+        assert_eq!(position.cost, Money::from(80));
+        assert_eq!(position.closed_gain, Money::ZERO);
     }
 
     #[test]
@@ -326,22 +648,23 @@ mod tests {
             account_id: 0,
             instrument_id: 0,
             quantity: 7,
-            cost: 70f32,
-            closed_gain: 0.0,
+            cost: Money::from(70),
+            closed_gain: Money::ZERO,
             update_time: current_time_millis(),
             version_number: 0,
         };
         let execution = Execution {
+            execution_id: 0,
             client_order_id: "".to_string(),
             instrument_id: 0,
             create_time: 0,
-            price: 10f32,
+            price: Money::from(10),
             quantity: -2,
         };
-        apply_execution(&mut position, execution);
+        apply_execution(&mut position, execution, Money::ZERO);
         assert_eq!(position.quantity, 5);
-        assert_eq!(round(position.cost, 2), round(50f32, 2));
-        assert_eq!(round(position.closed_gain, 2), round(0.0, 2));
+        assert_eq!(position.cost, Money::from(50));
+        assert_eq!(position.closed_gain, Money::ZERO);
     }
 
     #[test]
@@ -351,22 +674,23 @@ mod tests {
             account_id: 0,
             instrument_id: 0,
             quantity: -5,
-            cost: -50f32,
-            closed_gain: 0.0,
+            cost: Money::from(-50),
+            closed_gain: Money::ZERO,
             update_time: current_time_millis(),
             version_number: 0,
         };
         let execution = Execution {
+            execution_id: 0,
             client_order_id: "".to_string(),
             instrument_id: 0,
             create_time: 0,
-            price: 10f32,
+            price: Money::from(10),
             quantity: 2,
         };
-        apply_execution(&mut position, execution);
+        apply_execution(&mut position, execution, Money::ZERO);
         assert_eq!(position.quantity, -3);
-        assert_eq!(round(position.cost, 2), round(-30f32, 2));
-        assert_eq!(round(position.closed_gain, 2), round(0.0, 2));
+        assert_eq!(position.cost, Money::from(-30));
+        assert_eq!(position.closed_gain, Money::ZERO);
     }
 
     #[test]
@@ -376,22 +700,23 @@ mod tests {
             account_id: 0,
             instrument_id: 0,
             quantity: -9,
-            cost: -90f32,
-            closed_gain: 0.0,
+            cost: Money::from(-90),
+            closed_gain: Money::ZERO,
             update_time: current_time_millis(),
             version_number: 0,
         };
         let execution = Execution {
+            execution_id: 0,
             client_order_id: "".to_string(),
             instrument_id: 0,
             create_time: 0,
-            price: 20f32,
+            price: Money::from(20),
             quantity: -2,
         };
-        apply_execution(&mut position, execution);
+        apply_execution(&mut position, execution, Money::ZERO);
         assert_eq!(position.quantity, -11);
-        assert_eq!(round(position.cost, 2), round(-130f32, 2));
-        assert_eq!(round(position.closed_gain, 2), round(0.0, 2));
+        assert_eq!(position.cost, Money::from(-130));
+        assert_eq!(position.closed_gain, Money::ZERO);
     }
 
     #[test]
@@ -401,22 +726,23 @@ mod tests {
             account_id: 0,
             instrument_id: 0,
             quantity: 7,
-            cost: 70f32,
-            closed_gain: 0.0,
+            cost: Money::from(70),
+            closed_gain: Money::ZERO,
             update_time: current_time_millis(),
             version_number: 0,
         };
         let execution = Execution {
+            execution_id: 0,
             client_order_id: "".to_string(),
             instrument_id: 0,
             create_time: 0,
-            price: 12f32,
+            price: Money::from(12),
             quantity: -9,
         };
-        apply_execution(&mut position, execution);
+        apply_execution(&mut position, execution, Money::ZERO);
         assert_eq!(position.quantity, -2);
-        assert_eq!(round(position.cost, 2), round(-24f32, 2));
-        assert_eq!(round(position.closed_gain, 2), round(14f32, 2));
+        assert_eq!(position.cost, Money::from(-24));
+        assert_eq!(position.closed_gain, Money::from(14));
     }
 
     #[test]
@@ -426,31 +752,48 @@ mod tests {
             account_id: 0,
             instrument_id: 0,
             quantity: -5,
-            cost: -50f32,
-            closed_gain: 0.0,
+            cost: Money::from(-50),
+            closed_gain: Money::ZERO,
             update_time: current_time_millis(),
             version_number: 0,
         };
         let execution = Execution {
+            execution_id: 0,
             client_order_id: "".to_string(),
             instrument_id: 0,
             create_time: 0,
-            price: 12f32,
+            price: Money::from(12),
             quantity: 9,
         };
-        apply_execution(&mut position, execution);
+        apply_execution(&mut position, execution, Money::ZERO);
         assert_eq!(position.quantity, 4);
-        assert_eq!(round(position.cost, 2), round(48f32, 2));
-        assert_eq!(round(position.closed_gain, 2), round(-10f32, 2));
+        assert_eq!(position.cost, Money::from(48));
+        assert_eq!(position.closed_gain, Money::from(-10));
     }
 
-    fn round(val: f32, digits: u32) -> f32{
-        let power = 10_i8.pow(digits) as u8;
-        let pw = power as f32;
-        let big_val = val * pw;
-        let truncated_val = big_val as i64;
-        let rounded_val = truncated_val as f32 / pw ;
-        println!("rounded_val = {}", rounded_val);
-        rounded_val
+    #[test]
+    async fn test_fee_split_between_closing_and_opening() {
+        let mut position = Position {
+            position_id: 0,
+            account_id: 0,
+            instrument_id: 0,
+            quantity: 7,
+            cost: Money::from(70),
+            closed_gain: Money::ZERO,
+            update_time: current_time_millis(),
+            version_number: 0,
+        };
+        let execution = Execution {
+            execution_id: 0,
+            client_order_id: "".to_string(),
+            instrument_id: 0,
+            create_time: 0,
+            price: Money::from(12),
+            quantity: -9,
+        };
+        apply_execution(&mut position, execution, Money::from(9));
+        assert_eq!(position.quantity, -2);
+        assert_eq!(position.cost, Money::from(-22));
+        assert_eq!(position.closed_gain, Money::from(7));
     }
 }
@@ -0,0 +1,234 @@
+use crate::constants::ACCOUNT_UPDATE_QUEUE_NAME;
+use crate::entities::order::{is_order_status_held, OrderState, OrderStatus, TimeInForce};
+use crate::exchange_interface;
+use crate::instrument_manager::InstrumentManager;
+use crate::persistence::dao::Dao;
+use crate::time::{current_time_millis, millis_until_next_hour_utc};
+use crate::trade_handling::order_state_handling::update_order_state_loop;
+use crate::trade_handling::order_groups::handle_group_update;
+use crate::trade_handling::updates::AccountUpdate;
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use log::{error, info};
+use std::time::Duration;
+
+// Exchanges that accept a time in force expire their own orders; for the rest the
+// broker cancels lapsed orders itself and records them as Expired
+pub fn start_expiry_sweeper(dao: Dao,
+                            web_socket_server: WebSocketServer,
+                            instrument_manager: InstrumentManager,
+                            interval_millis: u64,
+                            end_of_day_hour_utc: u8) {
+    tokio::spawn(expiry_sweep_loop(dao, web_socket_server, instrument_manager, interval_millis, end_of_day_hour_utc));
+}
+
+async fn expiry_sweep_loop(dao: Dao,
+                           web_socket_server: WebSocketServer,
+                           instrument_manager: InstrumentManager,
+                           interval_millis: u64,
+                           end_of_day_hour_utc: u8) {
+    loop {
+        tokio::time::sleep(Duration::from_millis(interval_millis)).await;
+        match sweep_expired_orders(&dao, &web_socket_server, &instrument_manager, end_of_day_hour_utc).await {
+            Ok(_) => {},
+            Err(err) => error!("Unable to sweep expired orders: {}", err),
+        }
+    }
+}
+
+async fn sweep_expired_orders(dao: &Dao,
+                              web_socket_server: &WebSocketServer,
+                              instrument_manager: &InstrumentManager,
+                              end_of_day_hour_utc: u8) -> Result<(), Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let viable_orders = match txn.get_viable_orders().await {
+        Ok(viable_orders) => viable_orders,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get viable orders: {}", dao_error)),
+    };
+    match txn.rollback().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+    };
+
+    let now = current_time_millis();
+    for order_state in viable_orders.values().filter(|order_state| is_expiry_due(order_state, now, end_of_day_hour_utc)) {
+        match expire_order(web_socket_server, dao, instrument_manager, order_state).await {
+            Ok(_) => {},
+            Err(err) => error!("Unable to expire order {}: {}", order_state.order.client_order_id, err),
+        }
+    }
+    Ok(())
+}
+
+async fn expire_order(web_socket_server: &WebSocketServer,
+                      dao: &Dao,
+                      instrument_manager: &InstrumentManager,
+                      order_state: &OrderState) -> Result<(), Error> {
+    let instrument_id = match order_state.order.legs.first() {
+        Some(leg) => leg.instrument_id,
+        None => return Err(anyhow::anyhow!("Order has no legs")),
+    };
+    let instrument = match instrument_manager.get_instrument(instrument_id)? {
+        Some(instrument) => instrument,
+        None => return Err(anyhow::anyhow!("No instrument with id: {}", instrument_id)),
+    };
+    if is_order_status_held(&order_state.order_status) {
+        return expire_held_order(web_socket_server, dao, instrument_manager, order_state).await;
+    }
+    if instrument_manager.get_exchange_for_instrument(&instrument)?.supports_time_in_force {
+        return Ok(());
+    }
+    let exchange_client = instrument_manager.get_exchange_client_for_instrument(&instrument)?;
+    let mutex = instrument_manager.get_exchange_mutex(instrument.exchange_id)?;
+
+    info!("Expiring {} order {}", order_state.order.time_in_force, order_state.order.client_order_id);
+    let mut exchange_order_state = match exchange_client.cancel_order(order_state.order.client_order_id.clone()).await {
+        Ok(exchange_order_state) => exchange_order_state,
+        Err(exchange_error) => return Err(anyhow::anyhow!("Could not cancel order on exchange: {}", exchange_error)),
+    };
+    if exchange_order_state.order_status == exchange_interface::order::OrderStatus::Canceled {
+        exchange_order_state.order_status = exchange_interface::order::OrderStatus::Expired;
+    }
+    // Must win over the exchange's own Canceled notification for the same order
+    exchange_order_state.update_time = current_time_millis();
+    update_order_state_loop(mutex, web_socket_server.clone(), dao.clone(), instrument_manager.clone(), exchange_order_state).await;
+    Ok(())
+}
+
+// Held orders never reached the exchange, so the broker always expires them itself
+async fn expire_held_order(web_socket_server: &WebSocketServer,
+                           dao: &Dao,
+                           instrument_manager: &InstrumentManager,
+                           order_state: &OrderState) -> Result<(), Error> {
+    instrument_manager.get_trigger_engine().remove(order_state)?;
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let mut current_order_state = match txn.get_order_by_client_order_id(&order_state.order.client_order_id).await {
+        Ok(Some(current_order_state)) => current_order_state,
+        Ok(None) => return Err(anyhow::anyhow!("Order disappeared before expiry")),
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get order: {}", dao_error)),
+    };
+    if !is_order_status_held(&current_order_state.order_status) {
+        return Ok(());
+    }
+    info!("Expiring held {} order {}", current_order_state.order.time_in_force, current_order_state.order.client_order_id);
+    current_order_state.order_status = OrderStatus::Expired;
+    current_order_state.update_time = current_time_millis();
+    match txn.update_order(&mut current_order_state).await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not update order: {}", dao_error)),
+    };
+    let account = match txn.get_account(current_order_state.order.account_id).await {
+        Ok(Some(account)) => account,
+        Ok(None) => return Err(anyhow::anyhow!("No account for id: {}", current_order_state.order.account_id)),
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get account: {}", dao_error)),
+    };
+    match txn.commit().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+    };
+    let account_update = AccountUpdate {
+        balance: None,
+        position: None,
+        trade: None,
+        order_state: Some(current_order_state.to_rest_api_order_state(account.account_key.as_str(), instrument_manager)?),
+        margin_call: None,
+    };
+    web_socket_server.clone().send_account_message(account.account_key.as_str(), ACCOUNT_UPDATE_QUEUE_NAME, &account_update);
+    tokio::spawn(handle_group_update(dao.clone(), web_socket_server.clone(), instrument_manager.clone(), current_order_state));
+    Ok(())
+}
+
+// Only orders the exchange has acknowledged are swept; Pending ones are still in flight
+// and PendingCancel ones are already on their way out. Held orders only lapse by date,
+// since immediate instructions apply once they are released.
+fn is_expiry_due(order_state: &OrderState,
+                 now: i64,
+                 end_of_day_hour_utc: u8) -> bool {
+    match (&order_state.order_status, &order_state.order.time_in_force) {
+        (OrderStatus::Open | OrderStatus::PartiallyFilled, _) => {},
+        (OrderStatus::PendingTrigger | OrderStatus::PendingParent, TimeInForce::Day | TimeInForce::Gtd) => {},
+        _ => return false,
+    }
+    match order_state.order.time_in_force {
+        TimeInForce::Day => {
+            let create_time = order_state.order.create_time;
+            now >= create_time + millis_until_next_hour_utc(create_time, end_of_day_hour_utc)
+        },
+        TimeInForce::Gtd => match order_state.order.expire_time {
+            Some(expire_time) => now >= expire_time,
+            None => false,
+        },
+        TimeInForce::Ioc | TimeInForce::Fok => true,
+        TimeInForce::Gtc => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::order::{Order, OrderState, OrderStatus, OrderType, TimeInForce};
+    use crate::money::Money;
+    use crate::trade_handling::expiry::is_expiry_due;
+
+    const MIDNIGHT: i64 = 1_709_251_200_000;
+    const HOUR: i64 = 3600 * 1000;
+
+    fn order_state(order_status: OrderStatus, time_in_force: TimeInForce, expire_time: Option<i64>) -> OrderState {
+        OrderState {
+            order: Order {
+                order_id: 0,
+                account_id: 0,
+                order_number: 1,
+                ext_order_id: "ext".to_string(),
+                client_order_id: "client".to_string(),
+                create_time: MIDNIGHT + 10 * HOUR,
+                price: Money::from(10),
+                quantity: 1,
+                legs: vec![],
+                time_in_force,
+                expire_time,
+                order_type: OrderType::Limit,
+                stop_price: None,
+                trailing_offset: None,
+            },
+            update_time: 0,
+            order_status,
+            version_number: 0,
+            reject_reason: None,
+            filled_quantity: 0,
+            average_fill_price: Money::ZERO,
+        }
+    }
+
+    #[test]
+    async fn test_day_order_expires_at_end_of_day() {
+        let day_order = order_state(OrderStatus::Open, TimeInForce::Day, None);
+        assert!(!is_expiry_due(&day_order, MIDNIGHT + 21 * HOUR - 1, 21));
+        assert!(is_expiry_due(&day_order, MIDNIGHT + 21 * HOUR, 21));
+        assert!(!is_expiry_due(&order_state(OrderStatus::Open, TimeInForce::Gtc, None), MIDNIGHT + 100 * HOUR, 21));
+    }
+
+    #[test]
+    async fn test_gtd_and_ioc_expiry() {
+        let gtd_order = order_state(OrderStatus::PartiallyFilled, TimeInForce::Gtd, Some(MIDNIGHT + 12 * HOUR));
+        assert!(!is_expiry_due(&gtd_order, MIDNIGHT + 11 * HOUR, 21));
+        assert!(is_expiry_due(&gtd_order, MIDNIGHT + 12 * HOUR, 21));
+        assert!(is_expiry_due(&order_state(OrderStatus::Open, TimeInForce::Ioc, None), MIDNIGHT + 10 * HOUR, 21));
+        assert!(!is_expiry_due(&order_state(OrderStatus::Pending, TimeInForce::Ioc, None), MIDNIGHT + 10 * HOUR, 21));
+        assert!(!is_expiry_due(&order_state(OrderStatus::PendingTrigger, TimeInForce::Ioc, None), MIDNIGHT + 10 * HOUR, 21));
+        assert!(is_expiry_due(&order_state(OrderStatus::PendingTrigger, TimeInForce::Gtd, Some(MIDNIGHT + 12 * HOUR)), MIDNIGHT + 12 * HOUR, 21));
+    }
+}
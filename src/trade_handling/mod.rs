@@ -1,3 +1,11 @@
 pub(crate) mod order_state_handling;
 pub(crate) mod updates;
-pub(crate) mod execution_handling;
\ No newline at end of file
+pub(crate) mod execution_handling;
+pub(crate) mod reconciliation;
+pub(crate) mod expiry;
+pub(crate) mod trigger_engine;
+pub(crate) mod order_actions;
+pub(crate) mod order_entry;
+pub(crate) mod order_groups;
+pub(crate) mod outbox;
+pub(crate) mod trading_context;
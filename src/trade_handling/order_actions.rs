@@ -0,0 +1,248 @@
+use crate::constants::ACCOUNT_UPDATE_QUEUE_NAME;
+use crate::converters::order_converters::order_status_to_rest_api_order_status;
+use crate::dtos;
+use crate::entities::order::{is_order_status_held, OrderState, OrderStatus};
+use crate::exchange_interface;
+use crate::exchange_interface::exchange_client::ExchangeClient;
+use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
+use crate::persistence::dao::{Dao, DaoError};
+use crate::time::current_time_millis;
+use crate::trade_handling::outbox::{record_delivered, record_failed_attempt};
+use crate::trade_handling::updates::AccountUpdate;
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use deadpool_postgres::Object;
+use log::error;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub(crate) const EXCHANGE_REJECT: &str = "Exchange reject";
+
+// Sends a saved Pending order to the exchange at the given price. We'll get async notifications
+// for all status updates other than Rejected, which is recorded here. If the exchange cannot be
+// reached the order stays Pending and its outbox entry is left for the outbox worker to retry
+pub(crate) async fn submit_pending_order(dao: &Dao,
+                                         web_socket_server: &mut WebSocketServer,
+                                         instrument_manager: &InstrumentManager,
+                                         account_key: &str,
+                                         order_state: OrderState,
+                                         price: Money) -> Result<OrderState, Error> {
+    let (_, exchange_client) = exchange_client_for_order(instrument_manager, &order_state)?;
+    let exchange_order = order_state.order.to_exchange_order(instrument_manager, price)?;
+    let exchange_order_state = match exchange_client.submit_order(exchange_order).await {
+        Ok(exchange_order_state) => exchange_order_state,
+        Err(exchange_error) => {
+            record_failed_attempt(dao, order_state.order.order_id, exchange_error.to_string()).await;
+            return Ok(order_state);
+        },
+    };
+    record_delivered(dao, order_state.order.order_id).await;
+    if exchange_order_state.order_status != exchange_interface::order::OrderStatus::Rejected {
+        return Ok(order_state);
+    }
+    record_exchange_reject(dao, web_socket_server, instrument_manager, account_key, order_state, EXCHANGE_REJECT).await
+}
+
+// The orders bound for one exchange, with their positions in the caller's list
+type ExchangeBatch = (Arc<ExchangeClient>, Vec<(usize, OrderState)>, Vec<exchange_interface::order::Order>);
+
+// Sends saved Pending orders at their own prices, in one request per exchange. Results come
+// back in the order given; the orders of a failed request stay Pending for the outbox worker
+pub(crate) async fn submit_pending_orders(dao: &Dao,
+                                          web_socket_server: &mut WebSocketServer,
+                                          instrument_manager: &InstrumentManager,
+                                          account_key: &str,
+                                          order_states: Vec<OrderState>) -> Vec<Result<OrderState, Error>> {
+    let mut results: Vec<Option<Result<OrderState, Error>>> = order_states.iter().map(|_| None).collect();
+    let mut batches: HashMap<i32, ExchangeBatch> = HashMap::new();
+    for (index, order_state) in order_states.into_iter().enumerate() {
+        let exchange_client_and_order = exchange_client_for_order(instrument_manager, &order_state)
+            .and_then(|(exchange_id, exchange_client)| {
+                let exchange_order = order_state.order.to_exchange_order(instrument_manager, order_state.order.price)?;
+                Ok((exchange_id, exchange_client, exchange_order))
+            });
+        match exchange_client_and_order {
+            Ok((exchange_id, exchange_client, exchange_order)) => {
+                let batch = batches.entry(exchange_id).or_insert_with(|| (exchange_client, Vec::new(), Vec::new()));
+                batch.1.push((index, order_state));
+                batch.2.push(exchange_order);
+            },
+            Err(error) => results[index] = Some(Err(error)),
+        }
+    }
+
+    for (_, (exchange_client, batch, exchange_orders)) in batches {
+        let exchange_order_states: HashMap<String, exchange_interface::order::OrderState> = match exchange_client.submit_orders(exchange_orders).await {
+            Ok(exchange_order_states) => exchange_order_states.into_iter()
+                .map(|exchange_order_state| (exchange_order_state.order.client_order_id.clone(), exchange_order_state))
+                .collect(),
+            Err(exchange_error) => {
+                for (index, order_state) in batch {
+                    record_failed_attempt(dao, order_state.order.order_id, exchange_error.to_string()).await;
+                    results[index] = Some(Ok(order_state));
+                }
+                continue;
+            }
+        };
+        for (index, order_state) in batch {
+            let result = match exchange_order_states.get(&order_state.order.client_order_id) {
+                Some(exchange_order_state) => {
+                    record_delivered(dao, order_state.order.order_id).await;
+                    if exchange_order_state.order_status == exchange_interface::order::OrderStatus::Rejected {
+                        record_exchange_reject(dao, web_socket_server, instrument_manager, account_key, order_state, EXCHANGE_REJECT).await
+                    } else {
+                        Ok(order_state)
+                    }
+                },
+                None => {
+                    record_failed_attempt(dao, order_state.order.order_id, "Exchange returned no state for the order".to_string()).await;
+                    Ok(order_state)
+                },
+            };
+            results[index] = Some(result);
+        }
+    }
+    results.into_iter()
+        .map(|result| result.unwrap_or_else(|| Err(anyhow::anyhow!("Order was not submitted"))))
+        .collect()
+}
+
+pub(crate) fn exchange_client_for_order(instrument_manager: &InstrumentManager,
+                                        order_state: &OrderState) -> Result<(i32, Arc<ExchangeClient>), Error> {
+    let instrument_id = match order_state.order.legs.first() {
+        Some(leg) => leg.instrument_id,
+        None => return Err(anyhow::anyhow!("Order {} has no legs", order_state.order.client_order_id)),
+    };
+    let instrument = match instrument_manager.get_instrument(instrument_id)? {
+        Some(instrument) => instrument,
+        None => return Err(anyhow::anyhow!("No instrument with id: {}", instrument_id)),
+    };
+    let exchange_client = instrument_manager.get_exchange_client_for_instrument(&instrument)?;
+    Ok((instrument.exchange_id, exchange_client))
+}
+
+pub(crate) async fn record_exchange_reject(dao: &Dao,
+                                           web_socket_server: &mut WebSocketServer,
+                                           instrument_manager: &InstrumentManager,
+                                           account_key: &str,
+                                           mut order_state: OrderState,
+                                           reject_reason: &str) -> Result<OrderState, Error> {
+    order_state.order_status = OrderStatus::Rejected;
+    order_state.reject_reason = Some(reject_reason.to_string());
+    order_state.update_time = current_time_millis();
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    match txn.update_order(&mut order_state).await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not update order: {}", dao_error)),
+    };
+    match txn.commit().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+    };
+    send_order_state(web_socket_server, instrument_manager, account_key, &order_state)?;
+    Ok(order_state)
+}
+
+// Held orders never reached the exchange and are canceled here directly. If a held order is
+// being released right now, whichever of the cancel and the release commits first wins.
+pub(crate) async fn cancel_order_state(dao: &Dao,
+                                       web_socket_server: &mut WebSocketServer,
+                                       instrument_manager: &InstrumentManager,
+                                       account_key: &str,
+                                       order_state: OrderState) -> Result<OrderState, Error> {
+    let (order_state, _) = cancel_order_state_with_remaining(dao, web_socket_server, instrument_manager, account_key, order_state).await?;
+    Ok(order_state)
+}
+
+// Also gives the exchange's remaining quantity on the cancel; None for an order the broker held
+pub(crate) async fn cancel_order_state_with_remaining(dao: &Dao,
+                                                      web_socket_server: &mut WebSocketServer,
+                                                      instrument_manager: &InstrumentManager,
+                                                      account_key: &str,
+                                                      mut order_state: OrderState) -> Result<(OrderState, Option<i32>), Error> {
+    let held = is_order_status_held(&order_state.order_status);
+    if held {
+        instrument_manager.get_trigger_engine().remove(&order_state)?;
+        order_state.order_status = OrderStatus::Canceled;
+        order_state.update_time = current_time_millis();
+    } else {
+        order_state.order_status = OrderStatus::PendingCancel;
+    }
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    match txn.update_order(&mut order_state).await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not update order: {}", dao_error)),
+    };
+    match txn.commit().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+    };
+    send_order_state(web_socket_server, instrument_manager, account_key, &order_state)?;
+    if held {
+        return Ok((order_state, None));
+    }
+
+    let instrument_id = match order_state.order.legs.first() {
+        Some(leg) => leg.instrument_id,
+        None => return Err(anyhow::anyhow!("Order {} has no legs", order_state.order.client_order_id)),
+    };
+    let instrument = match instrument_manager.get_instrument(instrument_id)? {
+        Some(instrument) => instrument,
+        None => return Err(anyhow::anyhow!("No instrument with id: {}", instrument_id)),
+    };
+    let exchange_client = instrument_manager.get_exchange_client_for_instrument(&instrument)?;
+    let exchange_order_state = match exchange_client.cancel_order(order_state.order.client_order_id.clone()).await {
+        Ok(exchange_order_state) => exchange_order_state,
+        Err(exchange_error) => return Err(anyhow::anyhow!("Could not cancel order on exchange: {}", exchange_error)),
+    };
+
+    order_state.order_status = order_status_to_rest_api_order_status(exchange_order_state.order_status);
+    order_state.apply_remaining_quantity(exchange_order_state.remaining_quantity);
+    order_state.update_time = current_time_millis();
+    // The exchange's own notification of the cancel will bring us up to date if this fails
+    match update_order(dao, &mut db_connection, &mut order_state).await {
+        Ok(_) => {},
+        Err(dao_error) => error!("Could not update order state: {}", dao_error),
+    };
+    send_order_state(web_socket_server, instrument_manager, account_key, &order_state)?;
+    Ok((order_state, Some(exchange_order_state.remaining_quantity)))
+}
+
+async fn update_order(dao: &Dao,
+                      db_connection: &mut Object,
+                      order_state: &mut OrderState) -> Result<(), DaoError> {
+    let txn = dao.begin(db_connection).await?;
+    txn.update_order(order_state).await?;
+    txn.commit().await
+}
+
+pub(crate) fn send_order_state(web_socket_server: &mut WebSocketServer,
+                               instrument_manager: &InstrumentManager,
+                               account_key: &str,
+                               order_state: &OrderState) -> Result<dtos::order::OrderState, Error> {
+    let rest_api_order_state = order_state.to_rest_api_order_state(account_key, instrument_manager)?;
+    let account_update = AccountUpdate {
+        balance: None,
+        position: None,
+        trade: None,
+        order_state: Some(rest_api_order_state.clone()),
+        margin_call: None,
+    };
+    web_socket_server.send_account_message(account_key, ACCOUNT_UPDATE_QUEUE_NAME, &account_update);
+    Ok(rest_api_order_state)
+}
@@ -0,0 +1,685 @@
+use crate::converters::order_converters::order_status_to_rest_api_order_status;
+use crate::dtos::exchange::InstrumentStatus;
+use crate::dtos::order::{is_order_status_held, is_order_status_viable, Order, VettingResult};
+use crate::entities;
+use crate::entities::account::Position;
+use crate::entities::exchange::Instrument;
+use crate::entities::order::{OrderState, OrderStatus, OrderType};
+use crate::entities::routing::RoutedChild;
+use crate::exchange_interface;
+use crate::instrument_manager::InstrumentManager;
+use crate::margin::margin_calls::reduces_risk;
+use crate::money::Money;
+use crate::persistence::dao::Dao;
+use crate::time::current_time_millis;
+use crate::trade_handling::order_actions::{cancel_order_state, send_order_state, submit_pending_order};
+use crate::trade_handling::order_groups::handle_group_update;
+use crate::trade_handling::outbox::new_outbox_entry;
+use crate::trade_handling::reconciliation::replay_missing_executions;
+use crate::trade_handling::trading_context::TradingContext;
+use crate::validator::validator::{normalize_leg_ratios, Validator};
+use crate::vetting::vetter::reject;
+use crate::vetting::vetter_registry::VetterRegistry;
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use log::{error, warn};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+// Order entry shared by the REST API and the websocket. Callers check privileges first
+// and turn an OrderEntryError into their own kind of response.
+pub(crate) enum OrderEntryError {
+    Rejected(VettingResult),
+    PreconditionFailed(String),
+    BadRequest(String),
+    NotFound,
+    // The order filled or changed on the exchange before it could be replaced
+    Conflict(OrderState),
+    Failed(Error),
+}
+
+impl fmt::Display for OrderEntryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderEntryError::Rejected(check_result) => write!(f, "Rejected: {}", check_result.reject_reason.clone().unwrap_or_default()),
+            OrderEntryError::PreconditionFailed(reason) => reason.fmt(f),
+            OrderEntryError::BadRequest(reason) => reason.fmt(f),
+            OrderEntryError::NotFound => "Order not found".fmt(f),
+            OrderEntryError::Conflict(_) => "Order changed before it could be replaced".fmt(f),
+            OrderEntryError::Failed(error) => error.fmt(f),
+        }
+    }
+}
+
+pub(crate) async fn submit_order(context: &mut TradingContext,
+                                 vetter_registry: &VetterRegistry,
+                                 validator: &Validator,
+                                 account_key: &String,
+                                 may_make_markets: bool,
+                                 rest_api_order: Order) -> Result<OrderState, OrderEntryError> {
+    submit_routed_order(context, vetter_registry, validator, account_key, may_make_markets, rest_api_order, None).await
+}
+
+// A child of a routed order is linked to its parent in the transaction that saves it, so it is
+// never sent without the link
+pub(crate) async fn submit_routed_order(context: &mut TradingContext,
+                                        vetter_registry: &VetterRegistry,
+                                        validator: &Validator,
+                                        account_key: &String,
+                                        may_make_markets: bool,
+                                        mut rest_api_order: Order,
+                                        routed_order_id: Option<i64>) -> Result<OrderState, OrderEntryError> {
+    let dao = &context.dao;
+    let web_socket_server = &mut context.web_socket_server;
+    let instrument_manager = &context.instrument_manager;
+    normalize_leg_ratios(&mut rest_api_order, instrument_manager);
+    let check_result = match check_order(dao, vetter_registry, validator, &rest_api_order, account_key, may_make_markets, None).await {
+        Ok(check_result) => check_result,
+        Err(error) => return Err(OrderEntryError::Failed(error))
+    };
+    if !check_result.pass {
+        return Err(OrderEntryError::Rejected(check_result))
+    }
+
+    let instrument = match rest_api_order.legs.first() {
+        Some(leg0) => match instrument_manager.get_instrument_by_key(&leg0.instrument_key) {
+            Ok(Some(instrument)) => instrument,
+            Ok(None) => return Err(OrderEntryError::PreconditionFailed(format!("instrument {} is unknown", leg0.instrument_key))),
+            Err(instrument_error) => return Err(OrderEntryError::Failed(instrument_error)),
+        },
+        None => return Err(OrderEntryError::PreconditionFailed("no order legs".to_string()))
+    };
+
+    let ext_order_id = rest_api_order.ext_order_id.clone().unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+    rest_api_order.ext_order_id = Some(ext_order_id);
+    rest_api_order.account_key = Some(account_key.clone());
+
+    let order_state = match save_new_routed_order(dao, instrument_manager, account_key, &rest_api_order, &instrument, routed_order_id).await {
+        Ok(order_state) => order_state,
+        Err(save_error) => return Err(OrderEntryError::Failed(save_error)),
+    };
+    match send_order_state(web_socket_server, instrument_manager, account_key, &order_state) {
+        Ok(_) => {},
+        Err(send_error) => {
+            warn!("Unable to send order state: {}", send_error);
+        }
+    };
+    if order_state.order_status == OrderStatus::PendingTrigger {
+        match instrument_manager.get_trigger_engine().add(order_state.clone()) {
+            Ok(_) => {},
+            Err(error) => return Err(OrderEntryError::Failed(error))
+        };
+    }
+    if order_state.order_status != OrderStatus::Pending {
+        return Ok(order_state);
+    }
+
+    let price = order_state.order.price;
+    match submit_pending_order(dao, web_socket_server, instrument_manager, account_key, order_state, price).await {
+        Ok(order_state) => Ok(order_state),
+        Err(submit_error) => Err(OrderEntryError::Failed(submit_error)),
+    }
+}
+
+pub(crate) async fn cancel_order(dao: &Dao,
+                                 web_socket_server: &mut WebSocketServer,
+                                 instrument_manager: &InstrumentManager,
+                                 account_key: &String,
+                                 ext_order_id: &String) -> Result<OrderState, OrderEntryError> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not get connection: {}", dao_error))),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not begin: {}", dao_error))),
+    };
+    let order_state_option = match txn.get_order_by_ext_order_id(account_key, ext_order_id).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not get order: {}", dao_error))),
+    };
+    match txn.rollback().await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not rollback: {}", dao_error))),
+    };
+    let order_state = match order_state_option {
+        Some(x) => x,
+        None => return Err(OrderEntryError::NotFound),
+    };
+    if !is_order_status_viable(&order_state.order_status) {
+        return Err(OrderEntryError::PreconditionFailed("Order is no longer working".to_string()));
+    }
+
+    let order_state = match cancel_order_state(dao, web_socket_server, instrument_manager, account_key, order_state).await {
+        Ok(order_state) => order_state,
+        Err(cancel_error) => return Err(OrderEntryError::Failed(cancel_error)),
+    };
+    tokio::spawn(handle_group_update(dao.clone(), web_socket_server.clone(), instrument_manager.clone(), order_state.clone()));
+    Ok(order_state)
+}
+
+// Returns the replacement order. The old order keeps its ext_order_id and the replacement
+// is vetted as if the old one were already gone
+pub(crate) async fn replace_order(context: &mut TradingContext,
+                                  vetter_registry: &VetterRegistry,
+                                  validator: &Validator,
+                                  account_key: &String,
+                                  may_make_markets: bool,
+                                  ext_order_id: &String,
+                                  mut rest_api_order: Order) -> Result<OrderState, OrderEntryError> {
+    let dao = &context.dao;
+    let web_socket_server = &mut context.web_socket_server;
+    let instrument_manager = &context.instrument_manager;
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not get connection: {}", dao_error))),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not begin: {}", dao_error))),
+    };
+    let order_state_option = match txn.get_order_by_ext_order_id(account_key, ext_order_id).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not get order: {}", dao_error))),
+    };
+    let account = match txn.get_account_by_account_key(account_key).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not get account: {}", dao_error))),
+    };
+    let order_group_option = match &order_state_option {
+        Some(order_state) => match txn.get_order_group_for_order(order_state.order.order_id).await {
+            Ok(x) => x,
+            Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not get order group: {}", dao_error))),
+        },
+        None => None,
+    };
+    match txn.rollback().await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not rollback: {}", dao_error))),
+    };
+    let mut order_state = match order_state_option {
+        Some(x) => x,
+        None => return Err(OrderEntryError::NotFound),
+    };
+    if !is_order_status_viable(&order_state.order_status) || order_state.order_status == OrderStatus::PendingCancel {
+        return Err(OrderEntryError::PreconditionFailed("Order is no longer working".to_string()));
+    }
+    if is_order_status_held(&order_state.order_status) {
+        return Err(OrderEntryError::PreconditionFailed("Held orders must be canceled and resubmitted".to_string()));
+    }
+    if order_group_option.is_some() {
+        return Err(OrderEntryError::PreconditionFailed("Orders in a group must be canceled and resubmitted".to_string()));
+    }
+
+    let existing_order = match order_state.order.to_rest_api_order(account_key.as_str(), instrument_manager) {
+        Ok(existing_order) => existing_order,
+        Err(convert_error) => return Err(OrderEntryError::Failed(convert_error)),
+    };
+    if rest_api_order.legs.is_empty() {
+        rest_api_order.legs = existing_order.legs.clone();
+    }
+    normalize_leg_ratios(&mut rest_api_order, instrument_manager);
+    let same_legs = rest_api_order.legs.len() == existing_order.legs.len()
+        && rest_api_order.legs.iter().zip(existing_order.legs.iter())
+        .all(|(leg, existing_leg)| leg.instrument_key == existing_leg.instrument_key && leg.ratio == existing_leg.ratio);
+    if !same_legs {
+        return Err(OrderEntryError::BadRequest("The legs of an order cannot be replaced".to_string()));
+    }
+    if rest_api_order.quantity.signum() != order_state.order.quantity.signum()
+        || rest_api_order.quantity.abs() <= order_state.filled_quantity.abs() {
+        return Err(OrderEntryError::PreconditionFailed("Replacement quantity must be on the same side and above the filled quantity".to_string()));
+    }
+    let new_ext_order_id = rest_api_order.ext_order_id.clone().unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+    if &new_ext_order_id == ext_order_id {
+        return Err(OrderEntryError::BadRequest("The replacement order needs a new ext_order_id".to_string()));
+    }
+    rest_api_order.ext_order_id = Some(new_ext_order_id);
+    rest_api_order.account_key = Some(account_key.clone());
+
+    let check_result = match check_order(dao, vetter_registry, validator, &rest_api_order, account_key, may_make_markets, Some(ext_order_id)).await {
+        Ok(check_result) => check_result,
+        Err(error) => return Err(OrderEntryError::Failed(error))
+    };
+    if !check_result.pass {
+        return Err(OrderEntryError::Rejected(check_result))
+    }
+
+    let instrument = match order_state.order.legs.first() {
+        Some(leg0) => match instrument_manager.get_instrument(leg0.instrument_id) {
+            Ok(Some(instrument)) => instrument,
+            Ok(None) => return Err(OrderEntryError::PreconditionFailed("instrument is unknown".to_string())),
+            Err(instrument_error) => return Err(OrderEntryError::Failed(instrument_error)),
+        },
+        None => return Err(OrderEntryError::PreconditionFailed("no order legs".to_string()))
+    };
+    let exchange_client = match instrument_manager.get_exchange_client_for_instrument(&instrument) {
+        Ok(exchange_client) => exchange_client,
+        Err(instrument_error) => {
+            error!("Could not get exchange for instrument {}: {}", instrument.instrument_id, instrument_error);
+            return Err(OrderEntryError::PreconditionFailed(format!("No exchange for instrument {}", instrument.instrument_key)))
+        }
+    };
+    let mutex = match instrument_manager.get_exchange_mutex(instrument.exchange_id) {
+        Ok(mutex) => mutex,
+        Err(instrument_error) => return Err(OrderEntryError::Failed(instrument_error)),
+    };
+
+    let working_status = order_state.order_status.clone();
+    order_state.order_status = OrderStatus::PendingCancel;
+    let update_result = match dao.begin(&mut db_connection).await {
+        Ok(txn) => match txn.update_order(&mut order_state).await {
+            Ok(_) => txn.commit().await,
+            Err(dao_error) => Err(dao_error),
+        },
+        Err(dao_error) => Err(dao_error),
+    };
+    match update_result {
+        Ok(_) => {},
+        Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not update order: {}", dao_error))),
+    };
+    let _ = send_order_state(web_socket_server, instrument_manager, account_key, &order_state);
+
+    let exchange_order_state = match exchange_client.cancel_order(order_state.order.client_order_id.clone()).await {
+        Ok(exchange_order_state) => exchange_order_state,
+        Err(cancel_order_error) => {
+            // The order is still working as far as we know; a cancel that did reach the exchange
+            // will be reported by its own notification
+            restore_working_status(dao, web_socket_server, instrument_manager, &mutex, account_key, ext_order_id, working_status).await;
+            return Err(OrderEntryError::Failed(anyhow::anyhow!("cancel_order_error: {}", cancel_order_error)))
+        },
+    };
+
+    // Executions of the old order arrive over the websocket in their own time, possibly after the
+    // cancel response. The exchange's remaining quantity on the cancel is final, so it sizes the
+    // replacement, and any execution the exchange reports that is not yet recorded is applied now
+    // so the old order's own fills agree with it. Later deliveries of those executions are no-ops
+    let exchange_id = instrument.exchange_id;
+    match replay_missing_executions(mutex.clone(), web_socket_server, dao, instrument_manager, &exchange_client, exchange_id, &order_state).await {
+        Ok(_) => {},
+        Err(replay_error) => warn!("Could not replay executions of replaced order {}: {}", order_state.order.client_order_id, replay_error),
+    };
+    let (order_state, replacement_order_state) = {
+        let _lock = mutex.lock().await;
+        let txn = match dao.begin(&mut db_connection).await {
+            Ok(x) => x,
+            Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not begin: {}", dao_error))),
+        };
+        let mut order_state = match txn.get_order_by_ext_order_id(account_key, ext_order_id).await {
+            Ok(Some(x)) => x,
+            Ok(None) => return Err(OrderEntryError::NotFound),
+            Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not get order: {}", dao_error))),
+        };
+        let replacement_quantity = replacement_quantity(rest_api_order.quantity, &order_state, &exchange_order_state);
+        order_state.order_status = order_status_to_rest_api_order_status(exchange_order_state.order_status);
+        order_state.apply_remaining_quantity(exchange_order_state.remaining_quantity);
+        order_state.update_time = current_time_millis();
+
+        let replacement_quantity = match replacement_quantity {
+            Some(replacement_quantity) => replacement_quantity,
+            None => {
+                match txn.update_order(&mut order_state).await {
+                    Ok(_) => {},
+                    Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not update order: {}", dao_error))),
+                };
+                match txn.commit().await {
+                    Ok(x) => x,
+                    Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not commit: {}", dao_error))),
+                };
+                let _ = send_order_state(web_socket_server, instrument_manager, account_key, &order_state);
+                return Err(OrderEntryError::Conflict(order_state));
+            }
+        };
+
+        rest_api_order.quantity = replacement_quantity;
+        let client_order_id = Uuid::new_v4().simple().to_string();
+        let entities_order = match rest_api_order.to_entities_order(&account, client_order_id, instrument_manager) {
+            Ok(entities_order) => entities_order,
+            Err(err) => return Err(OrderEntryError::Failed(err))
+        };
+        let replacement_order_state = OrderState {
+            update_time: current_time_millis(),
+            order_status: OrderStatus::Pending,
+            reject_reason: None,
+            order: entities_order,
+            version_number: 0,
+            filled_quantity: 0,
+            average_fill_price: Money::ZERO,
+        };
+        let replacement_order_state = match txn.save_replacement_order(replacement_order_state, Some(order_state.order.order_id)).await {
+            Ok(x) => x,
+            Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not save replacement order: {}", dao_error))),
+        };
+        match txn.update_replaced_order(&mut order_state, Some(replacement_order_state.order.order_id)).await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not update replaced order: {}", dao_error))),
+        };
+        let outbox_entry = new_outbox_entry(replacement_order_state.order.order_id, replacement_order_state.order.price);
+        match txn.save_outbox_entry(&outbox_entry).await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not save outbox entry: {}", dao_error))),
+        };
+        match txn.commit().await {
+            Ok(x) => x,
+            Err(dao_error) => return Err(OrderEntryError::Failed(anyhow::anyhow!("Could not commit: {}", dao_error))),
+        };
+        (order_state, replacement_order_state)
+    };
+    let _ = send_order_state(web_socket_server, instrument_manager, account_key, &order_state);
+    let _ = send_order_state(web_socket_server, instrument_manager, account_key, &replacement_order_state);
+
+    let price = replacement_order_state.order.price;
+    match submit_pending_order(dao, web_socket_server, instrument_manager, account_key, replacement_order_state, price).await {
+        Ok(order_state) => Ok(order_state),
+        Err(submit_error) => Err(OrderEntryError::Failed(submit_error)),
+    }
+}
+
+// What is left of the requested quantity once the exchange's fills of the old order are taken
+// off. None when the exchange did not cancel the order or it has already filled that much
+fn replacement_quantity(requested_quantity: i32,
+                        order_state: &OrderState,
+                        exchange_order_state: &exchange_interface::order::OrderState) -> Option<i32> {
+    if exchange_order_state.order_status != exchange_interface::order::OrderStatus::Canceled {
+        return None;
+    }
+    let replacement_quantity = requested_quantity - order_state.exchange_filled_quantity(exchange_order_state.remaining_quantity);
+    if replacement_quantity.signum() != requested_quantity.signum() {
+        return None;
+    }
+    Some(replacement_quantity)
+}
+
+// Takes an order whose cancel failed back out of PendingCancel, unless the exchange has moved it on since
+async fn restore_working_status(dao: &Dao,
+                                web_socket_server: &mut WebSocketServer,
+                                instrument_manager: &InstrumentManager,
+                                mutex: &Arc<Mutex<()>>,
+                                account_key: &String,
+                                ext_order_id: &String,
+                                working_status: OrderStatus) {
+    let _lock = mutex.lock().await;
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => {
+            error!("Could not get connection: {}", dao_error);
+            return;
+        }
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => {
+            error!("Could not begin: {}", dao_error);
+            return;
+        }
+    };
+    let mut order_state = match txn.get_order_by_ext_order_id(account_key, ext_order_id).await {
+        Ok(Some(order_state)) if order_state.order_status == OrderStatus::PendingCancel => order_state,
+        Ok(_) => return,
+        Err(dao_error) => {
+            error!("Could not get order: {}", dao_error);
+            return;
+        }
+    };
+    // Fills may have arrived while the cancel was outstanding
+    order_state.order_status = match working_status {
+        OrderStatus::Open if order_state.filled_quantity != 0 => OrderStatus::PartiallyFilled,
+        working_status => working_status,
+    };
+    order_state.update_time = current_time_millis();
+    match txn.update_order(&mut order_state).await {
+        Ok(_) => {},
+        Err(dao_error) => {
+            error!("Could not restore status of order {}: {}", ext_order_id, dao_error);
+            return;
+        }
+    };
+    match txn.commit().await {
+        Ok(_) => {},
+        Err(dao_error) => {
+            error!("Could not commit: {}", dao_error);
+            return;
+        }
+    };
+    let _ = send_order_state(web_socket_server, instrument_manager, account_key, &order_state);
+}
+
+// An order being replaced is left out, since its terms give way to the new ones
+pub(crate) async fn check_order(dao: &Dao,
+                                vetter_registry: &VetterRegistry,
+                                validator: &Validator,
+                                rest_api_order: &Order,
+                                account_key: &String,
+                                may_make_markets: bool,
+                                replaced_ext_order_id: Option<&String>) -> Result<VettingResult, Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error))
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error))
+    };
+
+    let existing_orders = match txn.get_orders(account_key).await {
+        Ok(existing_orders) => existing_orders,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get orders: {}", dao_error))
+    };
+
+    let orders: HashMap<String, OrderState> = existing_orders.iter().filter(|(k, b)| {
+        is_order_status_viable(&b.order_status) && replaced_ext_order_id != Some(*k)
+    }).map(|(k, v)| (k.clone(), v.clone())).collect();
+
+    let existing_positions = match txn.get_positions(account_key).await {
+        Ok(positions) => positions,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get positions: {}", dao_error))
+    };
+
+    let positions: HashMap<i64, Position> = existing_positions.iter().filter(|(_, b)| {
+        b.quantity != 0
+    }).map(|(k, v)| (*k, v.clone())).collect();
+
+    let balance = match txn.get_balance(account_key).await {
+        Ok(balance) => balance,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get balance: {}", dao_error))
+    };
+
+    let vetter = vetter_registry.get_vetter_for_account(&txn, account_key).await?;
+
+    match txn.rollback().await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error))
+    };
+
+    let validation_result = match validator.validate_order(rest_api_order, &orders, may_make_markets) {
+        Ok(validation_result) => validation_result,
+        Err(validation_error) => return Err(anyhow::anyhow!("validation error: {}", validation_error))
+    };
+    if !validation_result.pass {
+        return Ok(validation_result);
+    }
+
+    // Nothing is saved for an exchange that cannot be reached; the legs all share one exchange
+    if let Some(leg0) = rest_api_order.legs.first()
+        && let Some(instrument) = validator.instrument_manager.get_instrument_by_key(&leg0.instrument_key)?
+        && !validator.instrument_manager.get_exchange_health().is_available(instrument.exchange_id)? {
+        return Ok(reject(format!("Exchange for {} is unavailable", instrument.instrument_key)));
+    }
+
+    // Whatever its vetter would allow, an account in a margin call may only reduce its positions
+    if let Some(margin_call) = validator.instrument_manager.get_margin_calls().get(account_key)?
+        && !reduces_risk(&validator.instrument_manager, rest_api_order, &positions)? {
+        return Ok(reject(format!("Account is in a margin call ({}); only orders that reduce positions are accepted", margin_call.status)));
+    }
+
+    let vetting_result = match vetter.vet_order(rest_api_order, &orders, &positions, &balance) {
+        Ok(x) => x,
+        Err(vetting_error) => return Err(anyhow::anyhow!("vetting error: {}", vetting_error))
+
+    };
+    Ok(vetting_result)
+}
+
+// Saves a checked order. Stop orders wait with the broker until their trigger fires, and
+// orders on inactive or expired instruments are saved as Rejected
+pub(crate) async fn save_new_order(dao: &Dao,
+                                   instrument_manager: &InstrumentManager,
+                                   account_key: &String,
+                                   rest_api_order: &Order,
+                                   instrument: &Instrument) -> Result<OrderState, Error> {
+    save_new_routed_order(dao, instrument_manager, account_key, rest_api_order, instrument, None).await
+}
+
+async fn save_new_routed_order(dao: &Dao,
+                               instrument_manager: &InstrumentManager,
+                               account_key: &String,
+                               rest_api_order: &Order,
+                               instrument: &Instrument,
+                               routed_order_id: Option<i64>) -> Result<OrderState, Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error))
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error))
+    };
+    let account = match txn.get_account_by_account_key(account_key).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get account: {}", dao_error))
+    };
+    let client_order_id = Uuid::new_v4().simple().to_string();
+    let entities_order = rest_api_order.to_entities_order(&account, client_order_id, instrument_manager)?;
+    let order_status = match entities_order.order_type {
+        OrderType::Limit => OrderStatus::Pending,
+        _ => OrderStatus::PendingTrigger,
+    };
+    let mut order_state = entities::order::OrderState {
+        update_time: current_time_millis(),
+        order_status,
+        reject_reason: None,
+        order: entities_order,
+        version_number: 0,
+        filled_quantity: 0,
+        average_fill_price: Money::ZERO,
+    };
+
+    match instrument.status {
+        InstrumentStatus::Active => {}
+        InstrumentStatus::Inactive => {
+            order_state.order_status = OrderStatus::Rejected;
+            order_state.reject_reason = Some("Instrument is inactive".to_string());
+        }
+    }
+
+    if instrument.expiration_time < current_time_millis() {
+        order_state.order_status = OrderStatus::Rejected;
+        order_state.reject_reason = Some("Instrument has expired".to_string());
+    }
+
+    let order_state = match txn.save_order(order_state).await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not save order: {}", dao_error))
+    };
+    if order_state.order_status == OrderStatus::Pending {
+        match txn.save_outbox_entry(&new_outbox_entry(order_state.order.order_id, order_state.order.price)).await {
+            Ok(x) => x,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not save outbox entry: {}", dao_error))
+        };
+    }
+    if let Some(routed_order_id) = routed_order_id {
+        let routed_child = RoutedChild {
+            order_id: order_state.order.order_id,
+            instrument_id: instrument.instrument_id,
+            quantity: order_state.order.quantity,
+        };
+        match txn.save_routed_order_child(routed_order_id, &routed_child).await {
+            Ok(x) => x,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not save routed order child: {}", dao_error))
+        };
+    }
+    match txn.commit().await {
+        Ok(x) => x,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error))
+    };
+    Ok(order_state)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::order::{Order, OrderLeg, OrderState, OrderStatus, OrderType, TimeInForce};
+    use crate::exchange_interface;
+    use crate::money::Money;
+    use crate::trade_handling::order_entry::replacement_quantity;
+
+    fn order_state(quantity: i32, filled_quantity: i32) -> OrderState {
+        OrderState {
+            order: Order {
+                order_id: 1,
+                account_id: 1,
+                order_number: 1,
+                ext_order_id: "ext".to_string(),
+                client_order_id: "client".to_string(),
+                create_time: 0,
+                price: Money::from(10),
+                quantity,
+                legs: vec![OrderLeg {
+                    order_leg_id: 1,
+                    instrument_id: 1,
+                    ratio: 1,
+                }],
+                time_in_force: TimeInForce::Gtc,
+                expire_time: None,
+                order_type: OrderType::Limit,
+                stop_price: None,
+                trailing_offset: None,
+            },
+            update_time: 0,
+            order_status: OrderStatus::PendingCancel,
+            version_number: 0,
+            reject_reason: None,
+            filled_quantity,
+            average_fill_price: Money::ZERO,
+        }
+    }
+
+    fn exchange_order_state(order_status: exchange_interface::order::OrderStatus,
+                            quantity: i32,
+                            remaining_quantity: i32) -> exchange_interface::order::OrderState {
+        exchange_interface::order::OrderState {
+            update_time: 1,
+            order_status,
+            remaining_quantity,
+            order: exchange_interface::order::Order {
+                client_order_id: "client".to_string(),
+                price: Money::from(10),
+                quantity,
+                legs: vec![],
+                time_in_force: None,
+                expire_time: None,
+            },
+        }
+    }
+
+    #[test]
+    async fn test_replacement_sized_from_exchange_remaining_quantity() {
+        use exchange_interface::order::OrderStatus::Canceled;
+        // Six filled on the exchange before the cancel, none of which has been delivered yet
+        assert_eq!(replacement_quantity(10, &order_state(10, 0), &exchange_order_state(Canceled, 10, 4)), Some(4));
+        // Executions already applied are not counted twice
+        assert_eq!(replacement_quantity(10, &order_state(10, 6), &exchange_order_state(Canceled, 10, 4)), Some(4));
+        assert_eq!(replacement_quantity(-8, &order_state(-10, 0), &exchange_order_state(Canceled, -10, -7)), Some(-5));
+        assert_eq!(replacement_quantity(12, &order_state(10, 0), &exchange_order_state(Canceled, 10, 10)), Some(12));
+    }
+
+    #[test]
+    async fn test_no_replacement_when_filled_or_not_canceled() {
+        use exchange_interface::order::OrderStatus::{Canceled, Filled, Open};
+        assert_eq!(replacement_quantity(6, &order_state(10, 0), &exchange_order_state(Canceled, 10, 4)), None);
+        assert_eq!(replacement_quantity(5, &order_state(10, 0), &exchange_order_state(Canceled, 10, 4)), None);
+        assert_eq!(replacement_quantity(10, &order_state(10, 0), &exchange_order_state(Filled, 10, 0)), None);
+        assert_eq!(replacement_quantity(10, &order_state(10, 0), &exchange_order_state(Open, 10, 10)), None);
+    }
+}
@@ -0,0 +1,448 @@
+use crate::dtos::order::is_order_status_viable;
+use crate::entities::order::{OrderState, OrderStatus, OrderType};
+use crate::entities::order_group::{OrderGroupMember, OrderGroupRole};
+use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
+use crate::persistence::dao::Dao;
+use crate::time::current_time_millis;
+use crate::trade_handling::order_actions::{cancel_order_state, cancel_order_state_with_remaining, send_order_state, submit_pending_order};
+use crate::trade_handling::outbox::new_outbox_entry;
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use log::{error, info};
+use uuid::Uuid;
+
+// Called whenever a member of a group may have changed: on fills, exchange status updates,
+// cancels, expiries and rejects. Every step re-reads the orders it touches, so repeated
+// calls for the same change are harmless.
+pub(crate) async fn handle_group_update(dao: Dao,
+                                        web_socket_server: WebSocketServer,
+                                        instrument_manager: InstrumentManager,
+                                        order_state: OrderState) {
+    match update_group(&dao, web_socket_server, &instrument_manager, &order_state).await {
+        Ok(_) => {},
+        Err(err) => error!("Unable to update the group of order {}: {}", order_state.order.client_order_id, err),
+    }
+}
+
+async fn update_group(dao: &Dao,
+                      mut web_socket_server: WebSocketServer,
+                      instrument_manager: &InstrumentManager,
+                      order_state: &OrderState) -> Result<(), Error> {
+    let (order_group, account_key) = {
+        let mut db_connection = match dao.get_connection().await {
+            Ok(db_connection) => db_connection,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+        };
+        let txn = match dao.begin(&mut db_connection).await {
+            Ok(txn) => txn,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+        };
+        let order_group = match txn.get_order_group_for_order(order_state.order.order_id).await {
+            Ok(Some(order_group)) => order_group,
+            Ok(None) => return Ok(()),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get order group: {}", dao_error)),
+        };
+        let account = match txn.get_account(order_group.account_id).await {
+            Ok(Some(account)) => account,
+            Ok(None) => return Err(anyhow::anyhow!("No account for id: {}", order_group.account_id)),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get account: {}", dao_error)),
+        };
+        match txn.rollback().await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+        };
+        (order_group, account.account_key)
+    };
+
+    let filled_quantity = order_state.filled_quantity;
+    match order_group.role_of(order_state.order.order_id) {
+        // An entry that ends part filled still gets its children, cut down to the filled quantity
+        Some(OrderGroupRole::Entry) => match order_state.order_status {
+            OrderStatus::Filled => {},
+            OrderStatus::Canceled | OrderStatus::Expired | OrderStatus::Rejected if filled_quantity != 0 => {},
+            OrderStatus::Canceled | OrderStatus::Expired | OrderStatus::Rejected => {
+                for child in order_group.children() {
+                    match cancel_member(dao, &mut web_socket_server, instrument_manager, &account_key, child).await {
+                        Ok(_) => {},
+                        Err(err) => error!("Unable to cancel bracket child {}: {}", child.client_order_id, err),
+                    }
+                }
+                return Ok(());
+            },
+            _ => return Ok(()),
+        },
+        // Siblings only give way entirely once the order is completely filled; until then they
+        // are cut down to cover what it has left, so the rest of the position stays protected
+        Some(_) if filled_quantity != 0 => {
+            let left_quantity = match order_state.order_status {
+                OrderStatus::Filled => 0,
+                _ => order_state.order.quantity.abs() - filled_quantity.abs(),
+            };
+            for sibling in order_group.siblings(order_state.order.order_id) {
+                if left_quantity <= 0 {
+                    match cancel_member(dao, &mut web_socket_server, instrument_manager, &account_key, sibling).await {
+                        Ok(_) => {},
+                        Err(err) => error!("Unable to cancel sibling order {}: {}", sibling.client_order_id, err),
+                    }
+                    continue;
+                }
+                match reduce_member(dao, &mut web_socket_server, instrument_manager, &account_key, order_group.order_group_id, sibling, left_quantity).await {
+                    Ok(_) => {},
+                    Err(err) => error!("Unable to reduce sibling order {}: {}", sibling.client_order_id, err),
+                }
+            }
+            return Ok(());
+        },
+        _ => return Ok(()),
+    }
+
+    for child in order_group.children() {
+        match activate_child(dao, &mut web_socket_server, instrument_manager, &account_key, child, filled_quantity).await {
+            Ok(_) => {},
+            Err(err) => error!("Unable to activate bracket child {}: {}", child.client_order_id, err),
+        }
+    }
+    Ok(())
+}
+
+async fn cancel_member(dao: &Dao,
+                       web_socket_server: &mut WebSocketServer,
+                       instrument_manager: &InstrumentManager,
+                       account_key: &str,
+                       member: &OrderGroupMember) -> Result<(), Error> {
+    let order_state = get_member_order_state(dao, member).await?;
+    if !is_order_status_viable(&order_state.order_status) || order_state.order_status == OrderStatus::PendingCancel {
+        return Ok(());
+    }
+    info!("Canceling {} order {} of its group", member.role, member.client_order_id);
+    cancel_order_state(dao, web_socket_server, instrument_manager, account_key, order_state).await?;
+    Ok(())
+}
+
+async fn get_member_order_state(dao: &Dao,
+                                member: &OrderGroupMember) -> Result<OrderState, Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let order_state = match txn.get_order_by_client_order_id(&member.client_order_id).await {
+        Ok(Some(order_state)) => order_state,
+        Ok(None) => return Err(anyhow::anyhow!("Order disappeared from its group")),
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get order: {}", dao_error)),
+    };
+    match txn.rollback().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+    };
+    Ok(order_state)
+}
+
+// The quantity a sibling should have so that what it has left to fill is no more than
+// left_quantity. None when it is already that small
+fn reduced_quantity(sibling_order_state: &OrderState,
+                    left_quantity: i32) -> Option<i32> {
+    let reduced_quantity = sibling_order_state.filled_quantity.abs() + left_quantity;
+    if sibling_order_state.order.quantity.abs() <= reduced_quantity {
+        return None;
+    }
+    Some(sibling_order_state.order.quantity.signum() * reduced_quantity)
+}
+
+async fn reduce_member(dao: &Dao,
+                       web_socket_server: &mut WebSocketServer,
+                       instrument_manager: &InstrumentManager,
+                       account_key: &str,
+                       order_group_id: i64,
+                       member: &OrderGroupMember,
+                       left_quantity: i32) -> Result<(), Error> {
+    let order_state = get_member_order_state(dao, member).await?;
+    if !is_order_status_viable(&order_state.order_status) || order_state.order_status == OrderStatus::PendingCancel {
+        return Ok(());
+    }
+    let quantity = match reduced_quantity(&order_state, left_quantity) {
+        Some(quantity) => quantity,
+        None => return Ok(()),
+    };
+    info!("Reducing {} order {} of its group from {} to {}", member.role, member.client_order_id, order_state.order.quantity, quantity);
+    let held = match order_state.order_status {
+        OrderStatus::PendingParent => true,
+        OrderStatus::PendingTrigger => instrument_manager.get_trigger_engine().remove(&order_state)?,
+        _ => false,
+    };
+    if held {
+        return resize_held_member(dao, web_socket_server, instrument_manager, account_key, order_state, quantity).await;
+    }
+    let replacement_quantity = match cancel_live_member(dao, web_socket_server, instrument_manager, account_key, order_state, left_quantity).await? {
+        Some(replacement_quantity) => replacement_quantity,
+        None => return Ok(()),
+    };
+    let (old_order_state, replacement_order_state) = save_replacement_member(dao, order_group_id, member, replacement_quantity).await?;
+    submit_replacement_member(dao, web_socket_server, instrument_manager, account_key, &old_order_state, replacement_order_state).await
+}
+
+// Orders the broker still holds are resized in place. One taken out of the trigger engine goes
+// back in whether or not the resize is saved
+async fn resize_held_member(dao: &Dao,
+                            web_socket_server: &mut WebSocketServer,
+                            instrument_manager: &InstrumentManager,
+                            account_key: &str,
+                            mut order_state: OrderState,
+                            quantity: i32) -> Result<(), Error> {
+    let resize_result = resize_order(dao, &mut order_state, quantity).await;
+    if order_state.order_status == OrderStatus::PendingTrigger {
+        instrument_manager.get_trigger_engine().add(order_state.clone())?;
+    }
+    resize_result?;
+    send_order_state(web_socket_server, instrument_manager, account_key, &order_state)?;
+    Ok(())
+}
+
+async fn resize_order(dao: &Dao,
+                      order_state: &mut OrderState,
+                      quantity: i32) -> Result<(), Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let mut resized_order_state = order_state.clone();
+    resized_order_state.order.quantity = quantity;
+    resized_order_state.update_time = current_time_millis();
+    match txn.update_order_quantity(resized_order_state.order.order_id, quantity).await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not update order quantity: {}", dao_error)),
+    };
+    match txn.update_order(&mut resized_order_state).await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not update order: {}", dao_error)),
+    };
+    match txn.commit().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+    };
+    *order_state = resized_order_state;
+    Ok(())
+}
+
+// An order at the exchange can't be resized there, so it is canceled and a smaller copy takes
+// its place in the group. Whatever the exchange filled before the cancel comes off the copy,
+// and no copy is needed when that leaves nothing
+async fn cancel_live_member(dao: &Dao,
+                            web_socket_server: &mut WebSocketServer,
+                            instrument_manager: &InstrumentManager,
+                            account_key: &str,
+                            order_state: OrderState,
+                            left_quantity: i32) -> Result<Option<i32>, Error> {
+    let filled_before_cancel = order_state.filled_quantity;
+    let (canceled_order_state, remaining_quantity) = cancel_order_state_with_remaining(dao, web_socket_server, instrument_manager, account_key, order_state).await?;
+    let remaining_quantity = match remaining_quantity {
+        Some(remaining_quantity) if canceled_order_state.order_status == OrderStatus::Canceled => remaining_quantity,
+        _ => return Ok(None),
+    };
+    // Executions delivered after the cancel response are recorded against the canceled order as usual
+    let filled_on_exchange = canceled_order_state.exchange_filled_quantity(remaining_quantity);
+    let replacement_quantity = left_quantity - (filled_on_exchange.abs() - filled_before_cancel.abs());
+    if replacement_quantity <= 0 {
+        return Ok(None);
+    }
+    Ok(Some(replacement_quantity))
+}
+
+async fn save_replacement_member(dao: &Dao,
+                                 order_group_id: i64,
+                                 member: &OrderGroupMember,
+                                 replacement_quantity: i32) -> Result<(OrderState, OrderState), Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let mut old_order_state = match txn.get_order_by_client_order_id(&member.client_order_id).await {
+        Ok(Some(order_state)) => order_state,
+        Ok(None) => return Err(anyhow::anyhow!("Order disappeared from its group")),
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get order: {}", dao_error)),
+    };
+    let mut replacement_order = old_order_state.order.clone();
+    replacement_order.ext_order_id = Uuid::new_v4().simple().to_string();
+    replacement_order.client_order_id = Uuid::new_v4().simple().to_string();
+    replacement_order.create_time = current_time_millis();
+    replacement_order.quantity = old_order_state.order.quantity.signum() * replacement_quantity;
+    let replacement_order_state = OrderState {
+        update_time: current_time_millis(),
+        order_status: match replacement_order.order_type {
+            OrderType::Limit => OrderStatus::Pending,
+            _ => OrderStatus::PendingTrigger,
+        },
+        reject_reason: None,
+        order: replacement_order,
+        version_number: 0,
+        filled_quantity: 0,
+        average_fill_price: Money::ZERO,
+    };
+    let replacement_order_state = match txn.save_replacement_order(replacement_order_state, Some(old_order_state.order.order_id)).await {
+        Ok(order_state) => order_state,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not save replacement order: {}", dao_error)),
+    };
+    match txn.update_replaced_order(&mut old_order_state, Some(replacement_order_state.order.order_id)).await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not update replaced order: {}", dao_error)),
+    };
+    let replacement_member = OrderGroupMember {
+        order_id: replacement_order_state.order.order_id,
+        client_order_id: replacement_order_state.order.client_order_id.clone(),
+        role: member.role.clone(),
+    };
+    match txn.save_order_group_member(order_group_id, &replacement_member).await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not save order group member: {}", dao_error)),
+    };
+    if replacement_order_state.order_status == OrderStatus::Pending {
+        match txn.save_outbox_entry(&new_outbox_entry(replacement_order_state.order.order_id, replacement_order_state.order.price)).await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not save outbox entry: {}", dao_error)),
+        };
+    }
+    match txn.commit().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+    };
+    Ok((old_order_state, replacement_order_state))
+}
+
+async fn submit_replacement_member(dao: &Dao,
+                                   web_socket_server: &mut WebSocketServer,
+                                   instrument_manager: &InstrumentManager,
+                                   account_key: &str,
+                                   old_order_state: &OrderState,
+                                   replacement_order_state: OrderState) -> Result<(), Error> {
+    send_order_state(web_socket_server, instrument_manager, account_key, old_order_state)?;
+    send_order_state(web_socket_server, instrument_manager, account_key, &replacement_order_state)?;
+    // A released stop goes back to waiting on its trigger rather than guessing at a price
+    if replacement_order_state.order_status == OrderStatus::PendingTrigger {
+        return instrument_manager.get_trigger_engine().add(replacement_order_state);
+    }
+    let price = replacement_order_state.order.price;
+    submit_pending_order(dao, web_socket_server, instrument_manager, account_key, replacement_order_state, price).await?;
+    Ok(())
+}
+
+async fn activate_child(dao: &Dao,
+                        web_socket_server: &mut WebSocketServer,
+                        instrument_manager: &InstrumentManager,
+                        account_key: &str,
+                        child: &OrderGroupMember,
+                        entry_filled_quantity: i32) -> Result<(), Error> {
+    let order_state = {
+        let mut db_connection = match dao.get_connection().await {
+            Ok(db_connection) => db_connection,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+        };
+        let txn = match dao.begin(&mut db_connection).await {
+            Ok(txn) => txn,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+        };
+        let mut order_state = match txn.get_order_by_client_order_id(&child.client_order_id).await {
+            Ok(Some(order_state)) => order_state,
+            Ok(None) => return Err(anyhow::anyhow!("Order disappeared from its group")),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get order: {}", dao_error)),
+        };
+        if order_state.order_status != OrderStatus::PendingParent {
+            return Ok(());
+        }
+        if order_state.order.quantity.abs() > entry_filled_quantity.abs() {
+            order_state.order.quantity = order_state.order.quantity.signum() * entry_filled_quantity.abs();
+            match txn.update_order_quantity(order_state.order.order_id, order_state.order.quantity).await {
+                Ok(_) => {},
+                Err(dao_error) => return Err(anyhow::anyhow!("Could not update order quantity: {}", dao_error)),
+            };
+        }
+        order_state.order_status = match order_state.order.order_type {
+            OrderType::Limit => OrderStatus::Pending,
+            _ => OrderStatus::PendingTrigger,
+        };
+        order_state.update_time = current_time_millis();
+        match txn.update_order(&mut order_state).await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not update order: {}", dao_error)),
+        };
+        if order_state.order_status == OrderStatus::Pending {
+            match txn.save_outbox_entry(&new_outbox_entry(order_state.order.order_id, order_state.order.price)).await {
+                Ok(_) => {},
+                Err(dao_error) => return Err(anyhow::anyhow!("Could not save outbox entry: {}", dao_error)),
+            };
+        }
+        match txn.commit().await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+        };
+        order_state
+    };
+    info!("Activating {} order {} as {}", child.role, child.client_order_id, order_state.order_status);
+    send_order_state(web_socket_server, instrument_manager, account_key, &order_state)?;
+    if order_state.order_status == OrderStatus::PendingTrigger {
+        return instrument_manager.get_trigger_engine().add(order_state);
+    }
+    let price = order_state.order.price;
+    submit_pending_order(dao, web_socket_server, instrument_manager, account_key, order_state, price).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::order::{Order, OrderLeg, OrderState, OrderStatus, OrderType, TimeInForce};
+    use crate::money::Money;
+    use crate::trade_handling::order_groups::reduced_quantity;
+
+    fn order_state(quantity: i32, filled_quantity: i32) -> OrderState {
+        OrderState {
+            order: Order {
+                order_id: 1,
+                account_id: 1,
+                order_number: 1,
+                ext_order_id: "ext".to_string(),
+                client_order_id: "client".to_string(),
+                create_time: 0,
+                price: Money::from(95),
+                quantity,
+                legs: vec![OrderLeg {
+                    order_leg_id: 1,
+                    instrument_id: 1,
+                    ratio: 1,
+                }],
+                time_in_force: TimeInForce::Gtc,
+                expire_time: None,
+                order_type: OrderType::Stop,
+                stop_price: Some(Money::from(95)),
+                trailing_offset: None,
+            },
+            update_time: 0,
+            order_status: OrderStatus::PendingTrigger,
+            version_number: 0,
+            reject_reason: None,
+            filled_quantity,
+            average_fill_price: Money::ZERO,
+        }
+    }
+
+    #[test]
+    async fn test_sibling_reduced_to_what_is_left() {
+        // Take profit of 100 filled 30, so the stop loss of 100 covers the other 70
+        assert_eq!(reduced_quantity(&order_state(-100, 0), 70), Some(-70));
+        // Repeated updates for the same fill leave it alone
+        assert_eq!(reduced_quantity(&order_state(-70, 0), 70), None);
+        // A sibling that filled some itself keeps those on top of what it has left to cover
+        assert_eq!(reduced_quantity(&order_state(-100, -20), 50), Some(-70));
+        assert_eq!(reduced_quantity(&order_state(100, 0), 40), Some(40));
+    }
+}
@@ -5,6 +5,7 @@ use crate::exchange_interface::order::{OrderState, OrderStatus};
 use crate::instrument_manager::InstrumentManager;
 use crate::persistence::dao::Dao;
 use crate::time::current_time_millis;
+use crate::trade_handling::order_groups::handle_group_update;
 use crate::trade_handling::updates::AccountUpdate;
 use crate::websockets::server::WebSocketServer;
 use anyhow::Error;
@@ -22,7 +23,7 @@ pub fn handle_order_state(mutex: Arc<Mutex<()>>,
     tokio::spawn(update_order_state_loop(mutex.clone(), web_socket_server.clone(), dao.clone(), instrument_manager.clone(), order_state));
 }
 
-async fn update_order_state_loop(mutex: Arc<Mutex<()>>, web_socket_server: WebSocketServer, dao: Dao, instrument_manager: InstrumentManager, order_state: OrderState) {
+pub(crate) async fn update_order_state_loop(mutex: Arc<Mutex<()>>, web_socket_server: WebSocketServer, dao: Dao, instrument_manager: InstrumentManager, order_state: OrderState) {
     let start = current_time_millis();
     let one_hundred_millis = std::time::Duration::from_millis(100);
     for attempt in 0..10 {
@@ -71,6 +72,7 @@ async fn update_order_state(mutex: Arc<Mutex<()>>,
                         return Ok(())
                     }
                     db_order_state.order_status = order_status_to_rest_api_order_status(order_state.order_status);
+                    db_order_state.apply_remaining_quantity(order_state.remaining_quantity);
                     if (db_order_state.order_status == entities::order::OrderStatus::Rejected) {
                         db_order_state.reject_reason = Some("Exchange reject".to_string());
                     }
@@ -113,8 +115,10 @@ async fn update_order_state(mutex: Arc<Mutex<()>>,
                         position: None,
                         trade: None,
                         order_state: Some(rest_api_order_state),
+                        margin_call: None,
                     };
                     web_socket_server.send_account_message(account.account_key.as_str(), ACCOUNT_UPDATE_QUEUE_NAME, &account_update);
+                    tokio::spawn(handle_group_update(dao.clone(), web_socket_server.clone(), instrument_manager.clone(), db_order_state));
                 }
                 _ => {
                     return Err(anyhow::anyhow!("update_order_state Trying to update unknown order {}", &order_state.order.client_order_id));
@@ -0,0 +1,279 @@
+use crate::config::BrokerConfig;
+use crate::entities::order::{OrderState, OrderStatus};
+use crate::entities::outbox::OutboxEntry;
+use crate::exchange_interface;
+use crate::exchange_interface::exchange_client::REQUEST_TIMEOUT_MILLIS;
+use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
+use crate::persistence::dao::{Dao, DaoError};
+use crate::time::current_time_millis;
+use crate::trade_handling::order_actions::{exchange_client_for_order, record_exchange_reject, EXCHANGE_REJECT};
+use crate::trade_handling::order_groups::handle_group_update;
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use log::{error, info, warn};
+use std::time::Duration;
+
+const UNDELIVERABLE: &str = "Exchange could not be reached";
+
+// An attempt in flight owns its entry for this long, so that no other attempt overlaps it
+const DELIVERY_LEASE_MILLIS: i64 = 2 * REQUEST_TIMEOUT_MILLIS as i64;
+
+#[derive(Clone, Copy, Debug)]
+pub struct OutboxSettings {
+    pub retry_millis: i64,
+    pub max_retry_millis: i64,
+    // An order the exchange has still not seen after this long is rejected rather than sent late
+    pub give_up_millis: i64,
+}
+
+impl OutboxSettings {
+    pub fn from_config(config: &BrokerConfig) -> OutboxSettings {
+        OutboxSettings {
+            retry_millis: config.outbox_retry_millis,
+            max_retry_millis: config.outbox_max_retry_millis,
+            give_up_millis: config.outbox_give_up_millis,
+        }
+    }
+}
+
+// Saved in the same transaction that makes the order Pending, so the order cannot be left
+// Pending without something responsible for delivering it
+pub(crate) fn new_outbox_entry(order_id: i64,
+                               price: Money) -> OutboxEntry {
+    let now = current_time_millis();
+    OutboxEntry {
+        order_id,
+        price,
+        attempts: 0,
+        next_attempt_time: now + DELIVERY_LEASE_MILLIS,
+        last_error: None,
+        create_time: now,
+    }
+}
+
+// The exchange has answered for the order, so nothing is left to deliver
+pub(crate) async fn record_delivered(dao: &Dao,
+                                     order_id: i64) {
+    match delete_entry(dao, order_id).await {
+        Ok(_) => {},
+        // The worker will find the order on the exchange and clear the entry then
+        Err(dao_error) => error!("Could not clear outbox entry for order {}: {}", order_id, dao_error),
+    };
+}
+
+// Whether the order reached the exchange is unknown; the worker will ask before sending again
+pub(crate) async fn record_failed_attempt(dao: &Dao,
+                                          order_id: i64,
+                                          description: String) {
+    warn!("Delivery of order {} failed, will retry: {}", order_id, description);
+    match note_failure(dao, order_id, description).await {
+        Ok(_) => {},
+        Err(dao_error) => error!("Could not record failed delivery of order {}: {}", order_id, dao_error),
+    };
+}
+
+async fn delete_entry(dao: &Dao,
+                      order_id: i64) -> Result<(), DaoError> {
+    let mut db_connection = dao.get_connection().await?;
+    let txn = dao.begin(&mut db_connection).await?;
+    txn.delete_outbox_entry(order_id).await?;
+    txn.commit().await
+}
+
+async fn note_failure(dao: &Dao,
+                      order_id: i64,
+                      description: String) -> Result<(), DaoError> {
+    let mut db_connection = dao.get_connection().await?;
+    let txn = dao.begin(&mut db_connection).await?;
+    if let Some(mut outbox_entry) = txn.get_outbox_entry_for_update(order_id).await? {
+        outbox_entry.attempts += 1;
+        outbox_entry.last_error = Some(description);
+        txn.update_outbox_entry(&outbox_entry).await?;
+    }
+    txn.commit().await
+}
+
+pub fn start_outbox_worker(dao: Dao,
+                           web_socket_server: WebSocketServer,
+                           instrument_manager: InstrumentManager,
+                           settings: OutboxSettings,
+                           interval_millis: u64) {
+    tokio::spawn(outbox_worker_loop(dao, web_socket_server, instrument_manager, settings, interval_millis));
+}
+
+async fn outbox_worker_loop(dao: Dao,
+                            web_socket_server: WebSocketServer,
+                            instrument_manager: InstrumentManager,
+                            settings: OutboxSettings,
+                            interval_millis: u64) {
+    loop {
+        tokio::time::sleep(Duration::from_millis(interval_millis)).await;
+        let due_entries = match get_due_entries(&dao).await {
+            Ok(due_entries) => due_entries,
+            Err(err) => {
+                error!("Unable to get due outbox entries: {}", err);
+                continue;
+            }
+        };
+        for outbox_entry in due_entries {
+            match retry_delivery(&dao, &web_socket_server, &instrument_manager, &settings, outbox_entry.order_id).await {
+                Ok(_) => {},
+                Err(err) => error!("Unable to retry delivery of order {}: {}", outbox_entry.order_id, err),
+            }
+        }
+    }
+}
+
+async fn get_due_entries(dao: &Dao) -> Result<Vec<OutboxEntry>, Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let due_entries = match txn.get_due_outbox_entries(current_time_millis()).await {
+        Ok(due_entries) => due_entries,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get due outbox entries: {}", dao_error)),
+    };
+    match txn.rollback().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+    };
+    Ok(due_entries)
+}
+
+// Every retry asks the exchange for the order by its client order id first, so an order whose
+// earlier attempt did arrive is never sent twice
+async fn retry_delivery(dao: &Dao,
+                        web_socket_server: &WebSocketServer,
+                        instrument_manager: &InstrumentManager,
+                        settings: &OutboxSettings,
+                        order_id: i64) -> Result<(), Error> {
+    let now = current_time_millis();
+    let (outbox_entry, order_state, account_key) = {
+        let mut db_connection = match dao.get_connection().await {
+            Ok(db_connection) => db_connection,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+        };
+        let txn = match dao.begin(&mut db_connection).await {
+            Ok(txn) => txn,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+        };
+        let mut outbox_entry = match txn.get_outbox_entry_for_update(order_id).await {
+            Ok(Some(outbox_entry)) if outbox_entry.next_attempt_time <= now => outbox_entry,
+            // Delivered, or claimed by another attempt, since the entries were read
+            Ok(_) => return Ok(()),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get outbox entry: {}", dao_error)),
+        };
+        let order_state = match txn.get_order_by_order_id(order_id).await {
+            Ok(Some(order_state)) => order_state,
+            Ok(None) => return Err(anyhow::anyhow!("No order with id: {}", order_id)),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get order: {}", dao_error)),
+        };
+        // An update from the exchange, or a cancel, has already moved the order on
+        if order_state.order_status != OrderStatus::Pending {
+            info!("Order {} is {}, no longer delivering it", order_state.order.client_order_id, order_state.order_status);
+            match txn.delete_outbox_entry(order_id).await {
+                Ok(_) => {},
+                Err(dao_error) => return Err(anyhow::anyhow!("Could not delete outbox entry: {}", dao_error)),
+            };
+            return match txn.commit().await {
+                Ok(_) => Ok(()),
+                Err(dao_error) => Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+            };
+        }
+        outbox_entry.attempts += 1;
+        outbox_entry.next_attempt_time = now + DELIVERY_LEASE_MILLIS + retry_delay_millis(settings, outbox_entry.attempts);
+        match txn.update_outbox_entry(&outbox_entry).await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not update outbox entry: {}", dao_error)),
+        };
+        let account = match txn.get_account(order_state.order.account_id).await {
+            Ok(Some(account)) => account,
+            Ok(None) => return Err(anyhow::anyhow!("No account for id: {}", order_state.order.account_id)),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get account: {}", dao_error)),
+        };
+        match txn.commit().await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+        };
+        (outbox_entry, order_state, account.account_key)
+    };
+
+    let (exchange_id, exchange_client) = exchange_client_for_order(instrument_manager, &order_state)?;
+    if !instrument_manager.get_exchange_health().is_available(exchange_id)? {
+        return Ok(());
+    }
+    let client_order_id = &order_state.order.client_order_id;
+    let exchange_order_state = match exchange_client.find_order(client_order_id).await {
+        Ok(Some(exchange_order_state)) => exchange_order_state,
+        Ok(None) if now - outbox_entry.create_time >= settings.give_up_millis => {
+            warn!("Giving up on delivering order {} after {} attempts", client_order_id, outbox_entry.attempts);
+            record_delivered(dao, order_id).await;
+            return reject(dao, web_socket_server, instrument_manager, &account_key, order_state, UNDELIVERABLE).await;
+        },
+        Ok(None) => {
+            info!("Resending order {}, attempt {}", client_order_id, outbox_entry.attempts);
+            let exchange_order = order_state.order.to_exchange_order(instrument_manager, outbox_entry.price)?;
+            match exchange_client.submit_order(exchange_order).await {
+                Ok(exchange_order_state) => exchange_order_state,
+                Err(exchange_error) => {
+                    record_failed_attempt(dao, order_id, exchange_error.to_string()).await;
+                    return Ok(());
+                }
+            }
+        },
+        Err(exchange_error) => {
+            record_failed_attempt(dao, order_id, exchange_error.to_string()).await;
+            return Ok(());
+        }
+    };
+
+    // Beyond a reject, the exchange's own notifications and reconciliation bring the order up to date
+    record_delivered(dao, order_id).await;
+    if exchange_order_state.order_status == exchange_interface::order::OrderStatus::Rejected {
+        return reject(dao, web_socket_server, instrument_manager, &account_key, order_state, EXCHANGE_REJECT).await;
+    }
+    Ok(())
+}
+
+async fn reject(dao: &Dao,
+                web_socket_server: &WebSocketServer,
+                instrument_manager: &InstrumentManager,
+                account_key: &str,
+                order_state: OrderState,
+                reject_reason: &str) -> Result<(), Error> {
+    let mut web_socket_server = web_socket_server.clone();
+    let order_state = record_exchange_reject(dao, &mut web_socket_server, instrument_manager, account_key, order_state, reject_reason).await?;
+    tokio::spawn(handle_group_update(dao.clone(), web_socket_server, instrument_manager.clone(), order_state));
+    Ok(())
+}
+
+// Doubles with each attempt, up to the maximum
+fn retry_delay_millis(settings: &OutboxSettings,
+                      attempts: i32) -> i64 {
+    let doublings = (attempts.max(1) - 1).min(30);
+    settings.retry_millis.saturating_mul(1i64 << doublings).min(settings.max_retry_millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trade_handling::outbox::{retry_delay_millis, OutboxSettings};
+
+    #[test]
+    async fn test_retry_delay_backs_off() {
+        let settings = OutboxSettings {
+            retry_millis: 1000,
+            max_retry_millis: 10000,
+            give_up_millis: 60000,
+        };
+        assert_eq!(retry_delay_millis(&settings, 1), 1000);
+        assert_eq!(retry_delay_millis(&settings, 2), 2000);
+        assert_eq!(retry_delay_millis(&settings, 4), 8000);
+        assert_eq!(retry_delay_millis(&settings, 5), 10000);
+        assert_eq!(retry_delay_millis(&settings, 100), 10000);
+    }
+}
@@ -0,0 +1,302 @@
+use crate::converters::order_converters::order_status_to_rest_api_order_status;
+use crate::entities;
+use crate::entities::order::{is_order_status_held, Trade};
+use crate::entities::reconciliation::ReconciliationAudit;
+use crate::exchange_interface::exchange_client::ExchangeClient;
+use crate::exchange_interface::order::Execution;
+use crate::instrument_manager::InstrumentManager;
+use crate::persistence::dao::Dao;
+use crate::time::current_time_millis;
+use crate::trade_handling::execution_handling::handle_execution_thread;
+use crate::trade_handling::order_state_handling::update_order_state_loop;
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use log::{error, info, warn};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const MISSING_EXECUTION: &str = "MissingExecution";
+const STATUS_MISMATCH: &str = "StatusMismatch";
+
+pub fn handle_connected(mutex: Arc<Mutex<()>>,
+                        dao: &Dao,
+                        web_socket_server: &WebSocketServer,
+                        instrument_manager: &InstrumentManager,
+                        exchange_id: i32) {
+    info!("Connected to exchange {}, reconciling", exchange_id);
+    tokio::spawn(reconcile_exchange(mutex.clone(), web_socket_server.clone(), dao.clone(), instrument_manager.clone(), exchange_id));
+}
+
+async fn reconcile_exchange(mutex: Arc<Mutex<()>>,
+                            web_socket_server: WebSocketServer,
+                            dao: Dao,
+                            instrument_manager: InstrumentManager,
+                            exchange_id: i32) {
+    let start = current_time_millis();
+    let exchange_client = match instrument_manager.get_exchange_client(exchange_id) {
+        Ok(exchange_client) => exchange_client,
+        Err(err) => {
+            error!("Unable to get exchange client for reconciliation: {}", err);
+            return;
+        }
+    };
+    let order_states = match get_viable_orders_for_exchange(&dao, &instrument_manager, exchange_id).await {
+        Ok(order_states) => order_states,
+        Err(err) => {
+            error!("Unable to get viable orders for reconciliation: {}", err);
+            return;
+        }
+    };
+    for order_state in order_states.iter() {
+        match reconcile_order(mutex.clone(), &web_socket_server, &dao, &instrument_manager, &exchange_client, exchange_id, order_state).await {
+            Ok(_) => {},
+            Err(err) => {
+                error!("Unable to reconcile order {}: {}", order_state.order.client_order_id, err);
+            }
+        }
+    }
+    let end = current_time_millis();
+    info!("reconcile_exchange checked {} orders for exchange {} in {} ms", order_states.len(), exchange_id, end-start);
+}
+
+async fn get_viable_orders_for_exchange(dao: &Dao,
+                                        instrument_manager: &InstrumentManager,
+                                        exchange_id: i32) -> Result<Vec<entities::order::OrderState>, Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let viable_orders = match txn.get_viable_orders().await {
+        Ok(viable_orders) => viable_orders,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get viable orders: {}", dao_error)),
+    };
+    match txn.rollback().await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+    };
+
+    let mut order_states = Vec::new();
+    // Held orders have not been sent to the exchange yet
+    for order_state in viable_orders.into_values().filter(|order_state| !is_order_status_held(&order_state.order_status)) {
+        let instrument_id = match order_state.order.legs.first() {
+            Some(leg) => leg.instrument_id,
+            None => continue
+        };
+        match instrument_manager.get_instrument(instrument_id)? {
+            Some(instrument) if instrument.exchange_id == exchange_id => order_states.push(order_state),
+            _ => {}
+        }
+    }
+    Ok(order_states)
+}
+
+async fn reconcile_order(mutex: Arc<Mutex<()>>,
+                         web_socket_server: &WebSocketServer,
+                         dao: &Dao,
+                         instrument_manager: &InstrumentManager,
+                         exchange_client: &ExchangeClient,
+                         exchange_id: i32,
+                         order_state: &entities::order::OrderState) -> Result<(), Error> {
+    let client_order_id = &order_state.order.client_order_id;
+    let mut exchange_order_state = match exchange_client.get_order(client_order_id).await {
+        Ok(exchange_order_state) => exchange_order_state,
+        Err(exchange_error) => return Err(anyhow::anyhow!("Could not get order from exchange: {}", exchange_error)),
+    };
+    replay_missing_executions(mutex.clone(), web_socket_server, dao, instrument_manager, exchange_client, exchange_id, order_state).await?;
+
+    // Compare against the order as it stands after any replayed fills
+    let current_order_state = {
+        let mut db_connection = match dao.get_connection().await {
+            Ok(db_connection) => db_connection,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+        };
+        let txn = match dao.begin(&mut db_connection).await {
+            Ok(txn) => txn,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+        };
+        let current_order_state = match txn.get_order_by_client_order_id(client_order_id).await {
+            Ok(Some(current_order_state)) => current_order_state,
+            Ok(None) => return Err(anyhow::anyhow!("Order disappeared during reconciliation")),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get order: {}", dao_error)),
+        };
+        match txn.rollback().await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+        };
+        current_order_state
+    };
+
+    let mut expected_order_state = current_order_state.clone();
+    expected_order_state.order_status = order_status_to_rest_api_order_status(exchange_order_state.order_status.clone());
+    expected_order_state.apply_remaining_quantity(exchange_order_state.remaining_quantity);
+    let exchange_filled_quantity = current_order_state.exchange_filled_quantity(exchange_order_state.remaining_quantity);
+    if expected_order_state.order_status != current_order_state.order_status
+        || exchange_filled_quantity != current_order_state.filled_quantity {
+        warn!("Correcting status of order {} from {} to {}", client_order_id, current_order_state.order_status, expected_order_state.order_status);
+        save_audit(dao, exchange_id, order_state.order.order_id, STATUS_MISMATCH,
+                   format!("Status {} with {} filled, exchange reports {} with {} filled",
+                           current_order_state.order_status, current_order_state.filled_quantity,
+                           expected_order_state.order_status, exchange_filled_quantity)).await?;
+        // The exchange's view is authoritative here, so it must not lose to our own more recent update time
+        exchange_order_state.update_time = current_time_millis();
+        update_order_state_loop(mutex, web_socket_server.clone(), dao.clone(), instrument_manager.clone(), exchange_order_state).await;
+    }
+    Ok(())
+}
+
+// Applies any execution of the order that the exchange reports and that was never recorded
+pub(crate) async fn replay_missing_executions(mutex: Arc<Mutex<()>>,
+                                              web_socket_server: &WebSocketServer,
+                                              dao: &Dao,
+                                              instrument_manager: &InstrumentManager,
+                                              exchange_client: &ExchangeClient,
+                                              exchange_id: i32,
+                                              order_state: &entities::order::OrderState) -> Result<(), Error> {
+    let client_order_id = &order_state.order.client_order_id;
+    let executions = match exchange_client.get_executions(client_order_id).await {
+        Ok(executions) => executions.executions,
+        Err(exchange_error) => return Err(anyhow::anyhow!("Could not get executions from exchange: {}", exchange_error)),
+    };
+
+    let trades = {
+        let mut db_connection = match dao.get_connection().await {
+            Ok(db_connection) => db_connection,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+        };
+        let txn = match dao.begin(&mut db_connection).await {
+            Ok(txn) => txn,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+        };
+        let trades = match txn.get_trades_for_order(order_state.order.order_id).await {
+            Ok(trades) => trades,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get trades: {}", dao_error)),
+        };
+        match txn.rollback().await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not rollback: {}", dao_error)),
+        };
+        trades
+    };
+
+    let mut legs = Vec::new();
+    for leg in order_state.order.legs.iter() {
+        let instrument = match instrument_manager.get_instrument(leg.instrument_id)? {
+            Some(instrument) => instrument,
+            None => return Err(anyhow::anyhow!("No instrument with id: {}", leg.instrument_id)),
+        };
+        legs.push((leg.order_leg_id, instrument.exchange_instrument_id));
+    }
+
+    for execution in find_missing_executions(&legs, &trades, executions) {
+        warn!("Replaying missing execution for order {}: {:?}", client_order_id, execution);
+        save_audit(dao, exchange_id, order_state.order.order_id, MISSING_EXECUTION,
+                   format!("Execution {} of {} at {} on exchange instrument {} at {} was not recorded",
+                           execution.execution_id, execution.quantity, execution.price, execution.instrument_id, execution.create_time)).await?;
+        handle_execution_thread(mutex.clone(), web_socket_server.clone(), dao.clone(), instrument_manager.clone(), execution).await;
+    }
+    Ok(())
+}
+
+async fn save_audit(dao: &Dao,
+                    exchange_id: i32,
+                    order_id: i64,
+                    discrepancy: &str,
+                    description: String) -> Result<(), Error> {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+    };
+    let reconciliation_audit = ReconciliationAudit {
+        reconciliation_audit_id: 0,
+        exchange_id,
+        order_id,
+        create_time: current_time_millis(),
+        discrepancy: discrepancy.to_string(),
+        description,
+    };
+    match txn.save_reconciliation_audit(reconciliation_audit).await {
+        Ok(_) => {},
+        Err(dao_error) => return Err(anyhow::anyhow!("Could not save reconciliation audit: {}", dao_error)),
+    };
+    match txn.commit().await {
+        Ok(_) => Ok(()),
+        Err(dao_error) => Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+    }
+}
+
+// Executions are matched to recorded trades by leg and exchange execution id
+fn find_missing_executions(legs: &[(i64, i64)],
+                           trades: &[Trade],
+                           executions: Vec<Execution>) -> Vec<Execution> {
+    let mut missing_executions: Vec<Execution> = executions.into_iter()
+        .filter(|execution| {
+            legs.iter()
+                .filter(|(_, exchange_instrument_id)| *exchange_instrument_id == execution.instrument_id)
+                .any(|(order_leg_id, _)| !trades.iter().any(|trade|
+                    trade.order_leg.order_leg_id == *order_leg_id && trade.exchange_execution_id == execution.execution_id))
+        })
+        .collect();
+    missing_executions.sort_by_key(|execution| execution.create_time);
+    missing_executions
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::order::{OrderLeg, Trade};
+    use crate::money::Money;
+    use crate::exchange_interface::order::Execution;
+    use crate::trade_handling::reconciliation::find_missing_executions;
+
+    fn trade(order_leg_id: i64, exchange_execution_id: i64) -> Trade {
+        Trade {
+            trade_id: 0,
+            create_time: 0,
+            ext_order_id: "ext".to_string(),
+            order_number: 1,
+            order_leg: OrderLeg {
+                order_leg_id,
+                instrument_id: 0,
+                ratio: 1,
+            },
+            exchange_execution_id,
+            price: Money::from(10),
+            quantity: 1,
+            fee: Money::ZERO,
+        }
+    }
+
+    fn execution(execution_id: i64, instrument_id: i64, create_time: i64) -> Execution {
+        Execution {
+            execution_id,
+            client_order_id: "client".to_string(),
+            instrument_id,
+            create_time,
+            price: Money::from(10),
+            quantity: 1,
+        }
+    }
+
+    #[test]
+    async fn test_no_missing_executions() {
+        let missing = find_missing_executions(&[(1, 100)], &[trade(1, 7)], vec![execution(7, 100, 5)]);
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    async fn test_missing_executions_per_leg() {
+        let missing = find_missing_executions(&[(1, 100), (2, 200)],
+                                              &[trade(1, 11), trade(2, 12)],
+                                              vec![execution(13, 100, 9), execution(11, 100, 5), execution(12, 200, 5), execution(14, 200, 7)]);
+        assert_eq!(missing.len(), 2);
+        assert_eq!(missing[0].execution_id, 14);
+        assert_eq!(missing[1].execution_id, 13);
+    }
+}
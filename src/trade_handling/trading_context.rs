@@ -0,0 +1,64 @@
+use crate::instrument_manager::InstrumentManager;
+use crate::persistence::dao::Dao;
+use crate::websockets::server::WebSocketServer;
+
+// The handles that order handling passes around together
+#[derive(Clone)]
+pub struct TradingContext {
+    pub dao: Dao,
+    pub web_socket_server: WebSocketServer,
+    pub instrument_manager: InstrumentManager,
+}
+
+impl TradingContext {
+    pub fn new(dao: Dao,
+               web_socket_server: WebSocketServer,
+               instrument_manager: InstrumentManager) -> Self {
+        TradingContext {
+            dao,
+            web_socket_server,
+            instrument_manager,
+        }
+    }
+}
+
+// A TradingContext on a test_dao with one account, trading against a MockExchange. Returns it
+// with the account's key
+#[cfg(test)]
+pub(crate) async fn test_trading_context() -> Option<(TradingContext, String)> {
+    use crate::exchange_interface::exchange_health::CircuitSettings;
+    use crate::exchange_interface::mock_exchange::MockExchange;
+    use crate::persistence::account_management::create_test_account;
+    use crate::persistence::dao::test_dao;
+
+    let dao = test_dao().await?;
+    MockExchange::start().save(&dao).await;
+    let account_key = create_test_account(&dao, "trader").await;
+    let web_socket_server = WebSocketServer::new();
+    // The mock exchange has no websocket, and its reconnect failures must not open the circuit
+    let circuit_settings = CircuitSettings { failure_threshold: i32::MAX, ..CircuitSettings::default() };
+    let mut instrument_manager = InstrumentManager::new(dao.clone(), web_socket_server.clone(), circuit_settings);
+    instrument_manager.initialize().await.unwrap();
+    Some((TradingContext::new(dao, web_socket_server, instrument_manager), account_key))
+}
+
+// Submits an order through order entry to a test_trading_context's mock exchange, where it rests
+#[cfg(test)]
+pub(crate) async fn test_working_order(context: &mut TradingContext,
+                                       account_key: &String,
+                                       order: serde_json::Value) -> crate::entities::order::OrderState {
+    use crate::trade_handling::order_entry;
+    use crate::validator::validator::Validator;
+    use crate::vetting::all_pass_vetter::{AllPassVetter, ALL_PASS_VETTER};
+    use crate::vetting::vetter_registry::VetterRegistry;
+    use std::sync::Arc;
+
+    let mut vetter_registry = VetterRegistry::new(ALL_PASS_VETTER);
+    vetter_registry.register(Arc::new(AllPassVetter::new()));
+    let validator = Validator::new(context.instrument_manager.clone());
+    match order_entry::submit_order(context, &vetter_registry, &validator, account_key, true,
+                                    serde_json::from_value(order).unwrap()).await {
+        Ok(order_state) => order_state,
+        Err(entry_error) => panic!("Could not submit order: {}", entry_error),
+    }
+}
@@ -0,0 +1,297 @@
+use crate::entities::order::{Order, OrderState, OrderStatus, OrderType};
+use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
+use crate::persistence::dao::{Dao, DaoTransaction};
+use crate::time::current_time_millis;
+use crate::trade_handling::order_actions::{send_order_state, submit_pending_order};
+use crate::trade_handling::order_groups::handle_group_update;
+use crate::trade_handling::outbox::new_outbox_entry;
+use crate::websockets::server::WebSocketServer;
+use anyhow::Error;
+use log::{error, info};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+// Stop, stop-limit and trailing-stop orders are held by the broker in PendingTrigger
+// until a last trade crosses their stop price, and only then sent to the exchange
+#[derive(Clone)]
+pub struct TriggerEngine {
+    dao: Dao,
+    web_socket_server: WebSocketServer,
+    // Held orders by the instrument of their single leg, then by order id
+    triggers: Arc<RwLock<HashMap<i64, HashMap<i64, OrderState>>>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum TriggerAction {
+    Hold,
+    MoveStop(Money),
+    Release(Money),
+}
+
+impl TriggerEngine {
+    pub fn new(dao: Dao,
+               web_socket_server: WebSocketServer) -> Self {
+        TriggerEngine {
+            dao,
+            web_socket_server,
+            triggers: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn load(&self,
+                      txn: &DaoTransaction<'_>) -> Result<(), Error> {
+        let order_states = match txn.get_pending_trigger_orders().await {
+            Ok(order_states) => order_states,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get pending trigger orders: {}", dao_error)),
+        };
+        let count = order_states.len();
+        for order_state in order_states {
+            self.add(order_state)?;
+        }
+        info!("Done loading {} pending trigger orders", count);
+        Ok(())
+    }
+
+    pub fn add(&self,
+               order_state: OrderState) -> Result<(), Error> {
+        let instrument_id = match order_state.order.legs.first() {
+            Some(leg) => leg.instrument_id,
+            None => return Err(anyhow::anyhow!("Order {} has no legs", order_state.order.order_id)),
+        };
+        let mut writable_triggers = match self.triggers.write() {
+            Ok(writable_triggers) => writable_triggers,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to triggers: {}", writable_error)),
+        };
+        writable_triggers.entry(instrument_id).or_default().insert(order_state.order.order_id, order_state);
+        Ok(())
+    }
+
+    // Returns whether the order was still held; once released it belongs to the exchange
+    pub fn remove(&self,
+                  order_state: &OrderState) -> Result<bool, Error> {
+        let mut writable_triggers = match self.triggers.write() {
+            Ok(writable_triggers) => writable_triggers,
+            Err(writable_error) => return Err(anyhow::anyhow!("Unable to get write access to triggers: {}", writable_error)),
+        };
+        Ok(order_state.order.legs.first()
+            .and_then(|leg| writable_triggers.get_mut(&leg.instrument_id))
+            .and_then(|held_orders| held_orders.remove(&order_state.order.order_id))
+            .is_some())
+    }
+
+    pub fn on_last_trade(&self,
+                         instrument_manager: &InstrumentManager,
+                         instrument_id: i64,
+                         last_price: Money) {
+        let mut writable_triggers = match self.triggers.write() {
+            Ok(writable_triggers) => writable_triggers,
+            Err(writable_error) => {
+                error!("Unable to get write access to triggers: {}", writable_error);
+                return;
+            }
+        };
+        let held_orders = match writable_triggers.get_mut(&instrument_id) {
+            Some(held_orders) => held_orders,
+            None => return,
+        };
+        let mut released = Vec::new();
+        for order_state in held_orders.values_mut() {
+            match evaluate_trigger(&order_state.order, last_price) {
+                TriggerAction::Hold => {},
+                TriggerAction::MoveStop(stop_price) => {
+                    order_state.order.stop_price = Some(stop_price);
+                    tokio::spawn(save_stop_price(self.dao.clone(), order_state.order.order_id, stop_price));
+                },
+                TriggerAction::Release(price) => released.push((order_state.order.order_id, price)),
+            }
+        }
+        for (order_id, price) in released {
+            if let Some(order_state) = held_orders.remove(&order_id) {
+                info!("Releasing {} order {} at {} on last trade {}", order_state.order.order_type, order_state.order.client_order_id, price, last_price);
+                tokio::spawn(release_order_loop(self.dao.clone(), self.web_socket_server.clone(), instrument_manager.clone(), order_state, price));
+            }
+        }
+    }
+}
+
+// Buys trigger when the market trades up through the stop, sells when it trades down through it.
+// A trailing stop without a stop price yet starts one offset away from the first trade it sees.
+fn evaluate_trigger(order: &Order,
+                    last_price: Money) -> TriggerAction {
+    let is_buy = order.quantity > 0;
+    let stop_price = match order.order_type {
+        OrderType::Limit => return TriggerAction::Hold,
+        OrderType::Stop | OrderType::StopLimit => match order.stop_price {
+            Some(stop_price) => stop_price,
+            None => return TriggerAction::Hold,
+        },
+        OrderType::TrailingStop => {
+            let trailing_offset = order.trailing_offset.unwrap_or(Money::ZERO);
+            let trailed_price = if is_buy { last_price + trailing_offset } else { last_price - trailing_offset };
+            match order.stop_price {
+                Some(stop_price) if (is_buy && trailed_price < stop_price) || (!is_buy && trailed_price > stop_price) => {
+                    return TriggerAction::MoveStop(trailed_price)
+                },
+                Some(stop_price) => stop_price,
+                None => return TriggerAction::MoveStop(trailed_price),
+            }
+        }
+    };
+    let triggered = if is_buy { last_price >= stop_price } else { last_price <= stop_price };
+    if !triggered {
+        return TriggerAction::Hold;
+    }
+    match order.order_type {
+        OrderType::StopLimit => TriggerAction::Release(order.price),
+        _ => TriggerAction::Release(last_price),
+    }
+}
+
+async fn save_stop_price(dao: Dao,
+                         order_id: i64,
+                         stop_price: Money) {
+    let mut db_connection = match dao.get_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(dao_error) => {
+            error!("Could not get connection to save stop price: {}", dao_error);
+            return;
+        }
+    };
+    let txn = match dao.begin(&mut db_connection).await {
+        Ok(txn) => txn,
+        Err(dao_error) => {
+            error!("Could not begin to save stop price: {}", dao_error);
+            return;
+        }
+    };
+    match txn.update_stop_price(order_id, stop_price).await {
+        Ok(_) => {},
+        Err(dao_error) => {
+            error!("Could not save stop price for order {}: {}", order_id, dao_error);
+            return;
+        }
+    };
+    match txn.commit().await {
+        Ok(_) => {},
+        Err(dao_error) => error!("Could not commit stop price for order {}: {}", order_id, dao_error),
+    };
+}
+
+async fn release_order_loop(dao: Dao,
+                            web_socket_server: WebSocketServer,
+                            instrument_manager: InstrumentManager,
+                            order_state: OrderState,
+                            price: Money) {
+    match release_order(&dao, web_socket_server, &instrument_manager, &order_state, price).await {
+        Ok(_) => {},
+        Err(err) => error!("Unable to release order {}: {}", order_state.order.client_order_id, err),
+    }
+}
+
+// The order is re-read so that a cancel or expiry that got in first wins
+async fn release_order(dao: &Dao,
+                       mut web_socket_server: WebSocketServer,
+                       instrument_manager: &InstrumentManager,
+                       held_order_state: &OrderState,
+                       price: Money) -> Result<(), Error> {
+    let (order_state, account_key) = {
+        let mut db_connection = match dao.get_connection().await {
+            Ok(db_connection) => db_connection,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get connection: {}", dao_error)),
+        };
+        let txn = match dao.begin(&mut db_connection).await {
+            Ok(txn) => txn,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not begin: {}", dao_error)),
+        };
+        let mut order_state = match txn.get_order_by_client_order_id(&held_order_state.order.client_order_id).await {
+            Ok(Some(order_state)) => order_state,
+            Ok(None) => return Err(anyhow::anyhow!("Order disappeared before release")),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get order: {}", dao_error)),
+        };
+        if order_state.order_status != OrderStatus::PendingTrigger {
+            info!("Order {} is {}, not releasing", order_state.order.client_order_id, order_state.order_status);
+            return Ok(());
+        }
+        order_state.order_status = OrderStatus::Pending;
+        order_state.update_time = current_time_millis();
+        match txn.update_order(&mut order_state).await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not update order: {}", dao_error)),
+        };
+        match txn.save_outbox_entry(&new_outbox_entry(order_state.order.order_id, price)).await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not save outbox entry: {}", dao_error)),
+        };
+        let account = match txn.get_account(order_state.order.account_id).await {
+            Ok(Some(account)) => account,
+            Ok(None) => return Err(anyhow::anyhow!("No account for id: {}", order_state.order.account_id)),
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get account: {}", dao_error)),
+        };
+        match txn.commit().await {
+            Ok(_) => {},
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not commit: {}", dao_error)),
+        };
+        (order_state, account.account_key)
+    };
+    send_order_state(&mut web_socket_server, instrument_manager, &account_key, &order_state)?;
+    let order_state = submit_pending_order(dao, &mut web_socket_server, instrument_manager, &account_key, order_state, price).await?;
+    if order_state.order_status == OrderStatus::Rejected {
+        tokio::spawn(handle_group_update(dao.clone(), web_socket_server, instrument_manager.clone(), order_state));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::order::{Order, OrderLeg, OrderType, TimeInForce};
+    use crate::money::Money;
+    use crate::trade_handling::trigger_engine::{evaluate_trigger, TriggerAction};
+
+    fn order(order_type: OrderType, quantity: i32, stop_price: Option<Money>, trailing_offset: Option<Money>) -> Order {
+        Order {
+            order_id: 0,
+            account_id: 0,
+            order_number: 1,
+            ext_order_id: "ext".to_string(),
+            client_order_id: "client".to_string(),
+            create_time: 0,
+            price: Money::from(99),
+            quantity,
+            legs: vec![OrderLeg {
+                order_leg_id: 0,
+                instrument_id: 1,
+                ratio: 1,
+            }],
+            time_in_force: TimeInForce::Gtc,
+            expire_time: None,
+            order_type,
+            stop_price,
+            trailing_offset,
+        }
+    }
+
+    #[test]
+    async fn test_stop_triggers_through_stop_price() {
+        let sell_stop = order(OrderType::Stop, -10, Some(Money::from(95)), None);
+        assert_eq!(evaluate_trigger(&sell_stop, Money::from(96)), TriggerAction::Hold);
+        assert_eq!(evaluate_trigger(&sell_stop, Money::from(94)), TriggerAction::Release(Money::from(94)));
+        let buy_stop_limit = order(OrderType::StopLimit, 10, Some(Money::from(98)), None);
+        assert_eq!(evaluate_trigger(&buy_stop_limit, Money::from(97)), TriggerAction::Hold);
+        assert_eq!(evaluate_trigger(&buy_stop_limit, Money::from(98)), TriggerAction::Release(Money::from(99)));
+        assert_eq!(evaluate_trigger(&order(OrderType::Limit, 10, None, None), Money::from(200)), TriggerAction::Hold);
+    }
+
+    #[test]
+    async fn test_trailing_stop_follows_market() {
+        let fresh_sell = order(OrderType::TrailingStop, -10, None, Some(Money::from(2)));
+        assert_eq!(evaluate_trigger(&fresh_sell, Money::from(100)), TriggerAction::MoveStop(Money::from(98)));
+        let sell = order(OrderType::TrailingStop, -10, Some(Money::from(98)), Some(Money::from(2)));
+        assert_eq!(evaluate_trigger(&sell, Money::from(103)), TriggerAction::MoveStop(Money::from(101)));
+        assert_eq!(evaluate_trigger(&sell, Money::from(99)), TriggerAction::Hold);
+        assert_eq!(evaluate_trigger(&sell, Money::from(98)), TriggerAction::Release(Money::from(98)));
+        let buy = order(OrderType::TrailingStop, 10, Some(Money::from(102)), Some(Money::from(2)));
+        assert_eq!(evaluate_trigger(&buy, Money::from(97)), TriggerAction::MoveStop(Money::from(99)));
+        assert_eq!(evaluate_trigger(&buy, Money::from(102)), TriggerAction::Release(Money::from(102)));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::dtos::account::{Balance, Position};
+use crate::dtos::account::{Balance, MarginCall, Position};
 use crate::dtos::order::OrderState;
 use crate::dtos::order::Trade;
 use serde::{Deserialize, Serialize};
@@ -8,5 +8,6 @@ pub struct AccountUpdate {
     pub position: Option<Position>,
     pub balance: Option<Balance>,
     pub trade: Option<Trade>,
-    pub order_state: Option<OrderState>
+    pub order_state: Option<OrderState>,
+    pub margin_call: Option<MarginCall>,
 }
\ No newline at end of file
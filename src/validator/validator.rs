@@ -1,9 +1,14 @@
 use crate::dtos;
-use crate::dtos::order::VettingResult;
-use crate::entities::order::OrderState;
+use crate::dtos::order::{OrderType, TimeInForce, VettingResult};
+use crate::dtos::order_group::{OrderGroup, OrderGroupRole, OrderGroupType};
+use crate::entities::exchange::{Exchange, Instrument};
+use crate::entities::order::{is_order_status_held, OrderState};
 use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
+use crate::time::current_time_millis;
 use anyhow::Error;
 use std::collections::HashMap;
+use std::ops::Neg;
 
 #[derive(Clone)]
 pub struct Validator {
@@ -16,45 +21,555 @@ impl Validator {
     }
     pub fn validate_order(&self,
                           rest_api_order: &dtos::order::Order,
-                          viable_orders: &HashMap<String, OrderState>) -> Result<VettingResult, Error> {
+                          viable_orders: &HashMap<String, OrderState>,
+                          may_make_markets: bool) -> Result<VettingResult, Error> {
         if (rest_api_order.quantity == 0) {
             return Ok(VettingResult {
                 pass: false,
-                reject_reason: Some("Order quantity is 0".to_string())
+                reject_reason: Some("Order quantity is 0".to_string()),
+                estimated_commission: None,
             })
         }
         
+        let mut leg_instruments = Vec::new();
         for leg in rest_api_order.legs.iter() {
-            let leg_instrument_option = self.instrument_manager.get_instrument_by_key(leg.instrument_key.as_str())?;
-            let leg_instrument = match leg_instrument_option {
-                Some(leg_instrument) => leg_instrument,
+            match self.instrument_manager.get_instrument_by_key(leg.instrument_key.as_str())? {
+                Some(leg_instrument) => leg_instruments.push(leg_instrument),
                 None => return Err(anyhow::anyhow!("Unable to find instrument for key {}", leg.instrument_key.as_str()))
             };
+        }
+        let exchange = match leg_instruments.first() {
+            Some(leg_instrument) => self.instrument_manager.get_exchange_for_instrument(leg_instrument)?,
+            None => return Ok(VettingResult {
+                pass: false,
+                reject_reason: Some("Order has no legs".to_string()),
+                estimated_commission: None,
+            })
+        };
+        if let Some(reject_reason) = check_leg_combination(rest_api_order, &leg_instruments, &exchange) {
+            return Ok(VettingResult {
+                pass: false,
+                reject_reason: Some(reject_reason),
+                estimated_commission: None,
+            })
+        }
 
-            for viable_order in viable_orders.values() {
-                for existing_leg in viable_order.order.clone().legs {
-                    if leg_instrument.instrument_id == existing_leg.instrument_id {
-                        let new_leg_quantity = rest_api_order.quantity * leg.ratio;
-                        let existing_leg_quantity = viable_order.order.quantity * existing_leg.ratio;
-
-                        if (new_leg_quantity > 0 && existing_leg_quantity < 0 &&
-                            rest_api_order.price >= viable_order.order.price) || (
-                            new_leg_quantity < 0 && existing_leg_quantity > 0 &&
-                            rest_api_order.price <= viable_order.order.price) {
-                            return Ok(VettingResult {
-                                pass: false,
-                                reject_reason:
-                                Some(format!("This order would immediately trade against your existing order # {}",
-                                             viable_order.order.order_number).to_string())
-                            })
-                        }
-                    }
-                }
+        for (leg, leg_instrument) in rest_api_order.legs.iter().zip(leg_instruments) {
+            if let Some(reject_reason) = check_price_increment(rest_api_order.price, &leg_instrument) {
+                return Ok(VettingResult {
+                    pass: false,
+                    reject_reason: Some(reject_reason),
+                    estimated_commission: None,
+                })
+            }
+
+            if let Some(reject_reason) = check_time_in_force(rest_api_order, exchange.supports_time_in_force, current_time_millis()) {
+                return Ok(VettingResult {
+                    pass: false,
+                    reject_reason: Some(reject_reason),
+                    estimated_commission: None,
+                })
+            }
+
+            if let Some(reject_reason) = check_order_type(rest_api_order, &leg_instrument) {
+                return Ok(VettingResult {
+                    pass: false,
+                    reject_reason: Some(reject_reason),
+                    estimated_commission: None,
+                })
+            }
+
+            if let Some(reject_reason) = check_viable_orders(rest_api_order, leg, &leg_instrument, viable_orders, may_make_markets) {
+                return Ok(VettingResult {
+                    pass: false,
+                    reject_reason: Some(reject_reason),
+                    estimated_commission: None,
+                })
             }
         }
         Ok(VettingResult {
             pass: true,
-            reject_reason: None
+            reject_reason: None,
+            estimated_commission: None,
         })
     }
 }
+
+fn check_viable_orders(rest_api_order: &dtos::order::Order,
+                       leg: &dtos::order::OrderLeg,
+                       leg_instrument: &Instrument,
+                       viable_orders: &HashMap<String, OrderState>,
+                       may_make_markets: bool) -> Option<String> {
+    // Held orders are not on the book, so cannot be traded against
+    for viable_order in viable_orders.values().filter(|viable_order| !is_order_status_held(&viable_order.order_status)) {
+        for existing_leg in viable_order.order.legs.iter() {
+            if leg_instrument.instrument_id == existing_leg.instrument_id {
+                let new_leg_quantity = rest_api_order.quantity * leg.ratio;
+                let existing_leg_quantity = viable_order.order.quantity * existing_leg.ratio;
+
+                if (new_leg_quantity > 0 && existing_leg_quantity < 0 &&
+                    rest_api_order.price >= viable_order.order.price) || (
+                    new_leg_quantity < 0 && existing_leg_quantity > 0 &&
+                    rest_api_order.price <= viable_order.order.price) {
+                    return Some(format!("This order would immediately trade against your existing order # {}",
+                                        viable_order.order.order_number));
+                }
+                if !may_make_markets && new_leg_quantity.signum() == existing_leg_quantity.signum().neg() {
+                    return Some(format!("Quoting both sides of {} requires the MakeMarkets privilege, see your existing order # {}",
+                                        leg.instrument_key, viable_order.order.order_number));
+                }
+            }
+        }
+    }
+    None
+}
+
+// Ratios are kept in lowest terms. The common factor moves into the quantity, and the prices,
+// which are per unit of the combination, are divided by it. An order whose divided prices would
+// fall off the tick of any of its legs, or with a leg that is not known, is left as submitted
+pub fn normalize_leg_ratios(rest_api_order: &mut dtos::order::Order,
+                            instrument_manager: &InstrumentManager) {
+    let tick_sizes: Option<Vec<Money>> = rest_api_order.legs.iter()
+        .map(|leg| match instrument_manager.get_instrument_by_key(leg.instrument_key.as_str()) {
+            Ok(Some(instrument)) => Some(instrument.tick_size),
+            _ => None,
+        })
+        .collect();
+    if let Some(tick_sizes) = tick_sizes {
+        normalize_leg_ratios_on_tick(rest_api_order, &tick_sizes);
+    }
+}
+
+fn normalize_leg_ratios_on_tick(rest_api_order: &mut dtos::order::Order,
+                                tick_sizes: &[Money]) {
+    let divisor = rest_api_order.legs.iter().fold(0, |divisor, leg| gcd(divisor, leg.ratio.abs()));
+    if divisor <= 1 {
+        return;
+    }
+    let price = rest_api_order.price / divisor;
+    let stop_price = rest_api_order.stop_price.map(|stop_price| stop_price / divisor);
+    let trailing_offset = rest_api_order.trailing_offset.map(|trailing_offset| trailing_offset / divisor);
+    let on_tick = |value: Money| tick_sizes.iter().all(|tick_size| tick_size.is_zero() || value.is_multiple_of(*tick_size));
+    if !on_tick(price) || !stop_price.is_none_or(on_tick) || !trailing_offset.is_none_or(on_tick) {
+        return;
+    }
+    for leg in rest_api_order.legs.iter_mut() {
+        leg.ratio /= divisor;
+    }
+    rest_api_order.quantity *= divisor;
+    rest_api_order.price = price;
+    rest_api_order.stop_price = stop_price;
+    rest_api_order.trailing_offset = trailing_offset;
+}
+
+fn gcd(a: i32,
+       b: i32) -> i32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// A multi-leg order goes to the exchange as one order, so every leg must trade there, each
+// instrument may appear only once, and there can be no more legs than the exchange accepts
+fn check_leg_combination(rest_api_order: &dtos::order::Order,
+                         leg_instruments: &[Instrument],
+                         exchange: &Exchange) -> Option<String> {
+    match exchange.max_order_legs {
+        Some(max_order_legs) if rest_api_order.legs.len() as i32 > max_order_legs => {
+            return Some(format!("{} accepts orders of at most {} legs", exchange.code, max_order_legs));
+        },
+        _ => {},
+    }
+    for (index, (leg, leg_instrument)) in rest_api_order.legs.iter().zip(leg_instruments).enumerate() {
+        if leg.ratio == 0 {
+            return Some(format!("The ratio of the {} leg is 0", leg_instrument.symbol));
+        }
+        if leg_instrument.exchange_id != exchange.exchange_id {
+            return Some(format!("{} does not trade on {}, where the other legs trade", leg_instrument.symbol, exchange.code));
+        }
+        if leg_instruments[..index].iter().any(|other| other.instrument_id == leg_instrument.instrument_id) {
+            return Some(format!("{} appears in more than one leg", leg_instrument.symbol));
+        }
+    }
+    None
+}
+
+// A multi-leg price has to respect the increment of every one of its legs
+fn check_price_increment(price: Money,
+                         instrument: &Instrument) -> Option<String> {
+    if price.decimal_places() as i32 > instrument.price_precision {
+        return Some(format!("Price {} has more than {} decimal places allowed for {}",
+                            price, instrument.price_precision, instrument.symbol));
+    }
+    if !price.is_multiple_of(instrument.tick_size) {
+        return Some(format!("Price {} is not a multiple of the {} tick size of {}",
+                            price, instrument.tick_size, instrument.symbol));
+    }
+    None
+}
+
+// Fill or kill cannot be emulated once part of the order has traded, so it needs the exchange's support
+fn check_time_in_force(rest_api_order: &dtos::order::Order,
+                       supports_time_in_force: bool,
+                       now: i64) -> Option<String> {
+    match (&rest_api_order.time_in_force, rest_api_order.expire_time) {
+        (TimeInForce::Gtd, None) => return Some("Good till date orders require an expire_time".to_string()),
+        (TimeInForce::Gtd, Some(expire_time)) if expire_time <= now => return Some(format!("Expire time {} has already passed", expire_time)),
+        (TimeInForce::Gtd, Some(_)) => {},
+        (time_in_force, Some(_)) => return Some(format!("An expire_time is not allowed on {} orders", time_in_force)),
+        (_, None) => {},
+    }
+    if rest_api_order.time_in_force == TimeInForce::Fok && !supports_time_in_force {
+        return Some("Fill or kill orders are not supported on this exchange".to_string());
+    }
+    None
+}
+
+// The broker holds stop orders itself and releases them as a single limit order
+fn check_order_type(rest_api_order: &dtos::order::Order,
+                    instrument: &Instrument) -> Option<String> {
+    if rest_api_order.order_type != OrderType::Limit && rest_api_order.legs.len() != 1 {
+        return Some(format!("{} orders must have a single leg", rest_api_order.order_type));
+    }
+    match rest_api_order.order_type {
+        OrderType::Limit => {
+            if rest_api_order.stop_price.is_some() || rest_api_order.trailing_offset.is_some() {
+                return Some("Limit orders take no stop_price or trailing_offset".to_string());
+            }
+        },
+        OrderType::Stop | OrderType::StopLimit => {
+            if rest_api_order.trailing_offset.is_some() {
+                return Some(format!("{} orders take no trailing_offset", rest_api_order.order_type));
+            }
+            match rest_api_order.stop_price {
+                Some(stop_price) if stop_price > Money::ZERO => return check_price_increment(stop_price, instrument),
+                _ => return Some(format!("{} orders require a positive stop_price", rest_api_order.order_type)),
+            }
+        },
+        OrderType::TrailingStop => {
+            if let Some(reject_reason) = rest_api_order.stop_price.and_then(|stop_price| check_price_increment(stop_price, instrument)) {
+                return Some(reject_reason);
+            }
+            match rest_api_order.trailing_offset {
+                Some(trailing_offset) if trailing_offset > Money::ZERO => return check_price_increment(trailing_offset, instrument),
+                _ => return Some("TrailingStop orders require a positive trailing_offset".to_string()),
+            }
+        },
+    }
+    None
+}
+
+// Exit orders must unwind the entry: the same legs on the other side, for no more than the entry.
+// Take profits rest on the book as limits, while stop losses wait with the broker for their trigger.
+pub fn check_order_group(order_group: &OrderGroup) -> Option<String> {
+    let count_of = |role: OrderGroupRole| order_group.members.iter().filter(|member| member.role == role).count();
+    match order_group.group_type {
+        OrderGroupType::Oco => {
+            if order_group.members.len() != 2 || count_of(OrderGroupRole::Oco) != 2 {
+                return Some("Oco groups must have exactly two Oco members".to_string());
+            }
+            None
+        },
+        OrderGroupType::Bracket => {
+            let take_profits = count_of(OrderGroupRole::TakeProfit);
+            let stop_losses = count_of(OrderGroupRole::StopLoss);
+            if count_of(OrderGroupRole::Entry) != 1 || take_profits > 1 || stop_losses > 1
+                || take_profits + stop_losses == 0 || count_of(OrderGroupRole::Oco) != 0 {
+                return Some("Bracket groups must have one Entry and at most one each of TakeProfit and StopLoss".to_string());
+            }
+            let entry = match order_group.members.iter().find(|member| member.role == OrderGroupRole::Entry) {
+                Some(entry) => &entry.order,
+                None => return Some("Bracket groups must have an Entry".to_string()),
+            };
+            for member in order_group.members.iter().filter(|member| member.role != OrderGroupRole::Entry) {
+                let same_legs = member.order.legs.len() == entry.legs.len()
+                    && member.order.legs.iter().zip(entry.legs.iter())
+                    .all(|(leg, entry_leg)| leg.instrument_key == entry_leg.instrument_key && leg.ratio == entry_leg.ratio);
+                if !same_legs {
+                    return Some(format!("{} must have the same legs as the Entry", member.role));
+                }
+                if member.order.quantity.signum() != -entry.quantity.signum() || member.order.quantity.abs() > entry.quantity.abs() {
+                    return Some(format!("{} must be on the other side of the Entry and no larger", member.role));
+                }
+                match (&member.role, &member.order.order_type) {
+                    (OrderGroupRole::TakeProfit, OrderType::Limit) => {},
+                    (OrderGroupRole::TakeProfit, _) => return Some("TakeProfit must be a Limit order".to_string()),
+                    (OrderGroupRole::StopLoss, OrderType::Limit) => return Some("StopLoss must be a stop order".to_string()),
+                    _ => {},
+                }
+            }
+            None
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dtos::exchange::{AssetClass, InstrumentStatus};
+    use crate::dtos::order::{Order, OrderLeg, OrderType, TimeInForce};
+    use crate::entities::exchange::{Exchange, Instrument};
+    use crate::money::Money;
+    use crate::dtos::order_group::{OrderGroup, OrderGroupMember, OrderGroupRole, OrderGroupType};
+    use crate::entities;
+    use crate::entities::order::{OrderState, OrderStatus};
+    use crate::validator::validator::{check_leg_combination, check_order_group, check_order_type, check_price_increment, check_time_in_force, check_viable_orders, normalize_leg_ratios_on_tick};
+    use std::collections::HashMap;
+
+    fn instrument(tick_size: Money, price_precision: i32) -> Instrument {
+        Instrument {
+            instrument_id: 1,
+            instrument_key: "key".to_string(),
+            exchange_id: 1,
+            exchange_instrument_id: 1,
+            status: InstrumentStatus::Active,
+            symbol: "ABC".to_string(),
+            asset_class: AssetClass::Equity,
+            description: "ABC".to_string(),
+            expiration_time: 0,
+            tick_size,
+            price_precision,
+        }
+    }
+
+    #[test]
+    async fn test_price_on_tick_passes() {
+        assert_eq!(check_price_increment(Money::new(1005, 2), &instrument(Money::new(5, 2), 2)), None);
+        assert_eq!(check_price_increment(Money::new(10100, 3), &instrument(Money::new(1, 2), 2)), None);
+    }
+
+    #[test]
+    async fn test_price_off_tick_rejected() {
+        let reject_reason = check_price_increment(Money::new(1003, 2), &instrument(Money::new(5, 2), 2));
+        assert!(reject_reason.unwrap().contains("tick size"));
+        let reject_reason = check_price_increment(Money::new(10005, 3), &instrument(Money::new(5, 3), 2));
+        assert!(reject_reason.unwrap().contains("decimal places"));
+    }
+
+    fn order(time_in_force: TimeInForce, expire_time: Option<i64>) -> Order {
+        Order {
+            create_time: 0,
+            order_number: None,
+            ext_order_id: None,
+            account_key: None,
+            price: Money::from(10),
+            quantity: 1,
+            legs: vec![OrderLeg {
+                instrument_key: "key".to_string(),
+                ratio: 1,
+            }],
+            time_in_force,
+            expire_time,
+            order_type: OrderType::Limit,
+            stop_price: None,
+            trailing_offset: None,
+        }
+    }
+
+    #[test]
+    async fn test_time_in_force_checks() {
+        assert_eq!(check_time_in_force(&order(TimeInForce::Day, None), false, 1000), None);
+        assert_eq!(check_time_in_force(&order(TimeInForce::Gtd, Some(2000)), false, 1000), None);
+        assert!(check_time_in_force(&order(TimeInForce::Gtd, None), false, 1000).is_some());
+        assert!(check_time_in_force(&order(TimeInForce::Gtd, Some(1000)), false, 1000).is_some());
+        assert!(check_time_in_force(&order(TimeInForce::Gtc, Some(2000)), false, 1000).is_some());
+        assert!(check_time_in_force(&order(TimeInForce::Fok, None), false, 1000).is_some());
+        assert_eq!(check_time_in_force(&order(TimeInForce::Fok, None), true, 1000), None);
+    }
+
+    #[test]
+    async fn test_order_type_checks() {
+        let tick_instrument = instrument(Money::new(5, 2), 2);
+        let mut stop_order = order(TimeInForce::Gtc, None);
+        stop_order.order_type = OrderType::Stop;
+        assert!(check_order_type(&stop_order, &tick_instrument).unwrap().contains("stop_price"));
+        stop_order.stop_price = Some(Money::new(995, 2));
+        assert_eq!(check_order_type(&stop_order, &tick_instrument), None);
+        stop_order.stop_price = Some(Money::new(993, 2));
+        assert!(check_order_type(&stop_order, &tick_instrument).unwrap().contains("tick size"));
+
+        let mut trailing_order = order(TimeInForce::Gtc, None);
+        trailing_order.order_type = OrderType::TrailingStop;
+        trailing_order.trailing_offset = Some(Money::new(50, 2));
+        assert_eq!(check_order_type(&trailing_order, &tick_instrument), None);
+        trailing_order.legs.push(trailing_order.legs[0].clone());
+        assert!(check_order_type(&trailing_order, &tick_instrument).unwrap().contains("single leg"));
+
+        let mut limit_order = order(TimeInForce::Gtc, None);
+        assert_eq!(check_order_type(&limit_order, &tick_instrument), None);
+        limit_order.trailing_offset = Some(Money::from(1));
+        assert!(check_order_type(&limit_order, &tick_instrument).is_some());
+    }
+
+    fn member(role: OrderGroupRole, order_type: OrderType, quantity: i32) -> OrderGroupMember {
+        let mut member_order = order(TimeInForce::Gtc, None);
+        member_order.order_type = order_type;
+        member_order.quantity = quantity;
+        OrderGroupMember {
+            role,
+            order: member_order,
+        }
+    }
+
+    #[test]
+    async fn test_order_group_checks() {
+        let bracket = OrderGroup {
+            group_type: OrderGroupType::Bracket,
+            members: vec![member(OrderGroupRole::Entry, OrderType::Limit, 10),
+                          member(OrderGroupRole::TakeProfit, OrderType::Limit, -10),
+                          member(OrderGroupRole::StopLoss, OrderType::Stop, -10)],
+        };
+        assert_eq!(check_order_group(&bracket), None);
+
+        let mut same_side = bracket.clone();
+        same_side.members[1].order.quantity = 10;
+        assert!(check_order_group(&same_side).unwrap().contains("other side"));
+
+        let mut limit_stop_loss = bracket.clone();
+        limit_stop_loss.members[2].order.order_type = OrderType::Limit;
+        assert!(check_order_group(&limit_stop_loss).is_some());
+
+        let mut no_entry = bracket.clone();
+        no_entry.members.remove(0);
+        assert!(check_order_group(&no_entry).is_some());
+
+        let oco = OrderGroup {
+            group_type: OrderGroupType::Oco,
+            members: vec![member(OrderGroupRole::Oco, OrderType::Limit, -10),
+                          member(OrderGroupRole::Oco, OrderType::Stop, -10)],
+        };
+        assert_eq!(check_order_group(&oco), None);
+        let mut lone_oco = oco.clone();
+        lone_oco.members.pop();
+        assert!(check_order_group(&lone_oco).is_some());
+    }
+
+    fn exchange(max_order_legs: Option<i32>) -> Exchange {
+        Exchange {
+            exchange_id: 1,
+            code: "EX".to_string(),
+            url: "".to_string(),
+            websocket_url: "".to_string(),
+            description: "".to_string(),
+            api_key: "".to_string(),
+            supports_time_in_force: false,
+            max_order_legs,
+        }
+    }
+
+    fn spread(ratios: &[i32]) -> (Order, Vec<Instrument>) {
+        let mut spread_order = order(TimeInForce::Gtc, None);
+        spread_order.legs = ratios.iter().enumerate().map(|(index, ratio)| OrderLeg {
+            instrument_key: format!("key{}", index),
+            ratio: *ratio,
+        }).collect();
+        let instruments = (0..ratios.len()).map(|index| {
+            let mut leg_instrument = instrument(Money::new(1, 2), 2);
+            leg_instrument.instrument_id = index as i64;
+            leg_instrument.symbol = format!("SYM{}", index);
+            leg_instrument
+        }).collect();
+        (spread_order, instruments)
+    }
+
+    #[test]
+    async fn test_leg_combination_checks() {
+        let (spread_order, instruments) = spread(&[1, -1]);
+        assert_eq!(check_leg_combination(&spread_order, &instruments, &exchange(Some(2))), None);
+        assert!(check_leg_combination(&spread_order, &instruments, &exchange(Some(1))).unwrap().contains("at most 1 legs"));
+        // An exchange without a limit takes any number of legs
+        let (three_legs, three_instruments) = spread(&[1, -2, 1]);
+        assert_eq!(check_leg_combination(&three_legs, &three_instruments, &exchange(None)), None);
+
+        let (zero_ratio, instruments) = spread(&[1, 0]);
+        assert!(check_leg_combination(&zero_ratio, &instruments, &exchange(Some(2))).unwrap().contains("is 0"));
+
+        let (spread_order, mut instruments) = spread(&[1, -1]);
+        instruments[1].exchange_id = 2;
+        assert!(check_leg_combination(&spread_order, &instruments, &exchange(Some(2))).unwrap().contains("does not trade on EX"));
+
+        let (spread_order, mut instruments) = spread(&[1, -1]);
+        instruments[1].instrument_id = instruments[0].instrument_id;
+        assert!(check_leg_combination(&spread_order, &instruments, &exchange(Some(2))).unwrap().contains("more than one leg"));
+    }
+
+    #[test]
+    async fn test_normalize_leg_ratios() {
+        let tick_sizes = [Money::new(1, 2), Money::new(5, 2)];
+        let (mut spread_order, _) = spread(&[2, -4]);
+        spread_order.quantity = 3;
+        normalize_leg_ratios_on_tick(&mut spread_order, &tick_sizes);
+        assert_eq!(spread_order.legs.iter().map(|leg| leg.ratio).collect::<Vec<i32>>(), vec![1, -2]);
+        assert_eq!(spread_order.quantity, 6);
+        assert_eq!(spread_order.price, Money::from(5));
+
+        let (mut lowest_terms, _) = spread(&[2, -3]);
+        normalize_leg_ratios_on_tick(&mut lowest_terms, &tick_sizes);
+        assert_eq!(lowest_terms.quantity, 1);
+        assert_eq!(lowest_terms.price, Money::from(10));
+
+        // 10.05 / 2 is off the 0.05 tick of the second leg, so the order stays as submitted
+        let (mut off_tick, _) = spread(&[2, -4]);
+        off_tick.price = Money::new(1005, 2);
+        normalize_leg_ratios_on_tick(&mut off_tick, &tick_sizes);
+        assert_eq!(off_tick.legs.iter().map(|leg| leg.ratio).collect::<Vec<i32>>(), vec![2, -4]);
+        assert_eq!((off_tick.quantity, off_tick.price), (1, Money::new(1005, 2)));
+
+        // So does one whose stop price would fall off the tick, even with the limit price on it
+        let (mut off_tick_stop, _) = spread(&[4, -8]);
+        off_tick_stop.price = Money::from(12);
+        off_tick_stop.stop_price = Some(Money::new(1010, 2));
+        normalize_leg_ratios_on_tick(&mut off_tick_stop, &tick_sizes[..1]);
+        assert_eq!(off_tick_stop.quantity, 1);
+        assert_eq!(off_tick_stop.stop_price, Some(Money::new(1010, 2)));
+    }
+
+    fn viable_order(order_status: OrderStatus, price: Money, quantity: i32) -> HashMap<String, OrderState> {
+        let order_state = OrderState {
+            order: entities::order::Order {
+                order_id: 1,
+                account_id: 1,
+                order_number: 7,
+                ext_order_id: "ext".to_string(),
+                client_order_id: "client".to_string(),
+                create_time: 0,
+                price,
+                quantity,
+                legs: vec![entities::order::OrderLeg {
+                    order_leg_id: 1,
+                    instrument_id: 1,
+                    ratio: 1,
+                }],
+                time_in_force: TimeInForce::Gtc,
+                expire_time: None,
+                order_type: OrderType::Limit,
+                stop_price: None,
+                trailing_offset: None,
+            },
+            update_time: 0,
+            order_status,
+            version_number: 0,
+            reject_reason: None,
+            filled_quantity: 0,
+            average_fill_price: Money::ZERO,
+        };
+        HashMap::from([("ext".to_string(), order_state)])
+    }
+
+    #[test]
+    async fn test_quoting_both_sides_requires_make_markets() {
+        let leg_instrument = instrument(Money::new(1, 2), 2);
+        let mut offer = order(TimeInForce::Gtc, None);
+        offer.quantity = -1;
+        offer.price = Money::from(11);
+        let leg = offer.legs[0].clone();
+        let bid = viable_order(OrderStatus::Open, Money::from(10), 1);
+
+        assert!(check_viable_orders(&offer, &leg, &leg_instrument, &bid, false).unwrap().contains("MakeMarkets"));
+        assert_eq!(check_viable_orders(&offer, &leg, &leg_instrument, &bid, true), None);
+        // The same side does not make a market
+        assert_eq!(check_viable_orders(&offer, &leg, &leg_instrument, &viable_order(OrderStatus::Open, Money::from(12), -1), false), None);
+        // A held stop is not on the book
+        assert_eq!(check_viable_orders(&offer, &leg, &leg_instrument, &viable_order(OrderStatus::PendingTrigger, Money::from(10), 1), false), None);
+
+        offer.price = Money::from(10);
+        assert!(check_viable_orders(&offer, &leg, &leg_instrument, &bid, true).unwrap().contains("immediately trade"));
+    }
+}
@@ -1,9 +1,12 @@
 use crate::dtos;
 use crate::dtos::order::VettingResult;
-use crate::entities::account::Position;
+use crate::entities::account::{Balance, Position};
 use crate::entities::order::OrderState;
+use crate::vetting::vetter::{pass, Vetter};
+use anyhow::Error;
 use std::collections::HashMap;
-use std::fmt::Error;
+
+pub const ALL_PASS_VETTER: &str = "AllPass";
 
 #[derive(Clone)]
 pub struct AllPassVetter {
@@ -13,13 +16,18 @@ impl AllPassVetter {
     pub fn new() -> AllPassVetter {
         AllPassVetter {}
     }
-    pub async fn vet_order(& self,
-                           rest_api_order: &dtos::order::Order,
-                           viable_orders: &HashMap<String, OrderState>,
-                           open_positions: &HashMap<i64, Position>) -> Result<VettingResult, Error> {
-        Ok(VettingResult {
-            pass: true,
-            reject_reason: None
-        })
+}
+
+impl Vetter for AllPassVetter {
+    fn name(&self) -> &str {
+        ALL_PASS_VETTER
+    }
+
+    fn vet_order(&self,
+                 _rest_api_order: &dtos::order::Order,
+                 _viable_orders: &HashMap<String, OrderState>,
+                 _open_positions: &HashMap<i64, Position>,
+                 _balance: &Balance) -> Result<VettingResult, Error> {
+        Ok(pass())
     }
 }
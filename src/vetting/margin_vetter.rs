@@ -0,0 +1,75 @@
+use crate::dtos;
+use crate::dtos::order::VettingResult;
+use crate::entities::account::{Balance, MarginSummary, Position};
+use crate::entities::order::OrderState;
+use crate::margin::margin_engine::MarginEngine;
+use crate::vetting::vetter::{pass, reject, Vetter};
+use anyhow::Error;
+use std::collections::HashMap;
+
+pub const MARGIN_VETTER: &str = "Margin";
+
+#[derive(Clone)]
+pub struct MarginVetter {
+    margin_engine: MarginEngine,
+}
+
+impl MarginVetter {
+    pub fn new(margin_engine: MarginEngine) -> MarginVetter {
+        MarginVetter {
+            margin_engine,
+        }
+    }
+}
+
+impl Vetter for MarginVetter {
+    fn name(&self) -> &str {
+        MARGIN_VETTER
+    }
+
+    fn vet_order(&self,
+                 rest_api_order: &dtos::order::Order,
+                 viable_orders: &HashMap<String, OrderState>,
+                 open_positions: &HashMap<i64, Position>,
+                 balance: &Balance) -> Result<VettingResult, Error> {
+        let new_order = self.margin_engine.resolve_order(rest_api_order)?;
+        let current = self.margin_engine.compute(balance, open_positions, viable_orders, &[])?;
+        let pro_forma = self.margin_engine.compute(balance, open_positions, viable_orders, &new_order)?;
+        Ok(vet_margin(&current, &pro_forma))
+    }
+}
+
+// Orders that do not add to the initial margin requirement pass even in a deficit, so risk can be reduced
+fn vet_margin(current: &MarginSummary,
+              pro_forma: &MarginSummary) -> VettingResult {
+    if pro_forma.initial_margin > current.initial_margin && pro_forma.initial_margin > pro_forma.equity {
+        return reject(format!("Insufficient margin: initial margin would be {:.2} against equity of {:.2}",
+                              pro_forma.initial_margin, pro_forma.equity));
+    }
+    pass()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::account::MarginSummary;
+    use crate::money::Money;
+    use crate::vetting::margin_vetter::vet_margin;
+
+    fn summary(equity: i32, initial_margin: i32) -> MarginSummary {
+        MarginSummary {
+            equity: Money::from(equity),
+            initial_margin: Money::from(initial_margin),
+            maintenance_margin: Money::ZERO,
+            excess_equity: Money::from(equity - initial_margin),
+            buying_power: Money::ZERO,
+        }
+    }
+
+    #[test]
+    async fn test_vet_margin() {
+        assert!(vet_margin(&summary(1000, 200), &summary(1000, 900)).pass);
+        assert!(!vet_margin(&summary(1000, 200), &summary(1000, 1100)).pass);
+        // Reducing an existing deficit is allowed
+        assert!(vet_margin(&summary(1000, 1500), &summary(1000, 1200)).pass);
+    }
+}
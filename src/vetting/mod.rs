@@ -1 +1,5 @@
 pub(crate) mod all_pass_vetter;
+pub(crate) mod margin_vetter;
+pub(crate) mod risk_vetter;
+pub(crate) mod vetter;
+pub(crate) mod vetter_registry;
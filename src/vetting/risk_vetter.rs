@@ -0,0 +1,258 @@
+use crate::config::BrokerConfig;
+use crate::dtos;
+use crate::dtos::order::VettingResult;
+use crate::entities::account::{Balance, Position};
+use crate::entities::order::OrderState;
+use crate::instrument_manager::InstrumentManager;
+use crate::money::Money;
+use crate::vetting::vetter::{pass, reject, Vetter};
+use anyhow::Error;
+use std::collections::HashMap;
+
+pub const RISK_VETTER: &str = "Risk";
+
+#[derive(Clone, Debug)]
+pub struct RiskLimits {
+    pub max_gross_exposure: Money,
+    pub max_net_exposure: Money,
+    pub max_open_order_notional: Money,
+}
+
+impl RiskLimits {
+    pub fn from_config(config: &BrokerConfig) -> RiskLimits {
+        RiskLimits {
+            max_gross_exposure: config.max_gross_exposure,
+            max_net_exposure: config.max_net_exposure,
+            max_open_order_notional: config.max_open_order_notional,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RiskVetter {
+    instrument_manager: InstrumentManager,
+    limits: RiskLimits,
+}
+
+impl RiskVetter {
+    pub fn new(instrument_manager: InstrumentManager,
+               limits: RiskLimits) -> RiskVetter {
+        RiskVetter {
+            instrument_manager,
+            limits,
+        }
+    }
+
+    fn resolve_legs(&self,
+                    rest_api_order: &dtos::order::Order) -> Result<Vec<(i64, i32)>, Error> {
+        let mut legs = Vec::new();
+        for leg in rest_api_order.legs.iter() {
+            let instrument = match self.instrument_manager.get_instrument_by_key(leg.instrument_key.as_str())? {
+                Some(instrument) => instrument,
+                None => return Err(anyhow::anyhow!("Unable to find instrument for key {}", leg.instrument_key))
+            };
+            legs.push((instrument.instrument_id, leg.ratio));
+        }
+        Ok(legs)
+    }
+}
+
+impl Vetter for RiskVetter {
+    fn name(&self) -> &str {
+        RISK_VETTER
+    }
+
+    fn vet_order(&self,
+                 rest_api_order: &dtos::order::Order,
+                 viable_orders: &HashMap<String, OrderState>,
+                 open_positions: &HashMap<i64, Position>,
+                 balance: &Balance) -> Result<VettingResult, Error> {
+        let legs = self.resolve_legs(rest_api_order)?;
+        Ok(vet_resolved_order(&self.limits,
+                              rest_api_order.price,
+                              rest_api_order.quantity,
+                              &legs,
+                              viable_orders,
+                              open_positions,
+                              balance.cash))
+    }
+}
+
+// Leg notional is approximated as the order price times the signed leg quantity,
+// since the broker does not know how a multi-leg price is split across legs.
+fn vet_resolved_order(limits: &RiskLimits,
+                      price: Money,
+                      quantity: i32,
+                      legs: &[(i64, i32)],
+                      viable_orders: &HashMap<String, OrderState>,
+                      open_positions: &HashMap<i64, Position>,
+                      cash: Money) -> VettingResult {
+    let new_notional = price * quantity;
+
+    let open_buy_notional = open_buy_notional(viable_orders);
+    if new_notional > Money::ZERO && open_buy_notional + new_notional > cash {
+        return reject(format!("Insufficient buying power: order requires {:.2}, {:.2} available",
+                              new_notional, cash - open_buy_notional));
+    }
+
+    let open_order_notional: Money = viable_orders.values()
+        .map(|order_state| (order_state.order.price * order_state.order.quantity).abs())
+        .sum();
+    if open_order_notional + new_notional.abs() > limits.max_open_order_notional {
+        return reject(format!("Open order notional {:.2} would exceed limit of {:.2}",
+                              open_order_notional + new_notional.abs(), limits.max_open_order_notional));
+    }
+
+    for (instrument_id, ratio) in legs {
+        let leg_notional = price * (quantity * ratio);
+        let position_cost = match open_positions.get(instrument_id) {
+            Some(position) => position.cost,
+            None => Money::ZERO
+        };
+
+        let net_exposure = (position_cost + leg_notional).abs();
+        if net_exposure > limits.max_net_exposure {
+            return reject(format!("Net exposure {:.2} would exceed limit of {:.2}",
+                                  net_exposure, limits.max_net_exposure));
+        }
+
+        let open_leg_notional: Money = viable_orders.values()
+            .flat_map(|order_state| order_state.order.legs.iter()
+                .filter(|existing_leg| existing_leg.instrument_id == *instrument_id)
+                .map(|existing_leg| (order_state.order.price * (order_state.order.quantity * existing_leg.ratio)).abs()))
+            .sum();
+        let gross_exposure = position_cost.abs() + open_leg_notional + leg_notional.abs();
+        if gross_exposure > limits.max_gross_exposure {
+            return reject(format!("Gross exposure {:.2} would exceed limit of {:.2}",
+                                  gross_exposure, limits.max_gross_exposure));
+        }
+    }
+    pass()
+}
+
+// Cash that working buy orders have a claim on
+pub(crate) fn open_buy_notional(viable_orders: &HashMap<String, OrderState>) -> Money {
+    viable_orders.values()
+        .filter(|order_state| order_state.order.quantity > 0)
+        .map(|order_state| order_state.order.price * order_state.order.quantity)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::account::Position;
+    use crate::entities::order::{Order, OrderLeg, OrderState, OrderStatus, OrderType, TimeInForce};
+    use crate::money::Money;
+    use crate::vetting::risk_vetter::{vet_resolved_order, RiskLimits};
+    use std::collections::HashMap;
+
+    fn limits() -> RiskLimits {
+        RiskLimits {
+            max_gross_exposure: Money::from(10000),
+            max_net_exposure: Money::from(5000),
+            max_open_order_notional: Money::from(8000),
+        }
+    }
+
+    fn order_state(instrument_id: i64, price: Money, quantity: i32) -> OrderState {
+        OrderState {
+            order: Order {
+                order_id: 0,
+                account_id: 0,
+                order_number: 1,
+                ext_order_id: "ext".to_string(),
+                client_order_id: "client".to_string(),
+                create_time: 0,
+                price,
+                quantity,
+                legs: vec![OrderLeg {
+                    order_leg_id: 0,
+                    instrument_id,
+                    ratio: 1,
+                }],
+                time_in_force: TimeInForce::Gtc,
+                expire_time: None,
+                order_type: OrderType::Limit,
+                stop_price: None,
+                trailing_offset: None,
+            },
+            update_time: 0,
+            order_status: OrderStatus::Open,
+            version_number: 0,
+            reject_reason: None,
+            filled_quantity: 0,
+            average_fill_price: Money::ZERO,
+        }
+    }
+
+    fn position(instrument_id: i64, quantity: i32, cost: Money) -> Position {
+        Position {
+            position_id: 0,
+            account_id: 0,
+            instrument_id,
+            quantity,
+            cost,
+            closed_gain: Money::ZERO,
+            update_time: 0,
+            version_number: 0,
+        }
+    }
+
+    #[test]
+    async fn test_order_within_limits_passes() {
+        let result = vet_resolved_order(&limits(), Money::from(10), 100, &[(1, 1)], &HashMap::new(), &HashMap::new(), Money::from(5000));
+        assert!(result.pass);
+    }
+
+    #[test]
+    async fn test_buy_beyond_cash_rejected() {
+        let mut viable_orders = HashMap::new();
+        viable_orders.insert("a".to_string(), order_state(2, Money::from(10), 300));
+        let result = vet_resolved_order(&limits(), Money::from(10), 100, &[(1, 1)], &viable_orders, &HashMap::new(), Money::from(3500));
+        assert!(!result.pass);
+        assert!(result.reject_reason.unwrap().starts_with("Insufficient buying power"));
+    }
+
+    #[test]
+    async fn test_sell_does_not_use_buying_power() {
+        let result = vet_resolved_order(&limits(), Money::from(10), -100, &[(1, 1)], &HashMap::new(), &HashMap::new(), Money::ZERO);
+        assert!(result.pass);
+    }
+
+    #[test]
+    async fn test_open_order_notional_limit() {
+        let mut viable_orders = HashMap::new();
+        viable_orders.insert("a".to_string(), order_state(2, Money::from(10), -700));
+        let result = vet_resolved_order(&limits(), Money::from(10), -200, &[(1, 1)], &viable_orders, &HashMap::new(), Money::ZERO);
+        assert!(!result.pass);
+        assert!(result.reject_reason.unwrap().starts_with("Open order notional"));
+    }
+
+    #[test]
+    async fn test_net_exposure_limit() {
+        let mut positions = HashMap::new();
+        positions.insert(1, position(1, 450, Money::from(4500)));
+        let result = vet_resolved_order(&limits(), Money::from(10), 100, &[(1, 1)], &HashMap::new(), &positions, Money::from(100000));
+        assert!(!result.pass);
+        assert!(result.reject_reason.unwrap().starts_with("Net exposure"));
+    }
+
+    #[test]
+    async fn test_reducing_order_passes_net_exposure() {
+        let mut positions = HashMap::new();
+        positions.insert(1, position(1, 450, Money::from(4500)));
+        let result = vet_resolved_order(&limits(), Money::from(10), -100, &[(1, 1)], &HashMap::new(), &positions, Money::ZERO);
+        assert!(result.pass);
+    }
+
+    #[test]
+    async fn test_gross_exposure_limit() {
+        let mut positions = HashMap::new();
+        positions.insert(1, position(1, -400, Money::from(-4000)));
+        let mut viable_orders = HashMap::new();
+        viable_orders.insert("a".to_string(), order_state(1, Money::from(10), 300));
+        let result = vet_resolved_order(&limits(), Money::from(10), 400, &[(1, 1)], &viable_orders, &positions, Money::from(100000));
+        assert!(!result.pass);
+        assert!(result.reject_reason.unwrap().starts_with("Gross exposure"));
+    }
+}
@@ -0,0 +1,32 @@
+use crate::dtos;
+use crate::dtos::order::VettingResult;
+use crate::entities::account::{Balance, Position};
+use crate::entities::order::OrderState;
+use anyhow::Error;
+use std::collections::HashMap;
+
+pub trait Vetter: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn vet_order(&self,
+                 rest_api_order: &dtos::order::Order,
+                 viable_orders: &HashMap<String, OrderState>,
+                 open_positions: &HashMap<i64, Position>,
+                 balance: &Balance) -> Result<VettingResult, Error>;
+}
+
+pub fn pass() -> VettingResult {
+    VettingResult {
+        pass: true,
+        reject_reason: None,
+        estimated_commission: None,
+    }
+}
+
+pub fn reject(reject_reason: String) -> VettingResult {
+    VettingResult {
+        pass: false,
+        reject_reason: Some(reject_reason),
+        estimated_commission: None,
+    }
+}
@@ -0,0 +1,49 @@
+use crate::persistence::dao::DaoTransaction;
+use crate::vetting::vetter::Vetter;
+use anyhow::Error;
+use log::debug;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct VetterRegistry {
+    default_vetter_name: String,
+    vetters: HashMap<String, Arc<dyn Vetter>>,
+}
+
+impl VetterRegistry {
+    pub fn new(default_vetter_name: &str) -> VetterRegistry {
+        VetterRegistry {
+            default_vetter_name: default_vetter_name.to_string(),
+            vetters: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self,
+                    vetter: Arc<dyn Vetter>) {
+        self.vetters.insert(vetter.name().to_string(), vetter);
+    }
+
+    pub fn has_vetter(&self,
+                      vetter_name: &str) -> bool {
+        self.vetters.contains_key(vetter_name)
+    }
+
+    pub async fn get_vetter_for_account(&self,
+                                        txn: &DaoTransaction<'_>,
+                                        account_key: &String) -> Result<Arc<dyn Vetter>, Error> {
+        let configured_vetter_name = match txn.get_vetter_name(account_key).await {
+            Ok(configured_vetter_name) => configured_vetter_name,
+            Err(dao_error) => return Err(anyhow::anyhow!("Could not get vetter name: {}", dao_error))
+        };
+        let vetter_name = match configured_vetter_name {
+            Some(vetter_name) => vetter_name,
+            None => self.default_vetter_name.clone()
+        };
+        debug!("Using vetter {} for account {}", vetter_name, account_key);
+        match self.vetters.get(&vetter_name) {
+            Some(vetter) => Ok(vetter.clone()),
+            None => Err(anyhow::anyhow!("No vetter named {} for account {}", vetter_name, account_key))
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::exchange_interface::exchange_client::get_customer_key_cookie;
 use crate::websockets::stomp;
 pub(crate) use crate::websockets::stomp::StompMessage;
-use crate::websockets::stomp::{parse_message, MessageContent};
+use crate::websockets::stomp::{negotiate_heart_beat, parse_frames, ConnectedContent, Frame, MessageContent, STOMP_VERSION};
 use anyhow::Result;
 use async_std::task;
 use futures_util::SinkExt;
@@ -21,6 +21,8 @@ pub struct WebsocketClient {
     websocket_address: String,
     customer_key: String,
     handlers: Arc<RwLock<HashMap<String, Arc<dyn Fn(&MessageContent) + Send + Sync + 'static>>>>,
+    connected_handler: Option<Arc<dyn Fn() + Send + Sync + 'static>>,
+    disconnected_handler: Option<Arc<dyn Fn() + Send + Sync + 'static>>,
 }
 
 impl WebsocketClient {
@@ -29,9 +31,21 @@ impl WebsocketClient {
             websocket_address,
             customer_key,
             handlers: Arc::new(RwLock::new(HashMap::new())),
+            connected_handler: None,
+            disconnected_handler: None,
         }
     }
 
+    // Called after every successful (re)connect, once subscriptions have been sent
+    pub fn on_connected(&mut self, func: Arc<dyn Fn() + Send + Sync + 'static>) {
+        self.connected_handler = Some(func);
+    }
+
+    // Called whenever a connection attempt fails or an open connection is lost
+    pub fn on_disconnected(&mut self, func: Arc<dyn Fn() + Send + Sync + 'static>) {
+        self.disconnected_handler = Some(func);
+    }
+
     pub fn subscribe(&mut self, destination: &str, func: Arc<dyn Fn(&MessageContent) + Send + Sync + 'static>){
         info!("Requesting subscribe to {}", destination);
         let mut writable_handlers = match self.handlers.write() {
@@ -45,13 +59,16 @@ impl WebsocketClient {
     }
 
     pub fn start(&mut self) {
-        let f = run_websocket(self.websocket_address.clone(), self.customer_key.clone(), self.handlers.clone());
+        let f = run_websocket(self.websocket_address.clone(), self.customer_key.clone(), self.handlers.clone(),
+                              self.connected_handler.clone(), self.disconnected_handler.clone());
         tokio::spawn(f);
     }
 }
 
 async fn run_websocket(websocket_address: String, broker_key: String,
-                       handlers: Arc<RwLock<HashMap<String, Arc<dyn Fn(&MessageContent) + Send + Sync + 'static>>>>) -> Result<()> {
+                       handlers: Arc<RwLock<HashMap<String, Arc<dyn Fn(&MessageContent) + Send + Sync + 'static>>>>,
+                       connected_handler: Option<Arc<dyn Fn() + Send + Sync + 'static>>,
+                       disconnected_handler: Option<Arc<dyn Fn() + Send + Sync + 'static>>) -> Result<()> {
     let mut unboxed_handlers = HashMap::new();
     match unbox_handlers(handlers, &mut unboxed_handlers) {
         Ok(_) => {},
@@ -72,55 +89,100 @@ async fn run_websocket(websocket_address: String, broker_key: String,
             return Err(anyhow::anyhow!("Could parse api key cookie: {}", parse_error)),
     };
     request.headers_mut().insert("Cookie", customer_key_cookie);
-    let five_seconds = time::Duration::from_millis(5000);
+    let mut reconnect_delay = MIN_RECONNECT_DELAY;
 
     loop {
-        run_one_web_socket(request.clone(), &unboxed_handlers).await;
-        task::sleep(five_seconds).await;
+        let connected = run_one_web_socket(request.clone(), &unboxed_handlers, &connected_handler).await;
+        if let Some(func) = &disconnected_handler {
+            func();
+        }
+        reconnect_delay = next_reconnect_delay(reconnect_delay, connected);
+        task::sleep(reconnect_delay).await;
+    }
+}
+
+const MIN_RECONNECT_DELAY: time::Duration = time::Duration::from_millis(5000);
+const MAX_RECONNECT_DELAY: time::Duration = time::Duration::from_millis(60000);
+
+// Retries quickly after losing a connection, then backs off while the endpoint stays unreachable
+fn next_reconnect_delay(previous: time::Duration,
+                        connected: bool) -> time::Duration {
+    if connected {
+        MIN_RECONNECT_DELAY
+    } else {
+        (previous * 2).min(MAX_RECONNECT_DELAY)
+    }
+}
+
+// The client never sends heart-beats, but asks for one at least this often
+const HEART_BEAT_WANTED: time::Duration = time::Duration::from_millis(10000);
+
+// Checks the server speaks STOMP 1.2 and returns how long the connection may stay silent before
+// it is given up for dead: twice the negotiated heart-beat period, or None when there are none
+fn read_timeout(connected: &ConnectedContent) -> Result<Option<time::Duration>> {
+    if connected.version != STOMP_VERSION {
+        return Err(anyhow::anyhow!("Server speaks STOMP {}, not {}", connected.version, STOMP_VERSION));
+    }
+    let (_, incoming) = negotiate_heart_beat(&connected.heart_beat, 0, HEART_BEAT_WANTED.as_millis() as u64)?;
+    if incoming == 0 {
+        return Ok(None);
     }
+    Ok(Some(time::Duration::from_millis(incoming) * 2))
 }
 
+// Returns whether the endpoint accepted the connection before it ended
 pub async fn run_one_web_socket(request: Request,
-                                unboxed_handlers: &HashMap<String, Arc<dyn Fn(&MessageContent) + Send + Sync>>) {
+                                unboxed_handlers: &HashMap<String, Arc<dyn Fn(&MessageContent) + Send + Sync>>,
+                                connected_handler: &Option<Arc<dyn Fn() + Send + Sync>>) -> bool {
     let (mut ws_stream, _) = match connect_async(request).await {
         Ok(x) => x,
         Err(connect_error) => {
             error!("Unable to connect to endpoint: {}", connect_error.to_string());
-            return;
+            return false;
         },
     };
     println!("WebSocket client connected");
-    match ws_stream.send(stomp::connect_message()).await {
+    match ws_stream.send(stomp::connect_message(&format!("0,{}", HEART_BEAT_WANTED.as_millis()))).await {
         Ok(_) => {},
         Err(connect_error) => {
             error!("Unable to send connect message: {}", connect_error.to_string());
-            return;
+            return true;
         },
     };
 
     let mut subscription_id = 0;
+    let mut silence_timeout = None;
 
     loop {
-        let msg_raw = ws_stream.next().await;
+        let msg_raw = match silence_timeout {
+            Some(silence_timeout) => match tokio::time::timeout(silence_timeout, ws_stream.next()).await {
+                Ok(msg_raw) => msg_raw,
+                Err(_) => {
+                    error!("Nothing received for {:?}, giving up on the connection", silence_timeout);
+                    return true;
+                }
+            },
+            None => ws_stream.next().await,
+        };
         let msg_result = match msg_raw {
             Some(msg_option) => msg_option,
             None => {
                 error!("End of web socket stream");
-                return;
+                return true;
             }
         };
         let msg = match msg_result {
             Ok(msg) => msg,
             Err(msg_error) => {
                 error!("Message error: {}", msg_error.to_string());
-                return;
+                return true;
             }
         };
-        subscription_id = match process_message(&mut ws_stream, unboxed_handlers, msg, subscription_id).await {
+        subscription_id = match process_message(&mut ws_stream, unboxed_handlers, connected_handler, msg, subscription_id, &mut silence_timeout).await {
             Ok(subscription_id) => subscription_id,
             Err(process_error) => {
                 error!("Process error: {}", process_error.to_string());
-                return;
+                return true;
             }
         };
     }
@@ -128,56 +190,22 @@ pub async fn run_one_web_socket(request: Request,
 
 async fn process_message(ws_stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
                          unboxed_handlers: &HashMap<String, Arc<dyn Fn(&MessageContent) + Send + Sync>>,
-                         msg: Message, subscription_id: u32) -> Result<u32> {
+                         connected_handler: &Option<Arc<dyn Fn() + Send + Sync>>,
+                         msg: Message, subscription_id: u32,
+                         silence_timeout: &mut Option<time::Duration>) -> Result<u32> {
     let mut new_subscription_id = subscription_id;
     match msg {
         Message::Text(text) => {
             debug!("Received message: {} on client", text);
-            let parsed_message_result = parse_message(&text.to_string());
-            let parsed_message = match parsed_message_result {
-                Ok(parsed_message) => parsed_message,
+            let frames = match parse_frames(&text) {
+                Ok(frames) => frames,
                 Err(parse_error) => {
                     return Err(anyhow::anyhow!("Unable to parse message: {}", parse_error.to_string()));
                 }
             };
-            match parsed_message {
-                StompMessage::Message(msg) => {
-                    debug!("Handling StompMessage:Message");
-                    match unboxed_handlers.get(msg.destination.as_str()) {
-                        Some(func) => func(&msg),
-                        _ => {}
-                    }
-                }
-                StompMessage::Connected(_) => {
-                    debug!("Received expected Connected message on client");
-                    for (destination, _) in unboxed_handlers.iter() {
-                        info!("Subscribing to {} with subscription id {}", destination, new_subscription_id);
-                        match ws_stream.send(stomp::subscribe_message(new_subscription_id, destination)).await {
-                            Ok(_) => {},
-                            Err(send_error) => {
-                                return Err(anyhow::anyhow!("Send error: {}", send_error.to_string()));
-                            }
-                        };
-                        new_subscription_id += 1
-                    }
-                }
-                StompMessage::Subscribe(sub) => {
-                    error!("Received unexpected subscribe message on client: {}", sub.destination);
-                },
-                StompMessage::Unsubscribe(us) => {
-                    error!("Received unexpected unsubscribe message on client: {}", us.id);
-                },
-                StompMessage::Connect(ct) => {
-                    error!("Received unexpected Connect message on client: {}", ct.accept_version);
-                },
-                StompMessage::Send(snd) => {
-                    error!("Received unexpected Send message on client: {}", snd.destination);
-                },
-                StompMessage::Disconnect(_) => {
-                    error!("Received unexpected Disconnect");
-                    return Err(anyhow::anyhow!("Received unexpected Disconnect"));
-                },
-            };
+            for frame in frames {
+                new_subscription_id = process_frame(ws_stream, unboxed_handlers, connected_handler, &frame, new_subscription_id, silence_timeout).await?;
+            }
         }
         unexpected_message => {
             warn!("Received unexpected non-text message on client: {:?}", unexpected_message);
@@ -186,6 +214,75 @@ async fn process_message(ws_stream: &mut WebSocketStream<MaybeTlsStream<TcpStrea
     Ok(new_subscription_id)
 }
 
+async fn process_frame(ws_stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+                       unboxed_handlers: &HashMap<String, Arc<dyn Fn(&MessageContent) + Send + Sync>>,
+                       connected_handler: &Option<Arc<dyn Fn() + Send + Sync>>,
+                       frame: &Frame, subscription_id: u32,
+                       silence_timeout: &mut Option<time::Duration>) -> Result<u32> {
+    let mut new_subscription_id = subscription_id;
+    let parsed_message = match frame.to_stomp_message() {
+        Ok(parsed_message) => parsed_message,
+        Err(parse_error) => {
+            return Err(anyhow::anyhow!("Unable to parse message: {}", parse_error.to_string()));
+        }
+    };
+    match parsed_message {
+        StompMessage::Message(msg) => {
+            debug!("Handling StompMessage:Message {} on subscription {} ({:?}, ack {:?})", msg.message_id, msg.subscription, msg.content_type, msg.ack);
+            match unboxed_handlers.get(msg.destination.as_str()) {
+                Some(func) => func(&msg),
+                _ => {}
+            }
+        }
+        StompMessage::Connected(connected) => {
+            debug!("Received expected Connected message on client");
+            *silence_timeout = read_timeout(&connected)?;
+            for (destination, _) in unboxed_handlers.iter() {
+                info!("Subscribing to {} with subscription id {}", destination, new_subscription_id);
+                match ws_stream.send(stomp::subscribe_message(new_subscription_id, destination)).await {
+                    Ok(_) => {},
+                    Err(send_error) => {
+                        return Err(anyhow::anyhow!("Send error: {}", send_error.to_string()));
+                    }
+                };
+                new_subscription_id += 1
+            }
+            if let Some(func) = connected_handler {
+                func();
+            }
+        }
+        StompMessage::Subscribe(sub) => {
+            error!("Received unexpected subscribe message on client: {}", sub.destination);
+        },
+        StompMessage::Unsubscribe(us) => {
+            error!("Received unexpected unsubscribe message on client: {}", us.id);
+        },
+        StompMessage::Connect(ct) => {
+            error!("Received unexpected Connect message on client: {:?}", ct.accept_version);
+        },
+        StompMessage::Send(snd) => {
+            error!("Received unexpected Send message on client: {}", snd.destination);
+        },
+        StompMessage::Ack(_) | StompMessage::Nack(_) => {
+            error!("Received unexpected {} message on client", frame.command);
+        },
+        StompMessage::Begin(tx) | StompMessage::Commit(tx) | StompMessage::Abort(tx) => {
+            error!("Received unexpected {} message on client for {}", frame.command, tx.transaction);
+        },
+        StompMessage::Receipt(receipt) => {
+            debug!("Received receipt {}", receipt.receipt_id);
+        },
+        StompMessage::Error(err) => {
+            return Err(anyhow::anyhow!("Received error {:?}: {}", err.message, err.body));
+        },
+        StompMessage::Disconnect(_) => {
+            error!("Received unexpected Disconnect");
+            return Err(anyhow::anyhow!("Received unexpected Disconnect"));
+        },
+    };
+    Ok(new_subscription_id)
+}
+
 fn unbox_handlers(handlers: Arc<RwLock<HashMap<String, Arc<dyn Fn(&MessageContent) + Send + Sync>>>>, unboxed_handlers: &mut HashMap<String, Arc<dyn Fn(&MessageContent) + Send + Sync>>) -> Result<()> {
     let readable_handlers = match handlers.read() {
         Ok(readable_handlers) => readable_handlers,
@@ -199,3 +296,28 @@ fn unbox_handlers(handlers: Arc<RwLock<HashMap<String, Arc<dyn Fn(&MessageConten
     Ok(())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use crate::websockets::client::read_timeout;
+    use crate::websockets::stomp::ConnectedContent;
+    use std::time::Duration;
+
+    fn connected(version: &str,
+                 heart_beat: &str) -> ConnectedContent {
+        ConnectedContent {
+            version: version.to_string(),
+            heart_beat: heart_beat.to_string(),
+            user_name: None,
+        }
+    }
+
+    #[test]
+    async fn test_read_timeout() {
+        assert_eq!(read_timeout(&connected("1.2", "0,0")).unwrap(), None);
+        assert_eq!(read_timeout(&connected("1.2", "5000,5000")).unwrap(), Some(Duration::from_millis(20000)));
+        assert_eq!(read_timeout(&connected("1.2", "30000,0")).unwrap(), Some(Duration::from_millis(60000)));
+        assert!(read_timeout(&connected("1.2", "often")).is_err());
+        assert!(read_timeout(&connected("1.1", "5000,5000")).is_err());
+    }
+}
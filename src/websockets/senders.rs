@@ -17,7 +17,7 @@ pub async fn send_positions(txn: DaoTransaction<'_>,
         },
     };
     for position in positions.values() {
-        let rest_api_position = match position.to_rest_api_position(account_key, &instrument_manager) {
+        let rest_api_position = match position.to_marked_rest_api_position(account_key, &instrument_manager) {
             Ok(rest_api_position) => rest_api_position,
             Err(convert_error) => {
                 error!("send_positions error while converting position: {}", convert_error);
@@ -29,6 +29,7 @@ pub async fn send_positions(txn: DaoTransaction<'_>,
             balance: None,
             trade: None,
             order_state: None,
+            margin_call: None,
         };
         let body = match serde_json::to_string(&account_update) {
             Ok(body) => body,
@@ -67,6 +68,7 @@ pub async fn send_balance(txn: DaoTransaction<'_>,
         balance: Some(balance.to_rest_api_balance(account_key)),
         trade: None,
         order_state: None,
+        margin_call: None,
     };
     let body = match serde_json::to_string(&account_update) {
         Ok(body) => body,
@@ -121,6 +123,7 @@ pub async fn send_orders(txn: DaoTransaction<'_>,
             balance: None,
             trade: None,
             order_state: Some(rest_api_order_state),
+            margin_call: None,
         };
         let body = match serde_json::to_string(&account_update) {
             Ok(body) => body,
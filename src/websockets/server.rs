@@ -60,6 +60,15 @@ impl WebSocketServer {
         };
         writable.insert(destination, queue_item);
     }
+    // The body of the last retained message, already serialized
+    pub fn get_retained_message(&self,
+                                destination: &str) -> Result<Option<String>, anyhow::Error> {
+        let readable = match self.retained_messages.read() {
+            Ok(readable) => readable,
+            Err(readable_error) => return Err(anyhow::anyhow!("Unable to get read access to retained_messages: {}", readable_error)),
+        };
+        Ok(readable.get(destination).map(|queue_item| queue_item.body.clone()))
+    }
     pub fn send_message(&mut self, destination: String, body: &impl Serialize) -> Result<QueueItem, anyhow::Error> {
         let queue_item = match Self::create_queue_item(&destination, body) {
             Ok(value) => value,
@@ -1,40 +1,303 @@
 use crate::constants::APPLICATION_JSON;
 use anyhow::anyhow;
 use log::error;
-use std::collections::HashMap;
 use tokio_tungstenite::tungstenite::Message;
 use uuid::Uuid;
 
-pub fn connect_message() -> Message {
-    Message::text("CONNECT\naccept-version:1.2,2.0\n\n\x00")
+pub const STOMP_VERSION: &str = "1.2";
+const SERVER_NAME: &str = "OpenBroker";
+
+pub fn connect_message(heart_beat: &str) -> Message {
+    Message::text(Frame::new(CONNECT)
+        .header(ACCEPT_VERSION, STOMP_VERSION)
+        .header(HEART_BEAT, heart_beat)
+        .to_text())
 }
 
-pub fn connected_message() -> Message {
-    Message::text("CONNECTED\nversion:1.2\n\n\x00")
+pub fn connected_message(heart_beat: &str) -> Frame {
+    Frame::new(CONNECTED)
+        .header(VERSION, STOMP_VERSION)
+        .header(HEART_BEAT, heart_beat)
+        .header(SERVER, SERVER_NAME)
 }
 
 pub fn subscribe_message(subscription_id: u32, destination: &str) -> Message {
-    Message::text(format!("SUBSCRIBE\nid:{}\ndestination:{}\nack:auto\n\n\x00", subscription_id, destination))
-}
-
-pub fn text_message(destination: String, 
-                    subscription: String, 
-                    body: &String) -> Message {
-    Message::text(format!("\
-    MESSAGE\n\
-    destination:{}\n\
-    content_type:{}\n\
-    subscription:{}\n\
-    message_id:{}\n\
-    content_length:{}\n\n\
-    {}\n\
-    \n\x00",
-    destination,
-    APPLICATION_JSON,
-        subscription,
-        Uuid::new_v4().simple().to_string(),
-        body.len(),
-        body))
+    Message::text(Frame::new(SUBSCRIBE)
+        .header(ID, subscription_id.to_string().as_str())
+        .header(DESTINATION, destination)
+        .header(ACK, AckMode::Auto.header_value())
+        .to_text())
+}
+
+pub fn text_message(destination: &str,
+                    subscription: &str,
+                    ack_id: Option<&str>,
+                    body: &str) -> Frame {
+    let message_id = Uuid::new_v4().simple().to_string();
+    let frame = Frame::new(MESSAGE)
+        .header(DESTINATION, destination)
+        .header(CONTENT_TYPE, APPLICATION_JSON)
+        .header(SUBSCRIPTION, subscription)
+        .header(MESSAGE_ID, message_id.as_str());
+    match ack_id {
+        Some(ack_id) => frame.header(ACK, ack_id),
+        None => frame,
+    }.body(body)
+}
+
+pub fn receipt_message(receipt_id: &str) -> Frame {
+    Frame::new(RECEIPT)
+        .header(RECEIPT_ID, receipt_id)
+}
+
+// The server closes the connection after sending this
+pub fn error_message(message: &str,
+                     receipt_id: Option<&str>,
+                     details: &str) -> Frame {
+    let frame = Frame::new(ERROR)
+        .header(MESSAGE_HEADER, message)
+        .header(CONTENT_TYPE, "text/plain");
+    match receipt_id {
+        Some(receipt_id) => frame.header(RECEIPT_ID, receipt_id),
+        None => frame,
+    }.body(details)
+}
+
+// Sent in reply to a CONNECT that does not offer 1.2
+pub fn version_error_message(receipt_id: Option<&str>) -> Frame {
+    error_message("Supported protocol versions are 1.2", receipt_id, "This server only speaks STOMP 1.2")
+        .header(VERSION, STOMP_VERSION)
+}
+
+// No accept-version header means the client only speaks 1.0
+pub fn accepts_version(accept_version: &Option<String>) -> bool {
+    match accept_version {
+        Some(accept_version) => accept_version.split(",").any(|version| version.trim() == STOMP_VERSION),
+        None => false,
+    }
+}
+
+// Picks the heart-beat periods in milliseconds from the client's "cx,cy" and what the server
+// offers. Returns (how often the server sends, how often it expects to hear from the client),
+// zero meaning never. A client negotiates the same way, passing the server's header and its own
+// send and receive periods
+pub fn negotiate_heart_beat(client_heart_beat: &str,
+                            server_send: u64,
+                            server_receive: u64) -> Result<(u64, u64), anyhow::Error> {
+    let (client_send, client_receive) = match client_heart_beat.split_once(",") {
+        Some((client_send, client_receive)) => (client_send.trim().parse::<u64>(), client_receive.trim().parse::<u64>()),
+        None => return Err(anyhow!("Malformed heart-beat header {}", client_heart_beat)),
+    };
+    let (client_send, client_receive) = match (client_send, client_receive) {
+        (Ok(client_send), Ok(client_receive)) => (client_send, client_receive),
+        _ => return Err(anyhow!("Malformed heart-beat header {}", client_heart_beat)),
+    };
+    let outgoing = if server_send == 0 || client_receive == 0 { 0 } else { server_send.max(client_receive) };
+    let incoming = if server_receive == 0 || client_send == 0 { 0 } else { server_receive.max(client_send) };
+    Ok((outgoing, incoming))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AckMode {
+    Auto,
+    Client,
+    ClientIndividual,
+}
+
+impl AckMode {
+    pub fn header_value(&self) -> &'static str {
+        match self {
+            AckMode::Auto => "auto",
+            AckMode::Client => "client",
+            AckMode::ClientIndividual => "client-individual",
+        }
+    }
+}
+
+impl std::str::FromStr for AckMode {
+    type Err = anyhow::Error;
+    fn from_str(input: &str) -> Result<AckMode, Self::Err> {
+        match input {
+            "auto" => Ok(AckMode::Auto),
+            "client" => Ok(AckMode::Client),
+            "client-individual" => Ok(AckMode::ClientIndividual),
+            _ => Err(anyhow!("Unknown ack mode {}", input)),
+        }
+    }
+}
+
+// A single STOMP frame. Repeated headers are kept, but only the first one counts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub command: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Frame {
+    pub fn new(command: &str) -> Frame {
+        Frame {
+            command: command.to_string(),
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Frame {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: &str) -> Frame {
+        self.body = body.to_string();
+        self
+    }
+
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(header_name, _)| header_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn require_header(&self, name: &str) -> Result<String, anyhow::Error> {
+        match self.get_header(name) {
+            Some(value) => Ok(value.to_string()),
+            None => Err(anyhow!("{} frame is missing the {} header", self.command, name)),
+        }
+    }
+
+    // CONNECT and CONNECTED headers are never escaped, for compatibility with STOMP 1.0
+    fn escapes_headers(&self) -> bool {
+        self.command != CONNECT && self.command != CONNECTED && self.command != STOMP
+    }
+
+    pub fn to_text(&self) -> String {
+        let escape = self.escapes_headers();
+        let mut text = self.command.clone();
+        text.push('\n');
+        for (name, value) in self.headers.iter() {
+            if escape {
+                text.push_str(&escape_header(name));
+                text.push(':');
+                text.push_str(&escape_header(value));
+            } else {
+                text.push_str(name);
+                text.push(':');
+                text.push_str(value);
+            }
+            text.push('\n');
+        }
+        if !self.body.is_empty() && self.get_header(CONTENT_LENGTH).is_none() {
+            text.push_str(&format!("{}:{}\n", CONTENT_LENGTH, self.body.len()));
+        }
+        text.push('\n');
+        text.push_str(&self.body);
+        text.push('\x00');
+        text
+    }
+}
+
+fn escape_header(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            ':' => escaped.push_str("\\c"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_header(value: &str) -> Result<String, anyhow::Error> {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some('c') => unescaped.push(':'),
+            Some(other) => return Err(anyhow!("Undefined escape sequence \\{} in header", other)),
+            None => return Err(anyhow!("Header ends with an escape character")),
+        }
+    }
+    Ok(unescaped)
+}
+
+// Parses every frame in a websocket message. End of lines between frames are heart-beats and
+// are skipped, so a message holding only a heart-beat gives no frames
+pub fn parse_frames(text: &str) -> Result<Vec<Frame>, anyhow::Error> {
+    let mut frames = Vec::new();
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start_matches(['\r', '\n']);
+        if rest.is_empty() {
+            return Ok(frames);
+        }
+        let (frame, remaining) = parse_frame(rest)?;
+        frames.push(frame);
+        rest = remaining;
+    }
+}
+
+fn parse_frame(text: &str) -> Result<(Frame, &str), anyhow::Error> {
+    let (command, mut rest) = match split_line(text) {
+        Some(split) => split,
+        None => return Err(anyhow!("Frame has no command line")),
+    };
+    let mut frame = Frame::new(command);
+    let escape = frame.escapes_headers();
+    loop {
+        let (line, remaining) = match split_line(rest) {
+            Some(split) => split,
+            None => return Err(anyhow!("{} frame has no end to its headers", frame.command)),
+        };
+        rest = remaining;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = match line.split_once(":") {
+            Some(parts) => parts,
+            None => return Err(anyhow!("Malformed header line {}", line)),
+        };
+        if escape {
+            frame.headers.push((unescape_header(name)?, unescape_header(value)?));
+        } else {
+            frame.headers.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    let body_length = match frame.get_header(CONTENT_LENGTH) {
+        Some(content_length) => match content_length.trim().parse::<usize>() {
+            Ok(content_length) => content_length,
+            Err(parse_error) => return Err(anyhow!("Could not parse content length {}: {}", content_length, parse_error)),
+        },
+        None => match rest.find('\x00') {
+            Some(nul_index) => nul_index,
+            None => return Err(anyhow!("{} frame is not terminated", frame.command)),
+        },
+    };
+    if rest.len() < body_length || !rest.is_char_boundary(body_length) {
+        return Err(anyhow!("{} frame body is shorter than its content length {}", frame.command, body_length));
+    }
+    frame.body = rest[..body_length].to_string();
+    let rest = &rest[body_length..];
+    match rest.strip_prefix('\x00') {
+        Some(rest) => Ok((frame, rest)),
+        None => Err(anyhow!("{} frame is not terminated after its body", frame.command)),
+    }
+}
+
+fn split_line(text: &str) -> Option<(&str, &str)> {
+    let (line, rest) = text.split_once('\n')?;
+    Some((line.strip_suffix('\r').unwrap_or(line), rest))
 }
 
 pub enum StompMessage {
@@ -44,32 +307,41 @@ pub enum StompMessage {
     Connected(ConnectedContent),
     Subscribe(SubscribeContent),
     Unsubscribe(UnsubscribeContent),
+    Ack(AckContent),
+    Nack(AckContent),
+    Begin(TransactionContent),
+    Commit(TransactionContent),
+    Abort(TransactionContent),
+    Receipt(ReceiptContent),
+    Error(ErrorContent),
     Disconnect(DisconnectContent)
 }
 
 pub struct MessageContent {
     pub destination: String,
-    pub content_type: String,
-    pub subscription: u16,
+    pub content_type: Option<String>,
+    pub subscription: String,
     pub message_id: String,
-    pub content_length: u16,
+    pub ack: Option<String>,
     pub body: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SendContent {
     pub destination: String,
+    pub transaction: Option<String>,
     pub body: String,
 }
 
 pub struct ConnectedContent {
     pub version: String,
     pub heart_beat: String,
-    pub user_name: String,
+    pub user_name: Option<String>,
 }
 
 pub struct ConnectContent {
-    pub accept_version: String,
+    pub accept_version: Option<String>,
+    pub heart_beat: String,
 }
 
 pub struct SubscribeContent {
@@ -82,123 +354,203 @@ pub struct UnsubscribeContent {
     pub id: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct AckContent {
+    pub id: String,
+    pub transaction: Option<String>,
+}
+
+pub struct TransactionContent {
+    pub transaction: String,
+}
+
+pub struct ReceiptContent {
+    pub receipt_id: String,
+}
+
+pub struct ErrorContent {
+    pub message: Option<String>,
+    pub body: String,
+}
+
 pub struct DisconnectContent {
 }
 
-const MESSAGE: &'static str = "MESSAGE";
-const SEND: &'static str = "SEND";
-const CONNECT: &'static str = "CONNECT";
-const CONNECTED: &'static str = "CONNECTED";
-const SUBSCRIBE: &'static str = "SUBSCRIBE";
-const UNSUBSCRIBE: &'static str = "UNSUBSCRIBE";
-const DISCONNECT: &'static str = "DISCONNECT";
-
-const DESTINATION: &'static str = "destination";
-const CONTENT_TYPE: &'static str = "content-type";
-const CONTENT_LENGTH: &'static str = "content-length";
-const SUBSCRIPTION: &'static str = "subscription";
-const MESSAGE_ID: &'static str = "message-id";
-const ACCEPT_VERSION: &'static str = "accept-version";
-const VERSION: &'static str = "version";
-const HEART_BEAT: &'static str = "heart-beat";
-const USER_NAME: &'static str = "user-name";
-const ID: &'static str = "id";
-const ACK: &'static str = "ack";
-const MESSAGE_TYPE: &'static str = "message-type";
-const BODY: &'static str = "body";
-
-pub fn parse_message(message: &String) -> Result<StompMessage, anyhow::Error> {
-    let mut vals: HashMap<&str, String> = HashMap::new();
-    let mut body_now = false;
-    for line in message.lines() {
-        let line = line.trim().trim_matches(char::from(0));
-        if !vals.contains_key(MESSAGE_TYPE) {
-            vals.insert(MESSAGE_TYPE, line.to_string());
-        }
-        else if line.len() == 0 {
-            body_now = true;
-        }
-        else if body_now {
-            vals.insert(BODY, line.to_string());
-        }
-        else {
-            let parts = line.split_once(":");
-            match parts {
-                None => {}
-                Some((key, value)) => {
-                  //  info!("key: {} value: {}", key, value);
-                    vals.insert(key, value.to_string());
-                }
+const MESSAGE: &str = "MESSAGE";
+const SEND: &str = "SEND";
+const CONNECT: &str = "CONNECT";
+const STOMP: &str = "STOMP";
+const CONNECTED: &str = "CONNECTED";
+const SUBSCRIBE: &str = "SUBSCRIBE";
+const UNSUBSCRIBE: &str = "UNSUBSCRIBE";
+const ACK_COMMAND: &str = "ACK";
+const NACK: &str = "NACK";
+const BEGIN: &str = "BEGIN";
+const COMMIT: &str = "COMMIT";
+const ABORT: &str = "ABORT";
+const RECEIPT: &str = "RECEIPT";
+const ERROR: &str = "ERROR";
+const DISCONNECT: &str = "DISCONNECT";
+
+const DESTINATION: &str = "destination";
+const CONTENT_TYPE: &str = "content-type";
+const CONTENT_LENGTH: &str = "content-length";
+const SUBSCRIPTION: &str = "subscription";
+const MESSAGE_ID: &str = "message-id";
+const ACCEPT_VERSION: &str = "accept-version";
+const VERSION: &str = "version";
+const HEART_BEAT: &str = "heart-beat";
+const SERVER: &str = "server";
+const USER_NAME: &str = "user-name";
+const ID: &str = "id";
+const ACK: &str = "ack";
+const TRANSACTION: &str = "transaction";
+const RECEIPT_HEADER: &str = "receipt";
+const RECEIPT_ID: &str = "receipt-id";
+const MESSAGE_HEADER: &str = "message";
+
+impl Frame {
+    pub fn receipt(&self) -> Option<&str> {
+        self.get_header(RECEIPT_HEADER)
+    }
+
+    pub fn to_stomp_message(&self) -> Result<StompMessage, anyhow::Error> {
+        let ret = match self.command.as_str() {
+            MESSAGE => StompMessage::Message(MessageContent {
+                destination: self.require_header(DESTINATION)?,
+                content_type: self.get_header(CONTENT_TYPE).map(|value| value.to_string()),
+                subscription: self.require_header(SUBSCRIPTION)?,
+                message_id: self.require_header(MESSAGE_ID)?,
+                ack: self.get_header(ACK).map(|value| value.to_string()),
+                body: self.body.clone(),
+            }),
+            SEND => StompMessage::Send(SendContent {
+                destination: self.require_header(DESTINATION)?,
+                transaction: self.get_header(TRANSACTION).map(|value| value.to_string()),
+                body: self.body.clone(),
+            }),
+            CONNECT | STOMP => StompMessage::Connect(ConnectContent {
+                accept_version: self.get_header(ACCEPT_VERSION).map(|value| value.to_string()),
+                heart_beat: self.get_header(HEART_BEAT).unwrap_or("0,0").to_string(),
+            }),
+            CONNECTED => StompMessage::Connected(ConnectedContent {
+                version: self.get_header(VERSION).unwrap_or("1.0").to_string(),
+                heart_beat: self.get_header(HEART_BEAT).unwrap_or("0,0").to_string(),
+                user_name: self.get_header(USER_NAME).map(|value| value.to_string()),
+            }),
+            SUBSCRIBE => StompMessage::Subscribe(SubscribeContent {
+                id: self.require_header(ID)?,
+                destination: self.require_header(DESTINATION)?,
+                ack: self.get_header(ACK).unwrap_or(AckMode::Auto.header_value()).to_string(),
+            }),
+            UNSUBSCRIBE => StompMessage::Unsubscribe(UnsubscribeContent {
+                id: self.require_header(ID)?,
+            }),
+            ACK_COMMAND => StompMessage::Ack(AckContent {
+                id: self.require_header(ID)?,
+                transaction: self.get_header(TRANSACTION).map(|value| value.to_string()),
+            }),
+            NACK => StompMessage::Nack(AckContent {
+                id: self.require_header(ID)?,
+                transaction: self.get_header(TRANSACTION).map(|value| value.to_string()),
+            }),
+            BEGIN => StompMessage::Begin(TransactionContent {
+                transaction: self.require_header(TRANSACTION)?,
+            }),
+            COMMIT => StompMessage::Commit(TransactionContent {
+                transaction: self.require_header(TRANSACTION)?,
+            }),
+            ABORT => StompMessage::Abort(TransactionContent {
+                transaction: self.require_header(TRANSACTION)?,
+            }),
+            RECEIPT => StompMessage::Receipt(ReceiptContent {
+                receipt_id: self.require_header(RECEIPT_ID)?,
+            }),
+            ERROR => StompMessage::Error(ErrorContent {
+                message: self.get_header(MESSAGE_HEADER).map(|value| value.to_string()),
+                body: self.body.clone(),
+            }),
+            DISCONNECT => StompMessage::Disconnect(DisconnectContent {
+            }),
+            _ => {
+                error!("Unknown message type {}", self.command);
+                return Err(anyhow!("Unknown message type {}", self.command));
             }
-        }
+        };
+        Ok(ret)
     }
-    let message_type = match vals.get(MESSAGE_TYPE) {
-        Some(message_type) => message_type,
-        None => return Err(anyhow!("Missing message type")),
-    };
-    let ret = match message_type.as_str() {
-        MESSAGE => {
-            let content_length = match vals[CONTENT_LENGTH].parse::<u16>() {
-                Ok(content_length) => content_length,
-                Err(parse_error) =>
-                    return Err(anyhow!("Could not parse content length {}: {}", vals[CONTENT_LENGTH], parse_error.to_string())),
-            };
-            let subscription = match vals[SUBSCRIPTION].parse::<u16>() {
-                Ok(subscription) => subscription,
-                Err(parse_error) =>
-                    return Err(anyhow!("Could not parse subscription {}: {}", vals[SUBSCRIPTION], parse_error.to_string())),
-            };
-            StompMessage::Message(MessageContent {
-                destination: vals[DESTINATION].to_string(),
-                body: vals[BODY].to_string(),
-                content_type: vals[CONTENT_TYPE].to_string(),
-                content_length,
-                subscription,
-                message_id: vals[MESSAGE_ID].to_string(),
-            })
-        },
-        SEND => {
-            StompMessage::Send(SendContent {
-                destination: vals[DESTINATION].to_string(),
-                body: vals[BODY].to_string(),
-            })
-        },
-        CONNECT => {
-            StompMessage::Connect(ConnectContent {
-                accept_version: vals[ACCEPT_VERSION].to_string(),
-            })
-        },
-        CONNECTED => {
-            StompMessage::Connected(ConnectedContent {
-                version: vals[VERSION].to_string(),
-                heart_beat: vals[HEART_BEAT].to_string(),
-                user_name: vals[USER_NAME].to_string(),
+}
 
-            })
-        },
-        SUBSCRIBE => {
-            StompMessage::Subscribe(SubscribeContent {
-                id: vals[ID].to_string(),
-                destination: vals[DESTINATION].to_string(),
-                ack: match vals.get(ACK) {
-                    Some(x) => x,
-                    None => "auto",
-                }.to_string(),
-            })
-        },
-        UNSUBSCRIBE => {
-            StompMessage::Unsubscribe(UnsubscribeContent {
-                id: vals[ID].to_string()
-            })
-        }
-        DISCONNECT => {
-            StompMessage::Disconnect(DisconnectContent {
-            })
-        }
-        _ => {
-            error!("Unknown message type {}", message_type);
-            return Err(anyhow::anyhow!("Unknown message type {}", message_type));
+#[cfg(test)]
+mod tests {
+    use crate::websockets::stomp::{accepts_version, negotiate_heart_beat, parse_frames, text_message, Frame, StompMessage};
+
+    #[test]
+    async fn test_header_escaping_round_trip() {
+        let frame = Frame::new("SEND")
+            .header("destination", "/accounts/a:b/orders")
+            .header("note", "line\nbreak\\slash\r")
+            .body("{\"request\": \"GET\"}");
+        let text = frame.to_text();
+        assert!(text.contains("destination:/accounts/a\\cb/orders\n"));
+        assert!(text.contains("note:line\\nbreak\\\\slash\\r\n"));
+
+        let parsed = parse_frames(&text).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].get_header("destination"), Some("/accounts/a:b/orders"));
+        assert_eq!(parsed[0].get_header("note"), Some("line\nbreak\\slash\r"));
+        assert_eq!(parsed[0].body, "{\"request\": \"GET\"}");
+
+        assert!(parse_frames("SEND\ndestination:bad\\t\n\n\x00").is_err());
+    }
+
+    #[test]
+    async fn test_connect_headers_are_not_escaped() {
+        let parsed = parse_frames("CONNECT\naccept-version:1.2\nlogin:a\\b\n\n\x00").unwrap();
+        assert_eq!(parsed[0].get_header("login"), Some("a\\b"));
+    }
+
+    #[test]
+    async fn test_body_and_frame_boundaries() {
+        // A content-length body may hold NULs, and end of lines between frames are heart-beats
+        let text = "\nSEND\r\ndestination:/q\r\ncontent-length:5\r\n\r\na\x00b\nc\x00\n\nSUBSCRIBE\nid:0\ndestination:/q\ndestination:/ignored\n\nmulti\nline\x00";
+        let frames = parse_frames(text).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].body, "a\x00b\nc");
+        assert_eq!(frames[1].get_header("destination"), Some("/q"));
+        assert_eq!(frames[1].body, "multi\nline");
+
+        assert!(parse_frames("\n\r\n").unwrap().is_empty());
+        assert!(parse_frames("SEND\ndestination:/q\n\nunterminated").is_err());
+    }
+
+    #[test]
+    async fn test_message_frame_parses() {
+        let text = text_message("/accounts/a/orders", "sub-0", Some("7"), "{}").to_text();
+        match parse_frames(&text).unwrap()[0].to_stomp_message().unwrap() {
+            StompMessage::Message(content) => {
+                assert_eq!(content.destination, "/accounts/a/orders");
+                assert_eq!(content.subscription, "sub-0");
+                assert_eq!(content.ack, Some("7".to_string()));
+                assert_eq!(content.body, "{}");
+            },
+            _ => panic!("Expected a MESSAGE"),
         }
-    };
-    Ok(ret)
+    }
+
+    #[test]
+    async fn test_heart_beat_negotiation() {
+        assert_eq!(negotiate_heart_beat("0,0", 5000, 5000).unwrap(), (0, 0));
+        assert_eq!(negotiate_heart_beat("10000,1000", 5000, 5000).unwrap(), (5000, 10000));
+        assert_eq!(negotiate_heart_beat("1000,0", 5000, 0).unwrap(), (0, 0));
+        assert!(negotiate_heart_beat("fast", 5000, 5000).is_err());
+    }
+
+    #[test]
+    async fn test_accepts_version() {
+        assert!(accepts_version(&Some("1.0,1.1,1.2".to_string())));
+        assert!(accepts_version(&Some("1.2".to_string())));
+        assert!(!accepts_version(&Some("1.0,1.1".to_string())));
+        assert!(!accepts_version(&None));
+    }
 }
@@ -1,13 +1,16 @@
 use crate::access_control::AccessControl;
 use crate::dtos::account::{Account, Privilege};
-use crate::instrument_manager::InstrumentManager;
-use crate::persistence::dao::Dao;
+use crate::dtos::order::{Order, OrderReceipt};
 use crate::rest_api::base_api;
-use crate::websockets::client::StompMessage;
+use crate::trade_handling::order_entry;
+use crate::trade_handling::order_entry::OrderEntryError;
+use crate::trade_handling::trading_context::TradingContext;
+use crate::validator::validator::Validator;
+use crate::vetting::vetter_registry::VetterRegistry;
 use crate::websockets::senders::{send_balance, send_orders, send_positions};
-use crate::websockets::server::{QueueItem, WebSocketServer};
+use crate::websockets::server::QueueItem;
 use crate::websockets::stomp;
-use crate::websockets::stomp::{parse_message, SendContent, SubscribeContent};
+use crate::websockets::stomp::{negotiate_heart_beat, parse_frames, AckContent, AckMode, Frame, SendContent, StompMessage, SubscribeContent};
 use actix_web::web::ThinData;
 use actix_web::{web, HttpRequest, HttpResponse};
 use actix_ws::{AggregatedMessage, AggregatedMessageStream, Closed, Session};
@@ -20,21 +23,23 @@ use serde;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tokio::task::spawn_local;
 use tokio::{sync::mpsc, time::interval};
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_PENDING_ACKS: usize = 1000;
 
 #[get("/ws")]
 pub async fn ws_setup(
     req: HttpRequest,
-    dao: ThinData<Dao>,
-    instrument_manager: ThinData<InstrumentManager>,
+    trading_context: ThinData<TradingContext>,
     session: actix_session::Session,
-    web_socket_server: ThinData<WebSocketServer>,
     access_control: ThinData<AccessControl>,
+    vetter_registry: ThinData<VetterRegistry>,
+    validator: ThinData<Validator>,
     stream: web::Payload,
 ) -> HttpResponse {
     info!("Websocket connection requested for session {:p}", &session);
@@ -57,12 +62,12 @@ pub async fn ws_setup(
     debug!("allowed_accounts: {:?}", allowed_accounts);
 
     spawn_local(ws_handler(
-        dao,
-        instrument_manager,
+        trading_context,
         allowed_accounts,
         ws_session,
-        web_socket_server,
         access_control,
+        vetter_registry,
+        validator,
         msg_stream
     ));
 
@@ -70,15 +75,15 @@ pub async fn ws_setup(
 }
 
 async fn ws_handler(
-    dao: ThinData<Dao>,
-    instrument_manager: ThinData<InstrumentManager>,
+    trading_context: ThinData<TradingContext>,
     allowed_accounts: HashMap<String, Account>,
     mut ws_session: Session,
-    web_socket_server: ThinData<WebSocketServer>,
     access_control: ThinData<AccessControl>,
+    vetter_registry: ThinData<VetterRegistry>,
+    validator: ThinData<Validator>,
     msg_stream: actix_ws::MessageStream,
 ) {
-    let mut ws_handler_obj = WsHandler::new(dao, instrument_manager, web_socket_server, access_control, allowed_accounts, msg_stream);
+    let mut ws_handler_obj = WsHandler::new(trading_context, access_control, vetter_registry, validator, allowed_accounts, msg_stream);
     ws_handler_obj.start(&mut ws_session).await;
     info!("Websocket closing");
     match ws_session.close(None).await {
@@ -91,33 +96,54 @@ async fn ws_handler(
 }
 
 struct WsHandler {
-    dao: ThinData<Dao>,
-    instrument_manager: ThinData<InstrumentManager>,
-    web_socket_server: ThinData<WebSocketServer>,
+    trading_context: ThinData<TradingContext>,
     access_control: ThinData<AccessControl>,
+    vetter_registry: ThinData<VetterRegistry>,
+    validator: ThinData<Validator>,
     allowed_accounts: HashMap<String, Account>,
     msg_stream: AggregatedMessageStream,
-    subscriptions: BiHashMap<String, String>
+    subscriptions: BiHashMap<String, String>,
+    ack_modes: HashMap<String, AckMode>,
+    pending_acks: HashMap<String, Vec<String>>,
+    transactions: HashMap<String, Vec<Frame>>,
+    next_ack_id: u64,
+    connected: bool,
+    outgoing_heart_beat: Duration,
+    incoming_heart_beat: Duration,
+    last_sent: Instant,
+    last_received: Instant,
+    // SEND frames go to the session's request worker, which handles them one at a time
+    request_tx: Option<UnboundedSender<SendContent>>,
 }
 
 impl WsHandler {
-    fn new(dao: ThinData<Dao>,
-           instrument_manager: ThinData<InstrumentManager>,
-           web_socket_server: ThinData<WebSocketServer>,
+    fn new(trading_context: ThinData<TradingContext>,
            access_control: ThinData<AccessControl>,
+           vetter_registry: ThinData<VetterRegistry>,
+           validator: ThinData<Validator>,
            allowed_accounts: HashMap<String, Account>,
            in_msg_stream: actix_ws::MessageStream) -> WsHandler {
         WsHandler {
-            dao,
-            instrument_manager,
-            web_socket_server,
+            trading_context,
             access_control,
+            vetter_registry,
+            validator,
             allowed_accounts,
             msg_stream:  in_msg_stream
                 .max_frame_size(128 * 1024)
                 .aggregate_continuations()
                 .max_continuation_size(2 * 1024 * 1024),
-            subscriptions: BiHashMap::new()
+            subscriptions: BiHashMap::new(),
+            ack_modes: HashMap::new(),
+            pending_acks: HashMap::new(),
+            transactions: HashMap::new(),
+            next_ack_id: 0,
+            connected: false,
+            outgoing_heart_beat: Duration::ZERO,
+            incoming_heart_beat: Duration::ZERO,
+            last_sent: Instant::now(),
+            last_received: Instant::now(),
+            request_tx: None,
         }
     }
 
@@ -125,9 +151,13 @@ impl WsHandler {
                    ws_session: &mut Session) {
         info!("Websocket connected");
         let mut last_heartbeat = Instant::now();
-        let mut interval = interval(HEARTBEAT_INTERVAL);
+        let mut last_ping = Instant::now();
+        let mut interval = interval(TICK_INTERVAL);
 
         let (conn_tx, mut conn_rx) = mpsc::unbounded_channel::<QueueItem>();
+        let (request_tx, request_rx) = mpsc::unbounded_channel::<SendContent>();
+        self.request_tx = Some(request_tx);
+        tokio::spawn(request_worker(self.request_context(), conn_tx.clone(), request_rx));
 
         loop {
             tokio::select! {
@@ -153,10 +183,17 @@ impl WsHandler {
                         }
 
                         AggregatedMessage::Text(text) => {
+                            self.last_received = Instant::now();
                             match self.parse_text_message(ws_session, &conn_tx, &text.to_string()).await {
-                                Ok(_) => {},
+                                Ok(true) => {},
+                                Ok(false) => {
+                                    info!("STOMP session ended");
+                                    self.unsubscribe_all(&conn_tx);
+                                    return;
+                                },
                                 Err(closed) => {
                                     error!("Could not send text, exiting, due to {}", closed);
+                                    self.unsubscribe_all(&conn_tx);
                                     return;
                                 },
                             };
@@ -177,16 +214,38 @@ impl WsHandler {
                         Ok(_) => {},
                         Err(closed) => {
                             error!("Could not send text for queued_item, exiting, due to {}", closed);
+                            self.unsubscribe_all(&conn_tx);
                             return;
                         },
                     };
                 }
                 _ = interval.tick() => {
-                    if Instant::now().duration_since(last_heartbeat) > CLIENT_TIMEOUT {
+                    let now = Instant::now();
+                    if now.duration_since(last_heartbeat) > CLIENT_TIMEOUT {
                         info!("Websocket client timeout");
+                        self.unsubscribe_all(&conn_tx);
                         return;
                     }
-                    let _ = ws_session.ping(b"").await;
+                    // Allow the client twice the negotiated period before giving up on it
+                    if !self.incoming_heart_beat.is_zero() && now.duration_since(self.last_received) > self.incoming_heart_beat * 2 {
+                        info!("STOMP heart-beat timeout");
+                        self.unsubscribe_all(&conn_tx);
+                        return;
+                    }
+                    if now.duration_since(last_ping) >= HEARTBEAT_INTERVAL {
+                        last_ping = now;
+                        let _ = ws_session.ping(b"").await;
+                    }
+                    if !self.outgoing_heart_beat.is_zero() && now.duration_since(self.last_sent) >= self.outgoing_heart_beat {
+                        match self.send_text(ws_session, "\n".to_string()).await {
+                            Ok(_) => {},
+                            Err(closed) => {
+                                error!("Could not send heart-beat, exiting, due to {}", closed);
+                                self.unsubscribe_all(&conn_tx);
+                                return;
+                            },
+                        };
+                    }
                 }
                 else => {
                     return;
@@ -194,23 +253,89 @@ impl WsHandler {
             }
         };
     }
+
+    async fn send_text(&mut self,
+                       session: &mut Session,
+                       text: String) -> Result<(), Closed> {
+        self.last_sent = Instant::now();
+        session.text(text).await
+    }
+
+    async fn send_frame(&mut self,
+                        session: &mut Session,
+                        frame: &Frame) -> Result<(), Closed> {
+        self.send_text(session, frame.to_text()).await
+    }
+
+    // Sends an ERROR frame; the caller closes the connection when this returns Ok(false)
+    async fn send_error(&mut self,
+                        session: &mut Session,
+                        message: &str,
+                        receipt_id: Option<&str>,
+                        details: &str) -> Result<bool, Error> {
+        warn!("Sending STOMP error {}: {}", message, details);
+        self.send_frame(session, &stomp::error_message(message, receipt_id, details)).await?;
+        Ok(false)
+    }
+
     async fn send_queue_item(&mut self,
                              session: &mut Session,
                              queue_item: QueueItem) -> Result<(), Closed> {
-        let subscription_id_options = self.subscriptions.get_by_left(&queue_item.destination);
-        let subscription_id = match subscription_id_options {
-            Some(subscription_id) => subscription_id,
+        let subscription_id = match self.subscriptions.get_by_left(&queue_item.destination) {
+            Some(subscription_id) => subscription_id.clone(),
             None => {
                 debug!("No subscription for {}", queue_item.destination);
                 return Ok(());
             },
         };
-        let data_message = stomp::text_message(queue_item.destination, subscription_id.clone(), &queue_item.body);
-        let data_message_string = data_message.to_string();
+        let ack_id = match self.ack_modes.get(&subscription_id) {
+            Some(AckMode::Client) | Some(AckMode::ClientIndividual) => {
+                self.next_ack_id += 1;
+                let ack_id = self.next_ack_id.to_string();
+                let pending = self.pending_acks.entry(subscription_id.clone()).or_default();
+                pending.push(ack_id.clone());
+                if pending.len() > MAX_PENDING_ACKS {
+                    let forgotten = pending.remove(0);
+                    warn!("Too many unacknowledged messages on subscription {}, forgetting {}", subscription_id, forgotten);
+                }
+                Some(ack_id)
+            },
+            _ => None,
+        };
+        let data_message_string = stomp::text_message(&queue_item.destination, &subscription_id, ack_id.as_deref(), &queue_item.body).to_text();
         trace!("Sending {}", data_message_string);
-        session.text(data_message_string).await
+        self.send_text(session, data_message_string).await
     }
 
+    // Updates are not redelivered, so acknowledging only clears the bookkeeping. In client mode
+    // an ACK or NACK covers every earlier message on the subscription as well
+    fn handle_ack(&mut self,
+                  id: &str,
+                  nack: bool) {
+        let subscription_id = match self.pending_acks.iter().find(|(_, pending)| pending.iter().any(|pending_id| pending_id == id)) {
+            Some((subscription_id, _)) => subscription_id.clone(),
+            None => {
+                warn!("Received acknowledgement for unknown message {}", id);
+                return;
+            },
+        };
+        let ack_mode = self.ack_modes.get(&subscription_id).cloned().unwrap_or(AckMode::Auto);
+        if let Some(pending) = self.pending_acks.get_mut(&subscription_id) {
+            if let Some(position) = pending.iter().position(|pending_id| pending_id == id) {
+                match ack_mode {
+                    AckMode::Client => {
+                        pending.drain(..=position);
+                    },
+                    _ => {
+                        pending.remove(position);
+                    },
+                };
+            }
+        }
+        if nack {
+            warn!("Client rejected message {} on subscription {}", id, subscription_id);
+        }
+    }
 
     async fn validate_subscription(&self,
                                    destination: &String) -> bool {
@@ -250,6 +375,8 @@ impl WsHandler {
     fn unsubscribe(&mut self,
                    conn_tx: &UnboundedSender<QueueItem>,
                    id: String) {
+        self.ack_modes.remove(&id);
+        self.pending_acks.remove(&id);
         let destination = match self.subscriptions.remove_by_right(&id) {
             Some(destination) => destination,
             None => {
@@ -257,7 +384,7 @@ impl WsHandler {
                 return;
             },
         };
-        let mut writable_conns = match self.web_socket_server.connections.write() {
+        let mut writable_conns = match self.trading_context.web_socket_server.connections.write() {
             Ok(writable_conns) => writable_conns,
             Err(poison_error) => {
                 error!("unsubscribe could not get writable_conns {}", poison_error.to_string());
@@ -285,108 +412,199 @@ impl WsHandler {
     }
 
 
+    fn request_context(&self) -> RequestContext {
+        RequestContext {
+            access_control: self.access_control.clone(),
+            allowed_accounts: self.allowed_accounts.clone(),
+            order_entry_context: OrderEntryContext {
+                trading_context: self.trading_context.0.clone(),
+                vetter_registry: self.vetter_registry.0.clone(),
+                validator: self.validator.0.clone(),
+            },
+        }
+    }
+
     async fn send_content(&self,
-                          conn_tx: UnboundedSender<QueueItem>,
-                          content: SendContent)  -> Result<(), Closed> {
+                          content: SendContent) -> bool {
         if !self.validate_subscription(&content.destination).await {
             error!("Request to send for forbidden destination {} {}", content.destination, content.body);
-            return Err(Closed);
+            return false;
         }
 
-        let dao_clone = self.dao.clone();
-        let instrument_manager_clone = self.instrument_manager.clone();
-
-        tokio::spawn(async move
-            {
-                let send_request: SendRequest = match serde_json::from_str(content.body.as_str()) {
-                    Ok(send_request) => send_request,
-                    Err(serde_error) => {
-                        error!("send_content deserialization error {}", serde_error.to_string());
-                        return;
-                    }
-                };
-                let account_key_result = extract_account_key(&content.destination);
-                let account_key = match account_key_result {
-                    Ok(account_key) => account_key,
-                    Err(broker_error) => {
-                        error!("{}", broker_error);
-                        return;
-                    }
-                };
-                match send_request.request {
-                    Request::GET => {
-                        send_get(dao_clone, instrument_manager_clone, conn_tx, &content.destination, &account_key, send_request.scope).await
-                    }
-                };
+        let request_tx = match &self.request_tx {
+            Some(request_tx) => request_tx,
+            None => {
+                error!("Request to send before the session started {}", content.destination);
+                return false;
             }
-        );
-        Ok(())
+        };
+        match request_tx.send(content) {
+            Ok(_) => {},
+            Err(send_error) => error!("Could not queue request: {}", send_error),
+        };
+        true
     }
 
     async fn parse_text_message(&mut self,
                                 session: &mut Session,
                                 conn_tx: &UnboundedSender<QueueItem>,
-                                text: &String) -> Result<(), Error> {
+                                text: &String) -> Result<bool, Error> {
         debug!("Text message {}", text);
-        let parsed_message_result = parse_message(&text.to_string());
-        let parsed_message = match parsed_message_result {
+        let frames = match parse_frames(text) {
+            Ok(frames) => frames,
+            Err(parse_error) => return self.send_error(session, "Malformed frame", None, &parse_error.to_string()).await,
+        };
+        if frames.is_empty() {
+            trace!("STOMP heart-beat");
+        }
+        for frame in frames {
+            if !self.handle_frame(session, conn_tx, &frame).await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Returns false once the connection should be closed
+    async fn handle_frame(&mut self,
+                          session: &mut Session,
+                          conn_tx: &UnboundedSender<QueueItem>,
+                          frame: &Frame) -> Result<bool, Error> {
+        let receipt = frame.receipt();
+        let parsed_message = match frame.to_stomp_message() {
             Ok(parsed_message) => parsed_message,
-            Err(parse_error) => return Err(anyhow::anyhow!("Unable to parse message: {}", parse_error.to_string()))
+            Err(parse_error) => return self.send_error(session, "Malformed frame", receipt, &parse_error.to_string()).await,
         };
-        let res = match parsed_message {
+        if !self.connected && !matches!(parsed_message, StompMessage::Connect(_)) {
+            return self.send_error(session, "Not connected", receipt, &format!("{} received before CONNECT", frame.command)).await;
+        }
+        match parsed_message {
             StompMessage::Message(msg) => {
                 error!("Received unexpected Message message on server: {}", msg.body);
-                Ok(())
+            }
+            StompMessage::Send(SendContent { transaction: Some(transaction), .. }) |
+            StompMessage::Ack(AckContent { transaction: Some(transaction), .. }) |
+            StompMessage::Nack(AckContent { transaction: Some(transaction), .. }) => {
+                match self.transactions.get_mut(&transaction) {
+                    Some(transaction_frames) => transaction_frames.push(frame.clone()),
+                    None => return self.send_error(session, "Unknown transaction", receipt, &transaction).await,
+                };
             }
             StompMessage::Send(msg) => {
                 info!("Received expected Send message on server: {} {}", msg.destination, msg.body );
-                self.send_content(conn_tx.clone(), msg).await
+                let destination = msg.destination.clone();
+                if !self.send_content(msg).await {
+                    return self.send_error(session, "Forbidden destination", receipt, &destination).await;
+                }
             }
             StompMessage::Connected(ct) => {
-                error!("Received unexpected Connected message on server: {}", ct.user_name);
-                Ok(())
+                error!("Received unexpected Connected message on server: {:?}", ct.user_name);
             }
             StompMessage::Subscribe(sub) => {
                 info!("Received expected Subscribe message on server: {} as {}", sub.destination, sub.id);
-                self.handle_subscribe(conn_tx, &sub).await
+                let ack_mode = match sub.ack.parse::<AckMode>() {
+                    Ok(ack_mode) => ack_mode,
+                    Err(ack_error) => return self.send_error(session, "Unsupported ack mode", receipt, &ack_error.to_string()).await,
+                };
+                if !self.handle_subscribe(conn_tx, &sub).await {
+                    return self.send_error(session, "Forbidden destination", receipt, &sub.destination).await;
+                }
+                // Only a subscription that was allowed gets an ack mode. Its retained message is
+                // queued rather than sent, so it still goes out under this mode
+                self.ack_modes.insert(sub.id.clone(), ack_mode);
             },
             StompMessage::Connect(ct) => {
-                info!("Received expected Connect message on server: {}", ct.accept_version);
-                 session.text(stomp::connected_message().to_string()).await
+                info!("Received expected Connect message on server: {:?}", ct.accept_version);
+                if self.connected {
+                    return self.send_error(session, "Already connected", receipt, "CONNECT may only be sent once").await;
+                }
+                if !stomp::accepts_version(&ct.accept_version) {
+                    self.send_frame(session, &stomp::version_error_message(receipt)).await?;
+                    return Ok(false);
+                }
+                let server_heart_beat = HEARTBEAT_INTERVAL.as_millis() as u64;
+                let (outgoing, incoming) = match negotiate_heart_beat(&ct.heart_beat, server_heart_beat, server_heart_beat) {
+                    Ok(negotiated) => negotiated,
+                    Err(heart_beat_error) => return self.send_error(session, "Malformed heart-beat", receipt, &heart_beat_error.to_string()).await,
+                };
+                self.outgoing_heart_beat = Duration::from_millis(outgoing);
+                self.incoming_heart_beat = Duration::from_millis(incoming);
+                self.connected = true;
+                self.send_frame(session, &stomp::connected_message(&format!("{},{}", server_heart_beat, server_heart_beat))).await?;
             },
             StompMessage::Unsubscribe(us) => {
                 info!("Received expected Unsubscribe message on server: {}", us.id);
-                self.unsubscribe(&conn_tx, us.id);
-                Ok(())
+                self.unsubscribe(conn_tx, us.id);
+            },
+            StompMessage::Ack(ack) => {
+                self.handle_ack(&ack.id, false);
+            },
+            StompMessage::Nack(nack) => {
+                self.handle_ack(&nack.id, true);
+            },
+            StompMessage::Begin(begin) => {
+                if self.transactions.contains_key(&begin.transaction) {
+                    return self.send_error(session, "Transaction already begun", receipt, &begin.transaction).await;
+                }
+                self.transactions.insert(begin.transaction, Vec::new());
+            },
+            StompMessage::Commit(commit) => {
+                let transaction_frames = match self.transactions.remove(&commit.transaction) {
+                    Some(transaction_frames) => transaction_frames,
+                    None => return self.send_error(session, "Unknown transaction", receipt, &commit.transaction).await,
+                };
+                for transaction_frame in transaction_frames {
+                    match transaction_frame.to_stomp_message() {
+                        Ok(StompMessage::Send(msg)) => {
+                            let destination = msg.destination.clone();
+                            if !self.send_content(msg).await {
+                                return self.send_error(session, "Forbidden destination", receipt, &destination).await;
+                            }
+                        },
+                        Ok(StompMessage::Ack(ack)) => self.handle_ack(&ack.id, false),
+                        Ok(StompMessage::Nack(nack)) => self.handle_ack(&nack.id, true),
+                        _ => error!("Unexpected {} frame in transaction {}", transaction_frame.command, commit.transaction),
+                    };
+                }
+            },
+            StompMessage::Abort(abort) => {
+                if self.transactions.remove(&abort.transaction).is_none() {
+                    return self.send_error(session, "Unknown transaction", receipt, &abort.transaction).await;
+                }
+            },
+            StompMessage::Receipt(receipt_content) => {
+                error!("Received unexpected Receipt message on server: {}", receipt_content.receipt_id);
+            },
+            StompMessage::Error(error_content) => {
+                error!("Received unexpected Error message on server: {:?} {}", error_content.message, error_content.body);
             },
             StompMessage::Disconnect(_) => {
                 info!("Received expected Disconnect message on server");
-                self.unsubscribe_all(&conn_tx);
-                Ok(())
+                self.unsubscribe_all(conn_tx);
+                if let Some(receipt) = receipt {
+                    self.send_frame(session, &stomp::receipt_message(receipt)).await?;
+                }
+                return Ok(false);
             }
         };
-        match res {
-            Ok(_) => Ok(()),
-            Err(closed_error) => {
-                Err(anyhow::Error::from(closed_error))
-            }
+        if let Some(receipt) = receipt {
+            self.send_frame(session, &stomp::receipt_message(receipt)).await?;
         }
+        Ok(true)
     }
 
     async fn handle_subscribe(&mut self,
                               conn_tx: &UnboundedSender<QueueItem>,
-                              sub: &SubscribeContent) -> Result<(), Closed> {
-        if sub.destination.starts_with("/accounts/") {
-            if !self.validate_subscription(&sub.destination).await {
-                error!("Request for forbidden destination {}", sub.destination);
-                return Err(Closed);
-            }
+                              sub: &SubscribeContent) -> bool {
+        if sub.destination.starts_with("/accounts/") && !self.validate_subscription(&sub.destination).await {
+            error!("Request for forbidden destination {}", sub.destination);
+            return false;
         }
-        let mut writable_conns = match self.web_socket_server.connections.write() {
+        let mut writable_conns = match self.trading_context.web_socket_server.connections.write() {
             Ok(writable_conns) => writable_conns,
             Err(poison_error) => {
                 error!("Subscribe message could not get writable_conns {}", poison_error.to_string());
-                return Ok(());
+                return true;
             },
         };
         if !writable_conns.contains_key(&sub.destination) {
@@ -398,15 +616,15 @@ impl WsHandler {
             },
             None => {
                 error!("No per_destination_conns for {}", sub.destination);
-                return Ok(());
+                return true;
             },
         };
         self.subscriptions.insert(sub.destination.clone(), sub.id.clone());
-        let readable = match self.web_socket_server.retained_messages.read() {
+        let readable = match self.trading_context.web_socket_server.retained_messages.read() {
             Ok(readable) => readable,
             Err(readable_error) => {
                 error!("Unable to get read access to retained_messages: {}", readable_error);
-                return Ok(());
+                return true;
             }
         };
         match readable.get(&sub.destination) {
@@ -415,14 +633,20 @@ impl WsHandler {
                 conn_tx.send(retained_message.clone()).unwrap_or_else(|send_error| error!("Error when sending retained message: {}", send_error));
             }
         };
-        Ok(())
+        true
     }
 }
 
 
 #[derive(Debug, Deserialize)]
 enum Request {
-    GET
+    GET,
+    #[serde(rename = "SUBMIT")]
+    Submit,
+    #[serde(rename = "CANCEL")]
+    Cancel,
+    #[serde(rename = "REPLACE")]
+    Replace,
 }
 
 #[derive(Debug, Deserialize)]
@@ -435,10 +659,105 @@ enum Scope {
     Orders,
 }
 
+// GET needs a scope. SUBMIT needs an order, CANCEL an ext_order_id and REPLACE both; the
+// order receipt sent back carries the request_id and ext_order_id given here
 #[derive(Debug, Deserialize)]
 struct SendRequest {
     pub request: Request,
-    pub scope: Scope,
+    #[serde(default)]
+    pub scope: Option<Scope>,
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(default)]
+    pub ext_order_id: Option<String>,
+    #[serde(default)]
+    pub order: Option<Order>,
+}
+
+#[derive(Clone)]
+struct OrderEntryContext {
+    trading_context: TradingContext,
+    vetter_registry: VetterRegistry,
+    validator: Validator,
+}
+
+struct RequestContext {
+    access_control: ThinData<AccessControl>,
+    allowed_accounts: HashMap<String, Account>,
+    order_entry_context: OrderEntryContext,
+}
+
+// Requests from one session are handled one at a time, in the order they arrived, so a cancel
+// or replace cannot overtake the submit it refers to. The worker ends with its session
+async fn request_worker(context: RequestContext,
+                        conn_tx: UnboundedSender<QueueItem>,
+                        mut request_rx: UnboundedReceiver<SendContent>) {
+    while let Some(content) = request_rx.recv().await {
+        handle_send_content(&context, conn_tx.clone(), content).await;
+    }
+}
+
+async fn handle_send_content(context: &RequestContext,
+                             conn_tx: UnboundedSender<QueueItem>,
+                             content: SendContent) {
+    let send_request: SendRequest = match serde_json::from_str(content.body.as_str()) {
+        Ok(send_request) => send_request,
+        Err(serde_error) => {
+            error!("send_content deserialization error {}", serde_error.to_string());
+            // The request_id, if it can still be read, lets the client match the rejection to its request
+            let request_id = serde_json::from_str::<serde_json::Value>(&content.body).ok()
+                .and_then(|body| body.get("request_id").and_then(|request_id| request_id.as_str()).map(|request_id| request_id.to_string()));
+            let order_receipt = rejected_receipt(request_id, None, format!("Malformed request: {}", serde_error));
+            send_order_receipt(conn_tx, &content.destination, &order_receipt);
+            return;
+        }
+    };
+    let account_key_result = extract_account_key(&content.destination);
+    let account_key = match account_key_result {
+        Ok(account_key) => account_key,
+        Err(broker_error) => {
+            error!("{}", broker_error);
+            let order_receipt = rejected_receipt(send_request.request_id, send_request.ext_order_id,
+                                                 format!("Bad destination {}", content.destination));
+            send_order_receipt(conn_tx, &content.destination, &order_receipt);
+            return;
+        }
+    };
+    let privilege = match send_request.request {
+        Request::GET => {
+            match send_request.scope {
+                Some(scope) => send_get(&context.order_entry_context.trading_context, conn_tx, &content.destination, &account_key, scope).await,
+                None => {
+                    error!("GET request without a scope for {}", content.destination);
+                    let order_receipt = rejected_receipt(send_request.request_id, send_request.ext_order_id, "GET needs a scope".to_string());
+                    send_order_receipt(conn_tx, &content.destination, &order_receipt);
+                },
+            };
+            return;
+        }
+        Request::Submit | Request::Replace => Privilege::Submit,
+        Request::Cancel => Privilege::Cancel,
+    };
+    let allowed = match context.access_control.is_allowed_from_map(&context.allowed_accounts, &account_key, privilege) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking access: {}", error);
+            false
+        }
+    };
+    let may_make_markets = match context.access_control.is_allowed_from_map(&context.allowed_accounts, &account_key, Privilege::MakeMarkets) {
+        Ok(allowed) => allowed,
+        Err(error) => {
+            error!("Failed while checking access: {}", error);
+            false
+        }
+    };
+    let order_receipt = if allowed {
+        handle_order_request(context.order_entry_context.clone(), &account_key, may_make_markets, send_request).await
+    } else {
+        rejected_receipt(send_request.request_id, send_request.ext_order_id, "Not permitted for this account".to_string())
+    };
+    send_order_receipt(conn_tx, &content.destination, &order_receipt);
 }
 
 
@@ -452,12 +771,13 @@ fn extract_account_key(destination: &String) -> Result<String, anyhow::Error> {
     Ok(account_key)
 }
 
-async fn send_get(dao: ThinData<Dao>,
-                  instrument_manager: ThinData<InstrumentManager>,
+async fn send_get(trading_context: &TradingContext,
                   conn_tx: UnboundedSender<QueueItem>,
                   destination: &String,
                   account_key: &String,
                   scope: Scope) {
+    let dao = &trading_context.dao;
+    let instrument_manager = &trading_context.instrument_manager;
     let mut db_connection = match dao.get_connection().await {
         Ok(db_connection) => db_connection,
         Err(dao_error) => {
@@ -477,10 +797,179 @@ async fn send_get(dao: ThinData<Dao>,
             send_balance(txn, conn_tx, destination, account_key).await;
         }
         Scope::Positions => {
-            send_positions(txn, &instrument_manager, conn_tx, destination, account_key).await;
+            send_positions(txn, instrument_manager, conn_tx, destination, account_key).await;
         }
         Scope::Orders => {
-            send_orders(txn, &instrument_manager, conn_tx, destination, account_key).await;
+            send_orders(txn, instrument_manager, conn_tx, destination, account_key).await;
         }
     };
-}
\ No newline at end of file
+}
+async fn handle_order_request(mut context: OrderEntryContext,
+                              account_key: &String,
+                              may_make_markets: bool,
+                              send_request: SendRequest) -> OrderReceipt {
+    let request_id = send_request.request_id;
+    let ext_order_id = send_request.ext_order_id;
+    let entry_result = match (send_request.request, send_request.order, &ext_order_id) {
+        (Request::Submit, Some(order), _) => {
+            order_entry::submit_order(&mut context.trading_context, &context.vetter_registry, &context.validator,
+                                      account_key, may_make_markets, order).await
+        },
+        (Request::Cancel, _, Some(ext_order_id)) => {
+            let trading_context = &mut context.trading_context;
+            order_entry::cancel_order(&trading_context.dao, &mut trading_context.web_socket_server, &trading_context.instrument_manager, account_key, ext_order_id).await
+        },
+        (Request::Replace, Some(order), Some(ext_order_id)) => {
+            order_entry::replace_order(&mut context.trading_context, &context.vetter_registry, &context.validator,
+                                       account_key, may_make_markets, ext_order_id, order).await
+        },
+        (request, _, _) => return rejected_receipt(request_id, ext_order_id, format!("Incomplete {:?} request", request)),
+    };
+    match entry_result {
+        Ok(order_state) => match order_state.to_rest_api_order_state(account_key, &context.trading_context.instrument_manager) {
+            Ok(rest_api_order_state) => OrderReceipt {
+                request_id,
+                ext_order_id: rest_api_order_state.order.ext_order_id.clone(),
+                accepted: true,
+                order_state: Some(rest_api_order_state),
+                check_result: None,
+                reject_reason: None,
+            },
+            Err(convert_error) => {
+                error!("Could not convert order state: {}", convert_error);
+                rejected_receipt(request_id, ext_order_id, "Internal error".to_string())
+            }
+        },
+        Err(OrderEntryError::Rejected(check_result)) => OrderReceipt {
+            request_id,
+            ext_order_id,
+            accepted: false,
+            order_state: None,
+            reject_reason: check_result.reject_reason.clone(),
+            check_result: Some(check_result),
+        },
+        Err(OrderEntryError::Failed(error)) => {
+            error!("Order request failed: {}", error);
+            rejected_receipt(request_id, ext_order_id, "Internal error".to_string())
+        },
+        Err(entry_error) => rejected_receipt(request_id, ext_order_id, entry_error.to_string()),
+    }
+}
+
+fn rejected_receipt(request_id: Option<String>,
+                    ext_order_id: Option<String>,
+                    reject_reason: String) -> OrderReceipt {
+    OrderReceipt {
+        request_id,
+        ext_order_id,
+        accepted: false,
+        order_state: None,
+        check_result: None,
+        reject_reason: Some(reject_reason),
+    }
+}
+
+fn send_order_receipt(conn_tx: UnboundedSender<QueueItem>,
+                      destination: &str,
+                      order_receipt: &OrderReceipt) {
+    let body = match serde_json::to_string(order_receipt) {
+        Ok(body) => body,
+        Err(json_error) => {
+            error!("send_order_receipt error while serializing: {}", json_error);
+            return;
+        },
+    };
+    let queue_item = QueueItem {
+        destination: destination.to_string(),
+        body,
+    };
+    match conn_tx.send(queue_item) {
+        Ok(_) => {},
+        Err(send_error) => error!("send_order_receipt error while sending: {}", send_error),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::access_control::AccessControl;
+    use crate::dtos::order::OrderReceipt;
+    use crate::exchange_interface::exchange_health::CircuitSettings;
+    use crate::instrument_manager::InstrumentManager;
+    use crate::persistence::dao::unreachable_dao;
+    use crate::trade_handling::trading_context::TradingContext;
+    use crate::validator::validator::Validator;
+    use crate::vetting::all_pass_vetter::ALL_PASS_VETTER;
+    use crate::vetting::vetter_registry::VetterRegistry;
+    use crate::websockets::server::WebSocketServer;
+    use crate::websockets::stomp::SendContent;
+    use crate::websockets::ws_handler::{handle_send_content, OrderEntryContext, Request, RequestContext, Scope, SendRequest};
+    use actix_web::web::ThinData;
+    use std::collections::HashMap;
+    use tokio::sync::mpsc;
+
+    // Sends one request on a session with no accounts and returns the receipt it got back
+    async fn receipt_for(destination: &str,
+                         body: &str) -> OrderReceipt {
+        let dao = unreachable_dao();
+        let web_socket_server = WebSocketServer::new();
+        let instrument_manager = InstrumentManager::new(dao.clone(), web_socket_server.clone(), CircuitSettings::default());
+        let context = RequestContext {
+            access_control: ThinData(AccessControl::new()),
+            allowed_accounts: HashMap::new(),
+            order_entry_context: OrderEntryContext {
+                trading_context: TradingContext::new(dao, web_socket_server, instrument_manager.clone()),
+                vetter_registry: VetterRegistry::new(ALL_PASS_VETTER),
+                validator: Validator::new(instrument_manager),
+            },
+        };
+        let (conn_tx, mut conn_rx) = mpsc::unbounded_channel();
+        handle_send_content(&context, conn_tx, SendContent {
+            destination: destination.to_string(),
+            transaction: None,
+            body: body.to_string(),
+        }).await;
+        let queue_item = conn_rx.recv().await.unwrap();
+        assert_eq!(queue_item.destination, destination);
+        serde_json::from_str(&queue_item.body).unwrap()
+    }
+
+    #[test]
+    async fn test_bad_requests_are_rejected() {
+        let malformed = receipt_for("/accounts/account/orders", "{\"request\": \"SUBMIT\", \"request_id\": \"r1\", \"order\": 5}").await;
+        assert!(!malformed.accepted);
+        assert_eq!(malformed.request_id, Some("r1".to_string()));
+        assert!(malformed.reject_reason.unwrap().starts_with("Malformed request"));
+
+        let not_json = receipt_for("/accounts/account/orders", "not json").await;
+        assert!(!not_json.accepted);
+        assert_eq!(not_json.request_id, None);
+
+        let bad_destination = receipt_for("/accounts/orders", "{\"request\": \"CANCEL\", \"request_id\": \"r2\", \"ext_order_id\": \"e1\"}").await;
+        assert!(!bad_destination.accepted);
+        assert_eq!(bad_destination.request_id, Some("r2".to_string()));
+        assert_eq!(bad_destination.ext_order_id, Some("e1".to_string()));
+        assert_eq!(bad_destination.reject_reason, Some("Bad destination /accounts/orders".to_string()));
+
+        let no_scope = receipt_for("/accounts/account/orders", "{\"request\": \"GET\", \"request_id\": \"r3\"}").await;
+        assert!(!no_scope.accepted);
+        assert_eq!(no_scope.request_id, Some("r3".to_string()));
+    }
+
+    #[test]
+    async fn test_send_request_parsing() {
+        let get: SendRequest = serde_json::from_str("{\"request\": \"GET\", \"scope\": \"orders\"}").unwrap();
+        assert!(matches!(get.request, Request::GET));
+        assert!(matches!(get.scope, Some(Scope::Orders)));
+
+        let submit: SendRequest = serde_json::from_str("{\"request\": \"SUBMIT\", \"request_id\": \"r1\", \
+            \"order\": {\"price\": 10.0, \"quantity\": 1, \"legs\": [{\"instrument_key\": \"inst\", \"ratio\": 1}]}}").unwrap();
+        assert!(matches!(submit.request, Request::Submit));
+        assert_eq!(submit.request_id, Some("r1".to_string()));
+        assert_eq!(submit.order.unwrap().quantity, 1);
+
+        let cancel: SendRequest = serde_json::from_str("{\"request\": \"CANCEL\", \"ext_order_id\": \"e1\"}").unwrap();
+        assert!(matches!(cancel.request, Request::Cancel));
+        assert_eq!(cancel.ext_order_id, Some("e1".to_string()));
+        assert!(cancel.order.is_none());
+    }
+}